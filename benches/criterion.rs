@@ -1,6 +1,11 @@
 mod insert;
 mod rand_access;
+mod raw_access;
 
 use criterion::criterion_main;
 
-criterion_main!(insert::bench_insert, rand_access::bench_rand_access);
+criterion_main!(
+    insert::bench_insert,
+    rand_access::bench_rand_access,
+    raw_access::bench_raw_access
+);
@@ -0,0 +1,69 @@
+use std::hint::black_box;
+
+use criterion::{AxisScale, BatchSize, BenchmarkId, Criterion, PlotConfiguration, criterion_group};
+use rand::{Rng, SeedableRng, rngs::SmallRng, seq::IndexedRandom};
+use skipidy::{SkipList, SkipMap};
+
+const ACCESSES: usize = 100_000;
+const SIZES: [usize; 6] = [1, 10, 100, 1000, 10_000, 100_000];
+
+pub fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("raw_access");
+    group.plot_config(PlotConfiguration::default().summary_scale(AxisScale::Logarithmic));
+    for size in SIZES {
+        let mut rng = SmallRng::seed_from_u64(0x1234_abcd);
+        let items: Vec<u64> = std::iter::repeat_with(|| rng.random()).take(size).collect();
+        let mut sl: SkipList<u64, _, 32> = SkipList::new();
+        let mut sm: SkipMap<u64, (), _, 32> = SkipMap::new();
+        for item in &items {
+            sl.insert(*item);
+            sm.insert(*item, ());
+        }
+        group.bench_function(BenchmarkId::new("SkipList::contains", size), |b| {
+            b.iter_batched(
+                || items.choose_multiple(&mut rng, ACCESSES),
+                |items| {
+                    for item in items {
+                        black_box(sl.contains(item));
+                    }
+                },
+                BatchSize::SmallInput,
+            );
+        });
+        group.bench_function(BenchmarkId::new("SkipList::contains_raw", size), |b| {
+            b.iter_batched(
+                || items.choose_multiple(&mut rng, ACCESSES),
+                |items| {
+                    for item in items {
+                        black_box(sl.contains_raw(*item));
+                    }
+                },
+                BatchSize::SmallInput,
+            );
+        });
+        group.bench_function(BenchmarkId::new("SkipMap::contains", size), |b| {
+            b.iter_batched(
+                || items.choose_multiple(&mut rng, ACCESSES),
+                |items| {
+                    for item in items {
+                        black_box(sm.contains(item));
+                    }
+                },
+                BatchSize::SmallInput,
+            );
+        });
+        group.bench_function(BenchmarkId::new("SkipMap::contains_raw", size), |b| {
+            b.iter_batched(
+                || items.choose_multiple(&mut rng, ACCESSES),
+                |items| {
+                    for item in items {
+                        black_box(sm.contains_raw(*item));
+                    }
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+}
+
+criterion_group!(bench_raw_access, bench);
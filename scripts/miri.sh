@@ -2,7 +2,28 @@
 
 set -euxo pipefail
 
-env \
-  PROPTEST_DISABLE_FAILURE_PERSISTENCE=true \
-  MIRIFLAGS='-Zmiri-env-forward=PROPTEST_DISABLE_FAILURE_PERSISTENCE' \
-  bash -c 'cargo +nightly miri test'
+# Runs the whole test suite under Miri, including the concurrent collections, since they hold most
+# of the unsafe code. The larger property tests and the concurrent tests work on fewer values under
+# Miri, and proptest runs fewer cases, to keep the run within hours rather than days.
+FEATURES=concurrent,hazard,async
+FLAGS='-Zmiri-env-forward=PROPTEST_CASES -Zmiri-env-forward=PROPTEST_DISABLE_FAILURE_PERSISTENCE'
+
+miri() {
+  env \
+    PROPTEST_CASES=16 \
+    PROPTEST_DISABLE_FAILURE_PERSISTENCE=true \
+    MIRIFLAGS="$1 $FLAGS" \
+    cargo +nightly miri test --features "$FEATURES" "${@:2}"
+}
+
+# Strict provenance makes sure that no pointer of the collections ever gets created from an
+# integer, which holds for everything but the collections built on crossbeam-epoch.
+miri -Zmiri-strict-provenance -- --skip concurrent::
+miri -Zmiri-strict-provenance --lib concurrent::hazard
+
+# crossbeam-epoch casts integers to pointers, and finds its thread records from their list entries
+# in a way that Stacked Borrows rejects, so the collections reclaimed by epochs get checked with
+# permissive provenance and Tree Borrows instead. The garbage still deferred to its global
+# collector when the tests exit never gets freed, so leaks aren't reported there.
+miri '-Zmiri-permissive-provenance -Zmiri-tree-borrows -Zmiri-ignore-leaks' \
+  --lib concurrent:: -- --skip concurrent::hazard
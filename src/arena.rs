@@ -0,0 +1,363 @@
+//! A skipmap variant that stores its nodes in an arena, linking them by 32-bit indices.
+
+use std::{
+    borrow::Borrow,
+    fmt,
+    num::{NonZeroU32, NonZeroUsize},
+};
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use crate::RandomBits;
+
+/// An ordered map backed by a skiplist whose nodes are stored contiguously, and linked by 32-bit
+/// indices instead of pointers.
+///
+/// Links take half the memory of those of a [`SkipMap`](crate::SkipMap), and nodes are packed
+/// densely, which suits maps holding many small entries. Removing an entry moves the last node of
+/// the arena into its place, so the arena never holds gaps.
+///
+/// Values are stored apart from the nodes, in the same order, so that traversals only looking at
+/// keys never load values into the cache, however large they are.
+///
+/// Like for a [`SkipMap`](crate::SkipMap), the random number generator `R` and the number of
+/// levels `N` have defaults, so that the skipmap can be written as `ArenaSkipMap<K, V>`.
+pub struct ArenaSkipMap<K, V, R = SmallRng, const N: usize = 32>
+where
+    R: Rng,
+{
+    rng: R,
+    bits: RandomBits,
+    head: Links<N>,
+    levels: NonZeroUsize,
+    nodes: Vec<ArenaNode<K, N>>,
+    values: Vec<V>,
+}
+
+impl<K, V, const N: usize> Default for ArenaSkipMap<K, V, SmallRng, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, R, const N: usize> fmt::Debug for ArenaSkipMap<K, V, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, const N: usize> ArenaSkipMap<K, V, SmallRng, N> {
+    /// Creates an empty skipmap.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_rng(crate::new_rng())
+    }
+
+    /// Creates an empty skipmap whose arena holds at least the given number of entries without
+    /// reallocating.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut skipmap = Self::new();
+        skipmap.reserve(capacity);
+        skipmap
+    }
+}
+
+impl<K, V, R, const N: usize> ArenaSkipMap<K, V, R, N>
+where
+    R: Rng + SeedableRng,
+{
+    /// Creates an empty skipmap whose random number generator is seeded from the given value, so
+    /// that the same operations always build the same structure.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(R::seed_from_u64(seed))
+    }
+}
+
+impl<K, V, R, const N: usize> ArenaSkipMap<K, V, R, N>
+where
+    R: Rng,
+{
+    /// Creates an empty skipmap that draws the heights of its nodes from the given random number
+    /// generator.
+    pub const fn with_rng(rng: R) -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            rng,
+            bits: RandomBits::new(),
+            head: [None; N],
+            levels: NonZeroUsize::MIN,
+            nodes: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns the number of entries in the skipmap.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether the skipmap holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the number of entries that the arena holds without reallocating.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    /// Reserves room in the arena for at least the given number of entries beyond the current
+    /// ones.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+        self.values.reserve(additional);
+    }
+
+    /// Returns an iterator over the entries, in ascending order of keys.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.indices()
+            .map(|index| (&self.nodes[index].key, &self.values[index]))
+    }
+
+    /// Returns an iterator over the keys, in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.indices().map(|index| &self.nodes[index].key)
+    }
+
+    /// Returns an iterator over the values, in ascending order of keys.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.indices().map(|index| &self.values[index])
+    }
+
+    /// Returns an iterator over the positions of the nodes in the arena, in ascending order of
+    /// keys.
+    fn indices(&self) -> impl Iterator<Item = usize> {
+        let mut next = self.head[0];
+        std::iter::from_fn(move || {
+            let index = next?.get();
+            next = self.nodes[index].nexts[0];
+            Some(index)
+        })
+    }
+
+    fn links(&self, tower: Tower) -> &Links<N> {
+        tower.map_or(&self.head, |index| &self.nodes[index.get()].nexts)
+    }
+
+    fn links_mut(&mut self, tower: Tower) -> &mut Links<N> {
+        tower.map_or(&mut self.head, |index| &mut self.nodes[index.get()].nexts)
+    }
+}
+
+impl<K, V, R, const N: usize> ArenaSkipMap<K, V, R, N>
+where
+    K: Ord,
+    R: Rng,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns a shared reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let trace = self.descend(|k| k.borrow() < key);
+        let index = self.links(trace[0])[0]?.get();
+        (self.nodes[index].key.borrow() == key).then_some(&self.values[index])
+    }
+
+    /// Inserts a value at the given key into the skipmap, returning the replaced value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the skipmap already holds `u32::MAX` entries, in which case it's left unchanged.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let trace = self.descend(|k| k < &key);
+        if let Some(index) = self.links(trace[0])[0]
+            && self.nodes[index.get()].key == key
+        {
+            return Some(std::mem::replace(&mut self.values[index.get()], value));
+        }
+        // Panics before anything changes if the arena is full.
+        let index = Index::new(self.nodes.len());
+        let height = self.random_height();
+        if height > self.levels.get() {
+            // The trace already points at the head for every level above the current ones.
+            self.levels = NonZeroUsize::MIN.saturating_add(height - 1);
+        }
+        let mut nexts = [None; N];
+        for (level, prev) in trace.into_iter().enumerate().take(height) {
+            nexts[level] = self.links_mut(prev)[level].replace(index);
+        }
+        self.nodes.push(ArenaNode { key, nexts });
+        self.values.push(value);
+        None
+    }
+
+    /// Removes a value at the given key from the skipmap, returning it if it exists.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let trace = self.descend(|k| k.borrow() < key);
+        let index = self.links(trace[0])[0]?;
+        if self.nodes[index.get()].key.borrow() != key {
+            return None;
+        }
+        let nexts = self.nodes[index.get()].nexts;
+        self.relink(&trace, index, nexts);
+        while self.levels.get() > 1 && self.head[self.levels.get() - 1].is_none() {
+            self.levels = NonZeroUsize::MIN.saturating_add(self.levels.get() - 2);
+        }
+        // Points the links to the last node at the vacated slot, then moves the node there.
+        let last = self.nodes.len() - 1;
+        if index.get() != last {
+            let trace = self.descend(|k| k < &self.nodes[last].key);
+            self.relink(&trace, Index::new(last), [Some(index); N]);
+        }
+        self.nodes.swap_remove(index.get());
+        Some(self.values.swap_remove(index.get()))
+    }
+
+    /// Replaces the links to the given node at every level that it's linked into, where the trace
+    /// holds the towers right before the node.
+    fn relink(&mut self, trace: &[Tower; N], index: Index, nexts: Links<N>) {
+        for (level, prev) in trace.iter().enumerate().take(self.levels.get()) {
+            let link = &mut self.links_mut(*prev)[level];
+            if *link != Some(index) {
+                break;
+            }
+            *link = nexts[level];
+        }
+    }
+
+    /// Traverses the skipmap, descending down all levels, and returning the last encountered tower
+    /// at each level whose key satisfies the given predicate. Levels above the current number of
+    /// levels point at the head.
+    fn descend<F>(&self, before: F) -> [Tower; N]
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut trace = [None; N];
+        let mut prev = None;
+        for level in (0..self.levels.get()).rev() {
+            while let Some(index) = self.links(prev)[level]
+                && before(&self.nodes[index.get()].key)
+            {
+                prev = Some(index);
+            }
+            trace[level] = prev;
+        }
+        trace
+    }
+
+    /// Determines the height of a new node, growing by at most one level at a time.
+    fn random_height(&mut self) -> usize {
+        let max = self.levels.saturating_add(1).get().min(N);
+        self.bits.height(&mut self.rng, max)
+    }
+}
+
+/// The position of a node in the arena, offset by one so that a missing link takes no space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Index(NonZeroU32);
+
+impl Index {
+    fn new(position: usize) -> Self {
+        u32::try_from(position)
+            .ok()
+            .and_then(|position| NonZeroU32::MIN.checked_add(position))
+            .map(Self)
+            .expect("arena holding more than u32::MAX nodes")
+    }
+
+    const fn get(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+/// The index of either a node or the head when absent.
+type Tower = Option<Index>;
+
+/// The next node at every level.
+type Links<const N: usize> = [Option<Index>; N];
+
+struct ArenaNode<K, const N: usize> {
+    key: K,
+    nexts: Links<N>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{ArenaSkipMap, Index, Links};
+
+    proptest! {
+        #[test]
+        fn test_insert_remove(ops in vec((any::<bool>(), 0..64usize, any::<usize>()), 256)) {
+            let mut skipmap = ArenaSkipMap::<usize, usize, _, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (insert, k, v) in ops {
+                if insert {
+                    assert_eq!(skipmap.insert(k, v), btree.insert(k, v));
+                } else {
+                    assert_eq!(skipmap.remove(&k), btree.remove(&k));
+                }
+                assert_eq!(skipmap.len(), btree.len());
+            }
+            for k in 0..64 {
+                assert_eq!(skipmap.get(&k), btree.get(&k));
+            }
+            assert!(skipmap.iter().eq(btree.iter()));
+            assert!(skipmap.keys().eq(btree.keys()));
+            assert!(skipmap.values().eq(btree.values()));
+        }
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut skipmap = ArenaSkipMap::<usize, usize, _, 8>::with_capacity(100);
+        let capacity = skipmap.capacity();
+        assert!(capacity >= 100);
+        for k in 0..100 {
+            skipmap.insert(k, k);
+        }
+        assert_eq!(skipmap.capacity(), capacity);
+    }
+
+    #[test]
+    #[should_panic(expected = "more than u32::MAX nodes")]
+    fn test_index() {
+        let last = u32::MAX as usize - 1;
+        assert_eq!(Index::new(last).get(), last);
+        // The arena holds `u32::MAX` nodes, so the next one has no index.
+        let _ = Index::new(last + 1);
+    }
+
+    #[test]
+    fn test_link_size() {
+        assert_eq!(size_of::<Links<8>>(), 32);
+    }
+}
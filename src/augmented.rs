@@ -0,0 +1,449 @@
+//! A skipmap variant that maintains aggregates over ranges of entries.
+
+use std::{
+    array,
+    borrow::Borrow,
+    fmt,
+    num::NonZeroUsize,
+    ops::{Bound, RangeBounds},
+    ptr::NonNull,
+};
+
+use rand::{Rng, rngs::SmallRng};
+
+use crate::RandomBits;
+
+/// An associative operation with an identity, used for aggregating the entries of an
+/// [`AugmentedSkipMap`].
+pub trait Monoid<K, V>: Clone {
+    /// Returns the identity of the operation.
+    fn empty() -> Self;
+
+    /// Returns the aggregate of a single entry.
+    fn lift(key: &K, value: &V) -> Self;
+
+    /// Combines two aggregates, where `self` covers the entries ordered before the ones covered
+    /// by `other`.
+    #[must_use]
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// A monoid counting the number of entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Count(pub usize);
+
+impl<K, V> Monoid<K, V> for Count {
+    fn empty() -> Self {
+        Self(0)
+    }
+
+    fn lift(_: &K, _: &V) -> Self {
+        Self(1)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+/// An ordered map backed by a skiplist whose links cache an aggregate over the entries they skip,
+/// allowing any range of entries to be aggregated in logarithmic time.
+pub struct AugmentedSkipMap<K, V, M, R, const N: usize>
+where
+    R: Rng,
+{
+    rng: R,
+    bits: RandomBits,
+    head: [Link<K, V, M, N>; N],
+    levels: NonZeroUsize,
+}
+
+impl<K, V, M, R, const N: usize> Drop for AugmentedSkipMap<K, V, M, R, N>
+where
+    R: Rng,
+{
+    fn drop(&mut self) {
+        let mut curr = self.head[0].next;
+        while let Some(curr_ptr) = curr {
+            let node = unsafe { Box::from_raw(curr_ptr.as_ptr()) };
+            curr = node.links[0].next;
+        }
+    }
+}
+
+// SAFETY: The skipmap exclusively owns all of its nodes, which are only reachable through it, so
+// sending it sends the entries and the aggregates along with it.
+#[expect(
+    clippy::non_send_fields_in_send_ty,
+    reason = "the links only point to nodes owned by the skipmap"
+)]
+unsafe impl<K, V, M, R, const N: usize> Send for AugmentedSkipMap<K, V, M, R, N>
+where
+    K: Send,
+    V: Send,
+    M: Send,
+    R: Rng + Send,
+{
+}
+
+// SAFETY: Nodes are only mutated through a mutable reference to the skipmap, so sharing the
+// skipmap only ever gives out shared references to the entries and the aggregates.
+unsafe impl<K, V, M, R, const N: usize> Sync for AugmentedSkipMap<K, V, M, R, N>
+where
+    K: Sync,
+    V: Sync,
+    M: Sync,
+    R: Rng + Sync,
+{
+}
+
+impl<K, V, M, const N: usize> Default for AugmentedSkipMap<K, V, M, SmallRng, N>
+where
+    M: Monoid<K, V>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, M, R, const N: usize> fmt::Debug for AugmentedSkipMap<K, V, M, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut curr = self.head[0].next;
+        while let Some(curr_ptr) = curr {
+            let node = unsafe { curr_ptr.as_ref() };
+            map.entry(&node.key, &node.value);
+            curr = node.links[0].next;
+        }
+        map.finish()
+    }
+}
+
+impl<K, V, M, const N: usize> AugmentedSkipMap<K, V, M, SmallRng, N>
+where
+    M: Monoid<K, V>,
+{
+    /// Creates an empty skipmap.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            rng: crate::new_rng(),
+            bits: RandomBits::new(),
+            head: array::from_fn(|_| Link::new()),
+            levels: NonZeroUsize::MIN,
+        }
+    }
+}
+
+impl<K, V, M, R, const N: usize> AugmentedSkipMap<K, V, M, R, N>
+where
+    K: Ord,
+    M: Monoid<K, V>,
+    R: Rng,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns a shared reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let trace = self.descend(|k| k.borrow() < key);
+        let curr_ptr = self.links(trace[0])[0].next?;
+        let curr = unsafe { curr_ptr.as_ref() };
+        (curr.key.borrow() == key).then_some(&curr.value)
+    }
+
+    /// Inserts a value at the given key into the skipmap, returning the replaced value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        // Traverses the skipmap and searches for the key, while tracking the towers whose
+        // aggregates might get updated due to the insertion.
+        let trace = self.descend(|k| k < &key);
+        if let Some(mut curr_ptr) = self.links(trace[0])[0].next {
+            let curr = unsafe { curr_ptr.as_mut() };
+            if curr.key == key {
+                let old = std::mem::replace(&mut curr.value, value);
+                curr.links[0].agg = M::lift(&curr.key, &curr.value);
+                self.refresh(&trace, Some(curr_ptr));
+                return Some(old);
+            }
+        }
+        let height = self.random_height();
+        if height > self.levels.get() {
+            // The trace already points at the head for every level above the current ones.
+            self.levels = unsafe { NonZeroUsize::new_unchecked(height) };
+        }
+        let curr_ptr = AugmentedNode::new(key, value, height).alloc();
+        for (level, prev) in trace.into_iter().enumerate().take(height) {
+            let prev = &mut self.links_mut(prev)[level];
+            let curr = unsafe { &mut *curr_ptr.as_ptr() };
+            curr.links[level].next = prev.next.replace(curr_ptr);
+        }
+        self.refresh(&trace, Some(curr_ptr));
+        None
+    }
+
+    /// Removes a value at the given key from the skipmap, returning it if it exists.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Traverses the skipmap and searches for the key, while tracking the towers whose
+        // aggregates might get updated due to the removal.
+        let trace = self.descend(|k| k.borrow() < key);
+        let curr_ptr = self.links(trace[0])[0].next?;
+        if unsafe { curr_ptr.as_ref() }.key.borrow() != key {
+            return None;
+        }
+        let curr = unsafe { AugmentedNode::dealloc(curr_ptr) };
+        for (level, prev) in trace.into_iter().enumerate().take(curr.height) {
+            self.links_mut(prev)[level].next = curr.links[level].next;
+        }
+        while self.levels.get() > 1 && self.head[self.levels.get() - 1].next.is_none() {
+            self.levels = unsafe { NonZeroUsize::new_unchecked(self.levels.get() - 1) };
+        }
+        self.refresh(&trace, None);
+        Some(curr.value)
+    }
+
+    /// Aggregates all entries whose keys are within the given range.
+    pub fn range_fold<Q, B>(&self, range: B) -> M
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        B: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.descend(|k| k.borrow() < start)[0],
+            Bound::Excluded(start) => self.descend(|k| k.borrow() <= start)[0],
+            Bound::Unbounded => None,
+        };
+        let before_end = |key: &K| match range.end_bound() {
+            Bound::Included(end) => key.borrow() <= end,
+            Bound::Excluded(end) => key.borrow() < end,
+            Bound::Unbounded => true,
+        };
+        // Every entry skipped by a link is ordered before the link's target, so the link can be
+        // taken whenever its target doesn't go past the end of the range.
+        let spans_within = |next: Option<NonNull<AugmentedNode<K, V, M, N>>>| match range
+            .end_bound()
+        {
+            Bound::Included(end) | Bound::Excluded(end) => {
+                next.is_some_and(|ptr| unsafe { ptr.as_ref() }.key.borrow() <= end)
+            }
+            Bound::Unbounded => true,
+        };
+        // Moves forward from the first entry in the range using the highest possible level.
+        let mut agg = M::empty();
+        let mut curr = self.links(start)[0].next;
+        while let Some(curr_ptr) = curr {
+            let node = unsafe { curr_ptr.as_ref() };
+            if !before_end(&node.key) {
+                break;
+            }
+            let level = (1..node.height)
+                .rev()
+                .find(|&level| spans_within(node.links[level].next))
+                .unwrap_or(0);
+            agg = agg.combine(&node.links[level].agg);
+            curr = node.links[level].next;
+        }
+        agg
+    }
+
+    /// Traverses the skipmap, descending down all levels, and returning the last encountered tower
+    /// at each level whose key satisfies the given predicate. Levels above the current number of
+    /// levels point at the head.
+    fn descend<F>(&self, before: F) -> [Tower<K, V, M, N>; N]
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut trace = [None; N];
+        let mut prev = None;
+        for level in (0..self.levels.get()).rev() {
+            while let Some(curr_ptr) = self.links(prev)[level].next
+                && before(&unsafe { curr_ptr.as_ref() }.key)
+            {
+                prev = Some(curr_ptr);
+            }
+            trace[level] = prev;
+        }
+        trace
+    }
+
+    /// Recomputes the aggregates of the towers in the trace and of the given tower, which are the
+    /// only ones that can span a modified entry.
+    fn refresh(&mut self, trace: &[Tower<K, V, M, N>; N], curr: Tower<K, V, M, N>) {
+        let curr_height = curr.map_or(0, |ptr| unsafe { ptr.as_ref() }.height);
+        for (level, prev) in trace.iter().enumerate().take(self.levels.get()).skip(1) {
+            self.refresh_link(*prev, level);
+            if level < curr_height {
+                self.refresh_link(curr, level);
+            }
+        }
+    }
+
+    /// Recomputes the aggregate of a tower at a level by combining the aggregates of the links it
+    /// spans on the level below.
+    fn refresh_link(&mut self, tower: Tower<K, V, M, N>, level: usize) {
+        let links = self.links(tower);
+        let end = links[level].next;
+        let mut agg = links[level - 1].agg.clone();
+        let mut curr = links[level - 1].next;
+        while curr != end {
+            let Some(curr_ptr) = curr else {
+                break;
+            };
+            let node = unsafe { curr_ptr.as_ref() };
+            agg = agg.combine(&node.links[level - 1].agg);
+            curr = node.links[level - 1].next;
+        }
+        self.links_mut(tower)[level].agg = agg;
+    }
+
+    /// Determines the height of a new node, growing by at most one level at a time.
+    fn random_height(&mut self) -> usize {
+        let max = self.levels.saturating_add(1).get().min(N);
+        self.bits.height(&mut self.rng, max)
+    }
+
+    fn links(&self, tower: Tower<K, V, M, N>) -> &[Link<K, V, M, N>; N] {
+        tower.map_or(&self.head, |ptr| unsafe { &(*ptr.as_ptr()).links })
+    }
+
+    fn links_mut(&mut self, tower: Tower<K, V, M, N>) -> &mut [Link<K, V, M, N>; N] {
+        tower.map_or(&mut self.head, |ptr| unsafe { &mut (*ptr.as_ptr()).links })
+    }
+}
+
+/// A pointer to either a node or the head when absent.
+type Tower<K, V, M, const N: usize> = Option<NonNull<AugmentedNode<K, V, M, N>>>;
+
+struct Link<K, V, M, const N: usize> {
+    next: Tower<K, V, M, N>,
+    /// The aggregate of the entries from the link's tower upto, but excluding, its target.
+    agg: M,
+}
+
+impl<K, V, M, const N: usize> Link<K, V, M, N>
+where
+    M: Monoid<K, V>,
+{
+    fn new() -> Self {
+        Self {
+            next: None,
+            agg: M::empty(),
+        }
+    }
+}
+
+struct AugmentedNode<K, V, M, const N: usize> {
+    key: K,
+    value: V,
+    height: usize,
+    links: [Link<K, V, M, N>; N],
+}
+
+impl<K, V, M, const N: usize> AugmentedNode<K, V, M, N>
+where
+    M: Monoid<K, V>,
+{
+    fn new(key: K, value: V, height: usize) -> Self {
+        let mut links: [Link<K, V, M, N>; N] = array::from_fn(|_| Link::new());
+        links[0].agg = M::lift(&key, &value);
+        Self {
+            key,
+            value,
+            height,
+            links,
+        }
+    }
+
+    fn alloc(self) -> NonNull<Self> {
+        let ptr = Box::into_raw(Box::new(self));
+        unsafe { NonNull::new_unchecked(ptr) }
+    }
+
+    unsafe fn dealloc(ptr: NonNull<Self>) -> Self {
+        let node = unsafe { Box::from_raw(ptr.as_ptr()) };
+        *node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{AugmentedSkipMap, Count, Monoid};
+
+    /// Concatenates the keys, which checks that aggregates are combined in order.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Keys(Vec<usize>);
+
+    impl Monoid<usize, usize> for Keys {
+        fn empty() -> Self {
+            Self(Vec::new())
+        }
+
+        fn lift(key: &usize, _: &usize) -> Self {
+            Self(vec![*key])
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            Self(self.0.iter().chain(&other.0).copied().collect())
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_range_fold(
+            ops in vec((any::<bool>(), 0..64usize, any::<usize>()), 256),
+            ranges in vec((0..64usize, 0..64usize), 16),
+        ) {
+            let mut skipmap = AugmentedSkipMap::<usize, usize, Keys, _, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (insert, k, v) in ops {
+                if insert {
+                    assert_eq!(skipmap.insert(k, v), btree.insert(k, v));
+                } else {
+                    assert_eq!(skipmap.remove(&k), btree.remove(&k));
+                }
+            }
+            for (start, end) in ranges {
+                let (start, end) = (start.min(end), start.max(end));
+                let expected: Vec<usize> = btree.range(start..end).map(|(k, _)| *k).collect();
+                assert_eq!(skipmap.range_fold(start..end).0, expected);
+                let expected: Vec<usize> = btree.range(start..=end).map(|(k, _)| *k).collect();
+                assert_eq!(skipmap.range_fold(start..=end).0, expected);
+            }
+            let expected: Vec<usize> = btree.keys().copied().collect();
+            assert_eq!(skipmap.range_fold(..).0, expected);
+        }
+
+        #[test]
+        fn test_count(items in vec(any::<usize>(), 256)) {
+            let mut skipmap = AugmentedSkipMap::<usize, (), Count, _, 8>::new();
+            for item in &items {
+                skipmap.insert(*item, ());
+            }
+            let expected = items.iter().collect::<std::collections::BTreeSet<_>>().len();
+            assert_eq!(skipmap.range_fold(..), Count(expected));
+        }
+    }
+}
@@ -0,0 +1,282 @@
+#[cfg(feature = "rayon")]
+use std::ptr;
+use std::{mem, ptr::NonNull};
+
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{NonEmptyStorage, Pool, RandomBits, SkipNode, level_cap};
+
+#[cfg(feature = "rayon")]
+impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
+where
+    T: Send,
+    R: Rng + SeedableRng + Send,
+{
+    /// Creates a storage from chunks of values that are sorted, both within every chunk and from
+    /// one chunk to the next. Gives back the random number generator if there's no value.
+    ///
+    /// Every chunk is turned into linked nodes on its own thread, then the chunks get linked
+    /// together by only changing the last node of each chunk at every level.
+    pub(crate) fn from_sorted_chunks<I, C>(chunks: I) -> Result<Self, R>
+    where
+        I: IntoIterator<Item = C>,
+        C: IntoIterator<Item: Into<T>> + Send,
+    {
+        let mut rng: R = crate::new_rng();
+        let chunks: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| (chunk, R::from_rng(&mut rng)))
+            .collect();
+        let segments: Vec<_> = chunks
+            .into_par_iter()
+            .map(|(chunk, mut rng)| {
+                let mut segment = Segment::default();
+                let mut bits = RandomBits::new();
+                for value in chunk {
+                    let height = bits.height(&mut rng, level_cap::<N>(segment.len + 1));
+                    segment.link(value.into(), height);
+                }
+                segment
+            })
+            .collect();
+        let mut nodes = Segment::default();
+        for segment in segments {
+            nodes.append(segment);
+        }
+        nodes.into_storage(rng)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
+where
+    T: Send,
+    R: Rng,
+{
+    /// Frees the nodes on the threads of rayon's global pool, forgoing the usual drop.
+    ///
+    /// The base level gets split into a few chunks per thread at the nodes of the level whose
+    /// expected number of nodes is closest to the number of chunks, so that finding the chunks only
+    /// takes a short traversal of that level.
+    pub(crate) fn drop_parallel(self) {
+        let chunks = rayon::current_num_threads() * 4;
+        let level = (level_cap::<N>(self.len / chunks) - 1).min(self.levels.get() - 1);
+        if level == 0 {
+            // There are too few nodes to be worth splitting.
+            return drop(self);
+        }
+        let mut storage = mem::ManuallyDrop::new(self);
+        storage.pool.shrink_to(0);
+        let head = unsafe { Box::from_raw(storage.head.as_ptr()) };
+        let mut starts = vec![head[0]];
+        let mut next = head[level];
+        while let Some(curr_ptr) = next {
+            starts.push(Some(curr_ptr));
+            next = unsafe { curr_ptr.as_ref() }.nexts[level];
+        }
+        let ends = starts.iter().skip(1).copied().chain([None]);
+        let chunks: Vec<_> = starts
+            .iter()
+            .copied()
+            .zip(ends)
+            .map(|(first, end)| Chunk { first, end })
+            .collect();
+        chunks.into_par_iter().for_each(Chunk::free);
+        unsafe { ptr::drop_in_place(&raw mut storage.rng) };
+    }
+}
+
+/// A run of nodes at the base level, starting at the first node and ending right before the end,
+/// which is freed on its own thread.
+#[cfg(feature = "rayon")]
+struct Chunk<T, const N: usize> {
+    first: Option<NonNull<SkipNode<T, N>>>,
+    end: Option<NonNull<SkipNode<T, N>>>,
+}
+
+// SAFETY: A chunk is only created for nodes that are no longer reachable through their storage, and
+// no two chunks share a node, so sending it sends the values along with it.
+#[cfg(feature = "rayon")]
+unsafe impl<T, const N: usize> Send for Chunk<T, N> where T: Send {}
+
+#[cfg(feature = "rayon")]
+impl<T, const N: usize> Chunk<T, N> {
+    fn free(self) {
+        let mut next = self.first;
+        while next != self.end
+            && let Some(curr_ptr) = next
+        {
+            next = unsafe { curr_ptr.as_ref() }.nexts[0];
+            unsafe { SkipNode::dealloc(curr_ptr) };
+        }
+    }
+}
+
+/// Creates a storage from values given in order, linking each value after the previous one as
+/// long as they are sorted.
+pub struct Builder<T, R, const N: usize> {
+    rng: R,
+    bits: RandomBits,
+    nodes: Segment<T, N>,
+    /// Whether values must be strictly greater than the previous one.
+    strict: bool,
+}
+
+impl<T, R, const N: usize> Builder<T, R, N>
+where
+    R: Rng + SeedableRng,
+{
+    /// Creates an empty builder. Equal values are only accepted if `strict` is false.
+    pub fn new(strict: bool) -> Self {
+        Self::with_rng(crate::new_rng(), strict)
+    }
+}
+
+impl<T, R, const N: usize> Builder<T, R, N>
+where
+    R: Rng,
+{
+    /// Creates an empty builder whose storage uses the given random number generator.
+    pub const fn with_rng(rng: R, strict: bool) -> Self {
+        Self {
+            rng,
+            bits: RandomBits::new(),
+            nodes: Segment::new(),
+            strict,
+        }
+    }
+}
+
+impl<T, R, const N: usize> Builder<T, R, N>
+where
+    T: Ord,
+    R: Rng,
+{
+    /// Adds a value after the previous ones, giving it back if it's out of order.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if !self.accepts(&value) {
+            return Err(value);
+        }
+        let height = self
+            .bits
+            .height(&mut self.rng, level_cap::<N>(self.nodes.len + 1));
+        self.nodes.link(value, height);
+        Ok(())
+    }
+
+    /// Adds a value after the previous ones, linking its node into the given number of levels,
+    /// which must be between 1 and `N`. Gives the value back if it's out of order.
+    pub fn push_with_height(&mut self, value: T, height: usize) -> Result<(), T> {
+        debug_assert!((1..=N).contains(&height));
+        if !self.accepts(&value) {
+            return Err(value);
+        }
+        self.nodes.link(value, height);
+        Ok(())
+    }
+
+    fn accepts(&self, value: &T) -> bool {
+        self.nodes.lasts[0].is_none_or(|last_ptr| {
+            let last = &unsafe { last_ptr.as_ref() }.value;
+            value > last || (!self.strict && value == last)
+        })
+    }
+
+    /// Creates a storage holding the added values, giving back the random number generator if
+    /// there's none.
+    pub fn finish(self) -> Result<NonEmptyStorage<T, R, N>, R> {
+        self.nodes.into_storage(self.rng)
+    }
+}
+
+/// A sequence of linked nodes, along with the first and the last node at every level.
+struct Segment<T, const N: usize> {
+    firsts: [Option<NonNull<SkipNode<T, N>>>; N],
+    lasts: [Option<NonNull<SkipNode<T, N>>>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> Default for Segment<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Segment<T, N> {
+    fn drop(&mut self) {
+        let mut next = self.firsts[0];
+        while let Some(curr_ptr) = next {
+            next = unsafe { curr_ptr.as_ref() }.nexts[0];
+            unsafe { SkipNode::dealloc(curr_ptr) };
+        }
+    }
+}
+
+// SAFETY: A segment exclusively owns its nodes until they get linked into a storage, so sending it
+// sends the values along with it.
+unsafe impl<T, const N: usize> Send for Segment<T, N> where T: Send {}
+
+impl<T, const N: usize> Segment<T, N> {
+    const fn new() -> Self {
+        Self {
+            firsts: [None; N],
+            lasts: [None; N],
+            len: 0,
+        }
+    }
+
+    /// Adds a node holding the value after every other node, at the given number of levels.
+    fn link(&mut self, value: T, height: usize) {
+        let mut curr_ptr = SkipNode::new(value).alloc();
+        unsafe { curr_ptr.as_mut() }.prev = self.lasts[0];
+        let links = self.firsts.iter_mut().zip(&mut self.lasts);
+        for (level, (first, last)) in links.enumerate().take(height) {
+            match last {
+                Some(last_ptr) => unsafe { last_ptr.as_mut() }.nexts[level] = Some(curr_ptr),
+                None => *first = Some(curr_ptr),
+            }
+            *last = Some(curr_ptr);
+        }
+        self.len += 1;
+    }
+
+    /// Links the nodes of the other segment after the nodes of this segment at every level.
+    #[cfg(feature = "rayon")]
+    fn append(&mut self, mut other: Self) {
+        self.len += mem::take(&mut other.len);
+        let firsts = mem::replace(&mut other.firsts, [None; N]);
+        if let Some(mut first_ptr) = firsts[0] {
+            unsafe { first_ptr.as_mut() }.prev = self.lasts[0];
+        }
+        for (level, (first, last)) in firsts.into_iter().zip(other.lasts).enumerate() {
+            let Some(first) = first else {
+                continue;
+            };
+            match self.lasts[level] {
+                Some(mut last_ptr) => unsafe { last_ptr.as_mut() }.nexts[level] = Some(first),
+                None => self.firsts[level] = Some(first),
+            }
+            self.lasts[level] = last;
+        }
+    }
+
+    /// Creates a storage owning the nodes, giving back the random number generator if there's no
+    /// node.
+    fn into_storage<R>(mut self, rng: R) -> Result<NonEmptyStorage<T, R, N>, R>
+    where
+        R: Rng,
+    {
+        if self.len == 0 {
+            return Err(rng);
+        }
+        // The head skips to the first node at every level.
+        let firsts = mem::replace(&mut self.firsts, [None; N]);
+        let pool = Pool::new();
+        let Some(storage) = NonEmptyStorage::from_links(rng, firsts, self.lasts, self.len, pool) else {
+            unreachable!();
+        };
+        Ok(storage)
+    }
+}
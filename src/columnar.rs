@@ -0,0 +1,115 @@
+//! Columns laid out like the buffers of Arrow arrays without nulls, so that analytical libraries
+//! can take them over without converting every entry.
+//!
+//! Fixed-width values are collected into a vector of the values themselves, which is the data
+//! buffer of an Arrow primitive array. Strings and byte strings are collected into a
+//! [`VariableColumn`], whose buffers are those of Arrow `LargeUtf8` and `LargeBinary` arrays.
+
+/// A type whose values can be collected into a column.
+pub trait Columnar {
+    /// The column holding the values.
+    type Column: Default;
+
+    /// Appends the value at the end of the column.
+    fn push(&self, column: &mut Self::Column);
+}
+
+macro_rules! impl_columnar_for_primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl Columnar for $ty {
+                type Column = Vec<Self>;
+
+                fn push(&self, column: &mut Self::Column) {
+                    column.push(*self);
+                }
+            }
+        )*
+    };
+}
+
+impl_columnar_for_primitive!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl Columnar for String {
+    type Column = VariableColumn;
+
+    fn push(&self, column: &mut Self::Column) {
+        column.push(self.as_bytes());
+    }
+}
+
+impl Columnar for Vec<u8> {
+    type Column = VariableColumn;
+
+    fn push(&self, column: &mut Self::Column) {
+        column.push(self);
+    }
+}
+
+/// A column of variable-length values, stored back to back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableColumn {
+    /// The start of every value within the data, followed by the end of the last value.
+    pub offsets: Vec<i64>,
+    /// The bytes of all values.
+    pub data: Vec<u8>,
+}
+
+impl Default for VariableColumn {
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            data: Vec::new(),
+        }
+    }
+}
+
+impl VariableColumn {
+    /// Returns the number of values in the column.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Returns whether the column holds no value.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the bytes of the value at the given index, if it exists.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let start = usize::try_from(*self.offsets.get(index)?).ok()?;
+        let end = usize::try_from(*self.offsets.get(index + 1)?).ok()?;
+        self.data.get(start..end)
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+        let end = i64::try_from(self.data.len()).expect("column larger than i64::MAX bytes");
+        self.offsets.push(end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{Columnar, VariableColumn};
+
+    proptest! {
+        #[test]
+        fn test_variable_column(values in vec(".*", 0..100)) {
+            let mut column = VariableColumn::default();
+            for value in &values {
+                value.push(&mut column);
+            }
+            assert_eq!(column.len(), values.len());
+            for (i, value) in values.iter().enumerate() {
+                assert_eq!(column.get(i), Some(value.as_bytes()));
+            }
+            assert_eq!(column.get(values.len()), None);
+        }
+    }
+}
@@ -0,0 +1,714 @@
+//! Lock-free ordered collections that can be shared between threads.
+//!
+//! Removed nodes are reclaimed using epoch-based memory reclamation, so they are only freed once
+//! no thread can still be traversing them.
+//!
+//! Every node keeps a chain of the values it has held, each stamped with the time at which it was
+//! written. Older values are only kept while a snapshot might still need them.
+
+#[cfg(feature = "hazard")]
+pub mod hazard;
+mod lazy;
+mod skipmap;
+mod skipset;
+pub mod spmc;
+
+use std::{
+    borrow::Borrow,
+    cmp,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering, fence},
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+pub use lazy::LazySkipSet;
+#[cfg(feature = "async")]
+pub use lazy::{Insert, Remove};
+pub use skipmap::{Entry, Range, SkipMap, Snapshot, SnapshotIter};
+pub use skipset::{SetEntry, SkipSet};
+
+/// Determines the height of a new node based on the number of consecutive one bits in the
+/// representation of a random number, growing by at most one level at a time.
+fn random_height<const N: usize>(seed: &AtomicU64, levels: &AtomicUsize) -> usize {
+    // Uses SplitMix64 since it only needs an atomic counter as its state.
+    const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut random = seed.fetch_add(GAMMA, Ordering::Relaxed).wrapping_add(GAMMA);
+    random = (random ^ (random >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    random = (random ^ (random >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    random ^= random >> 31;
+    let max = levels.load(Ordering::Relaxed).saturating_add(1).min(N);
+    (1..max)
+        .find(|level| random & (1 << level) == 0)
+        .unwrap_or(max)
+}
+
+/// The source of the stripes picked by threads, which are assigned in turn so that concurrent
+/// threads are spread evenly.
+static NEXT_STRIPE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static STRIPE: usize = NEXT_STRIPE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The state updated by every insertion, which is split across threads to avoid contention.
+/// Stripes are aligned so that no two of them share a cache line.
+#[repr(align(128))]
+struct Stripe {
+    /// The state of the generator used for picking node heights.
+    seed: AtomicU64,
+}
+
+/// The links of a node, or of the head, at every level. A link with a set tag belongs to a node
+/// that is being removed and must not be changed anymore.
+type Tower<K, V, const N: usize> = [Atomic<Node<K, V, N>>; N];
+
+struct Storage<K, V, const N: usize> {
+    head: Tower<K, V, N>,
+    /// The number of levels that might contain nodes.
+    levels: AtomicUsize,
+    stripes: Box<[Stripe]>,
+    /// The source of the stamps given to versions and snapshots.
+    clock: AtomicU64,
+    /// The number of snapshots that are alive.
+    snapshots: AtomicUsize,
+}
+
+impl<K, V, const N: usize> Drop for Storage<K, V, N> {
+    fn drop(&mut self) {
+        // Once no operation is running, every node that is still linked is reachable from the
+        // base level, and every unlinked node has already been scheduled for destruction.
+        let guard = unsafe { epoch::unprotected() };
+        let mut curr = self.head[0].load(Ordering::Relaxed, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let next = node.tower[0].load(Ordering::Relaxed, guard);
+            drop(unsafe { curr.into_owned() });
+            curr = next.with_tag(0);
+        }
+    }
+}
+
+impl<K, V, const N: usize> Storage<K, V, N> {
+    fn new(stripes: NonZeroUsize) -> Self {
+        crate::assert_levels::<N>();
+        let stripes = (0..stripes.get())
+            .map(|_| Stripe {
+                seed: AtomicU64::new(crate::random_seed()),
+            })
+            .collect();
+        Self {
+            head: [const { Atomic::null() }; N],
+            levels: AtomicUsize::new(1),
+            stripes,
+            clock: AtomicU64::new(0),
+            snapshots: AtomicUsize::new(0),
+        }
+    }
+
+    /// Calls the given function on every node that hasn't been removed, in order, along with its
+    /// current value.
+    fn for_each<'g, F>(&'g self, guard: &'g Guard, mut visit: F)
+    where
+        F: FnMut(&'g Node<K, V, N>, &'g V),
+    {
+        let mut curr = self.head[0].load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            if let Some(value) = node.value(guard) {
+                visit(node, value);
+            }
+            // The links of a removed node remain valid while the guard is held.
+            curr = node.tower[0].load(Ordering::Acquire, guard).with_tag(0);
+        }
+    }
+
+    /// Returns the value that the node had when the snapshot with the given stamp was taken.
+    fn value_at<'g>(&self, node: &'g Node<K, V, N>, stamp: u64, guard: &'g Guard) -> Option<&'g V> {
+        let mut curr = node.value.load(Ordering::Acquire, guard);
+        while let Some(version) = unsafe { curr.as_ref() } {
+            // Stamps only decrease along the chain.
+            if self.stamp(version) < stamp {
+                return version.value.as_ref();
+            }
+            curr = version.prev.load(Ordering::Acquire, guard);
+        }
+        None
+    }
+
+    /// Registers a new snapshot, returning its stamp. Every version stamped before it is visible
+    /// to the snapshot.
+    fn acquire_snapshot(&self) -> u64 {
+        // Registering first guarantees that writers seeing no snapshot only drop versions
+        // that were overwritten before the snapshot's stamp.
+        self.snapshots.fetch_add(1, Ordering::SeqCst);
+        self.clock.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Makes sure that the version has a stamp, returning it.
+    ///
+    /// A version is stamped after being published. Readers that find it unstamped stamp it
+    /// themselves, so that every snapshot agrees on whether it is visible.
+    fn stamp(&self, version: &Version<V>) -> u64 {
+        let stamp = version.stamp.load(Ordering::SeqCst);
+        if stamp != UNSTAMPED {
+            return stamp;
+        }
+        let now = self.clock.fetch_add(1, Ordering::SeqCst);
+        match version
+            .stamp
+            .compare_exchange(UNSTAMPED, now, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => now,
+            Err(stamp) => stamp,
+        }
+    }
+
+    /// Returns whether the versions stamped so far can be discarded once a newer version exists.
+    fn is_unobserved(&self) -> bool {
+        self.snapshots.load(Ordering::SeqCst) == 0
+    }
+
+    fn random_height(&self) -> usize {
+        // The stripe of a thread is unknown once its thread-local storage is being destroyed.
+        let stripe = STRIPE.try_with(|stripe| *stripe).unwrap_or(0);
+        let stripe = &self.stripes[stripe % self.stripes.len()];
+        random_height::<N>(&stripe.seed, &self.levels)
+    }
+}
+
+impl<K, V, const N: usize> Storage<K, V, N>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+{
+    /// Unregisters a snapshot, discarding the versions and nodes that were only kept for
+    /// snapshots once none is left.
+    fn release_snapshot(&self) {
+        if self.snapshots.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.vacuum(&epoch::pin());
+        }
+    }
+
+    /// Discards the versions that no snapshot can observe anymore, and unlinks the nodes that were
+    /// kept as removed. Stops as soon as a new snapshot gets taken, since it will do the same once
+    /// it's released.
+    fn vacuum(&self, guard: &Guard) {
+        let mut curr = self.head[0].load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let head = node.value.load(Ordering::Acquire, guard);
+            let version = unsafe { head.deref() };
+            self.stamp(version);
+            if !self.is_unobserved() {
+                return;
+            }
+            unsafe { version.truncate(guard) };
+            if version.value.is_none() {
+                node.mark_tower(guard);
+            }
+            curr = node.tower[0].load(Ordering::Acquire, guard).with_tag(0);
+        }
+        self.purge(guard);
+    }
+
+    /// Unlinks every marked node at every level.
+    fn purge(&self, guard: &Guard) {
+        for level in (0..self.levels.load(Ordering::Acquire)).rev() {
+            'retry: loop {
+                let mut pred = &self.head;
+                let mut curr = pred[level].load(Ordering::Acquire, guard);
+                while let Some(node) = unsafe { curr.as_ref() } {
+                    let succ = node.tower[level].load(Ordering::Acquire, guard);
+                    if succ.tag() == 1 {
+                        if pred[level]
+                            .compare_exchange(
+                                curr,
+                                succ.with_tag(0),
+                                Ordering::AcqRel,
+                                Ordering::Acquire,
+                                guard,
+                            )
+                            .is_err()
+                        {
+                            continue 'retry;
+                        }
+                        unsafe { node.release(guard) };
+                        curr = succ.with_tag(0);
+                        continue;
+                    }
+                    pred = &node.tower;
+                    curr = succ;
+                }
+                break;
+            }
+        }
+    }
+}
+
+impl<K, V, const N: usize> Storage<K, V, N>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    /// Returns the node with the given key along with its current value, if it exists.
+    ///
+    /// Unlike a search, this never changes a link nor starts over. Removed nodes are stepped over
+    /// instead of being unlinked, and are never descended from since their links might skip newer
+    /// nodes. Every step moves forward, so the lookup is wait-free.
+    fn get<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<(&'g Node<K, V, N>, &'g V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut pred = &self.head;
+        let mut curr = Shared::null();
+        for level in (0..self.levels.load(Ordering::Acquire)).rev() {
+            curr = pred[level].load(Ordering::Acquire, guard).with_tag(0);
+            while let Some(node) = unsafe { curr.as_ref() } {
+                let succ = node.tower[level].load(Ordering::Acquire, guard);
+                if succ.tag() == 0 {
+                    if node.key.borrow() >= key {
+                        break;
+                    }
+                    pred = &node.tower;
+                }
+                // The links of a removed node remain valid while the guard is held.
+                curr = succ.with_tag(0);
+            }
+        }
+        // Nodes that were removed without being unlinked can precede the one with the same key
+        // that holds the current value.
+        while let Some(node) = unsafe { curr.as_ref() } {
+            if node.key.borrow() != key {
+                return None;
+            }
+            if let Some(value) = node.value(guard) {
+                return Some((node, value));
+            }
+            curr = node.tower[0].load(Ordering::Acquire, guard).with_tag(0);
+        }
+        None
+    }
+
+    /// Inserts the key unless it already exists, in which case its value gets replaced when
+    /// requested. Returns whether the key was inserted.
+    fn insert(&self, key: K, value: V, replace: bool, guard: &Guard) -> bool {
+        let height = self.random_height();
+        // Makes sure that searches cover every level of the new node before it gets linked. The
+        // levels rarely grow, so they are only written when needed to keep their cache line shared.
+        if height > self.levels.load(Ordering::Acquire) {
+            self.levels.fetch_max(height, Ordering::AcqRel);
+        }
+        let mut node = Owned::new(Node::new(key, value, height));
+        let (node, mut pos) = loop {
+            let pos = self.search(&node.key, guard);
+            if let Some(found) = pos.found {
+                if !replace {
+                    return false;
+                }
+                let old = found.value.load(Ordering::Acquire, guard);
+                // The node got removed since being found, so the next search skips it.
+                if unsafe { old.deref() }.value.is_none() {
+                    continue;
+                }
+                let new = node.value.swap(Shared::null(), Ordering::Relaxed, guard);
+                match self.publish(found, old, unsafe { new.into_owned() }, guard) {
+                    Ok(new) => {
+                        if self.is_unobserved() {
+                            unsafe { new.deref().truncate(guard) };
+                        }
+                        return false;
+                    }
+                    Err(new) => node.value.store(new, Ordering::Relaxed),
+                }
+                continue;
+            }
+            // Adds the new node to the base level, which makes it visible to other threads.
+            for (level, succ) in pos.succs.iter().enumerate().take(height) {
+                node.tower[level].store(*succ, Ordering::Relaxed);
+            }
+            match pos.preds[0][0].compare_exchange(
+                pos.succs[0],
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(node) => break (node, pos),
+                Err(err) => node = err.new,
+            }
+        };
+        let node_ref = unsafe { node.deref() };
+        self.stamp(unsafe { node_ref.value.load(Ordering::Acquire, guard).deref() });
+        // Adds the new node to the higher levels, stopping as soon as it gets removed.
+        'build: for level in 1..height {
+            loop {
+                let next = node_ref.tower[level].load(Ordering::Acquire, guard);
+                if next.tag() == 1 {
+                    break 'build;
+                }
+                let succ = pos.succs[level];
+                // The only other change that can be made to the node's links is marking them.
+                if next != succ
+                    && node_ref.tower[level]
+                        .compare_exchange(next, succ, Ordering::AcqRel, Ordering::Acquire, guard)
+                        .is_err()
+                {
+                    break 'build;
+                }
+                node_ref.refs.fetch_add(1, Ordering::Relaxed);
+                if pos.preds[level][level]
+                    .compare_exchange(succ, node, Ordering::Release, Ordering::Relaxed, guard)
+                    .is_ok()
+                {
+                    break;
+                }
+                node_ref.refs.fetch_sub(1, Ordering::Relaxed);
+                pos = self.search(&node_ref.key, guard);
+                if pos.found.is_none_or(|found| !ptr::eq(found, node_ref)) {
+                    break 'build;
+                }
+            }
+        }
+        unsafe { node_ref.release(guard) };
+        // The node might have been removed while being linked, in which case the remover's search
+        // could have missed some of its links.
+        if node_ref.tower[height - 1]
+            .load(Ordering::Acquire, guard)
+            .tag()
+            == 1
+        {
+            self.search(&node_ref.key, guard);
+        }
+        true
+    }
+
+    /// Replaces the value of the node with the given key if the predicate holds for its current
+    /// value. Returns whether it was replaced.
+    fn update_if<Q, F>(&self, key: &Q, mut predicate: F, value: V, guard: &Guard) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnMut(&V) -> bool,
+    {
+        let mut new = Owned::new(Version::new(Some(value)));
+        loop {
+            let Some(node) = self.search(key, guard).found else {
+                return false;
+            };
+            let old = node.value.load(Ordering::Acquire, guard);
+            let Some(value) = &unsafe { old.deref() }.value else {
+                continue;
+            };
+            if !predicate(value) {
+                return false;
+            }
+            match self.publish(node, old, new, guard) {
+                Ok(new) => {
+                    if self.is_unobserved() {
+                        unsafe { new.deref().truncate(guard) };
+                    }
+                    return true;
+                }
+                Err(err) => new = err,
+            }
+        }
+    }
+
+    /// Removes the node with the given key if the predicate holds for its current value, returning
+    /// it along with that value if this call was the one removing it.
+    fn remove_if<'g, Q, F>(
+        &'g self,
+        key: &Q,
+        mut predicate: F,
+        guard: &'g Guard,
+    ) -> Option<(&'g Node<K, V, N>, &'g V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnMut(&V) -> bool,
+    {
+        loop {
+            let node = self.search(key, guard).found?;
+            let old = node.value.load(Ordering::Acquire, guard);
+            let Some(value) = &unsafe { old.deref() }.value else {
+                continue;
+            };
+            if !predicate(value) {
+                return None;
+            }
+            // Publishing a tombstone is the point at which the node is considered removed.
+            let tombstone = Owned::new(Version::new(None));
+            if let Ok(tombstone) = self.publish(node, old, tombstone, guard) {
+                // Otherwise, the node is kept until the last snapshot is released.
+                if self.is_unobserved() {
+                    unsafe { tombstone.deref().truncate(guard) };
+                    node.mark_tower(guard);
+                    self.search(key, guard);
+                }
+                return Some((node, value));
+            }
+        }
+    }
+
+    /// Replaces the latest version of the node with a new one, returning the new version if the
+    /// node's latest version was still the given one.
+    fn publish<'g>(
+        &self,
+        node: &Node<K, V, N>,
+        old: Shared<'g, Version<V>>,
+        new: Owned<Version<V>>,
+        guard: &'g Guard,
+    ) -> Result<Shared<'g, Version<V>>, Owned<Version<V>>> {
+        // Stamps are assigned in the order that versions are published.
+        self.stamp(unsafe { old.deref() });
+        new.prev.store(old, Ordering::Relaxed);
+        match node
+            .value
+            .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire, guard)
+        {
+            Ok(new) => {
+                self.stamp(unsafe { new.deref() });
+                Ok(new)
+            }
+            Err(err) => {
+                err.new.prev.store(Shared::null(), Ordering::Relaxed);
+                Err(err.new)
+            }
+        }
+    }
+
+    /// Traverses the storage, descending down all levels, and returning the last node before the
+    /// key along with the node after it at each level. Removed nodes encountered along the way are
+    /// unlinked, and removed nodes with the same key are skipped.
+    fn search<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Position<'g, K, V, N>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        'retry: loop {
+            let mut preds = [&self.head; N];
+            let mut succs = [Shared::null(); N];
+            let mut pred = &self.head;
+            for level in (0..self.levels.load(Ordering::Acquire)).rev() {
+                let mut curr = pred[level].load(Ordering::Acquire, guard);
+                // The predecessor got removed after being reached.
+                if curr.tag() == 1 {
+                    continue 'retry;
+                }
+                while let Some(node) = unsafe { curr.as_ref() } {
+                    let succ = node.tower[level].load(Ordering::Acquire, guard);
+                    if succ.tag() == 1 {
+                        if pred[level]
+                            .compare_exchange(
+                                curr,
+                                succ.with_tag(0),
+                                Ordering::AcqRel,
+                                Ordering::Acquire,
+                                guard,
+                            )
+                            .is_err()
+                        {
+                            continue 'retry;
+                        }
+                        unsafe { node.release(guard) };
+                        curr = succ.with_tag(0);
+                        continue;
+                    }
+                    match node.key.borrow().cmp(key) {
+                        cmp::Ordering::Greater => break,
+                        cmp::Ordering::Equal => {
+                            let head = unsafe { node.value.load(Ordering::Acquire, guard).deref() };
+                            if head.value.is_some() {
+                                break;
+                            }
+                            // Nodes inserted after this one must get newer stamps, so that
+                            // snapshots never observe the key twice.
+                            self.stamp(head);
+                        }
+                        cmp::Ordering::Less => {}
+                    }
+                    pred = &node.tower;
+                    curr = succ;
+                }
+                preds[level] = pred;
+                succs[level] = curr;
+            }
+            let found = unsafe { succs[0].as_ref() }.filter(|node| node.key.borrow() == key);
+            return Position {
+                found,
+                preds,
+                succs,
+            };
+        }
+    }
+}
+
+/// A reference to a node that holds onto a pinned guard, along with a value that the node had.
+/// Both remain valid even if the node gets removed in the meantime, because the node can't be
+/// reclaimed while the guard is held.
+struct NodeRef<'a, K, V, const N: usize> {
+    guard: Guard,
+    node: NonNull<Node<K, V, N>>,
+    value: NonNull<V>,
+    _storage: PhantomData<&'a Storage<K, V, N>>,
+}
+
+impl<K, V, const N: usize> Clone for NodeRef<'_, K, V, N> {
+    fn clone(&self) -> Self {
+        // Pinning again is fine since the current guard prevents reclamation until then.
+        Self {
+            guard: epoch::pin(),
+            node: self.node,
+            value: self.value,
+            _storage: PhantomData,
+        }
+    }
+}
+
+impl<K, V, const N: usize> NodeRef<'_, K, V, N> {
+    /// Creates a reference to a node and one of its values that were found while the given guard
+    /// was pinned.
+    ///
+    /// # Safety
+    ///
+    /// The node and the value must have been loaded using the given guard from a storage outliving
+    /// the reference.
+    const unsafe fn new(guard: Guard, node: NonNull<Node<K, V, N>>, value: NonNull<V>) -> Self {
+        Self {
+            guard,
+            node,
+            value,
+            _storage: PhantomData,
+        }
+    }
+
+    const fn key(&self) -> &K {
+        &unsafe { self.node.as_ref() }.key
+    }
+
+    const fn value(&self) -> &V {
+        unsafe { self.value.as_ref() }
+    }
+
+    fn is_removed(&self) -> bool {
+        unsafe { self.node.as_ref() }.is_removed(&self.guard)
+    }
+}
+
+/// The result of a search.
+struct Position<'g, K, V, const N: usize> {
+    /// The first node whose key equals the searched key and that hasn't been removed.
+    found: Option<&'g Node<K, V, N>>,
+    /// The links right before the searched key at each level.
+    preds: [&'g Tower<K, V, N>; N],
+    /// The nodes right after the links in `preds`.
+    succs: [Shared<'g, Node<K, V, N>>; N],
+}
+
+/// The stamp of a version that hasn't been stamped yet.
+const UNSTAMPED: u64 = u64::MAX;
+
+/// A value held by a node at some point, linked to the value it replaced.
+struct Version<V> {
+    /// The value, or nothing if the node was removed.
+    value: Option<V>,
+    stamp: AtomicU64,
+    prev: Atomic<Self>,
+}
+
+impl<V> Drop for Version<V> {
+    fn drop(&mut self) {
+        // Drops the older versions iteratively, since chains can get long while snapshots are
+        // alive.
+        let guard = unsafe { epoch::unprotected() };
+        let mut curr = self.prev.swap(Shared::null(), Ordering::Relaxed, guard);
+        while !curr.is_null() {
+            let version = unsafe { curr.into_owned() };
+            curr = version.prev.swap(Shared::null(), Ordering::Relaxed, guard);
+        }
+    }
+}
+
+impl<V> Version<V> {
+    const fn new(value: Option<V>) -> Self {
+        Self {
+            value,
+            stamp: AtomicU64::new(UNSTAMPED),
+            prev: Atomic::null(),
+        }
+    }
+
+    /// Discards the versions older than this one.
+    ///
+    /// # Safety
+    ///
+    /// No snapshot may need the older versions.
+    unsafe fn truncate(&self, guard: &Guard)
+    where
+        V: Send + 'static,
+    {
+        let prev = self.prev.swap(Shared::null(), Ordering::AcqRel, guard);
+        if !prev.is_null() {
+            unsafe { guard.defer_destroy(prev) };
+        }
+    }
+}
+
+struct Node<K, V, const N: usize> {
+    key: K,
+    /// The latest version of the value.
+    value: Atomic<Version<V>>,
+    height: usize,
+    /// The number of levels that the node is linked into, plus one while it is being inserted.
+    refs: AtomicUsize,
+    tower: Tower<K, V, N>,
+}
+
+impl<K, V, const N: usize> Drop for Node<K, V, N> {
+    fn drop(&mut self) {
+        let guard = unsafe { epoch::unprotected() };
+        let value = self.value.load(Ordering::Relaxed, guard);
+        if !value.is_null() {
+            drop(unsafe { value.into_owned() });
+        }
+    }
+}
+
+impl<K, V, const N: usize> Node<K, V, N> {
+    fn new(key: K, value: V, height: usize) -> Self {
+        Self {
+            key,
+            value: Atomic::new(Version::new(Some(value))),
+            height,
+            refs: AtomicUsize::new(2),
+            tower: [const { Atomic::null() }; N],
+        }
+    }
+
+    /// Returns the current value unless the node has been removed.
+    fn value<'g>(&'g self, guard: &'g Guard) -> Option<&'g V> {
+        let version = self.value.load(Ordering::Acquire, guard);
+        unsafe { version.deref() }.value.as_ref()
+    }
+
+    fn is_removed(&self, guard: &Guard) -> bool {
+        self.value(guard).is_none()
+    }
+
+    /// Marks every link of the node, from the top down, so that they can't be changed anymore.
+    fn mark_tower(&self, guard: &Guard) {
+        for link in self.tower[..self.height].iter().rev() {
+            link.fetch_or(1, Ordering::AcqRel, guard);
+        }
+    }
+
+    /// Drops a reference to the node, scheduling its destruction once it's no longer linked into
+    /// any level nor being inserted.
+    unsafe fn release(&self, guard: &Guard) {
+        if self.refs.fetch_sub(1, Ordering::Release) == 1 {
+            fence(Ordering::Acquire);
+            unsafe { guard.defer_destroy(Shared::from(ptr::from_ref(self))) };
+        }
+    }
+}
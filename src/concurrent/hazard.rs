@@ -0,0 +1,234 @@
+//! Lock-free collections whose removed nodes are reclaimed using hazard pointers.
+//!
+//! Before dereferencing a node, a thread publishes a pointer to it in a hazard slot, and a removed
+//! node is only freed once no slot points to it. Unlike with epochs, a stalled thread only keeps
+//! alive the few nodes it was looking at, so the amount of unreclaimed memory stays bounded.
+
+mod skipmap;
+
+use std::{
+    cell::RefCell,
+    mem, ptr,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence},
+    },
+};
+
+pub use skipmap::{Entry, SkipMap};
+
+/// The minimum number of retired objects that a thread accumulates before trying to reclaim them.
+const RECLAIM_THRESHOLD: usize = 64;
+
+/// The slots of every thread, which are never freed but get reused once released.
+static SLOTS: AtomicPtr<Slot> = AtomicPtr::new(ptr::null_mut());
+
+/// The number of slots in `SLOTS`.
+static SLOT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The retired objects left behind by threads that exited before they could be reclaimed.
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static LOCAL: Local = const {
+        Local {
+            slots: RefCell::new(Vec::new()),
+            retired: RefCell::new(Vec::new()),
+        }
+    };
+}
+
+/// A location in which a thread publishes the pointer it's about to dereference.
+struct Slot {
+    ptr: AtomicPtr<()>,
+    active: AtomicBool,
+    /// The next slot in `SLOTS`, which never changes once the slot is published.
+    next: *const Self,
+}
+
+impl Slot {
+    /// Claims a released slot, or allocates a new one if none is available.
+    fn acquire() -> &'static Self {
+        let mut curr = SLOTS.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { curr.as_ref() } {
+            if !slot.active.load(Ordering::Relaxed)
+                && slot
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return slot;
+            }
+            curr = slot.next.cast_mut();
+        }
+        let slot = Box::into_raw(Box::new(Self {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            next: ptr::null(),
+        }));
+        let mut head = SLOTS.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*slot).next = head };
+            match SLOTS.compare_exchange_weak(head, slot, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(new) => head = new,
+            }
+        }
+        SLOT_COUNT.fetch_add(1, Ordering::Relaxed);
+        unsafe { &*slot }
+    }
+
+    /// Returns every pointer that is currently protected, sorted.
+    fn protected() -> Vec<*mut ()> {
+        // Pairs with the fence in `HazardPointer::protect`, so that either the protecting thread
+        // sees that the object was unlinked, or it's seen here.
+        fence(Ordering::SeqCst);
+        let mut protected = Vec::new();
+        let mut curr = SLOTS.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { curr.as_ref() } {
+            let ptr = slot.ptr.load(Ordering::Acquire);
+            if !ptr.is_null() {
+                protected.push(ptr);
+            }
+            curr = slot.next.cast_mut();
+        }
+        protected.sort_unstable();
+        protected
+    }
+}
+
+/// The state kept by every thread.
+struct Local {
+    /// The slots released by the thread, which can be reused without searching for one.
+    slots: RefCell<Vec<&'static Slot>>,
+    /// The objects retired by the thread that haven't been reclaimed yet.
+    retired: RefCell<Vec<Retired>>,
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        for slot in self.slots.get_mut().drain(..) {
+            slot.active.store(false, Ordering::Release);
+        }
+        let retired = reclaim(mem::take(self.retired.get_mut()));
+        if !retired.is_empty() {
+            lock_orphans().extend(retired);
+        }
+    }
+}
+
+/// A handle to a slot through which a thread protects a single object from being reclaimed.
+struct HazardPointer {
+    slot: &'static Slot,
+}
+
+impl Drop for HazardPointer {
+    fn drop(&mut self) {
+        self.reset();
+        let slot = self.slot;
+        if LOCAL
+            .try_with(|local| local.slots.borrow_mut().push(slot))
+            .is_err()
+        {
+            slot.active.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl HazardPointer {
+    fn new() -> Self {
+        let slot = LOCAL
+            .try_with(|local| local.slots.borrow_mut().pop())
+            .ok()
+            .flatten()
+            .unwrap_or_else(Slot::acquire);
+        Self { slot }
+    }
+
+    /// Publishes the pointer, without checking whether the object is still reachable. This is
+    /// only sound if the object is already protected by another hazard pointer.
+    fn protect<T>(&self, ptr: *mut T) {
+        self.slot.ptr.store(ptr.cast(), Ordering::Release);
+        fence(Ordering::SeqCst);
+    }
+
+    /// Publishes the pointer, returning whether the link still points to the object afterward, in
+    /// which case the object can't have been retired before being protected.
+    fn protect_from<T>(&self, link: &AtomicPtr<T>, ptr: *mut T) -> bool {
+        self.protect(ptr);
+        link.load(Ordering::Acquire) == ptr
+    }
+
+    fn reset(&self) {
+        self.slot.ptr.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+/// An object that has been unlinked and will be freed once it's not protected anymore.
+struct Retired {
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+// SAFETY: Retired objects are required to be sendable when they get retired.
+unsafe impl Send for Retired {}
+
+/// Schedules a boxed object to be freed once no hazard pointer protects it.
+///
+/// # Safety
+///
+/// The object must have been allocated with [`Box`] and must not be reachable by threads that
+/// haven't protected it yet.
+unsafe fn retire<T>(ptr: *mut T)
+where
+    T: Send + 'static,
+{
+    unsafe fn drop_boxed<T>(ptr: *mut ()) {
+        drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+    }
+    let retired = Retired {
+        ptr: ptr.cast(),
+        drop: drop_boxed::<T>,
+    };
+    let mut retired = Some(retired);
+    let _ = LOCAL.try_with(|local| {
+        let mut pending = local.retired.borrow_mut();
+        pending.extend(retired.take());
+        let threshold = RECLAIM_THRESHOLD.max(2 * SLOT_COUNT.load(Ordering::Relaxed));
+        if pending.len() < threshold {
+            return;
+        }
+        let mut batch = mem::take(&mut *pending);
+        // Destructors might retire other objects, so the list must not be borrowed while reclaiming.
+        drop(pending);
+        if let Ok(mut orphans) = ORPHANS.try_lock() {
+            batch.append(&mut orphans);
+        }
+        let batch = reclaim(batch);
+        local.retired.borrow_mut().extend(batch);
+    });
+    if let Some(retired) = retired {
+        lock_orphans().push(retired);
+    }
+}
+
+/// Frees the retired objects that aren't protected, returning the others.
+fn reclaim(mut retired: Vec<Retired>) -> Vec<Retired> {
+    let protected = Slot::protected();
+    let mut freed = Vec::new();
+    retired.retain(|object| {
+        let keep = protected.binary_search(&object.ptr).is_ok();
+        if !keep {
+            freed.push((object.ptr, object.drop));
+        }
+        keep
+    });
+    for (ptr, drop) in freed {
+        unsafe { drop(ptr) };
+    }
+    retired
+}
+
+fn lock_orphans() -> std::sync::MutexGuard<'static, Vec<Retired>> {
+    ORPHANS.lock().unwrap_or_else(PoisonError::into_inner)
+}
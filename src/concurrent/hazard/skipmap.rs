@@ -0,0 +1,613 @@
+use std::{
+    array,
+    borrow::Borrow,
+    fmt,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering, fence},
+};
+
+use super::{HazardPointer, retire};
+use crate::concurrent::random_height;
+
+/// A lock-free ordered map backed by a skiplist, whose removed nodes are reclaimed using hazard
+/// pointers.
+pub struct SkipMap<K, V, const N: usize> {
+    /// The links of the head, at every level. A link with a set tag belongs to a node that is being
+    /// removed and must not be changed anymore.
+    head: Tower<K, V, N>,
+    /// The number of levels that might contain nodes.
+    levels: AtomicUsize,
+    /// The state of the generator used for picking node heights.
+    seed: AtomicU64,
+}
+
+impl<K, V, const N: usize> Default for SkipMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> Drop for SkipMap<K, V, N> {
+    fn drop(&mut self) {
+        // Once no operation is running, every node that is still linked is reachable from the
+        // base level, and every unlinked node has already been retired.
+        let mut curr = *self.head[0].get_mut();
+        while !curr.is_null() {
+            let mut node = unsafe { Box::from_raw(curr) };
+            curr = unmarked(*node.tower[0].get_mut());
+        }
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for SkipMap<K, V, N>
+where
+    K: Ord + Send + fmt::Debug + 'static,
+    V: Send + fmt::Debug + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut shields = Shields::new();
+        let value = HazardPointer::new();
+        // Keeps the last visited node protected while searching for the next one.
+        let last = HazardPointer::new();
+        let mut prev: Option<*const K> = None;
+        while let Some(node) = self.next_after(prev.map(|key| unsafe { &*key }), &mut shields) {
+            last.protect(ptr::from_ref(node).cast_mut());
+            if let Some(value) = node.value(&value) {
+                map.entry(&node.key, value);
+            }
+            prev = Some(ptr::from_ref(&node.key));
+        }
+        map.finish()
+    }
+}
+
+impl<K, V, const N: usize> SkipMap<K, V, N> {
+    /// Creates an empty skipmap.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            head: array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            levels: AtomicUsize::new(1),
+            seed: AtomicU64::new(crate::random_seed()),
+        }
+    }
+}
+
+impl<K, V, const N: usize> SkipMap<K, V, N>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut shields = Shields::new();
+        self.search(key, &mut shields)
+            .found
+            .is_some_and(|node| !is_marked(node.value.load(Ordering::Acquire)))
+    }
+
+    /// Returns an entry referencing the given key and its current value.
+    pub fn get<Q>(&self, key: &Q) -> Option<Entry<'_, K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut shields = Shields::new();
+        let node = self.search(key, &mut shields).found?;
+        let value = HazardPointer::new();
+        let found = NonNull::from(node.value(&value)?);
+        Some(Entry::new(node, found, value))
+    }
+
+    /// Inserts a value at the given key into the skipmap, replacing the existing value. Returns
+    /// whether the key didn't exist.
+    pub fn insert(&self, key: K, value: V) -> bool {
+        let height = random_height::<N>(&self.seed, &self.levels);
+        // Makes sure that searches cover every level of the new node before it gets linked.
+        self.levels.fetch_max(height, Ordering::AcqRel);
+        let node = Box::into_raw(Box::new(Node::new(key, value, height)));
+        let node_ref = unsafe { &*node };
+        let mut shields = Shields::new();
+        let value = HazardPointer::new();
+        let mut pos = loop {
+            let pos = self.search(&node_ref.key, &mut shields);
+            if let Some(found) = pos.found {
+                let old = found.value.load(Ordering::Acquire);
+                if !is_marked(old) {
+                    // Protects the old value from being reused while it's being replaced.
+                    if !value.protect_from(&found.value, old) {
+                        continue;
+                    }
+                    let new = node_ref.value.swap(ptr::null_mut(), Ordering::Relaxed);
+                    if found
+                        .value
+                        .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+                    {
+                        unsafe { retire(old) };
+                        drop(unsafe { Box::from_raw(node) });
+                        return false;
+                    }
+                    node_ref.value.store(new, Ordering::Relaxed);
+                    continue;
+                }
+                // Helps the ongoing removal, so that the next search unlinks the node.
+                found.mark_tower();
+                continue;
+            }
+            // Adds the new node to the base level, which makes it visible to other threads.
+            for (level, succ) in pos.succs.iter().enumerate().take(height) {
+                node_ref.tower[level].store(*succ, Ordering::Relaxed);
+            }
+            if pos.preds[0][0]
+                .compare_exchange(pos.succs[0], node, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                break pos;
+            }
+        };
+        // Adds the new node to the higher levels, stopping as soon as it gets removed.
+        'build: for level in 1..height {
+            loop {
+                let next = node_ref.tower[level].load(Ordering::Acquire);
+                if is_marked(next) {
+                    break 'build;
+                }
+                let succ = pos.succs[level];
+                // The only other change that can be made to the node's links is marking them.
+                if next != succ
+                    && node_ref.tower[level]
+                        .compare_exchange(next, succ, Ordering::AcqRel, Ordering::Acquire)
+                        .is_err()
+                {
+                    break 'build;
+                }
+                node_ref.refs.fetch_add(1, Ordering::Relaxed);
+                if pos.preds[level][level]
+                    .compare_exchange(succ, node, Ordering::Release, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    break;
+                }
+                node_ref.refs.fetch_sub(1, Ordering::Relaxed);
+                pos = self.search(&node_ref.key, &mut shields);
+                if pos.found.is_none_or(|found| !ptr::eq(found, node_ref)) {
+                    break 'build;
+                }
+            }
+        }
+        // The node might have been removed while being linked, in which case the remover's search
+        // could have missed some of its links.
+        let removed = is_marked(node_ref.tower[height - 1].load(Ordering::Acquire));
+        if removed {
+            self.search(&node_ref.key, &mut shields);
+        }
+        unsafe { Node::release(node) };
+        true
+    }
+
+    /// Removes a value at the given key from the skipmap, returning an entry referencing the
+    /// removed key and value if it existed.
+    pub fn remove<Q>(&self, key: &Q) -> Option<Entry<'_, K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut shields = Shields::new();
+        let value = HazardPointer::new();
+        loop {
+            let node = self.search(key, &mut shields).found?;
+            let old = node.value.load(Ordering::Acquire);
+            if is_marked(old) {
+                return None;
+            }
+            if !value.protect_from(&node.value, old) {
+                continue;
+            }
+            // Tagging the value is the point at which the node is considered removed.
+            if node
+                .value
+                .compare_exchange(old, marked(old), Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                let entry = Entry::new(node, NonNull::from(unsafe { &(*old).0 }), value);
+                node.mark_tower();
+                self.search(key, &mut shields);
+                return Some(entry);
+            }
+        }
+    }
+
+    /// Returns the first node with a key greater than the given one, or the first node when no key
+    /// is given.
+    ///
+    /// The returned node is protected by the given shields until they're used again.
+    fn next_after<'s>(
+        &self,
+        key: Option<&K>,
+        shields: &'s mut Shields<N>,
+    ) -> Option<&'s Node<K, V, N>> {
+        'retry: loop {
+            let mut curr = if let Some(key) = key {
+                self.search(key, shields).succs[0]
+            } else {
+                let curr = self.head[0].load(Ordering::Acquire);
+                if !shields.curr.protect_from(&self.head[0], curr) {
+                    continue 'retry;
+                }
+                curr
+            };
+            loop {
+                let node = unsafe { curr.as_ref() }?;
+                if key.is_none_or(|key| node.key > *key) {
+                    return Some(node);
+                }
+                let next = node.tower[0].load(Ordering::Acquire);
+                if is_marked(next) {
+                    continue 'retry;
+                }
+                mem::swap(&mut shields.pred, &mut shields.curr);
+                if !shields.curr.protect_from(&node.tower[0], next) {
+                    continue 'retry;
+                }
+                curr = next;
+            }
+        }
+    }
+
+    /// Traverses the skipmap, descending down all levels, and returning the last node before the
+    /// key along with the node after it at each level. Removed nodes encountered along the way are
+    /// unlinked.
+    ///
+    /// Every returned node is protected by the given shields until they're used again.
+    fn search<'s, Q>(&'s self, key: &Q, shields: &'s mut Shields<N>) -> Position<'s, K, V, N>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        'retry: loop {
+            let mut preds = [&self.head; N];
+            let mut succs = [ptr::null_mut(); N];
+            let mut pred = &self.head;
+            for level in (0..self.levels.load(Ordering::Acquire)).rev() {
+                let mut curr = pred[level].load(Ordering::Acquire);
+                // The predecessor got removed after being reached.
+                if is_marked(curr) || !shields.curr.protect_from(&pred[level], curr) {
+                    continue 'retry;
+                }
+                while let Some(node) = unsafe { curr.as_ref() } {
+                    let succ = node.tower[level].load(Ordering::Acquire);
+                    if is_marked(succ) {
+                        let succ = unmarked(succ);
+                        if pred[level]
+                            .compare_exchange(curr, succ, Ordering::AcqRel, Ordering::Acquire)
+                            .is_err()
+                        {
+                            continue 'retry;
+                        }
+                        unsafe { Node::release(curr) };
+                        curr = succ;
+                        if !shields.curr.protect_from(&pred[level], curr) {
+                            continue 'retry;
+                        }
+                        continue;
+                    }
+                    if node.key.borrow() >= key {
+                        break;
+                    }
+                    pred = &node.tower;
+                    mem::swap(&mut shields.pred, &mut shields.curr);
+                    curr = succ;
+                    if !shields.curr.protect_from(&pred[level], curr) {
+                        continue 'retry;
+                    }
+                }
+                // Both nodes are already protected, so protecting them again always succeeds.
+                shields.preds[level].protect(ptr::from_ref(pred).cast_mut());
+                shields.succs[level].protect(curr);
+                preds[level] = pred;
+                succs[level] = curr;
+            }
+            let found = unsafe { succs[0].as_ref() }.filter(|node| node.key.borrow() == key);
+            return Position {
+                found,
+                preds,
+                succs,
+            };
+        }
+    }
+}
+
+/// A reference to a key and a value in a [`SkipMap`].
+///
+/// The value is the one associated with the key when the entry was created. Both remain accessible
+/// while the entry is held, even if they get replaced or removed by another thread. Unlike with
+/// epochs, holding an entry only delays the reclamation of its own node and value.
+pub struct Entry<'a, K, V, const N: usize> {
+    /// Keeps the node from being reclaimed.
+    _node_hazard: HazardPointer,
+    /// Keeps the value from being reclaimed when it gets replaced.
+    _value_hazard: HazardPointer,
+    node: NonNull<Node<K, V, N>>,
+    value: NonNull<V>,
+    _map: PhantomData<&'a SkipMap<K, V, N>>,
+}
+
+impl<K, V, const N: usize> fmt::Debug for Entry<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Entry")
+            .field(self.key())
+            .field(self.value())
+            .finish()
+    }
+}
+
+impl<K, V, const N: usize> Entry<'_, K, V, N> {
+    /// Creates an entry from a protected node and a value protected by the given hazard pointer.
+    fn new(node: &Node<K, V, N>, value: NonNull<V>, value_hazard: HazardPointer) -> Self {
+        let node_hazard = HazardPointer::new();
+        node_hazard.protect(ptr::from_ref(node).cast_mut());
+        Self {
+            _node_hazard: node_hazard,
+            _value_hazard: value_hazard,
+            node: NonNull::from(node),
+            value,
+            _map: PhantomData,
+        }
+    }
+
+    /// Returns a shared reference to the key.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        &unsafe { self.node.as_ref() }.key
+    }
+
+    /// Returns a shared reference to the value.
+    #[must_use]
+    pub const fn value(&self) -> &V {
+        unsafe { self.value.as_ref() }
+    }
+
+    /// Returns whether the key has been removed from the skipmap.
+    #[must_use]
+    pub fn is_removed(&self) -> bool {
+        is_marked(unsafe { self.node.as_ref() }.value.load(Ordering::Acquire))
+    }
+}
+
+/// The links of a node, or of the head, at every level.
+type Tower<K, V, const N: usize> = [AtomicPtr<Node<K, V, N>>; N];
+
+/// The hazard pointers used by a single operation.
+struct Shields<const N: usize> {
+    /// Protects the node whose links are being followed.
+    pred: HazardPointer,
+    /// Protects the node being looked at.
+    curr: HazardPointer,
+    /// Protect the nodes in `Position::preds`.
+    preds: [HazardPointer; N],
+    /// Protect the nodes in `Position::succs`.
+    succs: [HazardPointer; N],
+}
+
+impl<const N: usize> Shields<N> {
+    fn new() -> Self {
+        Self {
+            pred: HazardPointer::new(),
+            curr: HazardPointer::new(),
+            preds: array::from_fn(|_| HazardPointer::new()),
+            succs: array::from_fn(|_| HazardPointer::new()),
+        }
+    }
+}
+
+/// The result of a search.
+struct Position<'s, K, V, const N: usize> {
+    /// The first node whose key equals the searched key.
+    found: Option<&'s Node<K, V, N>>,
+    /// The links right before the searched key at each level.
+    preds: [&'s Tower<K, V, N>; N],
+    /// The nodes right after the links in `preds`.
+    succs: [*mut Node<K, V, N>; N],
+}
+
+/// A heap-allocated value. The alignment leaves the lowest bit of pointers to it free, which is
+/// used for marking the node owning the value as removed.
+#[repr(align(2))]
+struct Value<V>(V);
+
+struct Node<K, V, const N: usize> {
+    key: K,
+    value: AtomicPtr<Value<V>>,
+    height: usize,
+    /// The number of levels that the node is linked into, plus one while it is being inserted.
+    refs: AtomicUsize,
+    tower: Tower<K, V, N>,
+}
+
+impl<K, V, const N: usize> Drop for Node<K, V, N> {
+    fn drop(&mut self) {
+        let value = unmarked(*self.value.get_mut());
+        if !value.is_null() {
+            drop(unsafe { Box::from_raw(value) });
+        }
+    }
+}
+
+impl<K, V, const N: usize> Node<K, V, N> {
+    fn new(key: K, value: V, height: usize) -> Self {
+        Self {
+            key,
+            value: AtomicPtr::new(Box::into_raw(Box::new(Value(value)))),
+            height,
+            refs: AtomicUsize::new(2),
+            tower: array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+        }
+    }
+
+    /// Protects the current value using the given hazard pointer, returning it unless the node has
+    /// been removed.
+    fn value<'a>(&'a self, hazard: &'a HazardPointer) -> Option<&'a V> {
+        loop {
+            let value = self.value.load(Ordering::Acquire);
+            if is_marked(value) {
+                return None;
+            }
+            if hazard.protect_from(&self.value, value) {
+                return Some(unsafe { &(*value).0 });
+            }
+        }
+    }
+
+    /// Marks every link of the node, from the top down, so that they can't be changed anymore.
+    fn mark_tower(&self) {
+        for link in self.tower[..self.height].iter().rev() {
+            link.fetch_or(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Drops a reference to the node, retiring it once it's no longer linked into any level nor
+    /// being inserted.
+    ///
+    /// The node is taken as the pointer it was allocated as, since a pointer derived from a shared
+    /// reference can't be used to free it.
+    unsafe fn release(node: *mut Self)
+    where
+        K: Send + 'static,
+        V: Send + 'static,
+    {
+        if unsafe { &(*node).refs }.fetch_sub(1, Ordering::Release) == 1 {
+            fence(Ordering::Acquire);
+            unsafe { retire(node) };
+        }
+    }
+}
+
+fn is_marked<T>(ptr: *mut T) -> bool {
+    ptr.addr() & 1 == 1
+}
+
+fn marked<T>(ptr: *mut T) -> *mut T {
+    ptr.map_addr(|addr| addr | 1)
+}
+
+fn unmarked<T>(ptr: *mut T) -> *mut T {
+    ptr.map_addr(|addr| addr & !1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::SkipMap;
+
+    proptest! {
+        #[test]
+        fn test_insert_get_remove(
+            ops in vec((any::<bool>(), 0..64usize, any::<usize>()), crate::TEST_SCALE.min(256)),
+        ) {
+            let skipmap = SkipMap::<usize, usize, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (insert, k, v) in ops {
+                if insert {
+                    assert_eq!(skipmap.insert(k, v), btree.insert(k, v).is_none());
+                } else {
+                    let entry = skipmap.remove(&k);
+                    assert_eq!(entry.map(|e| *e.value()), btree.remove(&k));
+                }
+                assert_eq!(skipmap.get(&k).map(|e| *e.value()), btree.get(&k).copied());
+            }
+            assert_eq!(format!("{skipmap:?}"), format!("{btree:?}"));
+        }
+    }
+
+    #[test]
+    fn test_entry_outlives_removal() {
+        let skipmap = SkipMap::<usize, String, 8>::new();
+        skipmap.insert(0, String::from("old"));
+        let entry = skipmap.get(&0).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| {
+                skipmap.insert(0, String::from("new"));
+                assert!(skipmap.remove(&0).is_some_and(|e| e.value() == "new"));
+            });
+        });
+        assert!(entry.is_removed());
+        assert_eq!((entry.key(), entry.value().as_str()), (&0, "old"));
+    }
+
+    #[test]
+    fn test_bounded_garbage() {
+        const ROUNDS: usize = if cfg!(miri) { 2000 } else { 10_000 };
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+
+        impl Counted {
+            fn new() -> Self {
+                LIVE.fetch_add(1, Ordering::Relaxed);
+                Self
+            }
+        }
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                LIVE.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+
+        let skipmap = SkipMap::<usize, Counted, 8>::new();
+        skipmap.insert(0, Counted::new());
+        // Unlike a pinned epoch, a held entry doesn't keep every removed node alive.
+        let _entry = skipmap.get(&0).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..ROUNDS {
+                    skipmap.insert(i, Counted::new());
+                    skipmap.remove(&i);
+                }
+            });
+        });
+        assert!(LIVE.load(Ordering::Relaxed) < 1000);
+    }
+
+    #[test]
+    fn test_concurrent_overwrite() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const ITEMS: usize = crate::TEST_SCALE;
+        let skipmap = SkipMap::<usize, usize, 16>::new();
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for i in 0..ITEMS {
+                        skipmap.insert(i, t);
+                        if i % 2 == 0 {
+                            skipmap.remove(&i);
+                        }
+                    }
+                });
+            }
+        });
+        for i in (0..ITEMS).filter(|i| i % 2 == 1) {
+            assert!(skipmap.get(&i).is_some_and(|e| *e.value() < THREADS));
+        }
+    }
+}
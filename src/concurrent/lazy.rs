@@ -0,0 +1,453 @@
+#[cfg(feature = "async")]
+mod future;
+
+use std::{
+    array,
+    borrow::Borrow,
+    fmt, hint, ptr,
+    sync::{
+        Mutex, MutexGuard, PoisonError, TryLockError,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use super::random_height;
+
+#[cfg(feature = "async")]
+pub use future::{Insert, Remove};
+
+/// A concurrent ordered set backed by a skiplist whose nodes are protected by locks.
+///
+/// Lookups never lock, while insertions and removals only lock the nodes right around the value
+/// being changed, validating that they are still adjacent once locked. This is simpler than the
+/// lock-free [`SkipSet`](super::SkipSet) at the cost of blocking when writers contend.
+///
+/// With the `async` feature, insertions and removals can also be awaited, yielding instead of
+/// blocking when a lock is contended.
+pub struct LazySkipSet<T, const N: usize> {
+    head: Node<T, N>,
+    /// The number of levels that might contain nodes.
+    levels: AtomicUsize,
+    /// The state of the generator used for picking node heights.
+    seed: AtomicU64,
+    /// The tasks waiting for the locks held by other threads to be released.
+    #[cfg(feature = "async")]
+    waiters: future::Waiters,
+}
+
+impl<T, const N: usize> Default for LazySkipSet<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for LazySkipSet<T, N> {
+    fn drop(&mut self) {
+        // Unlinked nodes have already been scheduled for destruction.
+        let guard = unsafe { epoch::unprotected() };
+        let mut curr = self.head.tower[0].load(Ordering::Relaxed, guard);
+        while !curr.is_null() {
+            let node = unsafe { curr.into_owned() };
+            curr = node.tower[0].load(Ordering::Relaxed, guard);
+        }
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for LazySkipSet<T, N>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = epoch::pin();
+        let mut set = f.debug_set();
+        let mut curr = self.head.tower[0].load(Ordering::Acquire, &guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            if node.is_present() {
+                set.entry(node.key());
+            }
+            curr = node.tower[0].load(Ordering::Acquire, &guard);
+        }
+        set.finish()
+    }
+}
+
+impl<T, const N: usize> LazySkipSet<T, N> {
+    /// Creates an empty skipset.
+    #[must_use]
+    pub fn new() -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            head: Node::new(None, N),
+            levels: AtomicUsize::new(1),
+            seed: AtomicU64::new(crate::random_seed()),
+            #[cfg(feature = "async")]
+            waiters: future::Waiters::default(),
+        }
+    }
+}
+
+impl<T, const N: usize> LazySkipSet<T, N>
+where
+    T: Ord + Send + 'static,
+{
+    /// Returns whether a value exists in the skipset.
+    pub fn contains<U>(&self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        self.search(value, &guard)
+            .found
+            .is_some_and(|(_, node)| node.is_present())
+    }
+
+    /// Inserts a value into the skipset, returning whether it didn't exist.
+    pub fn insert(&self, value: T) -> bool {
+        let guard = &epoch::pin();
+        let height = random_height::<N>(&self.seed, &self.levels);
+        // Makes sure that searches cover every level of the new node before it gets linked.
+        self.levels.fetch_max(height, Ordering::AcqRel);
+        let node = Owned::new(Node::new(Some(value), height));
+        waited(self.insert_node(node, true, guard).map_err(|_| Contended))
+    }
+
+    /// Removes a value from the skipset, returning whether it existed.
+    pub fn remove<U>(&self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let guard = &epoch::pin();
+        let Some(node) = waited(self.mark(value, true, guard)) else {
+            return false;
+        };
+        while !waited(self.unlink(node, true, guard)) {}
+        true
+    }
+
+    /// Links a node whose height is already covered by the searches, returning whether its value
+    /// didn't exist. Gives the node back if a lock is contended without waiting for it.
+    fn insert_node(
+        &self,
+        node: Owned<Node<T, N>>,
+        wait: bool,
+        guard: &Guard,
+    ) -> Result<bool, Owned<Node<T, N>>> {
+        let height = node.height;
+        loop {
+            let pos = self.search(node.key(), guard);
+            if let Some((_, found)) = pos.found {
+                if !found.marked.load(Ordering::Acquire) {
+                    // The value is only considered present once linked at every level.
+                    while !found.linked.load(Ordering::Acquire) {
+                        hint::spin_loop();
+                    }
+                    return Ok(false);
+                }
+                #[cfg(feature = "async")]
+                if found.abandoned.swap(false, Ordering::AcqRel) {
+                    // Finishes the removal of a future that was dropped before unlinking the node.
+                    if self.unlink_abandoned(found, wait, guard).is_err() {
+                        return Err(node);
+                    }
+                }
+                // The node is being removed, so the next search won't find it anymore.
+                continue;
+            }
+            let locks = match pos.lock(height, wait, |level, pred, succ| {
+                succ.is_none_or(|succ| !succ.marked.load(Ordering::Acquire))
+                    && pred.tower[level].load(Ordering::Acquire, guard).as_raw()
+                        == succ.map_or(ptr::null(), ptr::from_ref)
+            }) {
+                Ok(Some(locks)) => locks,
+                Ok(None) => continue,
+                Err(Contended) => return Err(node),
+            };
+            for (level, succ) in pos.succs.iter().enumerate().take(height) {
+                node.tower[level].store(*succ, Ordering::Relaxed);
+            }
+            let node = node.into_shared(guard);
+            for (level, pred) in pos.preds.iter().enumerate().take(height) {
+                pred.tower[level].store(node, Ordering::Release);
+            }
+            unsafe { node.deref() }
+                .linked
+                .store(true, Ordering::Release);
+            drop(locks);
+            #[cfg(feature = "async")]
+            self.waiters.wake();
+            return Ok(true);
+        }
+    }
+
+    /// Marks the node holding the value as being removed, returning it unless the value doesn't
+    /// exist. Only the thread that marked a node can unlink it, so it stays valid until then,
+    /// unless a dropped future hands the node over to the next insertion of an equal value.
+    fn mark<'g, U>(
+        &'g self,
+        value: &U,
+        wait: bool,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g Node<T, N>>, Contended>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let Some((level, node)) = self.search(value, guard).found else {
+            return Ok(None);
+        };
+        // Only nodes linked at every level and not being removed can be removed.
+        if !node.is_present() || level != node.height - 1 {
+            return Ok(None);
+        }
+        let lock = node.lock(wait).ok_or(Contended)?;
+        if node.marked.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        // Marking the node is the point at which the value is considered removed. Its links can't
+        // change afterwards, since predecessors are validated to not be marked once locked.
+        node.marked.store(true, Ordering::Release);
+        drop(lock);
+        Ok(Some(node))
+    }
+
+    /// Unlinks a marked node from every level, returning whether it got unlinked or the nodes
+    /// around it changed concurrently.
+    fn unlink(&self, node: &Node<T, N>, wait: bool, guard: &Guard) -> Result<bool, Contended> {
+        let pos = self.search(node.key(), guard);
+        let Some(locks) = pos.lock(node.height, wait, |level, pred, _| {
+            ptr::eq(
+                pred.tower[level].load(Ordering::Acquire, guard).as_raw(),
+                node,
+            )
+        })?
+        else {
+            return Ok(false);
+        };
+        for level in (0..node.height).rev() {
+            let succ = node.tower[level].load(Ordering::Acquire, guard);
+            pos.preds[level].tower[level].store(succ, Ordering::Release);
+        }
+        drop(locks);
+        #[cfg(feature = "async")]
+        self.waiters.wake();
+        unsafe { guard.defer_destroy(Shared::from(ptr::from_ref(node))) };
+        Ok(true)
+    }
+
+    /// Unlinks a node marked by a future, or whose removal was handed over by a dropped one,
+    /// handing it over to the next insertion if a lock is contended.
+    #[cfg(feature = "async")]
+    fn unlink_abandoned(
+        &self,
+        node: &Node<T, N>,
+        wait: bool,
+        guard: &Guard,
+    ) -> Result<(), Contended> {
+        loop {
+            match self.unlink(node, wait, guard) {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(Contended) => {
+                    node.abandoned.store(true, Ordering::Release);
+                    return Err(Contended);
+                }
+            }
+        }
+    }
+
+    /// Traverses the skipset without locking, descending down all levels, and returning the last
+    /// node before the value along with the node after it at each level.
+    fn search<'g, U>(&'g self, value: &U, guard: &'g Guard) -> Position<'g, T, N>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let mut found = None;
+        let mut preds = [&self.head; N];
+        let mut succs = [Shared::null(); N];
+        let mut pred = &self.head;
+        for level in (0..self.levels.load(Ordering::Acquire)).rev() {
+            let mut curr = pred.tower[level].load(Ordering::Acquire, guard);
+            while let Some(node) = unsafe { curr.as_ref() } {
+                if node.key().borrow() >= value {
+                    break;
+                }
+                pred = node;
+                curr = node.tower[level].load(Ordering::Acquire, guard);
+            }
+            if found.is_none() {
+                found = unsafe { curr.as_ref() }
+                    .filter(|node| node.key().borrow() == value)
+                    .map(|node| (level, node));
+            }
+            preds[level] = pred;
+            succs[level] = curr;
+        }
+        Position {
+            found,
+            preds,
+            succs,
+        }
+    }
+}
+
+/// The result of a search.
+struct Position<'g, T, const N: usize> {
+    /// The highest level at which a node with an equal value was found, along with that node.
+    found: Option<(usize, &'g Node<T, N>)>,
+    /// The nodes right before the searched value at each level.
+    preds: [&'g Node<T, N>; N],
+    /// The nodes right after the nodes in `preds`.
+    succs: [Shared<'g, Node<T, N>>; N],
+}
+
+impl<'g, T, const N: usize> Position<'g, T, N> {
+    /// Locks the predecessors at the lowest levels, returning the locks if every predecessor is
+    /// still present and passes the given validation. Fails if a lock is held by another thread
+    /// and isn't waited for.
+    fn lock<F>(
+        &self,
+        height: usize,
+        wait: bool,
+        mut validate: F,
+    ) -> Result<Option<Locks<'g, N>>, Contended>
+    where
+        F: FnMut(usize, &Node<T, N>, Option<&Node<T, N>>) -> bool,
+    {
+        // Locking from the bottom up acquires the locks in descending order, which can't deadlock.
+        let mut locks = array::from_fn(|_| None);
+        let mut locked = None;
+        for (level, lock) in locks.iter_mut().enumerate().take(height) {
+            let pred = self.preds[level];
+            if locked.is_none_or(|locked| !ptr::eq(pred, locked)) {
+                *lock = Some(pred.lock(wait).ok_or(Contended)?);
+                locked = Some(pred);
+            }
+            let succ = unsafe { self.succs[level].as_ref() };
+            if pred.marked.load(Ordering::Acquire) || !validate(level, pred, succ) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(locks))
+    }
+}
+
+/// The locks held on the predecessors at every level.
+type Locks<'g, const N: usize> = [Option<MutexGuard<'g, ()>>; N];
+
+/// The error returned when a lock is held by another thread, and waiting for it wasn't requested.
+struct Contended;
+
+/// Returns the result of an operation that waited for every lock, so that it couldn't have failed
+/// because of contention.
+fn waited<T>(result: Result<T, Contended>) -> T {
+    result.unwrap_or_else(|Contended| unreachable!("locks are waited for"))
+}
+
+struct Node<T, const N: usize> {
+    /// The value, or nothing for the head.
+    key: Option<T>,
+    height: usize,
+    lock: Mutex<()>,
+    /// Whether the node is being removed.
+    marked: AtomicBool,
+    /// Whether the node has been linked at every level.
+    linked: AtomicBool,
+    /// Whether the node was marked by a future that got dropped before unlinking it, so that the
+    /// next thread to take this flag unlinks it instead.
+    #[cfg(feature = "async")]
+    abandoned: AtomicBool,
+    tower: [Atomic<Self>; N],
+}
+
+impl<T, const N: usize> Node<T, N> {
+    const fn new(key: Option<T>, height: usize) -> Self {
+        Self {
+            key,
+            height,
+            lock: Mutex::new(()),
+            marked: AtomicBool::new(false),
+            linked: AtomicBool::new(false),
+            #[cfg(feature = "async")]
+            abandoned: AtomicBool::new(false),
+            tower: [const { Atomic::null() }; N],
+        }
+    }
+
+    const fn key(&self) -> &T {
+        self.key.as_ref().expect("the head is never compared")
+    }
+
+    /// Locks the node, returning nothing if it's locked by another thread and waiting wasn't
+    /// requested.
+    fn lock(&self, wait: bool) -> Option<MutexGuard<'_, ()>> {
+        // The lock doesn't protect any data, so poisoning can be ignored.
+        if wait {
+            return Some(self.lock.lock().unwrap_or_else(PoisonError::into_inner));
+        }
+        match self.lock.try_lock() {
+            Ok(lock) => Some(lock),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    fn is_present(&self) -> bool {
+        self.linked.load(Ordering::Acquire) && !self.marked.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, thread};
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::LazySkipSet;
+
+    proptest! {
+        #[test]
+        fn test_insert_remove(items in vec(any::<usize>(), crate::TEST_SCALE.min(256))) {
+            let skipset = LazySkipSet::<usize, 8>::new();
+            let mut btree = BTreeSet::new();
+            for item in &items {
+                assert_eq!(skipset.insert(*item), btree.insert(*item));
+            }
+            for item in &items {
+                assert!(skipset.contains(item));
+            }
+            for item in items.iter().rev() {
+                assert_eq!(skipset.remove(item), btree.remove(item));
+                assert!(!skipset.contains(item));
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_dedup() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const ITEMS: usize = crate::TEST_SCALE;
+        let skipset = LazySkipSet::<usize, 16>::new();
+        let inserted: usize = thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| s.spawn(|| (0..ITEMS).filter(|i| skipset.insert(*i)).count()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(inserted, ITEMS);
+        let removed: usize = thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| s.spawn(|| (0..ITEMS).filter(|i| skipset.remove(i)).count()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(removed, ITEMS);
+        for i in 0..ITEMS {
+            assert!(!skipset.contains(&i));
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    borrow::Borrow,
+    fmt,
+    pin::Pin,
+    ptr,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering, fence},
+    },
+    task::{Context, Poll, Waker},
+};
+
+use crossbeam_epoch::{self as epoch, Owned};
+
+use super::{Contended, LazySkipSet, Node, random_height};
+
+impl<T, const N: usize> LazySkipSet<T, N>
+where
+    T: Ord + Send + 'static,
+{
+    /// Inserts a value into the skipset, returning a future that resolves to whether the value
+    /// didn't exist.
+    ///
+    /// Instead of blocking the thread when a lock is held by another thread, the future yields
+    /// until a lock gets released.
+    pub fn insert_async(&self, value: T) -> Insert<'_, T, N> {
+        let height = random_height::<N>(&self.seed, &self.levels);
+        // Makes sure that searches cover every level of the new node before it gets linked.
+        self.levels.fetch_max(height, Ordering::AcqRel);
+        Insert {
+            skipset: self,
+            node: Some(Owned::new(Node::new(Some(value), height))),
+        }
+    }
+
+    /// Removes a value from the skipset, returning a future that resolves to whether the value
+    /// existed.
+    ///
+    /// Instead of blocking the thread when a lock is held by another thread, the future yields
+    /// until a lock gets released. Once the future has marked the value as removed, the value
+    /// stays removed even if the future gets dropped, and dropping it never blocks: a node that
+    /// it couldn't unlink right away is unlinked by the next insertion of an equal value.
+    pub const fn remove_async<'a, U>(&'a self, value: &'a U) -> Remove<'a, T, U, N>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        Remove {
+            skipset: self,
+            value: Some(value),
+            marked: None,
+        }
+    }
+}
+
+/// A future inserting a value into a [`LazySkipSet`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Insert<'a, T, const N: usize> {
+    skipset: &'a LazySkipSet<T, N>,
+    /// The node to link, or nothing once it was linked.
+    node: Option<Owned<Node<T, N>>>,
+}
+
+impl<T, const N: usize> fmt::Debug for Insert<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Insert").finish_non_exhaustive()
+    }
+}
+
+impl<T, const N: usize> Future for Insert<'_, T, N>
+where
+    T: Ord + Send + 'static,
+{
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.skipset.waiters.poll(cx, || {
+            let node = this.node.take().expect("`Insert` polled after completion");
+            match this.skipset.insert_node(node, false, &epoch::pin()) {
+                Ok(inserted) => Some(inserted),
+                Err(node) => {
+                    this.node = Some(node);
+                    None
+                }
+            }
+        })
+    }
+}
+
+/// A future removing a value from a [`LazySkipSet`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Remove<'a, T, U, const N: usize>
+where
+    T: Ord + Send + 'static,
+    U: ?Sized,
+{
+    skipset: &'a LazySkipSet<T, N>,
+    /// The value to remove, or nothing once it was removed.
+    value: Option<&'a U>,
+    /// The node that was marked as being removed but hasn't been unlinked yet.
+    marked: Option<&'a Node<T, N>>,
+}
+
+impl<T, U, const N: usize> Drop for Remove<'_, T, U, N>
+where
+    T: Ord + Send + 'static,
+    U: ?Sized,
+{
+    fn drop(&mut self) {
+        // A marked node would prevent the value from ever being inserted again, so it's either
+        // unlinked without waiting for locks, or handed over to the next insertion.
+        if let Some(node) = self.marked {
+            let _ = self.skipset.unlink_abandoned(node, false, &epoch::pin());
+        }
+    }
+}
+
+impl<T, U, const N: usize> fmt::Debug for Remove<'_, T, U, N>
+where
+    T: Ord + Send + 'static,
+    U: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Remove").finish_non_exhaustive()
+    }
+}
+
+impl<T, U, const N: usize> Future for Remove<'_, T, U, N>
+where
+    T: Borrow<U> + Ord + Send + 'static,
+    U: Ord + ?Sized,
+{
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let skipset = this.skipset;
+        skipset.waiters.poll(cx, || {
+            let guard = &epoch::pin();
+            let value = this.value.expect("`Remove` polled after completion");
+            let node = match this.marked {
+                Some(node) => node,
+                None => match skipset.mark(value, false, guard) {
+                    // Only this future can unlink the node, or hand it over once dropped, so it can't
+                    // be reclaimed until then.
+                    Ok(Some(node)) => unsafe { &*ptr::from_ref(node) },
+                    Ok(None) => {
+                        this.value = None;
+                        return Some(false);
+                    }
+                    Err(Contended) => return None,
+                },
+            };
+            this.marked = Some(node);
+            loop {
+                match skipset.unlink(node, false, guard) {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(Contended) => return None,
+                }
+            }
+            this.marked = None;
+            this.value = None;
+            Some(true)
+        })
+    }
+}
+
+/// The tasks waiting for locks to be released.
+#[derive(Default)]
+pub(super) struct Waiters {
+    /// The number of wakers, which can be read without locking.
+    count: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Waiters {
+    /// Makes an attempt that fails when a lock is contended, making another one after registering
+    /// the task so that it gets woken up if the lock is released in the meantime.
+    fn poll<R, F>(&self, cx: &Context<'_>, mut attempt: F) -> Poll<R>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        if let Some(output) = attempt() {
+            return Poll::Ready(output);
+        }
+        self.register(cx.waker());
+        attempt().map_or(Poll::Pending, Poll::Ready)
+    }
+
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+        self.count.store(wakers.len(), Ordering::Relaxed);
+        drop(wakers);
+        // Pairs with the fence in `wake`, so that either the next attempt sees the lock released,
+        // or the thread releasing it sees the waker.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Wakes every waiting task, after locks have been released.
+    pub(super) fn wake(&self) {
+        fence(Ordering::SeqCst);
+        if self.count.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        let woken = std::mem::take(&mut *wakers);
+        self.count.store(0, Ordering::Relaxed);
+        drop(wakers);
+        for waker in woken {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        pin::pin,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+    };
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::LazySkipSet;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_insert_remove(items in vec(any::<usize>(), crate::TEST_SCALE.min(256))) {
+            let skipset = LazySkipSet::<usize, 8>::new();
+            let mut btree = BTreeSet::new();
+            for item in &items {
+                assert_eq!(block_on(skipset.insert_async(*item)), btree.insert(*item));
+            }
+            for item in &items {
+                assert!(skipset.contains(item));
+            }
+            for item in items.iter().rev() {
+                assert_eq!(block_on(skipset.remove_async(item)), btree.remove(item));
+                assert!(!skipset.contains(item));
+            }
+        }
+    }
+
+    #[test]
+    fn test_wake_on_release() {
+        struct Count(AtomicUsize);
+
+        impl Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let skipset = LazySkipSet::<usize, 8>::new();
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&count));
+        let mut cx = Context::from_waker(&waker);
+        let mut insert = pin!(skipset.insert_async(1));
+        let lock = skipset.head.lock(true);
+        assert_eq!(insert.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(count.0.load(Ordering::Relaxed), 0);
+        drop(lock);
+        assert!(skipset.insert(0));
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        assert_eq!(insert.as_mut().poll(&mut cx), Poll::Ready(true));
+        assert!(skipset.contains(&1));
+    }
+
+    #[test]
+    fn test_drop_while_removing() {
+        let skipset = LazySkipSet::<usize, 8>::new();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        assert!(skipset.insert(0));
+        let lock = skipset.head.lock(true);
+        {
+            let mut remove = pin!(skipset.remove_async(&0));
+            assert_eq!(remove.as_mut().poll(&mut cx), Poll::Pending);
+            assert!(!skipset.contains(&0));
+        }
+        // The future got dropped without waiting for the lock, leaving the node to the insertion.
+        assert!(!skipset.contains(&0));
+        assert!(!skipset.remove(&0));
+        drop(lock);
+        assert!(skipset.insert(0));
+        assert!(skipset.contains(&0));
+    }
+
+    #[test]
+    fn test_concurrent_dedup() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const ITEMS: usize = crate::TEST_SCALE;
+        let skipset = LazySkipSet::<usize, 16>::new();
+        // Half of the threads block on contended locks while the others wait asynchronously.
+        let inserted: usize = thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let skipset = &skipset;
+                    s.spawn(move || {
+                        (0..ITEMS)
+                            .filter(|i| {
+                                if t % 2 == 0 {
+                                    skipset.insert(*i)
+                                } else {
+                                    block_on(skipset.insert_async(*i))
+                                }
+                            })
+                            .count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(inserted, ITEMS);
+        let removed: usize = thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let skipset = &skipset;
+                    s.spawn(move || {
+                        (0..ITEMS)
+                            .filter(|i| {
+                                if t % 2 == 0 {
+                                    skipset.remove(i)
+                                } else {
+                                    block_on(skipset.remove_async(i))
+                                }
+                            })
+                            .count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(removed, ITEMS);
+        for i in 0..ITEMS {
+            assert!(!skipset.contains(&i));
+        }
+    }
+}
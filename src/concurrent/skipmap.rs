@@ -0,0 +1,791 @@
+use std::{
+    borrow::Borrow,
+    fmt,
+    iter::FusedIterator,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    ops::{Bound, RangeBounds, RangeFull},
+    ptr::{self, NonNull},
+    sync::atomic::Ordering,
+};
+
+use crossbeam_epoch::{self as epoch, Guard};
+
+use super::{Node, NodeRef, Storage};
+
+/// A lock-free ordered map backed by a skiplist.
+///
+/// Lookups are wait-free: they never retry nor help other threads finish their changes, so they
+/// complete in a bounded number of steps even while writers keep contending.
+pub struct SkipMap<K, V, const N: usize>(Storage<K, V, N>);
+
+impl<K, V, const N: usize> Default for SkipMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for SkipMap<K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = epoch::pin();
+        let mut map = f.debug_map();
+        self.0.for_each(&guard, |node, value| {
+            map.entry(&node.key, value);
+        });
+        map.finish()
+    }
+}
+
+impl<K, V, const N: usize> SkipMap<K, V, N> {
+    /// Creates an empty skipmap.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Storage::new(NonZeroUsize::MIN))
+    }
+
+    /// Creates an empty skipmap that splits the state updated by every insertion into the given
+    /// number of stripes, each used by a different subset of threads.
+    ///
+    /// This reduces contention when many threads insert at once, at the cost of some memory for
+    /// each stripe. Using about as many stripes as there are inserting threads works best.
+    #[must_use]
+    pub fn with_stripes(stripes: NonZeroUsize) -> Self {
+        Self(Storage::new(stripes))
+    }
+}
+
+impl<K, V, const N: usize> SkipMap<K, V, N>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        self.0.get(key, &guard).is_some()
+    }
+
+    /// Returns an entry referencing the given key and its current value.
+    pub fn get<Q>(&self, key: &Q) -> Option<Entry<'_, K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let (node, value) = self.0.get(key, &guard)?;
+        let (node, value) = (NonNull::from(node), NonNull::from(value));
+        Some(Entry(unsafe { NodeRef::new(guard, node, value) }))
+    }
+
+    /// Inserts a value at the given key into the skipmap, replacing the existing value. Returns
+    /// whether the key didn't exist.
+    pub fn insert(&self, key: K, value: V) -> bool {
+        let guard = epoch::pin();
+        self.0.insert(key, value, true, &guard)
+    }
+
+    /// Inserts a value at the given key into the skipmap unless the key already exists. Returns
+    /// whether the value was inserted.
+    pub fn insert_if_absent(&self, key: K, value: V) -> bool {
+        let guard = epoch::pin();
+        self.0.insert(key, value, false, &guard)
+    }
+
+    /// Replaces the value at the given key if the predicate holds for the current value. Returns
+    /// whether the value was replaced.
+    ///
+    /// The predicate is called again whenever the value changes concurrently before it could be
+    /// replaced.
+    pub fn update_if<Q, F>(&self, key: &Q, predicate: F, value: V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnMut(&V) -> bool,
+    {
+        let guard = epoch::pin();
+        self.0.update_if(key, predicate, value, &guard)
+    }
+
+    /// Removes a value at the given key from the skipmap, returning an entry referencing the
+    /// removed key and value if it existed.
+    pub fn remove<Q>(&self, key: &Q) -> Option<Entry<'_, K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_if(key, |_| true)
+    }
+
+    /// Removes a value at the given key from the skipmap if the predicate holds for it, returning
+    /// an entry referencing the removed key and value.
+    ///
+    /// The predicate is called again whenever the value changes concurrently before it could be
+    /// removed.
+    pub fn remove_if<Q, F>(&self, key: &Q, predicate: F) -> Option<Entry<'_, K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnMut(&V) -> bool,
+    {
+        let guard = epoch::pin();
+        let (node, value) = self.0.remove_if(key, predicate, &guard)?;
+        let (node, value) = (NonNull::from(node), NonNull::from(value));
+        Some(Entry(unsafe { NodeRef::new(guard, node, value) }))
+    }
+
+    /// Returns an iterator over the entries of the skipmap, in order.
+    ///
+    /// The iterator observes the skipmap as it changes, with the same guarantees as
+    /// [`SkipMap::range`].
+    pub fn iter(&self) -> Range<'_, K, RangeFull, K, V, N> {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the entries whose keys are within the given range, in order.
+    ///
+    /// Unlike a [`Snapshot`], the iterator doesn't observe a consistent view of the skipmap, but
+    /// it tolerates concurrent changes:
+    /// - Every key that exists from the creation of the iterator until the iterator moves past it
+    ///   is yielded exactly once.
+    /// - Keys inserted or removed during the iteration might or might not be yielded, but never
+    ///   more than once.
+    /// - Keys are yielded in strictly ascending order, each with the value that it had when the
+    ///   iterator reached it.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, Q, R, K, V, N>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let guard = epoch::pin();
+        let mut iter = Range {
+            storage: &self.0,
+            bounds: range,
+            next: ptr::null(),
+            last: ptr::null(),
+            guard,
+            _bound: PhantomData,
+        };
+        iter.next = iter.seek();
+        iter
+    }
+
+    /// Takes a snapshot of the skipmap, which keeps observing the entries that exist right now
+    /// regardless of the changes made afterwards.
+    ///
+    /// Replaced values and removed keys are kept around until every snapshot is dropped, so
+    /// snapshots should be short-lived.
+    pub fn snapshot(&self) -> Snapshot<'_, K, V, N> {
+        Snapshot {
+            storage: &self.0,
+            stamp: self.0.acquire_snapshot(),
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize> IntoIterator for &'a SkipMap<K, V, N>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    type Item = Entry<'a, K, V, N>;
+    type IntoIter = Range<'a, K, RangeFull, K, V, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A reference to a key and a value in a [`SkipMap`].
+///
+/// The value is the one associated with the key when the entry was created. Both remain accessible
+/// while the entry is held, even if they get replaced or removed by another thread. Holding an entry
+/// delays the reclamation of all removed nodes, so it should be dropped as soon as possible.
+#[derive(Clone)]
+pub struct Entry<'a, K, V, const N: usize>(NodeRef<'a, K, V, N>);
+
+impl<K, V, const N: usize> fmt::Debug for Entry<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Entry")
+            .field(self.key())
+            .field(self.value())
+            .finish()
+    }
+}
+
+impl<K, V, const N: usize> Entry<'_, K, V, N> {
+    /// Returns a shared reference to the key.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        self.0.key()
+    }
+
+    /// Returns a shared reference to the value.
+    #[must_use]
+    pub const fn value(&self) -> &V {
+        self.0.value()
+    }
+
+    /// Returns whether the key has been removed from the skipmap.
+    #[must_use]
+    pub fn is_removed(&self) -> bool {
+        self.0.is_removed()
+    }
+}
+
+/// A consistent view of a [`SkipMap`] at the time it was taken.
+///
+/// Every change made to the skipmap either happened before the snapshot, in which case the
+/// snapshot observes it, or after, in which case it doesn't, even if multiple keys were changed
+/// concurrently.
+pub struct Snapshot<'a, K, V, const N: usize>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+{
+    storage: &'a Storage<K, V, N>,
+    stamp: u64,
+}
+
+impl<K, V, const N: usize> Drop for Snapshot<'_, K, V, N>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+{
+    fn drop(&mut self) {
+        self.storage.release_snapshot();
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for Snapshot<'_, K, V, N>
+where
+    K: fmt::Debug + Send + 'static,
+    V: fmt::Debug + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<K, V, const N: usize> Snapshot<'_, K, V, N>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+{
+    /// Returns an iterator over the entries of the snapshot, in order.
+    #[must_use]
+    pub fn iter(&self) -> SnapshotIter<'_, K, V, N> {
+        let guard = epoch::pin();
+        let head = self.storage.head[0]
+            .load(Ordering::Acquire, &guard)
+            .as_raw();
+        SnapshotIter {
+            storage: self.storage,
+            stamp: self.stamp,
+            next: head,
+            guard,
+        }
+    }
+}
+
+impl<'s, K, V, const N: usize> IntoIterator for &'s Snapshot<'_, K, V, N>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+{
+    type Item = (&'s K, &'s V);
+    type IntoIter = SnapshotIter<'s, K, V, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`Snapshot`], in order.
+pub struct SnapshotIter<'a, K, V, const N: usize> {
+    storage: &'a Storage<K, V, N>,
+    stamp: u64,
+    next: *const Node<K, V, N>,
+    /// Keeps the nodes that the snapshot doesn't observe alive while they are traversed.
+    guard: Guard,
+}
+
+impl<K, V, const N: usize> fmt::Debug for SnapshotIter<'_, K, V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotIter").finish_non_exhaustive()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for SnapshotIter<'a, K, V, N> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Nodes and versions observed by a snapshot aren't reclaimed until it's dropped.
+            let node: &'a Node<K, V, N> = unsafe { self.next.as_ref()? };
+            self.next = node.tower[0]
+                .load(Ordering::Acquire, &self.guard)
+                .with_tag(0)
+                .as_raw();
+            if let Some(value) = self.storage.value_at(node, self.stamp, &self.guard) {
+                return Some((&node.key, unsafe { &*ptr::from_ref(value) }));
+            }
+        }
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for SnapshotIter<'_, K, V, N> {}
+
+/// An iterator over the entries of a [`SkipMap`] within a range, in order.
+pub struct Range<'a, Q, R, K, V, const N: usize>
+where
+    Q: ?Sized,
+{
+    storage: &'a Storage<K, V, N>,
+    bounds: R,
+    next: *const Node<K, V, N>,
+    /// The node that was yielded last.
+    last: *const Node<K, V, N>,
+    /// Keeps the traversed nodes alive, even after they get removed.
+    guard: Guard,
+    _bound: PhantomData<fn(&Q)>,
+}
+
+impl<Q, R, K, V, const N: usize> fmt::Debug for Range<'_, Q, R, K, V, N>
+where
+    Q: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Range").finish_non_exhaustive()
+    }
+}
+
+impl<Q, R, K, V, const N: usize> Range<'_, Q, R, K, V, N>
+where
+    K: Borrow<Q> + Ord + Send + 'static,
+    V: Send + 'static,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    /// Returns the first node that might have to be yielded next, found by searching from the
+    /// top levels again.
+    fn seek(&self) -> *const Node<K, V, N> {
+        let guard = &self.guard;
+        if let Some(last) = unsafe { self.last.as_ref() } {
+            return self.storage.search::<K>(&last.key, guard).succs[0].as_raw();
+        }
+        match self.bounds.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => {
+                self.storage.search(start, guard).succs[0].as_raw()
+            }
+            Bound::Unbounded => self.storage.head[0].load(Ordering::Acquire, guard).as_raw(),
+        }
+    }
+
+    /// Returns whether the key comes after the last yielded key, and after the start of the range.
+    fn is_unseen(&self, key: &K) -> bool {
+        if let Some(last) = unsafe { self.last.as_ref() } {
+            return *key > last.key;
+        }
+        match self.bounds.start_bound() {
+            Bound::Included(start) => key.borrow() >= start,
+            Bound::Excluded(start) => key.borrow() > start,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn is_before_end(&self, key: &K) -> bool {
+        match self.bounds.end_bound() {
+            Bound::Included(end) => key.borrow() <= end,
+            Bound::Excluded(end) => key.borrow() < end,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'a, Q, R, K, V, const N: usize> Iterator for Range<'a, Q, R, K, V, N>
+where
+    K: Borrow<Q> + Ord + Send + 'static,
+    V: Send + 'static,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = Entry<'a, K, V, N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.next.as_ref()? };
+            if !self.is_before_end(&node.key) {
+                self.next = ptr::null();
+                return None;
+            }
+            let succ = node.tower[0].load(Ordering::Acquire, &self.guard);
+            if succ.tag() == 1 {
+                // The link of a removed node doesn't change anymore, so it might skip nodes that
+                // got inserted after it. Searching again can't skip any node that is still linked.
+                self.next = self.seek();
+                continue;
+            }
+            self.next = succ.as_raw();
+            if !self.is_unseen(&node.key) {
+                continue;
+            }
+            if let Some(value) = node.value(&self.guard) {
+                self.last = node;
+                // Pinning again is fine since the iterator's guard prevents reclamation until then.
+                let (node, value) = (NonNull::from(node), NonNull::from(value));
+                return Some(Entry(unsafe { NodeRef::new(epoch::pin(), node, value) }));
+            }
+        }
+    }
+}
+
+impl<Q, R, K, V, const N: usize> FusedIterator for Range<'_, Q, R, K, V, N>
+where
+    K: Borrow<Q> + Ord + Send + 'static,
+    V: Send + 'static,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, num::NonZeroUsize, ops::Bound, thread};
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::SkipMap;
+
+    proptest! {
+        #[test]
+        fn test_insert_get_remove(
+            ops in vec((any::<bool>(), 0..64usize, any::<usize>()), crate::TEST_SCALE.min(256)),
+        ) {
+            let skipmap = SkipMap::<usize, usize, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (insert, k, v) in ops {
+                if insert {
+                    assert_eq!(skipmap.insert(k, v), btree.insert(k, v).is_none());
+                } else {
+                    let entry = skipmap.remove(&k);
+                    assert_eq!(entry.map(|e| *e.value()), btree.remove(&k));
+                }
+                assert_eq!(skipmap.get(&k).map(|e| *e.value()), btree.get(&k).copied());
+            }
+        }
+
+        #[test]
+        fn test_snapshot(ops in vec((any::<bool>(), 0..64usize, any::<usize>()), 256)) {
+            let skipmap = SkipMap::<usize, usize, 8>::new();
+            let mut btree = BTreeMap::new();
+            let mut snapshots = Vec::new();
+            for (i, (insert, k, v)) in ops.into_iter().enumerate() {
+                if insert {
+                    skipmap.insert(k, v);
+                    btree.insert(k, v);
+                } else {
+                    skipmap.remove(&k);
+                    btree.remove(&k);
+                }
+                if i % 32 == 0 {
+                    snapshots.push((skipmap.snapshot(), btree.clone()));
+                }
+                if i % 64 == 63 {
+                    snapshots.remove(0);
+                }
+            }
+            for (snapshot, btree) in &snapshots {
+                assert!(snapshot.iter().eq(btree.iter()));
+            }
+        }
+
+        #[test]
+        fn test_range(items in vec((0..64usize, any::<usize>()), 64), start in 0..64usize, len in 0..64usize) {
+            let skipmap = SkipMap::<usize, usize, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (k, v) in items {
+                skipmap.insert(k, v);
+                btree.insert(k, v);
+            }
+            let end = start + len;
+            let ranges = [
+                (Bound::Unbounded, Bound::Unbounded),
+                (Bound::Included(start), Bound::Excluded(end)),
+                (Bound::Included(start), Bound::Included(end)),
+                (Bound::Unbounded, Bound::Excluded(end)),
+                (Bound::Excluded(start), Bound::Unbounded),
+            ];
+            for range in ranges {
+                let entries = skipmap.range(range).map(|e| (*e.key(), *e.value()));
+                assert!(entries.eq(btree.range(range).map(|(k, v)| (*k, *v))));
+            }
+            assert!(skipmap.iter().map(|e| *e.key()).eq(btree.keys().copied()));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_conditional(
+            ops in vec((0..3u8, 0..16usize, 0..4usize), crate::TEST_SCALE.min(256)),
+        ) {
+            let skipmap = SkipMap::<usize, usize, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (op, k, v) in ops {
+                match op {
+                    0 => {
+                        let absent = !btree.contains_key(&k);
+                        if absent {
+                            btree.insert(k, v);
+                        }
+                        assert_eq!(skipmap.insert_if_absent(k, v), absent);
+                    }
+                    1 => {
+                        let updated = btree.get_mut(&k).filter(|old| **old < v).map(|old| *old = v);
+                        assert_eq!(skipmap.update_if(&k, |old| *old < v, v), updated.is_some());
+                    }
+                    _ => {
+                        let removed = btree.get(&k).is_some_and(|old| *old == v);
+                        let entry = skipmap.remove_if(&k, |old| *old == v);
+                        assert_eq!(entry.is_some(), removed);
+                        if removed {
+                            btree.remove(&k);
+                        }
+                    }
+                }
+                assert_eq!(skipmap.get(&k).map(|e| *e.value()), btree.get(&k).copied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_entry_outlives_removal() {
+        let skipmap = SkipMap::<usize, String, 8>::new();
+        skipmap.insert(0, String::from("old"));
+        let entry = skipmap.get(&0).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| {
+                skipmap.insert(0, String::from("new"));
+                assert!(skipmap.remove(&0).is_some_and(|e| e.value() == "new"));
+            });
+        });
+        assert!(entry.is_removed());
+        assert_eq!((entry.key(), entry.value().as_str()), (&0, "old"));
+    }
+
+    #[test]
+    fn test_concurrent_overwrite() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const ITEMS: usize = crate::TEST_SCALE;
+        let skipmap = SkipMap::<usize, usize, 16>::new();
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for i in 0..ITEMS {
+                        skipmap.insert(i, t);
+                        if i % 2 == 0 {
+                            skipmap.remove(&i);
+                        }
+                    }
+                });
+            }
+        });
+        for i in (0..ITEMS).filter(|i| i % 2 == 1) {
+            assert!(skipmap.get(&i).is_some_and(|e| *e.value() < THREADS));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_get() {
+        const THREADS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 4 } else { 200 };
+        const ITEMS: usize = if cfg!(miri) { 32 } else { 256 };
+        let skipmap = SkipMap::<usize, usize, 16>::new();
+        for i in (0..ITEMS).step_by(2) {
+            skipmap.insert(i, 0);
+        }
+        // Lookups of the even keys must keep succeeding while the nodes around them get removed.
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for round in 0..ROUNDS {
+                        for i in (t * 2 + 1..ITEMS).step_by(THREADS * 2) {
+                            skipmap.insert(i, round);
+                            skipmap.remove(&i);
+                        }
+                    }
+                });
+                s.spawn(move || {
+                    for round in 0..ROUNDS {
+                        for i in (0..ITEMS).step_by(2) {
+                            assert!(skipmap.contains(&i));
+                            skipmap.insert(i, round);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(skipmap.iter().map(|e| *e.key()).eq((0..ITEMS).step_by(2)));
+    }
+
+    #[test]
+    fn test_concurrent_striped_insert() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const ITEMS: usize = crate::TEST_SCALE;
+        let skipmap =
+            SkipMap::<usize, usize, 16>::with_stripes(NonZeroUsize::new(THREADS).unwrap());
+        // Every thread inserts into the same region of keys at the same time.
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for i in 0..ITEMS {
+                        skipmap.insert(i * THREADS + t, t);
+                    }
+                });
+            }
+        });
+        assert!(skipmap.iter().map(|e| *e.key()).eq(0..THREADS * ITEMS));
+        assert!(skipmap.iter().all(|e| *e.key() % THREADS == *e.value()));
+    }
+
+    #[test]
+    fn test_concurrent_increment() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const INCREMENTS: usize = crate::TEST_SCALE;
+        let skipmap = SkipMap::<usize, usize, 8>::new();
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..INCREMENTS {
+                        if skipmap.insert_if_absent(0, 1) {
+                            continue;
+                        }
+                        loop {
+                            let count = *skipmap.get(&0).unwrap().value();
+                            if skipmap.update_if(&0, |old| *old == count, count + 1) {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            skipmap.get(&0).map(|e| *e.value()),
+            Some(THREADS * INCREMENTS)
+        );
+    }
+
+    #[test]
+    fn test_snapshot_outlives_removal() {
+        let skipmap = SkipMap::<usize, String, 8>::new();
+        skipmap.insert(0, String::from("old"));
+        skipmap.insert(1, String::from("old"));
+        let snapshot = skipmap.snapshot();
+        skipmap.insert(0, String::from("new"));
+        skipmap.remove(&1);
+        skipmap.insert(2, String::from("new"));
+        assert!(!skipmap.contains(&1));
+        assert!(
+            snapshot
+                .iter()
+                .map(|(k, v)| (*k, v.as_str()))
+                .eq([(0, "old"), (1, "old")])
+        );
+        drop(snapshot);
+        let snapshot = skipmap.snapshot();
+        assert!(
+            snapshot
+                .iter()
+                .map(|(k, v)| (*k, v.as_str()))
+                .eq([(0, "new"), (2, "new")])
+        );
+    }
+
+    #[test]
+    fn test_concurrent_snapshot() {
+        const READERS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 8 } else { 200 };
+        const ITEMS: usize = if cfg!(miri) { 16 } else { 64 };
+        let skipmap = SkipMap::<usize, usize, 16>::new();
+        thread::scope(|s| {
+            // Every round overwrites the keys in ascending order, so a consistent view sees the
+            // keys before some point at one round and the rest at the previous one.
+            s.spawn(|| {
+                for round in 1..=ROUNDS {
+                    for i in 0..ITEMS {
+                        skipmap.insert(i, round);
+                        if round % 3 == 0 && i % 2 == 0 {
+                            skipmap.remove(&i);
+                        }
+                    }
+                }
+            });
+            for _ in 0..READERS {
+                s.spawn(|| {
+                    for _ in 0..ROUNDS {
+                        let snapshot = skipmap.snapshot();
+                        let rounds: Vec<_> = snapshot.iter().map(|(_, round)| *round).collect();
+                        assert!(rounds.windows(2).all(|w| w[0] >= w[1] && w[0] - w[1] <= 1));
+                        assert!(snapshot.iter().map(|(k, _)| k).is_sorted_by(|a, b| a < b));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_concurrent_range() {
+        const WRITERS: usize = 4;
+        const READERS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 8 } else { 1000 };
+        const ITEMS: usize = if cfg!(miri) { 32 } else { 256 };
+        let skipmap = SkipMap::<usize, usize, 16>::new();
+        // Even keys exist throughout, while odd keys keep getting inserted and removed.
+        for i in (0..ITEMS).step_by(2) {
+            skipmap.insert(i, 0);
+        }
+        thread::scope(|s| {
+            for t in 0..WRITERS {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for round in 0..ROUNDS {
+                        for i in (t..ITEMS).step_by(WRITERS) {
+                            if i % 2 == 0 || round % 2 == 0 {
+                                skipmap.insert(i, round);
+                            } else {
+                                skipmap.remove(&i);
+                            }
+                        }
+                    }
+                });
+            }
+            for _ in 0..READERS {
+                s.spawn(|| {
+                    for round in 0..ROUNDS {
+                        let start = round % (ITEMS / 2);
+                        let keys: Vec<_> = skipmap.range(start..).map(|e| *e.key()).collect();
+                        assert!(keys.is_sorted_by(|a, b| a < b));
+                        let even = keys.iter().filter(|k| *k % 2 == 0).copied();
+                        assert!(even.eq((start.next_multiple_of(2)..ITEMS).step_by(2)));
+                    }
+                });
+            }
+        });
+    }
+}
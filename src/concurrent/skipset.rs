@@ -0,0 +1,168 @@
+use std::{borrow::Borrow, fmt, num::NonZeroUsize, ptr::NonNull};
+
+use crossbeam_epoch as epoch;
+
+use super::{NodeRef, Storage};
+
+/// A lock-free ordered set backed by a skiplist.
+///
+/// Lookups are wait-free: they never retry nor help other threads finish their changes, so they
+/// complete in a bounded number of steps even while writers keep contending.
+pub struct SkipSet<T, const N: usize>(Storage<T, (), N>);
+
+impl<T, const N: usize> Default for SkipSet<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for SkipSet<T, N>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = epoch::pin();
+        let mut set = f.debug_set();
+        self.0.for_each(&guard, |node, ()| {
+            set.entry(&node.key);
+        });
+        set.finish()
+    }
+}
+
+impl<T, const N: usize> SkipSet<T, N> {
+    /// Creates an empty skipset.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Storage::new(NonZeroUsize::MIN))
+    }
+}
+
+impl<T, const N: usize> SkipSet<T, N>
+where
+    T: Ord + Send + 'static,
+{
+    /// Returns whether a value exists in the skipset.
+    pub fn contains<U>(&self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        self.0.get(value, &guard).is_some()
+    }
+
+    /// Returns an entry referencing the value in the skipset equal to the given one.
+    pub fn get<U>(&self, value: &U) -> Option<SetEntry<'_, T, N>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let (node, value) = self.0.get(value, &guard)?;
+        let (node, value) = (NonNull::from(node), NonNull::from(value));
+        Some(SetEntry(unsafe { NodeRef::new(guard, node, value) }))
+    }
+
+    /// Inserts a value into the skipset, returning whether it didn't exist.
+    pub fn insert(&self, value: T) -> bool {
+        let guard = epoch::pin();
+        self.0.insert(value, (), false, &guard)
+    }
+
+    /// Removes a value from the skipset, returning an entry referencing it if it existed.
+    pub fn remove<U>(&self, value: &U) -> Option<SetEntry<'_, T, N>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let (node, value) = self.0.remove_if(value, |()| true, &guard)?;
+        let (node, value) = (NonNull::from(node), NonNull::from(value));
+        Some(SetEntry(unsafe { NodeRef::new(guard, node, value) }))
+    }
+}
+
+/// A reference to a value in a [`SkipSet`].
+///
+/// The value remains accessible while the entry is held, even if it gets removed from the skipset
+/// by another thread. Holding an entry delays the reclamation of all removed nodes, so it should be
+/// dropped as soon as possible.
+#[derive(Clone)]
+pub struct SetEntry<'a, T, const N: usize>(NodeRef<'a, T, (), N>);
+
+impl<T, const N: usize> fmt::Debug for SetEntry<'_, T, N>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SetEntry").field(self.value()).finish()
+    }
+}
+
+impl<T, const N: usize> SetEntry<'_, T, N> {
+    /// Returns a shared reference to the value.
+    #[must_use]
+    pub const fn value(&self) -> &T {
+        self.0.key()
+    }
+
+    /// Returns whether the value has been removed from the skipset.
+    #[must_use]
+    pub fn is_removed(&self) -> bool {
+        self.0.is_removed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, thread};
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::SkipSet;
+
+    proptest! {
+        #[test]
+        fn test_insert_remove(items in vec(any::<usize>(), crate::TEST_SCALE.min(256))) {
+            let skipset = SkipSet::<usize, 8>::new();
+            let mut btree = BTreeSet::new();
+            for item in &items {
+                assert_eq!(skipset.insert(*item), btree.insert(*item));
+            }
+            for item in &items {
+                assert!(skipset.get(item).is_some_and(|e| e.value() == item));
+            }
+            for item in items.iter().rev() {
+                let entry = skipset.remove(item);
+                assert_eq!(entry.is_some(), btree.remove(item));
+                assert!(entry.is_none_or(|e| e.value() == item && e.is_removed()));
+                assert!(!skipset.contains(item));
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_dedup() {
+        const THREADS: usize = if cfg!(miri) { 4 } else { 8 };
+        const ITEMS: usize = crate::TEST_SCALE;
+        let skipset = SkipSet::<usize, 16>::new();
+        let inserted: usize = thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| s.spawn(|| (0..ITEMS).filter(|i| skipset.insert(*i)).count()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(inserted, ITEMS);
+        let removed: usize = thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| s.spawn(|| (0..ITEMS).filter(|i| skipset.remove(i).is_some()).count()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(removed, ITEMS);
+        for i in 0..ITEMS {
+            assert!(!skipset.contains(&i));
+        }
+    }
+}
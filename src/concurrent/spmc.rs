@@ -0,0 +1,407 @@
+//! A skipmap with a single writer publishing its changes to many readers.
+//!
+//! Since only one thread ever changes the links, the writer doesn't need any atomic read-modify-write
+//! operation. Readers traverse the skipmap without locking, while removed nodes and replaced values
+//! are reclaimed once no reader can still be looking at them.
+
+use std::{
+    borrow::Borrow,
+    fmt,
+    marker::PhantomData,
+    ptr::NonNull,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned};
+
+use super::random_height;
+
+/// The handle through which a skipmap is changed. There is only ever one writer for a skipmap.
+pub struct Writer<K, V, const N: usize> {
+    inner: Arc<Inner<K, V, N>>,
+}
+
+impl<K, V, const N: usize> Default for Writer<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for Writer<K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Writer").field(&self.inner).finish()
+    }
+}
+
+impl<K, V, const N: usize> Writer<K, V, N> {
+    /// Creates an empty skipmap, returning its writer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner::new()),
+        }
+    }
+
+    /// Creates a new reader of the skipmap.
+    #[must_use]
+    pub fn reader(&self) -> Reader<K, V, N> {
+        Reader {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<K, V, const N: usize> Writer<K, V, N>
+where
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns a shared reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Only the writer reclaims nodes and values, so nothing reachable can be freed while it's
+        // borrowed.
+        let guard = unsafe { epoch::unprotected() };
+        let value = self
+            .inner
+            .get(key, guard)?
+            .value
+            .load(Ordering::Relaxed, guard);
+        Some(unsafe { value.deref() })
+    }
+
+    /// Inserts a value at the given key into the skipmap, replacing the existing value. Returns
+    /// whether the key didn't exist.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        let guard = &epoch::pin();
+        let preds = self.inner.search(&key, guard);
+        if let Some(found) = unsafe { preds[0][0].load(Ordering::Relaxed, guard).as_ref() }
+            .filter(|node| node.key == key)
+        {
+            let old = found
+                .value
+                .swap(Owned::new(value), Ordering::Release, guard);
+            unsafe { guard.defer_destroy(old) };
+            return false;
+        }
+        let height = random_height::<N>(&self.inner.seed, &self.inner.levels);
+        let node = Owned::new(Node::new(key, value, height));
+        for (level, pred) in preds.iter().enumerate().take(height) {
+            node.tower[level].store(
+                pred[level].load(Ordering::Relaxed, guard),
+                Ordering::Relaxed,
+            );
+        }
+        // Publishes the node's links before the node itself, from the bottom up, so that readers
+        // reaching it at any level find a valid path onward.
+        let node = node.into_shared(guard);
+        for (level, pred) in preds.iter().enumerate().take(height) {
+            pred[level].store(node, Ordering::Release);
+        }
+        self.inner.levels.fetch_max(height, Ordering::Release);
+        true
+    }
+
+    /// Removes a value at the given key from the skipmap, returning whether it existed.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = &epoch::pin();
+        let preds = self.inner.search(key, guard);
+        let curr = preds[0][0].load(Ordering::Relaxed, guard);
+        let Some(node) = (unsafe { curr.as_ref() }).filter(|node| node.key.borrow() == key) else {
+            return false;
+        };
+        // Unlinks the node from the top down, while its own links keep pointing forward for the
+        // readers that are still traversing it.
+        for level in (0..node.height).rev() {
+            let next = node.tower[level].load(Ordering::Relaxed, guard);
+            preds[level][level].store(next, Ordering::Release);
+        }
+        unsafe { guard.defer_destroy(curr) };
+        true
+    }
+}
+
+/// A handle through which a skipmap is read concurrently with its writer.
+pub struct Reader<K, V, const N: usize> {
+    inner: Arc<Inner<K, V, N>>,
+}
+
+impl<K, V, const N: usize> Clone for Reader<K, V, N> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for Reader<K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Reader").field(&self.inner).finish()
+    }
+}
+
+impl<K, V, const N: usize> Reader<K, V, N>
+where
+    K: Ord,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        self.inner.get(key, &guard).is_some()
+    }
+
+    /// Returns an entry referencing the given key and its current value.
+    pub fn get<Q>(&self, key: &Q) -> Option<ReadEntry<'_, K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let node = self.inner.get(key, &guard)?;
+        let value = unsafe { node.value.load(Ordering::Acquire, &guard).deref() };
+        let (node, value) = (NonNull::from(node), NonNull::from(value));
+        Some(ReadEntry {
+            _guard: guard,
+            node,
+            value,
+            _reader: PhantomData,
+        })
+    }
+}
+
+/// A reference to a key and a value read from a skipmap.
+///
+/// The value is the one associated with the key when the entry was created. Both remain accessible
+/// while the entry is held, even if the writer replaces or removes them. Holding an entry delays the
+/// reclamation of everything the writer removes, so it should be dropped as soon as possible.
+pub struct ReadEntry<'a, K, V, const N: usize> {
+    /// Keeps the node and the value from being reclaimed.
+    _guard: Guard,
+    node: NonNull<Node<K, V, N>>,
+    value: NonNull<V>,
+    _reader: PhantomData<&'a Reader<K, V, N>>,
+}
+
+impl<K, V, const N: usize> fmt::Debug for ReadEntry<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReadEntry")
+            .field(self.key())
+            .field(self.value())
+            .finish()
+    }
+}
+
+impl<K, V, const N: usize> ReadEntry<'_, K, V, N> {
+    /// Returns a shared reference to the key.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        &unsafe { self.node.as_ref() }.key
+    }
+
+    /// Returns a shared reference to the value.
+    #[must_use]
+    pub const fn value(&self) -> &V {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+struct Inner<K, V, const N: usize> {
+    head: [Atomic<Node<K, V, N>>; N],
+    /// The number of levels that might contain nodes.
+    levels: AtomicUsize,
+    /// The state of the generator used for picking node heights.
+    seed: AtomicU64,
+}
+
+impl<K, V, const N: usize> Drop for Inner<K, V, N> {
+    fn drop(&mut self) {
+        // Removed nodes have already been scheduled for destruction.
+        let guard = unsafe { epoch::unprotected() };
+        let mut curr = self.head[0].load(Ordering::Relaxed, guard);
+        while !curr.is_null() {
+            let node = unsafe { curr.into_owned() };
+            curr = node.tower[0].load(Ordering::Relaxed, guard);
+        }
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for Inner<K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = epoch::pin();
+        let mut map = f.debug_map();
+        let mut curr = self.head[0].load(Ordering::Acquire, &guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let value = node.value.load(Ordering::Acquire, &guard);
+            map.entry(&node.key, unsafe { value.deref() });
+            curr = node.tower[0].load(Ordering::Acquire, &guard);
+        }
+        map.finish()
+    }
+}
+
+impl<K, V, const N: usize> Inner<K, V, N> {
+    fn new() -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            head: [const { Atomic::null() }; N],
+            levels: AtomicUsize::new(1),
+            seed: AtomicU64::new(crate::random_seed()),
+        }
+    }
+
+    /// Returns the node with the given key.
+    fn get<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g Node<K, V, N>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let preds = self.search(key, guard);
+        unsafe { preds[0][0].load(Ordering::Acquire, guard).as_ref() }
+            .filter(|node| node.key.borrow() == key)
+    }
+
+    /// Traverses the skipmap, descending down all levels, and returning the last links before the
+    /// key at each level.
+    fn search<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> [&'g [Atomic<Node<K, V, N>>; N]; N]
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut preds = [&self.head; N];
+        let mut pred = &self.head;
+        for level in (0..self.levels.load(Ordering::Acquire)).rev() {
+            let mut curr = pred[level].load(Ordering::Acquire, guard);
+            while let Some(node) = unsafe { curr.as_ref() } {
+                if node.key.borrow() >= key {
+                    break;
+                }
+                pred = &node.tower;
+                curr = node.tower[level].load(Ordering::Acquire, guard);
+            }
+            preds[level] = pred;
+        }
+        preds
+    }
+}
+
+struct Node<K, V, const N: usize> {
+    key: K,
+    value: Atomic<V>,
+    height: usize,
+    tower: [Atomic<Self>; N],
+}
+
+impl<K, V, const N: usize> Drop for Node<K, V, N> {
+    fn drop(&mut self) {
+        let guard = unsafe { epoch::unprotected() };
+        drop(unsafe { self.value.load(Ordering::Relaxed, guard).into_owned() });
+    }
+}
+
+impl<K, V, const N: usize> Node<K, V, N> {
+    fn new(key: K, value: V, height: usize) -> Self {
+        Self {
+            key,
+            value: Atomic::new(value),
+            height,
+            tower: [const { Atomic::null() }; N],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, thread};
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::Writer;
+
+    proptest! {
+        #[test]
+        fn test_insert_get_remove(
+            ops in vec((any::<bool>(), 0..64usize, any::<usize>()), crate::TEST_SCALE.min(256)),
+        ) {
+            let mut writer = Writer::<usize, usize, 8>::new();
+            let reader = writer.reader();
+            let mut btree = BTreeMap::new();
+            for (insert, k, v) in ops {
+                if insert {
+                    assert_eq!(writer.insert(k, v), btree.insert(k, v).is_none());
+                } else {
+                    assert_eq!(writer.remove(&k), btree.remove(&k).is_some());
+                }
+                assert_eq!(writer.get(&k), btree.get(&k));
+                assert_eq!(reader.get(&k).map(|e| *e.value()), btree.get(&k).copied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_readers() {
+        const READERS: usize = 4;
+        const ITEMS: usize = crate::TEST_SCALE;
+        let mut writer = Writer::<usize, usize, 16>::new();
+        thread::scope(|s| {
+            for _ in 0..READERS {
+                let reader = writer.reader();
+                s.spawn(move || {
+                    for i in 0..ITEMS {
+                        // Keys are only ever mapped to themselves.
+                        assert!(reader.get(&i).is_none_or(|e| *e.key() == *e.value()));
+                    }
+                });
+            }
+            for i in 0..ITEMS {
+                writer.insert(i, i);
+                if i % 2 == 0 {
+                    writer.remove(&i);
+                }
+            }
+        });
+        let reader = writer.reader();
+        drop(writer);
+        for i in 0..ITEMS {
+            assert_eq!(reader.contains(&i), i % 2 == 1);
+        }
+    }
+}
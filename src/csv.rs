@@ -0,0 +1,129 @@
+//! Reading and writing rows of comma-separated values, following RFC 4180.
+//!
+//! Fields holding a comma, a double quote, or a line break are enclosed in double quotes, with
+//! their double quotes doubled. A row made of a single empty field is written as `""`, since it
+//! would otherwise be a blank line, which gets skipped when reading. Rows are written with CRLF
+//! line endings, while both CRLF and LF are accepted when reading.
+
+use std::{
+    io::{self, BufRead, Write},
+    mem,
+};
+
+/// Writes a row made of the given fields.
+pub fn write_row<W>(writer: &mut W, fields: &[&str]) -> io::Result<()>
+where
+    W: Write,
+{
+    if fields == [""] {
+        return writer.write_all(b"\"\"\r\n");
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\r\n")
+}
+
+/// Reads the fields of the next row, using the line as scratch space. Blank lines are skipped, and
+/// nothing is returned once the input is exhausted.
+pub fn read_row<R>(reader: &mut R, line: &mut String) -> io::Result<Option<Vec<String>>>
+where
+    R: BufRead,
+{
+    loop {
+        line.clear();
+        if reader.read_line(line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim_end_matches(['\r', '\n']).is_empty() {
+            break;
+        }
+    }
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut at_start = true;
+    let mut quoted = false;
+    let mut start = 0;
+    loop {
+        let mut chars = line[start..].chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted => {
+                    if chars.next_if_eq(&'"').is_some() {
+                        field.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                }
+                _ if quoted => field.push(c),
+                '"' if at_start => quoted = true,
+                ',' => {
+                    fields.push(mem::take(&mut field));
+                    at_start = true;
+                    continue;
+                }
+                // Drops the line ending of the row.
+                '\n' if chars.peek().is_none() => {}
+                '\r' if matches!(chars.peek(), Some('\n') | None) => {}
+                _ => field.push(c),
+            }
+            at_start = false;
+        }
+        if !quoted {
+            break;
+        }
+        // The line break belongs to a quoted field, which continues on the next line.
+        start = line.len();
+        if reader.read_line(line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "unterminated quoted field",
+            ));
+        }
+    }
+    fields.push(field);
+    Ok(Some(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{read_row, write_row};
+
+    proptest! {
+        #[test]
+        fn test_row(rows in proptest::collection::vec(proptest::collection::vec(".*", 1..4), 0..20)) {
+            let mut bytes = Vec::new();
+            for row in &rows {
+                let fields: Vec<_> = row.iter().map(String::as_str).collect();
+                write_row(&mut bytes, &fields).unwrap();
+            }
+            let mut reader = bytes.as_slice();
+            let mut line = String::new();
+            for row in &rows {
+                assert_eq!(read_row(&mut reader, &mut line).unwrap().as_ref(), Some(row));
+            }
+            assert_eq!(read_row(&mut reader, &mut line).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_read_row() {
+        let mut reader = &b"a,\"b,\"\"c\"\"\n\",d\n\nx,\r\n\"unterminated\n"[..];
+        let mut line = String::new();
+        let row = read_row(&mut reader, &mut line).unwrap();
+        assert_eq!(row.unwrap(), ["a", "b,\"c\"\n", "d"]);
+        let row = read_row(&mut reader, &mut line).unwrap();
+        assert_eq!(row.unwrap(), ["x", ""]);
+        let err = read_row(&mut reader, &mut line).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
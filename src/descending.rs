@@ -0,0 +1,324 @@
+//! Collections ordered from the greatest value to the least.
+//!
+//! A [`SkipList`] of [`Reverse`] values is ordered that way, but takes and gives back the wrappers
+//! at every call. [`DescendingSkipList`] and [`DescendingSkipMap`] hold their values and keys in
+//! [`Reverse`] and wrap or unwrap them at every call instead, so that e.g. popping the first value
+//! of a max-ordered collection gives back the greatest value as it was inserted.
+
+use std::{
+    cmp::Reverse,
+    fmt,
+    ops::{Bound, RangeBounds},
+    ptr,
+};
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use crate::{
+    SkipList, SkipMap,
+    iter::{
+        DescendingIter, DescendingIterMut, DescendingMapIter, DescendingMapRange, DescendingRange,
+    },
+    policy::{AllowDuplicates, DuplicatePolicy, OverwriteOnEqual},
+};
+
+/// A skiplist whose values are ordered from the greatest to the least.
+///
+/// It's created by [`SkipList::descending`], and lookups take the values themselves rather than a
+/// borrowed form of them.
+pub struct DescendingSkipList<T, R = SmallRng, const N: usize = 32, P = AllowDuplicates>(
+    SkipList<Reverse<T>, R, N, P>,
+)
+where
+    R: Rng,
+    P: DuplicatePolicy;
+
+impl<T, R, const N: usize, P> Default for DescendingSkipList<T, R, N, P>
+where
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    fn default() -> Self {
+        Self(SkipList::default())
+    }
+}
+
+impl<T, R, const N: usize, P> fmt::Debug for DescendingSkipList<T, R, N, P>
+where
+    T: fmt::Debug,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Formats the values from the greatest like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize, P> DescendingSkipList<T, SmallRng, N, P>
+where
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skiplist.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(SkipList::new())
+    }
+}
+
+impl<T, R, const N: usize, P> DescendingSkipList<T, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns an iterator over the values, from the greatest to the least.
+    pub fn iter(&self) -> DescendingIter<'_, T, N> {
+        DescendingIter(self.0.iter())
+    }
+
+    /// Returns the greatest value.
+    pub fn first(&self) -> Option<&T> {
+        self.0.first().map(|value| &value.0)
+    }
+
+    /// Returns the least value.
+    pub fn last(&self) -> Option<&T> {
+        self.0.last().map(|value| &value.0)
+    }
+
+    /// Removes the greatest value, returning it if the skiplist isn't empty.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.0.pop_first().map(|value| value.0)
+    }
+
+    /// Removes the least value, returning it if the skiplist isn't empty.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.0.pop_last().map(|value| value.0)
+    }
+
+    /// Returns the skiplist of [`Reverse`] values that holds the values, for the operations that
+    /// aren't wrapped.
+    pub fn into_inner(self) -> SkipList<Reverse<T>, R, N, P> {
+        self.0
+    }
+}
+
+impl<T, R, const N: usize, P> DescendingSkipList<T, R, N, P>
+where
+    T: Ord,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns whether a value exists in the skiplist.
+    pub fn contains(&self, value: &T) -> bool {
+        self.0.contains(reversed(value))
+    }
+
+    /// Returns an iterator over the values within the range, from the greatest to the least.
+    ///
+    /// The bounds are given in the order of the values rather than from the greatest, so e.g.
+    /// `range(2..5)` visits 4, 3, and 2.
+    pub fn range<B>(&self, range: B) -> DescendingRange<'_, T, N>
+    where
+        B: RangeBounds<T>,
+    {
+        DescendingRange(self.0.range(reversed_bounds(&range)))
+    }
+
+    /// Inserts a value into the skiplist, which returns what [`SkipList::insert`] does.
+    pub fn insert(&mut self, value: T) -> P::Outcome<T> {
+        P::outcome(self.0.insert_with_height(Reverse(value), None).map(|value| value.0))
+    }
+
+    /// Removes a value from the skiplist, returning it if it exists.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        self.0.remove(reversed(value)).map(|value| value.0)
+    }
+}
+
+impl<'a, T, R, const N: usize, P> IntoIterator for &'a DescendingSkipList<T, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    type Item = &'a T;
+    type IntoIter = DescendingIter<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A skipmap whose entries are ordered from the greatest key to the least.
+///
+/// It's created by [`SkipMap::descending`], and lookups take the keys themselves rather than a
+/// borrowed form of them.
+pub struct DescendingSkipMap<K, V, R = SmallRng, const N: usize = 32, P = OverwriteOnEqual>(
+    SkipMap<Reverse<K>, V, R, N, P>,
+)
+where
+    R: Rng,
+    P: DuplicatePolicy;
+
+impl<K, V, R, const N: usize, P> Default for DescendingSkipMap<K, V, R, N, P>
+where
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    fn default() -> Self {
+        Self(SkipMap::default())
+    }
+}
+
+impl<K, V, R, const N: usize, P> fmt::Debug for DescendingSkipMap<K, V, R, N, P>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Formats the entries from the greatest key like a map.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, const N: usize, P> DescendingSkipMap<K, V, SmallRng, N, P>
+where
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skipmap.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(SkipMap::new())
+    }
+}
+
+impl<K, V, R, const N: usize, P> DescendingSkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns an iterator over the entries, from the greatest key to the least.
+    pub fn iter(&self) -> DescendingMapIter<'_, K, V, N> {
+        DescendingMapIter(self.0.iter())
+    }
+
+    /// Returns an iterator over the entries, from the greatest key to the least, through which the
+    /// values can be mutated in place.
+    pub fn iter_mut(&mut self) -> DescendingIterMut<'_, K, V, N> {
+        DescendingIterMut(self.0.iter_mut())
+    }
+
+    /// Returns the entry at the greatest key.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.0.first_key_value().map(|(key, value)| (&key.0, value))
+    }
+
+    /// Returns the entry at the least key.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.0.last_key_value().map(|(key, value)| (&key.0, value))
+    }
+
+    /// Removes the entry at the greatest key, returning it if the skipmap isn't empty.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.0.pop_first().map(|(key, value)| (key.0, value))
+    }
+
+    /// Removes the entry at the least key, returning it if the skipmap isn't empty.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.0.pop_last().map(|(key, value)| (key.0, value))
+    }
+
+    /// Returns the skipmap of [`Reverse`] keys that holds the entries, for the operations that
+    /// aren't wrapped.
+    pub fn into_inner(self) -> SkipMap<Reverse<K>, V, R, N, P> {
+        self.0
+    }
+}
+
+impl<K, V, R, const N: usize, P> DescendingSkipMap<K, V, R, N, P>
+where
+    K: Ord,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains(&self, key: &K) -> bool {
+        self.0.contains(reversed(key))
+    }
+
+    /// Returns a shared reference to the value associated with the given key.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.0.get(reversed(key))
+    }
+
+    /// Returns a mutable reference to the value associated with the given key.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.0.get_mut(reversed(key))
+    }
+
+    /// Returns an iterator over the entries whose keys are within the range, from the greatest key
+    /// to the least.
+    ///
+    /// The bounds are given in the order of the keys rather than from the greatest, so e.g.
+    /// `range(2..5)` visits the entries at 4, 3, and 2.
+    pub fn range<B>(&self, range: B) -> DescendingMapRange<'_, K, V, N>
+    where
+        B: RangeBounds<K>,
+    {
+        DescendingMapRange(self.0.range::<Reverse<K>, _>(reversed_bounds(&range)))
+    }
+
+    /// Inserts a value at the given key into the skipmap, which returns what [`SkipMap::insert`]
+    /// does.
+    pub fn insert(&mut self, key: K, value: V) -> P::Outcome<V> {
+        self.0.insert(Reverse(key), value)
+    }
+
+    /// Removes a value at the given key from the skipmap, returning it if it exists.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.0.remove(reversed(key))
+    }
+}
+
+impl<'a, K, V, R, const N: usize, P> IntoIterator for &'a DescendingSkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = DescendingMapIter<'a, K, V, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, R, const N: usize, P> IntoIterator for &'a mut DescendingSkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = DescendingIterMut<'a, K, V, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Views a value as wrapped in [`Reverse`], so that it can be looked up without being moved.
+const fn reversed<T>(value: &T) -> &Reverse<T> {
+    // SAFETY: `Reverse` is a transparent wrapper, so it has the same layout as the value.
+    unsafe { &*ptr::from_ref(value).cast::<Reverse<T>>() }
+}
+
+/// Views the bounds of a range as wrapped in [`Reverse`], swapping them so that they bound the
+/// same values in reverse order.
+fn reversed_bounds<T, B>(range: &B) -> (Bound<&Reverse<T>>, Bound<&Reverse<T>>)
+where
+    B: RangeBounds<T>,
+{
+    (range.end_bound().map(reversed), range.start_bound().map(reversed))
+}
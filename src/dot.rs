@@ -0,0 +1,125 @@
+//! Drawing the structure of a skiplist as a graph in the DOT language of Graphviz.
+//!
+//! The head and every node are drawn as a column holding one field per level that they're linked
+//! into, followed by a label, and every link is drawn as an edge between the fields of its level.
+//! The graph goes from left to right, so that towers stand upright, and nodes of lazily removed
+//! values are dashed.
+
+use std::fmt::Write;
+
+use rand::Rng;
+
+use crate::NonEmptyStorage;
+
+/// Returns the graph of a storage, labelling the nodes by the given function.
+pub fn to_dot<T, R, const N: usize, F>(
+    storage: Option<&NonEmptyStorage<T, R, N>>,
+    label: F,
+) -> String
+where
+    R: Rng,
+    F: Fn(&T) -> String,
+{
+    let mut levels = 1;
+    let mut towers = Vec::new();
+    let mut removed = Vec::new();
+    if let Some(storage) = storage {
+        levels = storage.levels.get();
+        towers.extend(
+            storage
+                .towers()
+                .map(|(height, value)| (height, label(value))),
+        );
+        let mut next = unsafe { storage.head.as_ref()[0] };
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            removed.push(curr.removed);
+            next = curr.nexts[0];
+        }
+    }
+    let mut dot = String::from("digraph {\n    rankdir=LR;\n    node [shape=record];\n");
+    let _ = writeln!(dot, "    head [label=\"{}|head\"];", fields(levels));
+    for (position, (height, label)) in towers.iter().enumerate() {
+        let style = if removed[position] {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        let label = escape(label);
+        let _ = writeln!(
+            dot,
+            "    n{position} [label=\"{}|{label}\"{style}];",
+            fields(*height)
+        );
+    }
+    for level in 0..levels {
+        let mut prev = String::from("head");
+        for (position, _) in towers.iter().enumerate().filter(|(_, (h, _))| *h > level) {
+            let _ = writeln!(dot, "    {prev}:l{level} -> n{position}:l{level};");
+            prev = format!("n{position}");
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Returns the fields of a column linked into the given number of levels, from the top.
+fn fields(height: usize) -> String {
+    let fields: Vec<_> = (0..height)
+        .rev()
+        .map(|level| format!("<l{level}>"))
+        .collect();
+    fields.join("|")
+}
+
+/// Escapes the characters that have a meaning in the labels of records.
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::rngs::SmallRng;
+
+    use crate::{SkipList, SkipMap};
+
+    #[test]
+    fn test_to_dot() {
+        let mut skiplist = SkipList::<u8, SmallRng, 8>::from(BTreeSet::from([1, 2, 3]));
+        skiplist.compact();
+        skiplist.remove_lazily(&3);
+        let dot = [
+            "digraph {",
+            "    rankdir=LR;",
+            "    node [shape=record];",
+            "    head [label=\"<l1>|<l0>|head\"];",
+            "    n0 [label=\"<l0>|1\"];",
+            "    n1 [label=\"<l1>|<l0>|2\"];",
+            "    n2 [label=\"<l0>|3\", style=dashed];",
+            "    head:l0 -> n0:l0;",
+            "    n0:l0 -> n1:l0;",
+            "    n1:l0 -> n2:l0;",
+            "    head:l1 -> n1:l1;",
+            "}",
+            "",
+        ];
+        assert_eq!(skiplist.to_dot(), dot.join("\n"));
+    }
+
+    #[test]
+    fn test_to_dot_escaped() {
+        let mut skipmap = SkipMap::<&str, (), SmallRng, 8>::new();
+        assert!(skipmap.to_dot().contains("head [label=\"<l0>|head\"];"));
+        skipmap.insert("{a|b}", ());
+        assert!(skipmap.to_dot().contains("[label=\"<l0>|\\{a\\|b\\}\"];"));
+    }
+}
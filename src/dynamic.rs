@@ -0,0 +1,235 @@
+//! A skipmap whose keys, values, and order are erased from its type.
+//!
+//! Generic collections get monomorphized into every crate using them, so they can't be handed
+//! across a boundary where both sides aren't compiled together, such as between a host and the
+//! plugins it loads from dynamic libraries. A [`DynSkipMap`] holds keys as bytes ordered by a boxed
+//! comparator and values as boxed [`Any`] values, and is used through the object-safe [`DynMap`]
+//! trait, so that it can be passed around as a `Box<dyn DynMap>`.
+
+use std::{any::Any, cmp::Ordering, fmt, sync::Arc};
+
+use crate::{SkipMap, sorted::SortedMap};
+
+/// Orders the keys of a [`DynSkipMap`], which must be a total order.
+pub type Comparator = Box<dyn Fn(&[u8], &[u8]) -> Ordering + Send + Sync>;
+
+/// A value of a [`DynSkipMap`].
+pub type DynValue = Box<dyn Any + Send>;
+
+/// The operations of an ordered map from byte keys to boxed values, usable as a trait object.
+pub trait DynMap: Send {
+    /// Returns the number of entries.
+    fn len(&self) -> usize;
+
+    /// Returns whether the map holds no entry.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value at the given key, if it exists.
+    fn get(&self, key: &[u8]) -> Option<&(dyn Any + Send)>;
+
+    /// Returns whether an entry exists at the given key.
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts a value at the given key, returning the replaced value if it exists.
+    fn insert(&mut self, key: &[u8], value: DynValue) -> Option<DynValue>;
+
+    /// Removes the entry at the given key, returning its value if it exists.
+    fn remove(&mut self, key: &[u8]) -> Option<DynValue>;
+
+    /// Returns the entry at the least key.
+    fn first_key_value(&self) -> Option<(&[u8], &(dyn Any + Send))>;
+
+    /// Returns the entry at the greatest key.
+    fn last_key_value(&self) -> Option<(&[u8], &(dyn Any + Send))>;
+
+    /// Removes the entry at the least key, returning it if the map isn't empty.
+    fn pop_first(&mut self) -> Option<(Box<[u8]>, DynValue)>;
+
+    /// Removes the entry at the greatest key, returning it if the map isn't empty.
+    fn pop_last(&mut self) -> Option<(Box<[u8]>, DynValue)>;
+
+    /// Visits the entries in ascending order of keys.
+    fn for_each(&self, visit: &mut dyn FnMut(&[u8], &(dyn Any + Send)));
+}
+
+/// A skipmap from byte keys ordered by a comparator to boxed values.
+///
+/// Every key shares the comparator of the skipmap, and lookups copy the given key once to compare
+/// it with the keys of the skipmap.
+pub struct DynSkipMap {
+    map: SkipMap<DynKey, DynValue>,
+    compare: SharedComparator,
+    len: usize,
+}
+
+impl DynSkipMap {
+    /// Creates an empty skipmap whose keys are ordered lexicographically.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_comparator(Box::new(<[u8]>::cmp))
+    }
+
+    /// Creates an empty skipmap whose keys are ordered by the given comparator.
+    #[must_use]
+    pub fn with_comparator(compare: Comparator) -> Self {
+        Self {
+            map: SkipMap::new(),
+            compare: Arc::from(compare),
+            len: 0,
+        }
+    }
+
+    /// Creates a key that compares with the keys of the skipmap.
+    fn key(&self, bytes: &[u8]) -> DynKey {
+        DynKey {
+            bytes: bytes.into(),
+            compare: Arc::clone(&self.compare),
+        }
+    }
+}
+
+impl Default for DynSkipMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DynSkipMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.for_each(&mut |key, value| {
+            map.entry(&key, &value);
+        });
+        map.finish()
+    }
+}
+
+impl DynMap for DynSkipMap {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, key: &[u8]) -> Option<&(dyn Any + Send)> {
+        self.map.get(&self.key(key)).map(Box::as_ref)
+    }
+
+    fn insert(&mut self, key: &[u8], value: DynValue) -> Option<DynValue> {
+        let old = self.map.insert(self.key(key), value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<DynValue> {
+        let old = self.map.remove(&self.key(key));
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    fn first_key_value(&self) -> Option<(&[u8], &(dyn Any + Send))> {
+        let (key, value) = self.map.first_key_value()?;
+        Some((&key.bytes, value.as_ref()))
+    }
+
+    fn last_key_value(&self) -> Option<(&[u8], &(dyn Any + Send))> {
+        let (key, value) = self.map.last_key_value()?;
+        Some((&key.bytes, value.as_ref()))
+    }
+
+    fn pop_first(&mut self) -> Option<(Box<[u8]>, DynValue)> {
+        let (key, value) = self.map.pop_first()?;
+        self.len -= 1;
+        Some((key.bytes, value))
+    }
+
+    fn pop_last(&mut self) -> Option<(Box<[u8]>, DynValue)> {
+        let (key, value) = self.map.pop_last()?;
+        self.len -= 1;
+        Some((key.bytes, value))
+    }
+
+    fn for_each(&self, visit: &mut dyn FnMut(&[u8], &(dyn Any + Send))) {
+        for (key, value) in SortedMap::iter(&self.map) {
+            visit(&key.bytes, value.as_ref());
+        }
+    }
+}
+
+type SharedComparator = Arc<dyn Fn(&[u8], &[u8]) -> Ordering + Send + Sync>;
+
+/// A key of a [`DynSkipMap`], which carries the comparator so that it implements [`Ord`].
+struct DynKey {
+    bytes: Box<[u8]>,
+    compare: SharedComparator,
+}
+
+impl PartialEq for DynKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DynKey {}
+
+impl PartialOrd for DynKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DynKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.compare)(&self.bytes, &other.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::Any, collections::BTreeMap};
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{DynMap, DynSkipMap, DynValue};
+
+    proptest! {
+        #[test]
+        fn test_dyn_map(ops in vec((0..3u8, vec(any::<u8>(), 0..3), any::<u32>()), 0..200)) {
+            // Keys are ordered from the greatest to the least.
+            let mut map: Box<dyn DynMap> = Box::new(DynSkipMap::with_comparator(Box::new(
+                |a: &[u8], b: &[u8]| b.cmp(a),
+            )));
+            let mut btree = BTreeMap::new();
+            let downcast = |value: DynValue| *value.downcast::<u32>().unwrap();
+            for (op, key, value) in ops {
+                match op {
+                    0 => {
+                        let old = map.insert(&key, Box::new(value)).map(downcast);
+                        assert_eq!(old, btree.insert(key.clone(), value));
+                    }
+                    1 => assert_eq!(map.remove(&key).map(downcast), btree.remove(&key)),
+                    _ => {
+                        let value = map.get(&key).and_then(<dyn Any + Send>::downcast_ref);
+                        assert_eq!(value, btree.get(&key));
+                    }
+                }
+                assert_eq!(map.len(), btree.len());
+            }
+            let first = map.first_key_value().map(|(key, _)| key.to_vec());
+            assert_eq!(first.as_ref(), btree.keys().next_back());
+            let mut keys = Vec::new();
+            map.for_each(&mut |key, _| keys.push(key.to_vec()));
+            assert!(keys.iter().eq(btree.keys().rev()));
+            while let Some((key, _)) = map.pop_last() {
+                assert_eq!(btree.pop_first().map(|(k, _)| k.into_boxed_slice()), Some(key));
+            }
+            assert!(map.is_empty());
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Running sequences of operations decoded from arbitrary bytes, for the fuzz targets in `fuzz/`.
+//!
+//! The first eight bytes seed the random number generator, and every operation that follows is an
+//! opcode byte followed by its operands, until the bytes run out. Keys are single bytes, so that
+//! operations often hit existing keys. Every sequence runs against collections linking nodes into
+//! 1, 2, 4, and 32 levels, wrapped by the [`shadow`](crate::shadow) types, which check every result
+//! against the collections of the standard library and the invariants after every mutation.
+//! Skiplists keeping equal values get their own run, since equal values take other paths through
+//! insertions and removals. The operations cover the ones that splice several nodes at once, such
+//! as draining a range and extracting the values matching a predicate.
+
+use std::ops::{Bound, RangeBounds};
+
+use rand::{SeedableRng, rngs::SmallRng};
+
+use crate::shadow::{ShadowMap, ShadowMultiset, ShadowSet};
+
+/// Runs the operations held by the bytes against skiplists of unique values.
+///
+/// # Panics
+///
+/// Panics if a skiplist disagrees with a set of the standard library, or gets corrupted.
+pub fn run_set(bytes: &[u8]) {
+    let Some((seed, bytes)) = bytes.split_first_chunk() else {
+        return;
+    };
+    let seed = u64::from_le_bytes(*seed);
+    run_set_with::<1>(seed, bytes);
+    run_set_with::<2>(seed, bytes);
+    run_set_with::<4>(seed, bytes);
+    run_set_with::<32>(seed, bytes);
+}
+
+/// Runs the operations held by the bytes against skiplists keeping equal values.
+///
+/// # Panics
+///
+/// Panics if a skiplist disagrees with a map of the standard library counting every value, or
+/// gets corrupted.
+pub fn run_multiset(bytes: &[u8]) {
+    let Some((seed, bytes)) = bytes.split_first_chunk() else {
+        return;
+    };
+    let seed = u64::from_le_bytes(*seed);
+    run_multiset_with::<1>(seed, bytes);
+    run_multiset_with::<2>(seed, bytes);
+    run_multiset_with::<4>(seed, bytes);
+    run_multiset_with::<32>(seed, bytes);
+}
+
+/// Runs the operations held by the bytes against skipmaps.
+///
+/// # Panics
+///
+/// Panics if a skipmap disagrees with a map of the standard library, or gets corrupted.
+pub fn run_map(bytes: &[u8]) {
+    let Some((seed, bytes)) = bytes.split_first_chunk() else {
+        return;
+    };
+    let seed = u64::from_le_bytes(*seed);
+    run_map_with::<1>(seed, bytes);
+    run_map_with::<2>(seed, bytes);
+    run_map_with::<4>(seed, bytes);
+    run_map_with::<32>(seed, bytes);
+}
+
+fn run_set_with<const N: usize>(seed: u64, bytes: &[u8]) {
+    let mut set = ShadowSet::<u8, SmallRng, N>::with_rng(SmallRng::seed_from_u64(seed));
+    for op in Ops(bytes) {
+        match op {
+            Op::Insert(key, _) => {
+                let _ = set.insert(key);
+            }
+            Op::Remove(key) => {
+                set.remove(&key);
+            }
+            Op::RemoveLazily(key) => {
+                set.remove_lazily(&key);
+            }
+            // Skiplists have no entry API.
+            Op::Get(key) | Op::Entry(key, _) => {
+                set.contains(&key);
+            }
+            Op::Range(start, end) => {
+                set.range((start, end));
+            }
+            Op::PopFirst => {
+                set.pop_first();
+            }
+            Op::PopLast => {
+                set.pop_last();
+            }
+            Op::Compact => set.compact(),
+            Op::Vacuum => set.vacuum(),
+            // Skiplists have no drain of a range, so the range gets extracted instead.
+            Op::DrainRange(start, end) => {
+                set.extract_if(|value| (start, end).contains(value), usize::MAX);
+            }
+            Op::Retain(modulus) => set.retain(|value| value % modulus != 0),
+            Op::ExtractIf(modulus, limit) => {
+                set.extract_if(|value| value % modulus == 0, limit);
+            }
+            Op::PushMax(key, _) => {
+                let _ = set.push_max(key);
+            }
+            Op::TryInsert(key, _) => {
+                let _ = set.try_insert(key);
+            }
+            Op::TryRemove(key) => {
+                let _ = set.try_remove(&key);
+            }
+            Op::InsertHashed(key, _) => {
+                let _ = set.insert_hashed(key);
+            }
+        }
+        let _ = (set.first(), set.last());
+    }
+}
+
+fn run_multiset_with<const N: usize>(seed: u64, bytes: &[u8]) {
+    let rng = SmallRng::seed_from_u64(seed);
+    let mut multiset = ShadowMultiset::<u8, SmallRng, N>::with_rng(rng);
+    for op in Ops(bytes) {
+        match op {
+            Op::Insert(key, _) => multiset.insert(key),
+            Op::Remove(key) => {
+                multiset.remove(&key);
+            }
+            Op::RemoveLazily(key) => {
+                multiset.remove_lazily(&key);
+            }
+            // Skiplists have no entry API.
+            Op::Get(key) | Op::Entry(key, _) => {
+                multiset.contains(&key);
+            }
+            Op::Range(start, end) => {
+                multiset.range((start, end));
+            }
+            Op::PopFirst => {
+                multiset.pop_first();
+            }
+            Op::PopLast => {
+                multiset.pop_last();
+            }
+            Op::Compact => multiset.compact(),
+            Op::Vacuum => multiset.vacuum(),
+            // Skiplists have no drain of a range, so the range gets extracted instead.
+            Op::DrainRange(start, end) => {
+                multiset.extract_if(|value| (start, end).contains(value), usize::MAX);
+            }
+            Op::Retain(modulus) => multiset.retain(|value| value % modulus != 0),
+            Op::ExtractIf(modulus, limit) => {
+                multiset.extract_if(|value| value % modulus == 0, limit);
+            }
+            Op::PushMax(key, _) => {
+                let _ = multiset.push_max(key);
+            }
+            Op::TryInsert(key, _) => {
+                let _ = multiset.try_insert(key);
+            }
+            Op::TryRemove(key) => {
+                let _ = multiset.try_remove(&key);
+            }
+            Op::InsertHashed(key, _) => multiset.insert_hashed(key),
+        }
+        let _ = (multiset.first(), multiset.last());
+    }
+}
+
+fn run_map_with<const N: usize>(seed: u64, bytes: &[u8]) {
+    let mut map = ShadowMap::<u8, u8, SmallRng, N>::with_rng(SmallRng::seed_from_u64(seed));
+    for op in Ops(bytes) {
+        match op {
+            Op::Insert(key, value) => {
+                map.insert(key, value);
+            }
+            Op::Remove(key) => {
+                map.remove(&key);
+            }
+            Op::RemoveLazily(key) => {
+                map.remove_lazily(&key);
+            }
+            Op::Get(key) => {
+                map.get(&key);
+            }
+            Op::Range(start, end) => {
+                map.range((start, end));
+            }
+            Op::PopFirst => {
+                map.pop_first();
+            }
+            Op::PopLast => {
+                map.pop_last();
+            }
+            Op::Compact => map.compact(),
+            Op::Vacuum => map.vacuum(),
+            // The entry gets inserted or removed depending on the parity of the value.
+            Op::Entry(key, value) if value % 2 == 0 => {
+                map.or_insert(key, value);
+            }
+            Op::Entry(key, _) => {
+                map.remove_entry(&key);
+            }
+            Op::DrainRange(start, end) => {
+                map.drain_range((start, end));
+            }
+            Op::Retain(modulus) => map.retain(|key, value| (key ^ value) % modulus != 0),
+            Op::ExtractIf(modulus, limit) => {
+                map.extract_if(|key, value| (key ^ value) % modulus == 0, limit);
+            }
+            Op::PushMax(key, value) => {
+                let _ = map.push_max(key, value);
+            }
+            Op::TryInsert(key, value) => {
+                let _ = map.try_insert(key, value);
+            }
+            Op::TryRemove(key) => {
+                let _ = map.try_remove(&key);
+            }
+            Op::InsertHashed(key, value) => {
+                map.insert_hashed(key, value);
+            }
+        }
+        let _ = (map.first_key_value(), map.last_key_value());
+    }
+}
+
+/// An operation, along with its operands.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Insert(u8, u8),
+    Remove(u8),
+    RemoveLazily(u8),
+    Get(u8),
+    Range(Bound<u8>, Bound<u8>),
+    PopFirst,
+    PopLast,
+    Compact,
+    Vacuum,
+    Entry(u8, u8),
+    DrainRange(Bound<u8>, Bound<u8>),
+    /// Keeps the keys that the modulus doesn't divide, which is at least 2.
+    Retain(u8),
+    /// Removes upto the given number of keys that the modulus divides, which is at least 2.
+    ExtractIf(u8, usize),
+    PushMax(u8, u8),
+    TryInsert(u8, u8),
+    TryRemove(u8),
+    InsertHashed(u8, u8),
+}
+
+/// Decodes operations from bytes, favoring insertions so that the collections grow.
+struct Ops<'a>(&'a [u8]);
+
+impl Ops<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(byte)
+    }
+}
+
+impl Iterator for Ops<'_> {
+    type Item = Op;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let op = match self.byte()? % 32 {
+            0..=7 => Op::Insert(self.byte()?, self.byte()?),
+            8..=10 => Op::Remove(self.byte()?),
+            11..=13 => Op::RemoveLazily(self.byte()?),
+            14..=16 => Op::Get(self.byte()?),
+            17 => {
+                let (start, end) = range(self.byte()?, self.byte()?, self.byte()?);
+                Op::Range(start, end)
+            }
+            18 => Op::PopFirst,
+            19 => Op::PopLast,
+            20 => Op::Compact,
+            21 => Op::Vacuum,
+            22 | 23 => Op::Entry(self.byte()?, self.byte()?),
+            24 => {
+                let (start, end) = range(self.byte()?, self.byte()?, self.byte()?);
+                Op::DrainRange(start, end)
+            }
+            25 => Op::Retain(self.byte()? % 8 + 2),
+            26 => Op::ExtractIf(self.byte()? % 8 + 2, usize::from(self.byte()? % 8)),
+            27 => Op::PushMax(self.byte()?, self.byte()?),
+            28 => Op::TryInsert(self.byte()?, self.byte()?),
+            29 => Op::TryRemove(self.byte()?),
+            _ => Op::InsertHashed(self.byte()?, self.byte()?),
+        };
+        Some(op)
+    }
+}
+
+/// Returns the range between two keys, whose kinds of bounds are held by the lowest bits of a byte.
+/// The keys get ordered, and the start gets included if both bounds exclude the same key, since the
+/// collections of the standard library panic on such ranges.
+fn range(kinds: u8, a: u8, b: u8) -> (Bound<u8>, Bound<u8>) {
+    let bound = |kind: u8, key| match kind & 3 {
+        0 => Bound::Included(key),
+        1 => Bound::Excluded(key),
+        _ => Bound::Unbounded,
+    };
+    let (start, end) = (a.min(b), a.max(b));
+    let end = bound(kinds >> 2, end);
+    let start = match bound(kinds, start) {
+        Bound::Excluded(key) if end == Bound::Excluded(key) => Bound::Included(key),
+        start => start,
+    };
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use proptest::{collection::vec, prelude::*};
+
+    proptest! {
+        #[test]
+        fn test_run_set(bytes in vec(any::<u8>(), 0..1000)) {
+            super::run_set(&bytes);
+        }
+
+        #[test]
+        fn test_run_multiset(bytes in vec(any::<u8>(), 0..1000)) {
+            super::run_multiset(&bytes);
+        }
+
+        #[test]
+        fn test_run_map(bytes in vec(any::<u8>(), 0..1000)) {
+            super::run_map(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(
+            super::range(0b0100, 2, 1),
+            (Bound::Included(1), Bound::Excluded(2))
+        );
+        assert_eq!(
+            super::range(0b0101, 1, 1),
+            (Bound::Included(1), Bound::Excluded(1))
+        );
+        assert_eq!(
+            super::range(0b1011, 3, 1),
+            (Bound::Unbounded, Bound::Unbounded)
+        );
+    }
+}
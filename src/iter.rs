@@ -0,0 +1,609 @@
+//! Iterators over the values and the entries of the collections, in order.
+//!
+//! Nodes link back to the previous node at the base level, so every iterator is double-ended, and
+//! its values can be visited from the greatest with [`Iterator::rev`].
+
+use std::{cmp::Reverse, fmt, iter::FusedIterator, marker::PhantomData};
+
+use rand::Rng;
+
+use crate::skipmap::Entry;
+
+/// An iterator over the values of a [`SkipList`](crate::SkipList), in order, which is returned by
+/// [`SkipList::iter`](crate::SkipList::iter).
+pub struct Iter<'a, T, const N: usize>(pub(crate) crate::Iter<'a, T, N>);
+
+impl<T, const N: usize> Clone for Iter<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Iter<'_, T, N>
+where
+    T: fmt::Debug,
+{
+    /// Formats the remaining values like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Iter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl<T, const N: usize> FusedIterator for Iter<'_, T, N> {}
+
+/// An iterator over the values of a descending skiplist, from the greatest, which is returned by
+/// [`DescendingSkipList::iter`](crate::descending::DescendingSkipList::iter).
+pub struct DescendingIter<'a, T, const N: usize>(pub(crate) Iter<'a, Reverse<T>, N>);
+
+impl<T, const N: usize> Clone for DescendingIter<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for DescendingIter<'_, T, N>
+where
+    T: fmt::Debug,
+{
+    /// Formats the remaining values like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for DescendingIter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|value| &value.0)
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for DescendingIter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|value| &value.0)
+    }
+}
+
+impl<T, const N: usize> FusedIterator for DescendingIter<'_, T, N> {}
+
+/// An iterator over the values of a [`SkipList`](crate::SkipList) within a range, in order, which
+/// is returned by [`SkipList::range`](crate::SkipList::range).
+pub struct Range<'a, T, const N: usize>(pub(crate) crate::Iter<'a, T, N>);
+
+impl<T, const N: usize> Clone for Range<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Range<'_, T, N>
+where
+    T: fmt::Debug,
+{
+    /// Formats the remaining values like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for Range<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Range<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl<T, const N: usize> FusedIterator for Range<'_, T, N> {}
+
+/// An iterator over the values of a descending skiplist within a range, from the greatest, which
+/// is returned by [`DescendingSkipList::range`](crate::descending::DescendingSkipList::range).
+pub struct DescendingRange<'a, T, const N: usize>(pub(crate) Range<'a, Reverse<T>, N>);
+
+impl<T, const N: usize> Clone for DescendingRange<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for DescendingRange<'_, T, N>
+where
+    T: fmt::Debug,
+{
+    /// Formats the remaining values like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for DescendingRange<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|value| &value.0)
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for DescendingRange<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|value| &value.0)
+    }
+}
+
+impl<T, const N: usize> FusedIterator for DescendingRange<'_, T, N> {}
+
+/// An iterator that moves the values out of a [`SkipList`](crate::SkipList), in order, which is
+/// returned by [`SkipList::drain`](crate::SkipList::drain).
+///
+/// The skiplist is emptied when the iterator is created, and the values that don't get iterated
+/// are dropped along with the iterator.
+pub struct Drain<'a, T, const N: usize>(
+    pub(crate) crate::IntoIter<T, N>,
+    pub(crate) PhantomData<&'a mut T>,
+);
+
+impl<T, const N: usize> fmt::Debug for Drain<'_, T, N>
+where
+    T: fmt::Debug,
+{
+    /// Formats the remaining values like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.as_iter()).finish()
+    }
+}
+
+impl<T, const N: usize> Iterator for Drain<'_, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Drain<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl<T, const N: usize> FusedIterator for Drain<'_, T, N> {}
+
+/// An iterator that removes the values of a [`SkipList`](crate::SkipList) that match a predicate,
+/// in order, which is returned by [`SkipList::extract_if`](crate::SkipList::extract_if).
+///
+/// Values are only removed as the iterator reaches them, so the values it hasn't reached stay in
+/// the skiplist if it's dropped early.
+pub struct ExtractIf<'a, T, R, const N: usize, F>(
+    pub(crate) crate::ExtractIf<'a, T, R, N>,
+    pub(crate) F,
+)
+where
+    R: Rng;
+
+impl<T, R, const N: usize, F> fmt::Debug for ExtractIf<'_, T, R, N, F>
+where
+    T: fmt::Debug,
+    R: Rng,
+{
+    /// Formats the values that haven't been reached like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.as_iter()).finish()
+    }
+}
+
+impl<T, R, const N: usize, F> Iterator for ExtractIf<'_, T, R, N, F>
+where
+    R: Rng,
+    F: FnMut(&T) -> bool,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next(|value| (self.1)(value))
+    }
+}
+
+impl<T, R, const N: usize, F> FusedIterator for ExtractIf<'_, T, R, N, F>
+where
+    R: Rng,
+    F: FnMut(&T) -> bool,
+{
+}
+
+/// An iterator over the entries of a [`SkipMap`](crate::SkipMap), in order of their keys, which
+/// is returned by [`SkipMap::iter`](crate::SkipMap::iter).
+pub struct MapIter<'a, K, V, const N: usize>(pub(crate) crate::Iter<'a, Entry<K, V>, N>);
+
+impl<K, V, const N: usize> Clone for MapIter<'_, K, V, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for MapIter<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for MapIter<'a, K, V, N> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next()?;
+        Some((&entry.key, &entry.value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for MapIter<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next_back()?;
+        Some((&entry.key, &entry.value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for MapIter<'_, K, V, N> {}
+
+/// An iterator over the entries of a descending skipmap, from the greatest key, which is returned
+/// by [`DescendingSkipMap::iter`](crate::descending::DescendingSkipMap::iter).
+pub struct DescendingMapIter<'a, K, V, const N: usize>(pub(crate) MapIter<'a, Reverse<K>, V, N>);
+
+impl<K, V, const N: usize> Clone for DescendingMapIter<'_, K, V, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for DescendingMapIter<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for DescendingMapIter<'a, K, V, N> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (&key.0, value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for DescendingMapIter<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(key, value)| (&key.0, value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for DescendingMapIter<'_, K, V, N> {}
+
+/// An iterator over the entries of a [`SkipMap`](crate::SkipMap), in order of their keys, through
+/// which the values can be mutated, which is returned by
+/// [`SkipMap::iter_mut`](crate::SkipMap::iter_mut).
+pub struct IterMut<'a, K, V, const N: usize>(pub(crate) crate::IterMut<'a, Entry<K, V>, N>);
+
+impl<K, V, const N: usize> fmt::Debug for IterMut<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(MapIter(self.0.as_iter())).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for IterMut<'a, K, V, N> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next()?;
+        // Only the values are given out mutably, so the keys stay in order.
+        Some((&entry.key, &mut entry.value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for IterMut<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next_back()?;
+        Some((&entry.key, &mut entry.value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for IterMut<'_, K, V, N> {}
+
+/// An iterator over the entries of a descending skipmap, from the greatest key, through which the
+/// values can be mutated.
+///
+/// It's returned by
+/// [`DescendingSkipMap::iter_mut`](crate::descending::DescendingSkipMap::iter_mut).
+pub struct DescendingIterMut<'a, K, V, const N: usize>(pub(crate) IterMut<'a, Reverse<K>, V, N>);
+
+impl<K, V, const N: usize> fmt::Debug for DescendingIterMut<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(DescendingMapIter(MapIter(self.0.0.as_iter()))).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for DescendingIterMut<'a, K, V, N> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (&key.0, value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for DescendingIterMut<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(key, value)| (&key.0, value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for DescendingIterMut<'_, K, V, N> {}
+
+/// An iterator over the values of a [`SkipMap`](crate::SkipMap), in order of their keys, through
+/// which they can be mutated, which is returned by
+/// [`SkipMap::values_mut`](crate::SkipMap::values_mut).
+pub struct ValuesMut<'a, K, V, const N: usize>(pub(crate) crate::IterMut<'a, Entry<K, V>, N>);
+
+impl<K, V, const N: usize> fmt::Debug for ValuesMut<'_, K, V, N>
+where
+    V: fmt::Debug,
+{
+    /// Formats the remaining values like a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = MapIter(self.0.as_iter()).map(|(_, value)| value);
+        f.debug_list().entries(values).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for ValuesMut<'a, K, V, N> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(&mut self.0.next()?.value)
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for ValuesMut<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        Some(&mut self.0.next_back()?.value)
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for ValuesMut<'_, K, V, N> {}
+
+/// An iterator over the entries of a [`SkipMap`](crate::SkipMap) whose keys are within a range, in
+/// order of their keys, which is returned by [`SkipMap::range`](crate::SkipMap::range).
+pub struct MapRange<'a, K, V, const N: usize>(pub(crate) crate::Iter<'a, Entry<K, V>, N>);
+
+impl<K, V, const N: usize> Clone for MapRange<'_, K, V, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for MapRange<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for MapRange<'a, K, V, N> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next()?;
+        Some((&entry.key, &entry.value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for MapRange<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next_back()?;
+        Some((&entry.key, &entry.value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for MapRange<'_, K, V, N> {}
+
+/// An iterator over the entries of a descending skipmap whose keys are within a range, from the
+/// greatest key.
+///
+/// It's returned by [`DescendingSkipMap::range`](crate::descending::DescendingSkipMap::range).
+pub struct DescendingMapRange<'a, K, V, const N: usize>(pub(crate) MapRange<'a, Reverse<K>, V, N>);
+
+impl<K, V, const N: usize> Clone for DescendingMapRange<'_, K, V, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for DescendingMapRange<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for DescendingMapRange<'a, K, V, N> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (&key.0, value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for DescendingMapRange<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(key, value)| (&key.0, value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for DescendingMapRange<'_, K, V, N> {}
+
+/// An iterator over the entries of a [`SkipMap`](crate::SkipMap) whose keys are within a range,
+/// through which the values can be mutated.
+///
+/// It's returned by [`SkipMap::range_mut`](crate::SkipMap::range_mut).
+pub struct RangeMut<'a, K, V, const N: usize>(pub(crate) crate::IterMut<'a, Entry<K, V>, N>);
+
+impl<K, V, const N: usize> fmt::Debug for RangeMut<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(MapIter(self.0.as_iter())).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> Iterator for RangeMut<'a, K, V, N> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next()?;
+        // Only the values are given out mutably, so the keys stay in order.
+        Some((&entry.key, &mut entry.value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for RangeMut<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next_back()?;
+        Some((&entry.key, &mut entry.value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for RangeMut<'_, K, V, N> {}
+
+/// An iterator that moves the entries out of a [`SkipMap`](crate::SkipMap), in order of their
+/// keys.
+///
+/// It's returned by [`SkipMap::drain`](crate::SkipMap::drain) and
+/// [`SkipMap::drain_range`](crate::SkipMap::drain_range). The entries are removed from the skipmap
+/// when the iterator is created, and the entries that don't get iterated are dropped along with
+/// the iterator.
+pub struct MapDrain<'a, K, V, const N: usize>(
+    pub(crate) crate::IntoIter<Entry<K, V>, N>,
+    pub(crate) PhantomData<&'a mut (K, V)>,
+);
+
+impl<K, V, const N: usize> fmt::Debug for MapDrain<'_, K, V, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(MapIter(self.0.as_iter())).finish()
+    }
+}
+
+impl<K, V, const N: usize> Iterator for MapDrain<'_, K, V, N> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next()?;
+        Some((entry.key, entry.value))
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for MapDrain<'_, K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next_back()?;
+        Some((entry.key, entry.value))
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for MapDrain<'_, K, V, N> {}
+
+/// An iterator that removes the entries of a [`SkipMap`](crate::SkipMap) that match a predicate,
+/// in order of their keys, which is returned by
+/// [`SkipMap::extract_if`](crate::SkipMap::extract_if).
+///
+/// Entries are only removed as the iterator reaches them, so the entries it hasn't reached stay in
+/// the skipmap if it's dropped early.
+pub struct MapExtractIf<'a, K, V, R, const N: usize, F>(
+    pub(crate) crate::ExtractIf<'a, Entry<K, V>, R, N>,
+    pub(crate) F,
+)
+where
+    R: Rng;
+
+impl<K, V, R, const N: usize, F> fmt::Debug for MapExtractIf<'_, K, V, R, N, F>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    /// Formats the entries that haven't been reached like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(MapIter(self.0.as_iter())).finish()
+    }
+}
+
+impl<K, V, R, const N: usize, F> Iterator for MapExtractIf<'_, K, V, R, N, F>
+where
+    R: Rng,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next(|entry| (self.1)(&entry.key, &mut entry.value))?;
+        Some((entry.key, entry.value))
+    }
+}
+
+impl<K, V, R, const N: usize, F> FusedIterator for MapExtractIf<'_, K, V, R, N, F>
+where
+    R: Rng,
+    F: FnMut(&K, &mut V) -> bool,
+{
+}
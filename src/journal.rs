@@ -0,0 +1,172 @@
+//! Recording of skipmap mutations so that they can be shipped to and replayed on a replica.
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::{
+    SkipMap,
+    observer::{Event, Observed, Observer},
+};
+
+/// A skipmap that records every successful mutation into a [`Journal`].
+pub type JournaledSkipMap<K, V, R, const N: usize> = Observed<K, V, Journal<K, V>, R, N>;
+
+/// A recorded mutation.
+///
+/// Overwrites are recorded as insertions because replaying them yields the same contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<K, V> {
+    /// A value was inserted at a key.
+    Insert(K, V),
+    /// A key was removed.
+    Remove(K),
+}
+
+impl<K, V> Op<K, V>
+where
+    K: Ord,
+{
+    /// Applies the mutation to the given skipmap.
+    pub fn apply<R, const N: usize>(self, map: &mut SkipMap<K, V, R, N>)
+    where
+        R: Rng,
+    {
+        match self {
+            Self::Insert(key, value) => {
+                map.insert(key, value);
+            }
+            Self::Remove(key) => {
+                map.remove(&key);
+            }
+        }
+    }
+}
+
+/// Applies the given mutations to the skipmap in order.
+pub fn replay<K, V, R, I, const N: usize>(map: &mut SkipMap<K, V, R, N>, ops: I)
+where
+    K: Ord,
+    R: Rng,
+    I: IntoIterator<Item = Op<K, V>>,
+{
+    for op in ops {
+        op.apply(map);
+    }
+}
+
+/// An observer that records every mutation in the order they were made.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Journal<K, V>(Vec<Op<K, V>>);
+
+impl<K, V> Default for Journal<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> fmt::Debug for Journal<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.0).finish()
+    }
+}
+
+impl<K, V> Journal<K, V> {
+    /// Creates an empty journal.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Returns the number of recorded mutations.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether no mutation has been recorded.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Takes all recorded mutations, leaving the journal empty.
+    pub fn take(&mut self) -> Vec<Op<K, V>> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl<K, V> Observer<K, V> for Journal<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn observe(&mut self, event: Event<'_, K, V>) {
+        let op = match event {
+            Event::Insert { key, value } | Event::Update { key, new: value, .. } => {
+                Op::Insert(key.clone(), value.clone())
+            }
+            Event::Remove { key, .. } => Op::Remove(key.clone()),
+        };
+        self.0.push(op);
+    }
+}
+
+impl<K, V, R, const N: usize> JournaledSkipMap<K, V, R, N>
+where
+    R: Rng,
+{
+    /// Takes all mutations recorded since the journal was last taken.
+    pub fn take_journal(&mut self) -> Vec<Op<K, V>> {
+        self.observer_mut().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{Journal, JournaledSkipMap, replay};
+    use crate::SkipMap;
+
+    proptest! {
+        #[test]
+        fn test_replay(ops in vec((0..6u8, 0..16usize, any::<usize>()), 64)) {
+            let mut primary = JournaledSkipMap::<usize, usize, _, 4>::new(Journal::new());
+            let mut replica = SkipMap::<usize, usize, _, 4>::new();
+            for (i, (op, k, v)) in ops.into_iter().enumerate() {
+                match op {
+                    0 | 1 => {
+                        primary.insert(k, v);
+                    }
+                    2 => {
+                        primary.remove(&k);
+                    }
+                    3 => {
+                        primary.pop_first();
+                        primary.remove_lazily(&k);
+                    }
+                    4 => {
+                        primary.drain_range(k..k + 4);
+                        primary.retain(|key, _| key % 4 != k % 4);
+                    }
+                    _ => primary.for_each_mut(|key, value| {
+                        if *key == k {
+                            *value = v;
+                        }
+                    }),
+                }
+                if i % 8 == 0 {
+                    replay(&mut replica, primary.take_journal());
+                }
+            }
+            replay(&mut replica, primary.take_journal());
+            assert!(primary.observer().is_empty());
+            assert!(primary.as_map().iter().eq(&replica));
+        }
+    }
+}
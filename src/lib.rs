@@ -9,330 +9,2001 @@
 )]
 #![deny(clippy::all, missing_docs, rust_2018_idioms, rust_2021_compatibility)]
 
+pub mod arena;
+pub mod augmented;
+mod bulk;
+#[cfg(feature = "columnar")]
+pub mod columnar;
+#[cfg(feature = "concurrent")]
+pub mod concurrent;
+#[cfg(feature = "csv")]
+mod csv;
+pub mod descending;
+#[cfg(feature = "dot")]
+mod dot;
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod iter;
+#[cfg(feature = "journal")]
+pub mod journal;
+pub mod memory;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "observer")]
+pub mod observer;
+pub mod order;
+pub mod policy;
+pub mod prelude;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+pub mod raw;
+#[cfg(any(test, feature = "shadow"))]
+pub mod shadow;
 mod skiplist;
 mod skipmap;
+pub mod small;
+pub mod snapshot;
+pub mod sorted;
+pub mod unrolled;
 
 use std::{
+    alloc,
     borrow::Borrow,
+    cell::Cell,
     cmp, fmt,
-    mem::{ManuallyDrop, MaybeUninit},
+    hash::{BuildHasher, DefaultHasher, Hash, Hasher, RandomState},
+    mem::{self, ManuallyDrop, MaybeUninit},
     num::NonZeroUsize,
-    ptr::NonNull,
+    ops::{Bound, RangeBounds},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
+use policy::OnEqual;
 use rand::{Rng, SeedableRng};
+use raw::RawKey;
 
 pub use skiplist::SkipList;
-pub use skipmap::SkipMap;
+pub use skipmap::{SkipMap, entry};
+
+/// A skiplist whose nodes link into upto 16 levels, which suits upto about 65 thousand values.
+pub type SkipList16<T> = SkipList<T, rand::rngs::SmallRng, 16>;
+
+/// A skiplist whose nodes link into upto 32 levels, which suits upto about 4 billion values.
+pub type SkipList32<T> = SkipList<T, rand::rngs::SmallRng, 32>;
+
+/// A skiplist whose random number generator is seeded from a fixed value, so that the same
+/// operations build the same structure on every run, whatever the seed of the thread.
+pub type DeterministicSkipList<T> = SkipList<T, FixedRng, 32>;
+
+/// A skipmap whose nodes link into upto 16 levels, which suits upto about 65 thousand entries.
+pub type SkipMap16<K, V> = SkipMap<K, V, rand::rngs::SmallRng, 16>;
+
+/// A skipmap whose nodes link into upto 32 levels, which suits upto about 4 billion entries.
+pub type SkipMap32<K, V> = SkipMap<K, V, rand::rngs::SmallRng, 32>;
+
+thread_local! {
+    /// The seed from which the random number generators of new collections are derived on the
+    /// current thread, if it has been set.
+    static SEED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// The environment variable holding the seed of every thread in the deterministic mode.
+#[cfg(feature = "deterministic")]
+const SEED_VAR: &str = "SKIPIDY_SEED";
+
+/// The seed of every thread in the deterministic mode when `SKIPIDY_SEED` isn't set.
+#[cfg(feature = "deterministic")]
+const DEFAULT_SEED: u64 = 0;
+
+/// Returns the seed that every thread starts from in the deterministic mode, which is `0` unless
+/// the `SKIPIDY_SEED` environment variable holds another one.
+///
+/// Threads that weren't seeded with [`seed_thread`] derive the seeds of their collections from this
+/// one, so every run of a program builds the same structures out of the box, and setting
+/// `SKIPIDY_SEED` explores other ones. A failure found that way gets reproduced by rerunning with
+/// the same value.
+#[cfg(feature = "deterministic")]
+#[must_use]
+pub fn seed() -> u64 {
+    static PROCESS_SEED: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+    *PROCESS_SEED.get_or_init(|| {
+        std::env::var(SEED_VAR)
+            .ok()
+            .and_then(|seed| seed.trim().parse().ok())
+            .unwrap_or(DEFAULT_SEED)
+    })
+}
+
+/// The number of values that the larger property tests insert, which gets scaled down under Miri
+/// so that the same tests run there, only on fewer values.
+#[cfg(test)]
+const TEST_SCALE: usize = if cfg!(miri) { 64 } else { 1000 };
+
+/// Seeds the random number generators of the collections created afterward on the current thread
+/// from the given value, instead of from the entropy of the operating system.
+///
+/// Every collection gets a different seed derived from the value, so a program creating the same
+/// collections in the same order makes the same random choices on every run. Without the `os_rng`
+/// feature, e.g. on `wasm32-unknown-unknown`, collections are otherwise seeded from cheap entropy
+/// taken from a counter and the address of the stack.
+///
+/// With the `deterministic` feature, threads that aren't seeded start from the seed returned by
+/// [`seed`], so that failures involving the structure of collections can be reproduced.
+pub fn seed_thread(seed: u64) {
+    let _ = SEED.try_with(|cell| cell.set(Some(seed)));
+}
+
+/// A random number generator that is seeded from [`FixedRng::SEED`] unless it's given another seed,
+/// for collections that make the same random choices on every run.
+///
+/// It doesn't implement [`SeedableRng`], since collections with such a generator seed it from the
+/// thread or the operating system when they're created through [`Default`], [`FromIterator`] and
+/// the like. Collections with this one are created through [`Default`] or from a generator instead.
+#[derive(Clone, Debug)]
+pub struct FixedRng(rand::rngs::SmallRng);
+
+impl FixedRng {
+    /// The seed of the generators created through [`Default`].
+    pub const SEED: u64 = 0;
+
+    /// Creates a generator seeded from the given value.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self(rand::rngs::SmallRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for FixedRng {
+    fn default() -> Self {
+        Self::with_seed(Self::SEED)
+    }
+}
+
+impl rand::RngCore for FixedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.0.fill_bytes(dst);
+    }
+}
+
+/// Returns the number of levels that suits a collection expected to hold about the given number of
+/// values, to be used as its `N`, e.g. `SkipMap<K, V, SmallRng, { levels_for(10_000) }>`.
+///
+/// Heights get promoted to the next level with a probability of a half, so `N` only needs to be
+/// about the logarithm of the number of values. Every node holds a link for each of the `N` levels,
+/// so a smaller `N` saves memory, while a collection outgrowing its hint only gets slower lookups.
+#[must_use]
+pub const fn levels_for(len: usize) -> usize {
+    level_cap::<MAX_LEVELS>(len)
+}
+
+/// Creates the random number generator of a new collection, derived from the seed set with
+/// [`seed_thread`] if any, and seeded by the operating system otherwise.
+fn new_rng<R>() -> R
+where
+    R: SeedableRng,
+{
+    // Moves the seed of the thread along a Weyl sequence, so every collection gets a different one.
+    const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+    let seed = SEED.try_with(|cell| {
+        #[cfg(feature = "deterministic")]
+        let seed = cell.get().unwrap_or_else(crate::seed).wrapping_add(GAMMA);
+        #[cfg(not(feature = "deterministic"))]
+        let seed = cell.get()?.wrapping_add(GAMMA);
+        cell.set(Some(seed));
+        Some(seed)
+    });
+    if let Ok(Some(seed)) = seed {
+        return R::seed_from_u64(seed);
+    }
+    #[cfg(feature = "os_rng")]
+    if let Ok(rng) = R::try_from_os_rng() {
+        return rng;
+    }
+    R::seed_from_u64(fallback_seed())
+}
+
+/// The random number generator that a collection keeps while it's empty, which gets handed to its
+/// storage on the next insertion.
+enum Spare<R> {
+    /// The generator is owned by the storage of the collection.
+    Lent,
+    /// The generator is kept by the collection.
+    Ready(R),
+    /// The generator gets created by the function when it's first needed, so that an empty
+    /// collection can be created in a const context.
+    Lazy(fn() -> R),
+}
+
+impl<R> Spare<R> {
+    /// Returns a generator that gets created by [`new_rng`] when it's first needed.
+    const fn lazy() -> Self
+    where
+        R: SeedableRng,
+    {
+        Self::Lazy(new_rng::<R>)
+    }
+
+    /// Takes the generator for a new storage, creating it if it's lazy.
+    fn take(&mut self) -> R {
+        match mem::replace(self, Self::Lent) {
+            Self::Ready(rng) => rng,
+            Self::Lazy(new) => new(),
+            Self::Lent => unreachable!("the generator is owned by the storage"),
+        }
+    }
+}
+
+/// Returns a random seed for a concurrent collection, whose heights come from a counter.
+#[cfg(feature = "concurrent")]
+fn random_seed() -> u64 {
+    new_rng::<rand::rngs::SmallRng>().random()
+}
+
+/// Returns a seed from entropy that is cheap to get on every target. The seed is only meant to
+/// differ between calls, so it mixes a counter with the address of the stack and the random keys
+/// of the hasher of the standard library, which are fixed on targets without entropy.
+fn fallback_seed() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let local = 0_u8;
+    let mut hasher = RandomState::new().build_hasher();
+    COUNTER.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+    ptr::from_ref(&local).addr().hash(&mut hasher);
+    hasher.finish()
+}
 
 struct NonEmptyStorage<T, R: Rng, const N: usize> {
     rng: R,
-    head: NonNull<SkipNode<T, N>>,
+    bits: RandomBits,
+    /// The links of the sentinel head, which holds no value and skips to the first node at every
+    /// level.
+    head: NonNull<Links<T, N>>,
     levels: NonZeroUsize,
+    /// The number of linked nodes, including those of lazily removed values.
+    len: usize,
+    /// The number of lazily removed values.
+    tombstones: usize,
+    /// The links of the last node at every level, or those of the head for the levels without
+    /// nodes, so that the greatest value can be reached and appended to without a traversal.
+    lasts: [NonNull<Links<T, N>>; N],
+    /// The nodes whose values have been removed, kept for reuse by later insertions.
+    pool: Pool<T, N>,
 }
 
+/// The next node at every level.
+type Links<T, const N: usize> = [Option<NonNull<SkipNode<T, N>>>; N];
+
+/// The links that come right before a value at every level, of which only the current levels get
+/// written. It's only ever passed by reference, so that operations never copy the inactive levels.
+type Trace<T, const N: usize> = [MaybeUninit<NonNull<Links<T, N>>>; N];
+
 impl<T, R, const N: usize> Drop for NonEmptyStorage<T, R, N>
 where
     R: Rng,
 {
     fn drop(&mut self) {
-        let mut curr_ptr = self.head;
-        loop {
-            let next_ptr = {
-                let curr = unsafe { curr_ptr.as_ref() };
-                curr.nexts[0]
+        unsafe { self.free() };
+    }
+}
+
+/// Nodes without values, linked through their base level, which are kept for reuse by later
+/// insertions. Only the link at the base level of a pooled node is ever written.
+///
+/// A collection keeps a pool of its own while it's empty, along with the head of its next storage,
+/// so that reserving ahead of the first insertion leaves nothing to allocate.
+struct Pool<T, const N: usize> {
+    first: Option<NonNull<SkipNode<T, N>>>,
+    len: usize,
+    /// The links of a head allocated ahead of the storage, which takes it when it's created.
+    head: Option<NonNull<Links<T, N>>>,
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Pool<T, N> {
+    fn drop(&mut self) {
+        self.shrink_to(0);
+    }
+}
+
+// SAFETY: The pool only holds allocations without values, which it exclusively owns.
+unsafe impl<T, const N: usize> Send for Pool<T, N> {}
+
+// SAFETY: The pool can't be read or changed through a shared reference.
+unsafe impl<T, const N: usize> Sync for Pool<T, N> {}
+
+impl<T, const N: usize> Pool<T, N> {
+    const fn new() -> Self {
+        Self {
+            first: None,
+            len: 0,
+            head: None,
+        }
+    }
+
+    /// Returns the number of bytes allocated for the pooled nodes and the head, if any.
+    const fn allocated_bytes(&self) -> usize {
+        let head = if self.head.is_some() { size_of::<Links<T, N>>() } else { 0 };
+        head + self.len * size_of::<SkipNode<T, N>>()
+    }
+
+    /// Allocates nodes into the pool until it holds at least the given number of nodes.
+    fn reserve(&mut self, len: usize) {
+        while self.len < len {
+            let layout = alloc::Layout::new::<SkipNode<T, N>>();
+            let Some(curr_ptr) = NonNull::new(unsafe { alloc::alloc(layout) }) else {
+                alloc::handle_alloc_error(layout);
             };
+            unsafe { self.push(curr_ptr.cast()) };
+        }
+    }
+
+    /// Allocates the head of the next storage, unless it already has been.
+    fn reserve_head(&mut self) {
+        if self.head.is_none() {
+            self.head = Some(unsafe { NonNull::new_unchecked(Box::into_raw(Box::new([None; N]))) });
+        }
+    }
+
+    /// Frees the pooled nodes beyond the given number, along with the head once none is left.
+    fn shrink_to(&mut self, len: usize) {
+        while self.len > len
+            && let Some(curr_ptr) = self.pop()
+        {
+            unsafe { SkipNode::free(curr_ptr) };
+        }
+        if len == 0
+            && let Some(head) = self.head.take()
+        {
+            drop(unsafe { Box::from_raw(head.as_ptr()) });
+        }
+    }
+
+    /// Adds a node without a value to the pool.
+    const unsafe fn push(&mut self, curr_ptr: NonNull<SkipNode<T, N>>) {
+        unsafe { SkipNode::links(curr_ptr).cast().write(self.first) };
+        self.first = Some(curr_ptr);
+        self.len += 1;
+    }
+
+    /// Takes a node without a value out of the pool, if there's one.
+    const fn pop(&mut self) -> Option<NonNull<SkipNode<T, N>>> {
+        let Some(curr_ptr) = self.first else {
+            return None;
+        };
+        self.first = unsafe { SkipNode::pooled_next(curr_ptr) };
+        self.len -= 1;
+        Some(curr_ptr)
+    }
+
+    /// Creates a node holding the value, reusing a pooled node if there's one.
+    fn alloc(&mut self, value: T) -> NonNull<SkipNode<T, N>> {
+        let Some(curr_ptr) = self.pop() else {
+            return SkipNode::new(value).alloc();
+        };
+        unsafe { curr_ptr.as_ptr().write(SkipNode::new(value)) };
+        curr_ptr
+    }
+
+    /// Creates a head holding the links, reusing the allocated head if there's one.
+    fn alloc_head(&mut self, links: Links<T, N>) -> NonNull<Links<T, N>> {
+        let Some(head) = self.head.take() else {
+            return unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(links))) };
+        };
+        unsafe { head.write(links) };
+        head
+    }
+}
+
+/// Formats the structure of a collection under the given name, for `{:#?}`, as the values of its
+/// nodes in order, the positions of the nodes linked into every level from the base level up,
+/// and the positions of the nodes of lazily removed values. Positions count the nodes along the
+/// base level from 0, and stand in for their addresses.
+fn fmt_structure<T, R, const N: usize>(
+    storage: Option<&NonEmptyStorage<T, R, N>>,
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+) -> fmt::Result
+where
+    T: fmt::Debug,
+    R: Rng,
+{
+    /// Positions of nodes, which are formatted on a single line even with `{:#?}`.
+    struct Positions(Vec<usize>);
+
+    impl fmt::Debug for Positions {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    let mut nodes = Vec::new();
+    let mut levels = Vec::new();
+    let mut removed = Vec::new();
+    if let Some(storage) = storage {
+        levels.resize_with(storage.levels.get(), || Positions(Vec::new()));
+        for (position, (height, value)) in storage.towers().enumerate() {
+            nodes.push(value);
+            for level in &mut levels[..height] {
+                level.0.push(position);
+            }
+        }
+        let mut next = unsafe { storage.head.as_ref()[0] };
+        let mut position = 0;
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            if curr.removed {
+                removed.push(position);
+            }
+            next = curr.nexts[0];
+            position += 1;
+        }
+    }
+    f.debug_struct(name)
+        .field("nodes", &nodes)
+        .field("levels", &levels)
+        .field("removed", &Positions(removed))
+        .finish()
+}
+
+// SAFETY: The storage exclusively owns all of its nodes, which are only reachable through it, so
+// sending it sends the values along with it.
+unsafe impl<T, R, const N: usize> Send for NonEmptyStorage<T, R, N>
+where
+    T: Send,
+    R: Rng + Send,
+{
+}
+
+// SAFETY: Nodes are only mutated through a mutable reference to the storage, so sharing the storage
+// only ever gives out shared references to the values.
+unsafe impl<T, R, const N: usize> Sync for NonEmptyStorage<T, R, N>
+where
+    T: Sync,
+    R: Rng + Sync,
+{
+}
+
+impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
+where
+    R: Rng,
+{
+    /// Creates a storage whose head skips to the given number of nodes, which must be linked into
+    /// as many levels as there are leading links, and end with the given last nodes at every
+    /// level.
+    fn from_links(
+        rng: R,
+        links: Links<T, N>,
+        lasts: Links<T, N>,
+        len: usize,
+        mut pool: Pool<T, N>,
+    ) -> Option<Self> {
+        assert_levels::<N>();
+        let levels = NonZeroUsize::new(links.iter().take_while(|link| link.is_some()).count())?;
+        let head = pool.alloc_head(links);
+        let lasts = lasts.map(|last| last.map_or(head, |ptr| unsafe { SkipNode::links(ptr) }));
+        Some(Self {
+            rng,
+            bits: RandomBits::new(),
+            head,
+            levels,
+            len,
+            tombstones: 0,
+            lasts,
+            pool,
+        })
+    }
+
+    /// Frees the head and every node, whether linked or pooled, after which the storage must not
+    /// be used.
+    unsafe fn free(&mut self) {
+        let head = unsafe { Box::from_raw(self.head.as_ptr()) };
+        let mut next = head[0];
+        while let Some(curr_ptr) = next {
+            next = unsafe { curr_ptr.as_ref() }.nexts[0];
             unsafe {
                 SkipNode::dealloc(curr_ptr);
             }
-            let Some(next_ptr) = next_ptr else {
+        }
+        self.pool.shrink_to(0);
+    }
+
+    /// Replaces the random number generator, discarding the bits drawn from the previous one.
+    fn set_rng(&mut self, rng: R) {
+        self.rng = rng;
+        self.bits = RandomBits::new();
+    }
+
+    /// Relinks the nodes so that every other node of a level is also linked into the level above,
+    /// as long as the number of values allows, and frees the pooled nodes. Lazily removed values
+    /// get vacuumed first, so the storage must be dropped if it becomes empty.
+    fn compact(&mut self) {
+        self.vacuum();
+        self.pool.shrink_to(0);
+        let cap = level_cap::<N>(self.len);
+        // Tracks the links of the last node at every level, which the next node at the level gets
+        // linked after.
+        let mut lasts = [self.head; N];
+        let mut next = unsafe { self.head.as_ref()[0] };
+        let mut position = 0_usize;
+        while let Some(curr_ptr) = next {
+            position += 1;
+            let height = (position.trailing_zeros() as usize + 1).min(cap);
+            let mut links_ptr = unsafe { SkipNode::links(curr_ptr) };
+            let links = unsafe { links_ptr.as_mut() };
+            next = links[0];
+            for (level, last_ptr) in lasts.iter_mut().enumerate().take(height).skip(1) {
+                unsafe { last_ptr.as_mut()[level] = Some(curr_ptr) };
+                *last_ptr = links_ptr;
+            }
+            // The node is no longer linked into the levels above its height.
+            links[height..].fill(None);
+        }
+        for (level, last_ptr) in lasts.iter_mut().enumerate().skip(1) {
+            unsafe { last_ptr.as_mut()[level] = None };
+        }
+        // The base level hasn't changed.
+        lasts[0] = self.lasts[0];
+        self.lasts = lasts;
+        self.levels = NonZeroUsize::MIN.saturating_add(cap - 1);
+    }
+
+    /// Creates a node holding the value, reusing a pooled node if there's one.
+    fn alloc(&mut self, value: T) -> NonNull<SkipNode<T, N>> {
+        self.pool.alloc(value)
+    }
+
+    /// Moves the value out of an unlinked node, and adds the node to the pool.
+    const unsafe fn release(&mut self, curr_ptr: NonNull<SkipNode<T, N>>) -> T {
+        let value = unsafe { SkipNode::take(curr_ptr) };
+        unsafe { self.pool.push(curr_ptr) };
+        value
+    }
+
+    /// Returns the number of bytes allocated for the head and the nodes, including pooled ones.
+    const fn allocated_bytes(&self) -> usize {
+        size_of::<Links<T, N>>()
+            + self.len * size_of::<SkipNode<T, N>>()
+            + self.pool.allocated_bytes()
+    }
+
+    /// Returns the number of bytes allocated on the heap by the linked values, including the
+    /// lazily removed ones, as measured by the given function.
+    fn heap_size<F>(&self, heap_size: F) -> usize
+    where
+        F: Fn(&T) -> usize,
+    {
+        let mut size = 0;
+        let mut next = unsafe { self.head.as_ref()[0] };
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            size += heap_size(&curr.value);
+            next = curr.nexts[0];
+        }
+        size
+    }
+
+    /// Adds an unlinked node to the pool, returning its value unless it was removed lazily, in
+    /// which case the value gets dropped.
+    unsafe fn reclaim(&mut self, curr_ptr: NonNull<SkipNode<T, N>>) -> Option<T> {
+        let removed = unsafe { curr_ptr.as_ref() }.removed;
+        let value = unsafe { self.release(curr_ptr) };
+        if removed {
+            self.tombstones -= 1;
+            return None;
+        }
+        Some(value)
+    }
+
+    /// Returns the node owning the links, or `None` if they're those of the head.
+    fn node_of(&self, links: NonNull<Links<T, N>>) -> Option<NonNull<SkipNode<T, N>>> {
+        (links != self.head).then(|| unsafe { SkipNode::from_links(links) })
+    }
+
+    /// Returns the last node, whose value is the greatest.
+    const fn tail(&self) -> NonNull<SkipNode<T, N>> {
+        unsafe { SkipNode::from_links(self.lasts[0]) }
+    }
+
+    /// Replaces the value of a linked node, returning the replaced value. The node is revived if it
+    /// was removed lazily, and the replaced value is the removed one.
+    const unsafe fn revive(&mut self, mut curr_ptr: NonNull<SkipNode<T, N>>, value: T) -> T {
+        let curr = unsafe { curr_ptr.as_mut() };
+        if curr.removed {
+            curr.removed = false;
+            self.tombstones -= 1;
+        }
+        mem::replace(&mut curr.value, value)
+    }
+
+    /// Returns the value of the last node, even if it was removed lazily.
+    const fn tail_value(&self) -> &T {
+        unsafe { &(*self.tail().as_ptr()).value }
+    }
+
+    /// Returns the least value, skipping lazily removed ones.
+    fn first(&self) -> Option<&T> {
+        self.iter().next()
+    }
+
+    /// Returns the greatest value, skipping lazily removed ones.
+    fn last(&self) -> Option<&T> {
+        self.iter().next_back()
+    }
+
+    /// Removes the least value, unlinking the lazily removed ones before it. The storage must be
+    /// dropped if it becomes empty.
+    fn pop_first(&mut self) -> Option<T> {
+        while !self.is_empty() {
+            let first_ptr = self.unlink_first();
+            if let Some(value) = unsafe { self.reclaim(first_ptr) } {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Removes the greatest value, unlinking the lazily removed ones after it. The storage must be
+    /// dropped if it becomes empty.
+    fn pop_last(&mut self) -> Option<T> {
+        while !self.is_empty() {
+            let tail_ptr = self.unlink_last();
+            if let Some(value) = unsafe { self.reclaim(tail_ptr) } {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Unlinks the first node, only following the links of the head.
+    fn unlink_first(&mut self) -> NonNull<SkipNode<T, N>> {
+        let head = unsafe { self.head.as_mut() };
+        let Some(first_ptr) = head[0] else {
+            unreachable!();
+        };
+        let first = unsafe { first_ptr.as_ref() };
+        for (level, link) in head.iter_mut().enumerate().take(self.levels.get()) {
+            if *link != Some(first_ptr) {
+                break;
+            }
+            *link = first.nexts[level];
+            if link.is_none() {
+                self.lasts[level] = self.head;
+            }
+        }
+        if let Some(mut next_ptr) = first.nexts[0] {
+            unsafe { next_ptr.as_mut() }.prev = None;
+        }
+        self.shrink_levels();
+        self.len -= 1;
+        first_ptr
+    }
+
+    /// Unlinks the last node, finding the links to it without comparing values.
+    fn unlink_last(&mut self) -> NonNull<SkipNode<T, N>> {
+        let tail_ptr = self.tail();
+        let mut prev_ptr = self.head;
+        for level in (0..self.levels.get()).rev() {
+            while let Some(curr_ptr) = unsafe { prev_ptr.as_ref()[level] }
+                && curr_ptr != tail_ptr
+            {
+                prev_ptr = unsafe { SkipNode::links(curr_ptr) };
+            }
+            // The tail is the last node of every level it's linked into.
+            let prev = unsafe { prev_ptr.as_mut() };
+            if prev[level] == Some(tail_ptr) {
+                prev[level] = None;
+                self.lasts[level] = prev_ptr;
+            }
+        }
+        self.shrink_levels();
+        self.len -= 1;
+        tail_ptr
+    }
+
+    /// Unlinks the node right after the links in the trace, which must have been written by
+    /// [`Self::trace`] since the last change to the storage, or kept up to date by a walk along the
+    /// base level like [`ExtractIf`] does.
+    fn unlink_after(&mut self, trace: &Trace<T, N>, curr_ptr: NonNull<SkipNode<T, N>>) {
+        {
+            let curr = unsafe { curr_ptr.as_ref() };
+            // Removes the node at every level.
+            for (level, prev_ptr) in trace[..self.levels.get()].iter().enumerate() {
+                let prev = unsafe { prev_ptr.assume_init().as_mut() };
+                if prev[level].is_none_or(|ptr| ptr != curr_ptr) {
+                    break;
+                }
+                prev[level] = curr.nexts[level];
+                if prev[level].is_none() {
+                    self.lasts[level] = unsafe { prev_ptr.assume_init() };
+                }
+            }
+            if let Some(mut next_ptr) = curr.nexts[0] {
+                unsafe { next_ptr.as_mut() }.prev = curr.prev;
+            }
+        }
+        // Updates the storage's level by counting the number of links that was removed from the
+        // head.
+        self.shrink_levels();
+        self.len -= 1;
+    }
+
+    /// Unlinks the nodes of every lazily removed value in a single pass over each level, dropping
+    /// the values. The storage must be dropped if it becomes empty.
+    fn vacuum(&mut self) {
+        if self.tombstones == 0 {
+            return;
+        }
+        // Unlinks the nodes from the base level last, once no other level links to them.
+        for level in (0..self.levels.get()).rev() {
+            let mut prev_ptr = self.head;
+            while let Some(curr_ptr) = unsafe { prev_ptr.as_ref()[level] } {
+                let curr = unsafe { curr_ptr.as_ref() };
+                if !curr.removed {
+                    prev_ptr = unsafe { SkipNode::links(curr_ptr) };
+                    continue;
+                }
+                unsafe { prev_ptr.as_mut()[level] = curr.nexts[level] };
+                if level == 0 {
+                    if let Some(mut next_ptr) = curr.nexts[0] {
+                        unsafe { next_ptr.as_mut() }.prev = self.node_of(prev_ptr);
+                    }
+                    drop(unsafe { self.release(curr_ptr) });
+                }
+            }
+            self.lasts[level] = prev_ptr;
+        }
+        self.len -= self.tombstones;
+        self.tombstones = 0;
+        self.shrink_levels();
+    }
+
+    /// Links a node holding the value after the last node, which must not hold a greater value,
+    /// only following the links of the last nodes. The node gets linked into the given number of
+    /// levels, or a random one if there's none.
+    fn append(&mut self, value: T, height: Option<usize>) -> NonNull<SkipNode<T, N>> {
+        let mut curr_ptr = self.alloc(value);
+        unsafe { curr_ptr.as_mut() }.prev = self.node_of(self.lasts[0]);
+        let links_ptr = unsafe { SkipNode::links(curr_ptr) };
+        self.len += 1;
+        let height = height.unwrap_or_else(|| self.random_height());
+        for level in 0..height {
+            if level >= self.levels.get() {
+                // The head is the last at the levels above the current ones.
+                self.levels = self.levels.saturating_add(1);
+            }
+            unsafe { self.lasts[level].as_mut()[level] = Some(curr_ptr) };
+            self.lasts[level] = links_ptr;
+        }
+        curr_ptr
+    }
+
+    /// Determines the height of a new node, which goes upto one level higher than the current
+    /// levels, as long as the storage holds enough values to make use of it.
+    fn random_height(&mut self) -> usize {
+        let max = self.levels.saturating_add(1).get().min(level_cap::<N>(self.len));
+        self.bits.height(&mut self.rng, max)
+    }
+
+    /// Drops the empty levels at the top, keeping at least one level.
+    const fn shrink_levels(&mut self) {
+        let head = unsafe { self.head.as_ref() };
+        while self.levels.get() > 1 && head[self.levels.get() - 1].is_none() {
+            self.levels = unsafe { NonZeroUsize::new_unchecked(self.levels.get() - 1) };
+        }
+    }
+
+    /// Returns whether the last value has been removed, after which the storage must be dropped.
+    const fn is_empty(&self) -> bool {
+        unsafe { self.head.as_ref()[0] }.is_none()
+    }
+
+    /// Returns an iterator over the values, in order.
+    const fn iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            front: unsafe { self.head.as_ref()[0] },
+            back: Some(self.tail()),
+            _storage: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the values, in order, through which they can be mutated without
+    /// changing their order.
+    const fn iter_mut(&mut self) -> IterMut<'_, T, N> {
+        IterMut {
+            front: unsafe { self.head.as_ref()[0] },
+            back: Some(self.tail()),
+            _storage: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the values, in order, along with the number of levels that the
+    /// node of each value is linked into.
+    fn towers(&self) -> impl Iterator<Item = (usize, &T)> {
+        // Tracks the node that comes next at every level, which is the node being visited for
+        // every level that the node is linked into.
+        let mut nexts = *unsafe { self.head.as_ref() };
+        let mut next = nexts[0];
+        std::iter::from_fn(move || {
+            let curr_ptr = next?;
+            let curr = unsafe { curr_ptr.as_ref() };
+            let height = nexts.iter().take_while(|&&ptr| ptr == Some(curr_ptr)).count();
+            nexts[..height].copy_from_slice(&curr.nexts[..height]);
+            next = curr.nexts[0];
+            Some((height, &curr.value))
+        })
+    }
+
+    /// Moves the values out of the storage, in order, freeing every node and dropping the values
+    /// that were removed lazily.
+    fn into_vec(self) -> Vec<T> {
+        self.into_values().1.collect()
+    }
+
+    /// Frees the head and the pooled nodes, returning the random number generator along with an
+    /// iterator that moves the values out of the linked nodes, freeing them as it goes.
+    fn into_values(mut self) -> (R, IntoIter<T, N>) {
+        self.pool.shrink_to(0);
+        let storage = ManuallyDrop::new(self);
+        let values = IntoIter {
+            front: unsafe { storage.head.as_ref()[0] },
+            back: Some(storage.tail()),
+        };
+        // The random number generator and the head are the only fields that still have to be
+        // dropped.
+        drop(unsafe { Box::from_raw(storage.head.as_ptr()) });
+        (unsafe { ptr::read(&raw const storage.rng) }, values)
+    }
+}
+
+impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
+where
+    R: Rng,
+{
+    fn new(value: T, rng: R, pool: Pool<T, N>) -> Self {
+        Self::with_height(value, 1, rng, pool)
+    }
+
+    /// Creates a storage holding a single value, whose node is linked into the given number of
+    /// levels, and whose head and node come from the pool if it has them.
+    fn with_height(value: T, height: usize, rng: R, mut pool: Pool<T, N>) -> Self {
+        let mut links = [None; N];
+        links[..height].fill(Some(pool.alloc(value)));
+        let Some(storage) = Self::from_links(rng, links, links, 1, pool) else {
+            unreachable!();
+        };
+        storage
+    }
+
+    /// Frees every node, returning the random number generator so that it can be handed to the
+    /// next storage of the collection.
+    fn into_rng(self) -> R {
+        let mut storage = ManuallyDrop::new(self);
+        unsafe { storage.free() };
+        unsafe { ptr::read(&raw const storage.rng) }
+    }
+}
+
+impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
+where
+    T: Ord,
+    R: Rng,
+{
+    /// Panics if the structure of the storage is inconsistent. Values of the base level must be
+    /// in order, and strictly so if equal values aren't allowed, while every level must link a
+    /// subsequence of the nodes of the level below, and the counters must match the nodes.
+    #[cfg(any(test, feature = "invariants"))]
+    fn assert_invariants(&self, strict: bool) {
+        let head = unsafe { self.head.as_ref() };
+        for (level, next) in head.iter().enumerate() {
+            let populated = level < self.levels.get();
+            assert_eq!(next.is_some(), populated, "the head doesn't match the levels at {level}");
+        }
+        let (mut len, mut tombstones) = (0, 0);
+        let mut prev: Option<&T> = None;
+        let mut prev_ptr = None;
+        let mut next = head[0];
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            assert_eq!(curr.prev, prev_ptr, "the node at position {len} doesn't link back");
+            prev_ptr = Some(curr_ptr);
+            if let Some(prev) = prev {
+                let ordered = if strict { prev < &curr.value } else { prev <= &curr.value };
+                assert!(ordered, "the value at position {len} is out of order");
+            }
+            prev = Some(&curr.value);
+            len += 1;
+            tombstones += usize::from(curr.removed);
+            next = curr.nexts[0];
+        }
+        assert_eq!(len, self.len, "the length doesn't match the nodes");
+        assert_eq!(tombstones, self.tombstones, "the tombstones don't match the nodes");
+        for level in 0..N {
+            let mut last = self.head;
+            let mut below = level.checked_sub(1).and_then(|below| head[below]);
+            let mut next = head[level];
+            while let Some(curr_ptr) = next {
+                if level > 0 {
+                    // Skips the nodes of the level below that aren't linked into this level.
+                    while below.is_some_and(|ptr| ptr != curr_ptr) {
+                        below = below.and_then(|ptr| unsafe { ptr.as_ref() }.nexts[level - 1]);
+                    }
+                    assert!(below.is_some(), "a node at {level} is missing from the level below");
+                    below = unsafe { curr_ptr.as_ref() }.nexts[level - 1];
+                }
+                last = unsafe { SkipNode::links(curr_ptr) };
+                next = unsafe { curr_ptr.as_ref() }.nexts[level];
+            }
+            assert_eq!(last, self.lasts[level], "the last node at {level} is stale");
+        }
+        let mut pool_len = 0;
+        let mut next = self.pool.first;
+        while let Some(curr_ptr) = next {
+            pool_len += 1;
+            next = unsafe { SkipNode::pooled_next(curr_ptr) };
+        }
+        assert_eq!(pool_len, self.pool.len, "the length of the pool doesn't match its nodes");
+        assert!(self.pool.head.is_none(), "the storage holds a spare head");
+    }
+
+    fn get<'t, U>(&'t self, value: &U) -> Option<&'t T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let curr_ptr = self.find(value)?;
+        let curr = unsafe { curr_ptr.as_ref() };
+        Some(&curr.value)
+    }
+
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "the value gets mutated through the reference"
+    )]
+    fn get_mut<'t, U>(&'t mut self, value: &U) -> Option<&'t mut T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let mut curr_ptr = self.find(value)?;
+        let curr = unsafe { curr_ptr.as_mut() };
+        Some(&mut curr.value)
+    }
+
+    /// Returns the node of a value equal to the given one that hasn't been removed lazily.
+    fn find<U>(&self, value: &U) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        if self.tombstones == 0 {
+            return self.search(value, |_, _| {});
+        }
+        // Looks through every equal value, since only some of them might have been removed.
+        let mut next = self.lower_bound(value);
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            if curr.value.borrow() != value {
+                break;
+            }
+            if !curr.removed {
+                return Some(curr_ptr);
+            }
+            next = curr.nexts[0];
+        }
+        None
+    }
+
+    /// Returns the node of a value equal to the given primitive key that hasn't been removed
+    /// lazily, traversing like [`Self::lower_bound`] without reusing comparisons.
+    fn find_raw<K>(&self, key: K) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<K>,
+        K: RawKey,
+    {
+        let mut prev_ptr = self.head;
+        let mut probe = Probe::default();
+        for level in (0..self.levels.get()).rev() {
+            while let Some(curr_ptr) = unsafe { prev_ptr.as_ref()[level] }
+                && {
+                    probe.compared();
+                    *unsafe { curr_ptr.as_ref() }.value.borrow() < key
+                }
+            {
+                prev_ptr = unsafe { SkipNode::links(curr_ptr) };
+                probe.hopped();
+            }
+        }
+        probe.finish();
+        let mut next = unsafe { prev_ptr.as_ref()[0] };
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            if *curr.value.borrow() != key {
+                break;
+            }
+            if !curr.removed {
+                return Some(curr_ptr);
+            }
+            next = curr.nexts[0];
+        }
+        None
+    }
+
+    /// Marks a value equal to the given one as removed without unlinking its node, returning
+    /// whether there was such a value. The storage gets vacuumed once most of its nodes hold
+    /// removed values, so it must be dropped if it becomes empty.
+    fn remove_lazily<U>(&mut self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let Some(mut curr_ptr) = self.find(value) else {
+            return false;
+        };
+        unsafe { curr_ptr.as_mut() }.removed = true;
+        self.tombstones += 1;
+        if self.tombstones * 2 > self.len {
+            self.vacuum();
+        }
+        true
+    }
+
+    /// Inserts the value or replaces an equal one, returning the stored value along with the
+    /// replaced one. A new node gets linked into the given number of levels, or a random one if
+    /// there's none.
+    fn upsert(&mut self, value: T, height: Option<usize>) -> (&T, Option<T>) {
+        if self.tail_value() < &value {
+            let curr_ptr = self.append(value, height);
+            return (unsafe { &(*curr_ptr.as_ptr()).value }, None);
+        }
+        // Traverses the storage and searches for the value, while tracking the links that might get
+        // updated due to the insertion.
+        let mut trace: Trace<T, N> = [MaybeUninit::uninit(); N];
+        let found = self.search(&value, |level, ptr| {
+            trace[level].write(ptr);
+        });
+        if let Some(curr_ptr) = found {
+            let removed = unsafe { curr_ptr.as_ref() }.removed;
+            let old = unsafe { self.revive(curr_ptr, value) };
+            let curr = unsafe { curr_ptr.as_ref() };
+            return (&curr.value, (!removed).then_some(old));
+        }
+        let curr_ptr = self.insert_after(&trace, value, height);
+        let curr = unsafe { curr_ptr.as_ref() };
+        (&curr.value, None)
+    }
+
+    fn insert(&mut self, value: T, height: Option<usize>) {
+        if self.tail_value() <= &value {
+            self.append(value, height);
+            return;
+        }
+        // Traverses the storage and searches for the value, while tracking the links that might get
+        // updated due to the insertion.
+        let mut trace: Trace<T, N> = [MaybeUninit::uninit(); N];
+        self.trace(&value, &mut trace);
+        self.insert_after(&trace, value, height);
+    }
+
+    /// Inserts the value, keeping, replacing, or rejecting an equal one as given, and returns the
+    /// replaced or the rejected value. A lazily removed equal value always gets replaced, and a new
+    /// node gets linked into the given number of levels, or a random one if there's none.
+    fn insert_on_equal(&mut self, value: T, height: Option<usize>, on_equal: OnEqual) -> Option<T> {
+        match on_equal {
+            OnEqual::Keep => {
+                self.insert(value, height);
+                None
+            }
+            OnEqual::Replace => self.upsert(value, height).1,
+            OnEqual::Reject => {
+                if self.tail_value() < &value {
+                    self.append(value, height);
+                    return None;
+                }
+                let mut trace: Trace<T, N> = [MaybeUninit::uninit(); N];
+                let Some(curr_ptr) = self.trace(&value, &mut trace) else {
+                    self.insert_after(&trace, value, height);
+                    return None;
+                };
+                if !unsafe { curr_ptr.as_ref() }.removed {
+                    return Some(value);
+                }
+                unsafe { self.revive(curr_ptr, value) };
+                None
+            }
+        }
+    }
+
+    /// Links a node holding the value right after the links in the trace, which must have been
+    /// written by [`Self::trace`] since the last change to the storage. The node gets linked into
+    /// the given number of levels, or a random one if there's none.
+    fn insert_after(
+        &mut self,
+        trace: &Trace<T, N>,
+        value: T,
+        height: Option<usize>,
+    ) -> NonNull<SkipNode<T, N>> {
+        // Adds the new node to the base level.
+        let mut curr_ptr = self.alloc(value);
+        let curr = unsafe { curr_ptr.as_mut() };
+        {
+            let mut prev_ptr = unsafe { trace[0].assume_init() };
+            curr.prev = self.node_of(prev_ptr);
+            let prev = unsafe { prev_ptr.as_mut() };
+            curr.nexts[0] = prev[0];
+            prev[0] = Some(curr_ptr);
+        }
+        let links_ptr = unsafe { SkipNode::links(curr_ptr) };
+        match curr.nexts[0] {
+            Some(mut next_ptr) => unsafe { next_ptr.as_mut() }.prev = Some(curr_ptr),
+            None => self.lasts[0] = links_ptr,
+        }
+        self.len += 1;
+        let height = height.unwrap_or_else(|| self.random_height());
+        // Skips the base level.
+        for level in 1..height {
+            let prev = if level >= self.levels.get() {
+                // Increases the current number of levels and uses the head as the "previous" links.
+                // This ensures the head can skip to the new node.
+                self.levels = self.levels.saturating_add(1);
+                unsafe { self.head.as_mut() }
+            } else {
+                unsafe { trace[level].assume_init().as_mut() }
+            };
+            // Adds the new node to the current level.
+            curr.nexts[level] = prev[level];
+            prev[level] = Some(curr_ptr);
+            if curr.nexts[level].is_none() {
+                self.lasts[level] = links_ptr;
+            }
+        }
+        curr_ptr
+    }
+
+    /// Removes a value equal to the given one, returning it if it exists. Lazily removed values
+    /// that are equal get unlinked along the way. The storage must be dropped if it becomes empty.
+    fn remove<U>(&mut self, value: &U) -> Option<T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        loop {
+            let curr_ptr = self.unlink(value)?;
+            if let Some(value) = unsafe { self.reclaim(curr_ptr) } {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Unlinks the first node whose value is equal to the given one, returning it if it exists.
+    fn unlink<U>(&mut self, value: &U) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        // Traverses the storage and searches for the value, while tracking the links that might get
+        // updated due to the removal.
+        let mut trace: Trace<T, N> = [MaybeUninit::uninit(); N];
+        let curr_ptr = self.trace(value, &mut trace)?;
+        self.unlink_after(&trace, curr_ptr);
+        Some(curr_ptr)
+    }
+
+    /// Traverses the storage like [`Self::descend`], writing the links that come right before the
+    /// value at every level into the trace. Returns the first node whose value is equal, if any,
+    /// which might have been removed lazily.
+    fn trace<U>(&self, value: &U, trace: &mut Trace<T, N>) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        self.descend(value, |level, ptr| {
+            trace[level].write(ptr);
+        })
+    }
+
+    /// Returns an iterator over the values within the range, in order.
+    fn range<U, B>(&self, range: &B) -> Iter<'_, T, N>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        B: RangeBounds<U>,
+    {
+        let (front, back) = self.bounds(range);
+        Iter {
+            front,
+            back,
+            _storage: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the values within the range, in order, through which they can be
+    /// mutated without changing their order.
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "the values get mutated through the iterator"
+    )]
+    fn range_mut<U, B>(&mut self, range: &B) -> IterMut<'_, T, N>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        B: RangeBounds<U>,
+    {
+        let (front, back) = self.bounds(range);
+        IterMut {
+            front,
+            back,
+            _storage: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the first and the last node within the range, seeking both from the head. Both are
+    /// `None` if the range holds no node, e.g. when its start is greater than its end.
+    #[expect(clippy::type_complexity, reason = "the nodes are returned as a pair")]
+    fn bounds<U, B>(
+        &self,
+        range: &B,
+    ) -> (Option<NonNull<SkipNode<T, N>>>, Option<NonNull<SkipNode<T, N>>>)
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        B: RangeBounds<U>,
+    {
+        let first = match range.start_bound() {
+            Bound::Included(start) => self.lower_bound(start),
+            Bound::Excluded(start) => self.upper_bound(start),
+            Bound::Unbounded => unsafe { self.head.as_ref()[0] },
+        };
+        // The first node beyond the end comes at or before the first node of the range, which
+        // leaves the range empty.
+        let Some(first_ptr) = first else {
+            return (None, None);
+        };
+        let value: &U = unsafe { first_ptr.as_ref() }.value.borrow();
+        let end = match range.end_bound() {
+            Bound::Included(end) if value <= end => self.upper_bound(end),
+            Bound::Excluded(end) if value < end => self.lower_bound(end),
+            Bound::Unbounded => None,
+            _ => return (None, None),
+        };
+        // The node before the end comes at or after the first node.
+        let last = end.map_or_else(|| self.tail(), |end_ptr| {
+            let Some(last_ptr) = unsafe { end_ptr.as_ref() }.prev else {
+                unreachable!();
+            };
+            last_ptr
+        });
+        (first, Some(last))
+    }
+
+    /// Returns the first node whose value is greater than the given one, if any.
+    fn upper_bound<U>(&self, value: &U) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let mut prev_ptr = self.head;
+        self.descend_past(value, |_, ptr| prev_ptr = ptr);
+        unsafe { prev_ptr.as_ref()[0] }
+    }
+
+    /// Traverses the storage like [`Self::descend`], but calls the given function on the links of
+    /// the last node that isn't greater than the value at each level, so past the equal values.
+    fn descend_past<U, V>(&self, value: &U, mut visit: V)
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        V: FnMut(usize, NonNull<Links<T, N>>),
+    {
+        let mut prev_ptr = self.head;
+        let mut stop = None;
+        let mut probe = Probe::default();
+        for level in (0..self.levels.get()).rev() {
+            while let Some(curr_ptr) = unsafe { prev_ptr.as_ref()[level] } {
+                let ordering = Self::compare(curr_ptr, level, value, stop, &mut probe);
+                if ordering == cmp::Ordering::Greater {
+                    stop = Some((curr_ptr, ordering));
+                    break;
+                }
+                prev_ptr = unsafe { SkipNode::links(curr_ptr) };
+                probe.hopped();
+            }
+            visit(level, prev_ptr);
+        }
+        probe.finish();
+    }
+
+    /// Unlinks the nodes within the range, returning an iterator that moves their values out. The
+    /// links before and after the range are found by descending the levels once for each end, and
+    /// every level gets spliced once, however many nodes the range holds. The storage must be
+    /// dropped if it becomes empty.
+    fn unlink_range<U, B>(&mut self, range: &B) -> IntoIter<T, N>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        B: RangeBounds<U>,
+    {
+        let levels = self.levels.get();
+        // The links of the last node before the range at every level.
+        let mut starts: Trace<T, N> = [MaybeUninit::uninit(); N];
+        match range.start_bound() {
+            Bound::Included(start) => {
+                self.trace(start, &mut starts);
+            }
+            Bound::Excluded(start) => self.descend_past(start, |level, ptr| {
+                starts[level].write(ptr);
+            }),
+            Bound::Unbounded => starts[..levels].fill(MaybeUninit::new(self.head)),
+        }
+        let Some(first_ptr) = (unsafe { starts[0].assume_init().as_ref()[0] }) else {
+            return IntoIter::empty();
+        };
+        // The links of the last node within the range at every level, or of the last node before
+        // it for the levels without such a node.
+        let mut ends: Trace<T, N> = [MaybeUninit::uninit(); N];
+        let value: &U = unsafe { first_ptr.as_ref() }.value.borrow();
+        match range.end_bound() {
+            Bound::Included(end) if value <= end => self.descend_past(end, |level, ptr| {
+                ends[level].write(ptr);
+            }),
+            Bound::Excluded(end) if value < end => {
+                self.trace(end, &mut ends);
+            }
+            Bound::Unbounded => {
+                for (end, last) in ends.iter_mut().zip(self.lasts).take(levels) {
+                    end.write(last);
+                }
+            }
+            // The range starts after its end.
+            _ => return IntoIter::empty(),
+        }
+        let last_ptr = unsafe { SkipNode::from_links(ends[0].assume_init()) };
+        // Counts the nodes of the range, which the storage no longer holds once they're unlinked.
+        let (mut len, mut tombstones) = (0, 0);
+        let mut curr_ptr = first_ptr;
+        loop {
+            let curr = unsafe { curr_ptr.as_ref() };
+            len += 1;
+            tombstones += usize::from(curr.removed);
+            if curr_ptr == last_ptr {
                 break;
+            }
+            let Some(next_ptr) = curr.nexts[0] else {
+                unreachable!();
             };
             curr_ptr = next_ptr;
         }
+        for (level, (start, end)) in starts.iter().zip(&ends).enumerate().take(levels) {
+            let (mut prev_ptr, last_ptr) = unsafe { (start.assume_init(), end.assume_init()) };
+            if prev_ptr == last_ptr {
+                // No node of the range is linked into the level.
+                continue;
+            }
+            let next = unsafe { last_ptr.as_ref()[level] };
+            unsafe { prev_ptr.as_mut()[level] = next };
+            match next {
+                Some(mut next_ptr) if level == 0 => {
+                    unsafe { next_ptr.as_mut() }.prev = self.node_of(prev_ptr);
+                }
+                Some(_) => {}
+                None => self.lasts[level] = prev_ptr,
+            }
+        }
+        self.len -= len;
+        self.tombstones -= tombstones;
+        self.shrink_levels();
+        IntoIter {
+            front: Some(first_ptr),
+            back: Some(last_ptr),
+        }
     }
-}
 
-impl<T, R, const N: usize> fmt::Debug for NonEmptyStorage<T, R, N>
-where
-    T: fmt::Debug,
-    R: Rng,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// Returns the first node whose value isn't less than the given one, if any.
+    fn lower_bound<U>(&self, value: &U) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let mut prev_ptr = self.head;
+        self.descend(value, |_, ptr| prev_ptr = ptr);
+        unsafe { prev_ptr.as_ref()[0] }
+    }
+
+    /// Traverses the storage like [`Self::descend`], but stops at the first node equal to the value,
+    /// which is returned. The function is only called on the levels that were fully traversed.
+    fn search<U, V>(&self, value: &U, mut visit: V) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        V: FnMut(usize, NonNull<Links<T, N>>),
+    {
+        let mut prev_ptr = self.head;
+        let mut stop = None;
+        let mut probe = Probe::default();
         for level in (0..self.levels.get()).rev() {
-            write!(f, "[")?;
-            let mut curr_ptr = self.head;
-            loop {
-                let curr = unsafe { curr_ptr.as_ref() };
-                write!(f, "{:?} ({:#p})", curr.value, curr_ptr)?;
-                let Some(next_ptr) = curr.nexts[level] else {
-                    break;
-                };
-                write!(f, ", ")?;
-                curr_ptr = next_ptr;
+            while let Some(curr_ptr) = unsafe { prev_ptr.as_ref()[level] } {
+                match Self::compare(curr_ptr, level, value, stop, &mut probe) {
+                    cmp::Ordering::Less => {
+                        prev_ptr = unsafe { SkipNode::links(curr_ptr) };
+                        probe.hopped();
+                    }
+                    cmp::Ordering::Equal => {
+                        probe.finish();
+                        return Some(curr_ptr);
+                    }
+                    cmp::Ordering::Greater => {
+                        stop = Some((curr_ptr, cmp::Ordering::Greater));
+                        break;
+                    }
+                }
             }
-            if level == 0 {
-                write!(f, "]")?;
-            } else {
-                writeln!(f, "]")?;
+            visit(level, prev_ptr);
+        }
+        probe.finish();
+        None
+    }
+
+    /// Traverses the storage, descending down all levels, and calling the given function on the
+    /// links of the last node less than the value at each level, or on the head's links if there's
+    /// no such node. Returns the node right after those links at the base level if it's equal to
+    /// the value.
+    fn descend<U, V>(&self, value: &U, mut visit: V) -> Option<NonNull<SkipNode<T, N>>>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        V: FnMut(usize, NonNull<Links<T, N>>),
+    {
+        let mut prev_ptr = self.head;
+        let mut stop = None;
+        let mut probe = Probe::default();
+        for level in (0..self.levels.get()).rev() {
+            while let Some(curr_ptr) = unsafe { prev_ptr.as_ref()[level] } {
+                let ordering = Self::compare(curr_ptr, level, value, stop, &mut probe);
+                if ordering != cmp::Ordering::Less {
+                    stop = Some((curr_ptr, ordering));
+                    break;
+                }
+                prev_ptr = unsafe { SkipNode::links(curr_ptr) };
+                probe.hopped();
             }
+            visit(level, prev_ptr);
         }
-        Ok(())
+        probe.finish();
+        let next = unsafe { prev_ptr.as_ref()[0] };
+        stop.and_then(|(ptr, ordering)| {
+            (Some(ptr) == next && ordering == cmp::Ordering::Equal).then_some(ptr)
+        })
     }
-}
 
-impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
-where
-    R: Rng + SeedableRng,
-{
-    fn new(value: T) -> Self {
-        Self {
-            rng: R::from_os_rng(),
-            head: SkipNode::new(value).alloc(),
-            levels: NonZeroUsize::MIN,
+    /// Compares the value of the node with the given one, reusing the result for the node that
+    /// stopped the traversal of the level above, which gets reached again on the level below.
+    fn compare<U>(
+        curr_ptr: NonNull<SkipNode<T, N>>,
+        level: usize,
+        value: &U,
+        stop: Option<(NonNull<SkipNode<T, N>>, cmp::Ordering)>,
+        probe: &mut Probe,
+    ) -> cmp::Ordering
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        if let Some((stop_ptr, ordering)) = stop
+            && stop_ptr == curr_ptr
+        {
+            return ordering;
         }
+        let curr = unsafe { curr_ptr.as_ref() };
+        // Fetches the next candidate while the current one gets compared.
+        prefetch(curr.nexts[level]);
+        probe.compared();
+        let curr_value: &U = curr.value.borrow();
+        curr_value.cmp(value)
     }
 }
 
-impl<T, R, const N: usize> NonEmptyStorage<T, R, N>
+/// The greatest number of levels that a collection can link nodes into. A storage would need more
+/// than `2^64` nodes for its towers to reach any higher.
+const MAX_LEVELS: usize = 64;
+
+/// Fails to compile unless collections link nodes into at least one level, and at most
+/// [`MAX_LEVELS`], so that an invalid `N` gets caught when the collection is created rather than
+/// misbehaving or wasting memory at runtime.
+const fn assert_levels<const N: usize>() {
+    const {
+        assert!(N >= 1, "collections must link nodes into at least one level");
+        assert!(N <= MAX_LEVELS, "collections can link nodes into at most 64 levels");
+    };
+}
+
+/// Returns the number of levels that nodes can reach in a storage holding the given number of
+/// values, which is about the logarithm of the number of values, so that the towers of small
+/// storages stay short whatever `N` is.
+const fn level_cap<const N: usize>(len: usize) -> usize {
+    let cap = match len.checked_ilog2() {
+        Some(log) => log as usize + 1,
+        None => 1,
+    };
+    if cap < N { cap } else { N }
+}
+
+/// Returns the height of a node derived from the hash of its value, which has the same chance to
+/// reach every level as a random height, but only depends on the value. The hasher of the standard
+/// library is created with fixed keys, so heights are the same on every run of the same build.
+fn hashed_height<U, const N: usize>(value: &U) -> usize
 where
-    T: Ord,
-    R: Rng,
+    U: Hash + ?Sized,
 {
-    fn get<'t, U>(&'t self, value: &U) -> Option<&'t T>
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    (hasher.finish().trailing_ones() as usize + 1).min(N)
+}
+
+/// Random bits drawn ahead of time, which get consumed a few at a time to decide the heights of new
+/// nodes, so that a random number isn't drawn for every node.
+#[derive(Debug)]
+struct RandomBits {
+    bits: u64,
+    len: u32,
+}
+
+impl RandomBits {
+    const fn new() -> Self {
+        Self { bits: 0, len: 0 }
+    }
+
+    /// Returns a height between 1 and the given maximum, where the chance to reach a level drops
+    /// by half at every level.
+    ///
+    /// The maximum is at most [`MAX_LEVELS`], which is as many bits as a random number holds, since
+    /// [`assert_levels`] caps `N` there. The bits left over from earlier heights can still run out
+    /// midway, in which case more get drawn and the height carries on from where it was.
+    fn height<R>(&mut self, rng: &mut R, max: usize) -> usize
     where
-        T: Borrow<U>,
-        U: Ord + ?Sized,
+        R: Rng,
     {
-        match self.head_cmp(value) {
-            cmp::Ordering::Greater => None,
-            cmp::Ordering::Equal => {
-                let head = unsafe { self.head.as_ref() };
-                Some(&head.value)
-            }
-            cmp::Ordering::Less => {
-                // Traverses the storage and searches for the value.
-                let mut prev_ptr = self.head;
-                self.descend(value, |_, ptr| prev_ptr = ptr);
-                // Checks if the value exists. The trace only includes upto the node right before
-                // the one that will potentially be matched.
-                let curr_ptr = {
-                    let prev = unsafe { prev_ptr.as_ref() };
-                    prev.nexts[0]?
-                };
-                let curr = unsafe { curr_ptr.as_ref() };
-                (curr.value.borrow() == value).then_some(&curr.value)
+        debug_assert!((1..=MAX_LEVELS).contains(&max));
+        // Every consecutive one bit promotes the node to the next level, until a zero bit is
+        // reached, drawing more bits whenever they run out.
+        let mut height = 1;
+        while height < max {
+            if self.len == 0 {
+                self.bits = rng.random();
+                self.len = u64::BITS;
+            }
+            let ones = self.bits.trailing_ones();
+            let remaining = u32::try_from(max - height).unwrap_or(u32::MAX);
+            if ones >= remaining {
+                // Stops at the maximum without looking at the bits after the last promotion.
+                self.consume(remaining);
+                return max;
+            }
+            height += ones as usize;
+            if ones < self.len {
+                // Consumes the zero bit that ended the promotions.
+                self.consume(ones + 1);
+                break;
             }
+            self.consume(ones);
         }
+        height
     }
 
-    fn upsert(&mut self, value: T) -> Option<T> {
-        match self.head_cmp(&value) {
-            cmp::Ordering::Greater => {
-                self.insert_head(value);
-                None
+    const fn consume(&mut self, len: u32) {
+        self.bits = match self.bits.checked_shr(len) {
+            Some(bits) => bits,
+            None => 0,
+        };
+        self.len -= len;
+    }
+}
+
+/// An iterator over the values of a storage, in order, from both ends of a run of nodes at the
+/// base level.
+struct Iter<'t, T, const N: usize> {
+    /// The first node that hasn't been visited, or `None` once every node has been.
+    front: Option<NonNull<SkipNode<T, N>>>,
+    /// The last node that hasn't been visited, or `None` once every node has been.
+    back: Option<NonNull<SkipNode<T, N>>>,
+    _storage: std::marker::PhantomData<&'t SkipNode<T, N>>,
+}
+
+impl<T, const N: usize> Iter<'_, T, N> {
+    /// Returns an iterator over no values, for an empty collection.
+    const fn empty() -> Self {
+        Self {
+            front: None,
+            back: None,
+            _storage: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize> Clone for Iter<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            front: self.front,
+            back: self.back,
+            _storage: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'t, T, const N: usize> Iterator for Iter<'t, T, N> {
+    type Item = &'t T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let curr_ptr = self.front?;
+            let curr: &'t SkipNode<T, N> = unsafe { curr_ptr.as_ref() };
+            if self.back == Some(curr_ptr) {
+                // Both ends met at the node.
+                *self = Self::empty();
+            } else {
+                self.front = curr.nexts[0];
             }
-            cmp::Ordering::Equal => {
-                let head = unsafe { self.head.as_mut() };
-                Some(std::mem::replace(&mut head.value, value))
-            }
-            cmp::Ordering::Less => {
-                // Traverses the storage and searches for the value, while tracking the nodes that
-                // might get updated due to the insertion.
-                let mut trace = [MaybeUninit::uninit(); N];
-                self.descend(&value, |level, ptr| {
-                    trace[level].write(ptr);
-                });
-                {
-                    let prev = unsafe { trace[0].assume_init_mut().as_mut() };
-                    if let Some(mut curr_ptr) = prev.nexts[0] {
-                        let curr = unsafe { curr_ptr.as_mut() };
-                        if curr.value == value {
-                            return Some(std::mem::replace(&mut curr.value, value));
-                        }
-                    }
-                };
-                self.insert_after(trace, value);
-                None
+            if !curr.removed {
+                return Some(&curr.value);
             }
         }
     }
+}
 
-    fn insert(&mut self, value: T) {
-        match self.head_cmp(&value) {
-            cmp::Ordering::Greater | cmp::Ordering::Equal => {
-                self.insert_head(value);
+impl<T, const N: usize> DoubleEndedIterator for Iter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let curr_ptr = self.back?;
+            let curr = unsafe { curr_ptr.as_ref() };
+            if self.front == Some(curr_ptr) {
+                // Both ends met at the node.
+                *self = Self::empty();
+            } else {
+                self.back = curr.prev;
             }
-            cmp::Ordering::Less => {
-                // Traverses the storage and searches for the value, while tracking the nodes that
-                // might get updated due to the insertion.
-                let mut trace = [MaybeUninit::uninit(); N];
-                self.descend(&value, |level, ptr| {
-                    trace[level].write(ptr);
-                });
-                self.insert_after(trace, value);
+            if !curr.removed {
+                return Some(&curr.value);
             }
         }
     }
+}
+
+// SAFETY: The iterator only gives out shared references to the values.
+unsafe impl<T, const N: usize> Send for Iter<'_, T, N> where T: Sync {}
 
-    fn insert_head(&mut self, value: T) {
-        // Adds the existing head's next nodes as the next nodes of the new head at every level.
-        let mut new_head = SkipNode::new(value);
-        new_head.nexts[0] = Some(self.head);
-        let old_head = unsafe { self.head.as_mut() };
-        for level in 1..self.levels.get() {
-            new_head.nexts[level] = old_head.nexts[level].take();
+// SAFETY: The iterator only gives out shared references to the values.
+unsafe impl<T, const N: usize> Sync for Iter<'_, T, N> where T: Sync {}
+
+/// An iterator over the values of a storage, in order, from both ends of a run of nodes at the
+/// base level, through which the values can be mutated.
+struct IterMut<'t, T, const N: usize> {
+    /// The first node that hasn't been visited, or `None` once every node has been.
+    front: Option<NonNull<SkipNode<T, N>>>,
+    /// The last node that hasn't been visited, or `None` once every node has been.
+    back: Option<NonNull<SkipNode<T, N>>>,
+    _storage: std::marker::PhantomData<&'t mut SkipNode<T, N>>,
+}
+
+impl<T, const N: usize> IterMut<'_, T, N> {
+    /// Returns an iterator over no values, for an empty collection.
+    const fn empty() -> Self {
+        Self {
+            front: None,
+            back: None,
+            _storage: std::marker::PhantomData,
         }
-        // Replaces the storage's head when the current head's value is greater than the
-        // inserted value.
-        self.head = new_head.alloc();
     }
 
-    fn insert_after(&mut self, mut trace: [MaybeUninit<NonNull<SkipNode<T, N>>>; N], value: T) {
-        // Adds the new node to the base level.
-        let mut curr_ptr = SkipNode::new(value).alloc();
-        let curr = unsafe { curr_ptr.as_mut() };
-        {
-            let prev = unsafe { trace[0].assume_init_mut().as_mut() };
-            curr.nexts[0] = prev.nexts[0];
-            prev.nexts[0] = Some(curr_ptr);
-        }
-        // Determines whether a node is added to a level based on the number of consecutive one
-        // bits in the representation of a random number.
-        let random: u64 = self.rng.random();
-        for (level, mut prev_ptr) in trace
-            .into_iter()
-            .enumerate()
-            // Attempts to go to one level higher than the current level.
-            .take(self.levels.saturating_add(1).get().min(N))
-            // Skips the base level.
-            .skip(1)
-        {
-            // The chance to get added to a level drops by half when getting to a higher level.
-            if random & (1 << level) == 0 {
-                break;
+    /// Returns an iterator over the remaining values, which borrows them from this one.
+    const fn as_iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            front: self.front,
+            back: self.back,
+            _storage: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'t, T, const N: usize> Iterator for IterMut<'t, T, N> {
+    type Item = &'t mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut curr_ptr = self.front?;
+            // Every node is visited once, so the references never alias.
+            let curr: &'t mut SkipNode<T, N> = unsafe { curr_ptr.as_mut() };
+            if self.back == Some(curr_ptr) {
+                // Both ends met at the node.
+                *self = Self::empty();
+            } else {
+                self.front = curr.nexts[0];
             }
-            let prev = if level >= self.levels.get() {
-                // Increases the current number of levels and uses the current head as the
-                // "previous" node. This ensures the head can skip to the new node.
-                self.levels = self.levels.saturating_add(1);
-                unsafe { self.head.as_mut() }
+            if !curr.removed {
+                return Some(&mut curr.value);
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IterMut<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut curr_ptr = self.back?;
+            // Every node is visited once, so the references never alias.
+            let curr = unsafe { curr_ptr.as_mut() };
+            if self.front == Some(curr_ptr) {
+                // Both ends met at the node.
+                *self = Self::empty();
             } else {
-                unsafe { prev_ptr.assume_init_mut().as_mut() }
-            };
-            // Adds the new node to the current level.
-            curr.nexts[level] = prev.nexts[level];
-            prev.nexts[level] = Some(curr_ptr);
+                self.back = curr.prev;
+            }
+            if !curr.removed {
+                return Some(&mut curr.value);
+            }
+        }
+    }
+}
+
+// SAFETY: The iterator gives out mutable references to the values, like a `&mut T` does.
+unsafe impl<T, const N: usize> Send for IterMut<'_, T, N> where T: Send {}
+
+// SAFETY: The iterator gives out mutable references to the values, like a `&mut T` does.
+unsafe impl<T, const N: usize> Sync for IterMut<'_, T, N> where T: Sync {}
+
+/// An iterator that moves the values out of a run of nodes at the base level, in order, from both
+/// ends, freeing every node and dropping the values that were removed lazily. The nodes must no
+/// longer be reachable through a storage.
+struct IntoIter<T, const N: usize> {
+    /// The first node that hasn't been freed, or `None` once every node has been.
+    front: Option<NonNull<SkipNode<T, N>>>,
+    /// The last node that hasn't been freed, or `None` once every node has been.
+    back: Option<NonNull<SkipNode<T, N>>>,
+}
+
+impl<T, const N: usize> IntoIter<T, N> {
+    /// Returns an iterator over no values, for an empty collection.
+    const fn empty() -> Self {
+        Self {
+            front: None,
+            back: None,
+        }
+    }
+
+    /// Returns an iterator over the remaining values, which borrows them from this one.
+    const fn as_iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            front: self.front,
+            back: self.back,
+            _storage: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let curr_ptr = self.front?;
+            let curr = unsafe { curr_ptr.as_ref() };
+            let removed = curr.removed;
+            if self.back == Some(curr_ptr) {
+                // Both ends met at the node. Assigning an empty iterator would drop this one.
+                (self.front, self.back) = (None, None);
+            } else {
+                self.front = curr.nexts[0];
+            }
+            let value = unsafe { SkipNode::dealloc(curr_ptr) };
+            if !removed {
+                return Some(value);
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let curr_ptr = self.back?;
+            let curr = unsafe { curr_ptr.as_ref() };
+            let removed = curr.removed;
+            if self.front == Some(curr_ptr) {
+                // Both ends met at the node.
+                (self.front, self.back) = (None, None);
+            } else {
+                self.back = curr.prev;
+            }
+            let value = unsafe { SkipNode::dealloc(curr_ptr) };
+            if !removed {
+                return Some(value);
+            }
+        }
+    }
+}
+
+// SAFETY: The iterator exclusively owns the nodes, so sending it sends the values along with it.
+unsafe impl<T, const N: usize> Send for IntoIter<T, N> where T: Send {}
+
+// SAFETY: The iterator only gives out the values by moving them out of the nodes.
+unsafe impl<T, const N: usize> Sync for IntoIter<T, N> where T: Sync {}
+
+/// A cursor that walks the base level of a storage once, unlinking the nodes of the values that a
+/// predicate matches from every level they're linked into. Lazily removed values get unlinked and
+/// dropped along the way, and the storage is dropped as soon as it becomes empty, so that it stays
+/// valid between any two steps.
+struct ExtractIf<'t, T, R, const N: usize>
+where
+    R: Rng,
+{
+    storage: &'t mut Option<NonEmptyStorage<T, R, N>>,
+    spare: &'t mut Spare<R>,
+    /// The links of the last kept node at every level, or those of the head for the levels without
+    /// one, which come right before the next node at every level that it's linked into.
+    trace: Trace<T, N>,
+    /// The next node to visit, or `None` once every node has been.
+    next: Option<NonNull<SkipNode<T, N>>>,
+}
+
+impl<'t, T, R, const N: usize> ExtractIf<'t, T, R, N>
+where
+    R: Rng,
+{
+    /// Starts at the first node of the storage, whose generator goes to the spare if it becomes
+    /// empty.
+    fn new(storage: &'t mut Option<NonEmptyStorage<T, R, N>>, spare: &'t mut Spare<R>) -> Self {
+        let (trace, next) = storage.as_ref().map_or(([MaybeUninit::uninit(); N], None), |storage| {
+            ([MaybeUninit::new(storage.head); N], unsafe { storage.head.as_ref()[0] })
+        });
+        Self {
+            storage,
+            spare,
+            trace,
+            next,
         }
     }
 
-    fn remove<U>(mut storage: ManuallyDrop<Self>, value: &U) -> (Option<Self>, Option<T>)
+    /// Visits the nodes until the predicate matches a value, and returns the value after
+    /// unlinking its node.
+    fn next<F>(&mut self, mut pred: F) -> Option<T>
     where
-        T: Borrow<U>,
-        U: Ord + ?Sized,
+        F: FnMut(&mut T) -> bool,
     {
-        let value = match storage.head_cmp(value) {
-            cmp::Ordering::Greater => return (Some(ManuallyDrop::into_inner(storage)), None),
-            cmp::Ordering::Equal => {
-                let head = unsafe { storage.head.as_ref() };
-                let old_head_ptr = if let Some(new_head_ptr) = head.nexts[0] {
-                    std::mem::replace(&mut storage.head, new_head_ptr)
-                } else {
-                    // The head gets removed and there's no next node.
-                    let value = unsafe { SkipNode::dealloc(storage.head) };
-                    return (None, Some(value));
-                };
-                // Adds the next head node to higher levels when it's not already added.
-                let new_head = unsafe { storage.head.as_mut() };
-                for level in (1..storage.levels.get()).rev() {
-                    if head.nexts[level] == head.nexts[0] || new_head.nexts[level].is_some() {
+        while let Some(mut curr_ptr) = self.next {
+            let storage = self.storage.as_mut()?;
+            let curr = unsafe { curr_ptr.as_mut() };
+            self.next = curr.nexts[0];
+            if !curr.removed && !pred(&mut curr.value) {
+                // The node is kept, so it comes right before the next node at its levels.
+                let links_ptr = unsafe { SkipNode::links(curr_ptr) };
+                for (level, prev_ptr) in self.trace[..storage.levels.get()].iter_mut().enumerate() {
+                    if unsafe { prev_ptr.assume_init().as_ref()[level] } != Some(curr_ptr) {
                         break;
                     }
-                    new_head.nexts[level] = head.nexts[level];
+                    prev_ptr.write(links_ptr);
                 }
-                unsafe { SkipNode::dealloc(old_head_ptr) }
-            }
-            cmp::Ordering::Less => {
-                // Traverses the storage and searches for the value, while tracking the nodes that
-                // might get updated due to the removal.
-                let mut trace = [MaybeUninit::uninit(); N];
-                storage.descend(value, |level, ptr| {
-                    trace[level].write(ptr);
-                });
-                // Checks if the value exists. The trace only includes upto the node right before
-                // the one that will potentially be removed.
-                let Some(curr_ptr) = ({
-                    let prev = unsafe { trace[0].assume_init_ref().as_ref() };
-                    prev.nexts[0]
-                }) else {
-                    return (Some(ManuallyDrop::into_inner(storage)), None);
-                };
-                {
-                    let curr = unsafe { curr_ptr.as_ref() };
-                    if curr.value.borrow() != value {
-                        return (Some(ManuallyDrop::into_inner(storage)), None);
-                    }
-                    // Removes the node at every level.
-                    for (level, mut prev_ptr) in
-                        trace.into_iter().enumerate().take(storage.levels.get())
-                    {
-                        let prev = unsafe { prev_ptr.assume_init_mut().as_mut() };
-                        if prev.nexts[level].is_none_or(|ptr| ptr != curr_ptr) {
-                            break;
-                        }
-                        prev.nexts[level] = curr.nexts[level];
-                    }
-                }
-                unsafe { SkipNode::dealloc(curr_ptr) }
+                continue;
+            }
+            storage.unlink_after(&self.trace, curr_ptr);
+            let value = unsafe { storage.reclaim(curr_ptr) };
+            if storage.is_empty()
+                && let Some(storage) = self.storage.take()
+            {
+                *self.spare = Spare::Ready(storage.into_rng());
+            }
+            if value.is_some() {
+                return value;
             }
-        };
-        // Updates the storage's level by counting the number of next pointers that was removed
-        // from the head.
-        let head = unsafe { storage.head.as_ref() };
-        while storage.levels.get() > 1 && head.nexts[storage.levels.get() - 1].is_none() {
-            storage.levels = unsafe { NonZeroUsize::new_unchecked(storage.levels.get() - 1) };
         }
-        (Some(ManuallyDrop::into_inner(storage)), Some(value))
+        None
     }
 
-    /// Traverses the storage, descending down all levels, and calling the given function on the
-    /// last encountered node at each level.
-    fn descend<U, V>(&self, value: &U, mut visit: V)
-    where
-        T: Borrow<U>,
-        U: Ord + ?Sized,
-        V: FnMut(usize, NonNull<SkipNode<T, N>>),
-    {
-        let mut prev_node_ptr = self.head;
-        for level in (0..self.levels.get()).rev() {
-            while let Some(curr_node_ptr) = {
-                let prev_node = unsafe { prev_node_ptr.as_ref() };
-                prev_node.nexts[level]
-            } && {
-                let curr_node = unsafe { curr_node_ptr.as_ref() };
-                curr_node.value.borrow() < value
-            } {
-                prev_node_ptr = curr_node_ptr;
-            }
-            visit(level, prev_node_ptr);
+    /// Returns an iterator over the values that haven't been visited, which borrows them from
+    /// this one.
+    const fn as_iter(&self) -> Iter<'_, T, N> {
+        match self.storage.as_ref() {
+            Some(storage) if self.next.is_some() => Iter {
+                front: self.next,
+                back: Some(storage.tail()),
+                _storage: std::marker::PhantomData,
+            },
+            _ => Iter::empty(),
         }
     }
+}
 
-    fn head_cmp<U>(&self, value: &U) -> cmp::Ordering
-    where
-        T: Borrow<U>,
-        U: Ord + ?Sized,
-    {
-        let head = unsafe { self.head.as_ref() };
-        let head_value: &U = head.value.borrow();
-        head_value.cmp(value)
+// SAFETY: The cursor mutates the storage through a mutable reference to it, like a
+// `&mut NonEmptyStorage` does.
+unsafe impl<T, R, const N: usize> Send for ExtractIf<'_, T, R, N>
+where
+    T: Send,
+    R: Rng + Send,
+{
+}
+
+// SAFETY: The cursor only gives out the values by moving them out of the nodes.
+unsafe impl<T, R, const N: usize> Sync for ExtractIf<'_, T, R, N>
+where
+    T: Sync,
+    R: Rng + Sync,
+{
+}
+
+/// Counts the work done by a traversal while it runs. The counts are only published with the
+/// `metrics` feature, and optimized away otherwise.
+#[derive(Default)]
+struct Probe {
+    comparisons: u64,
+    hops: u64,
+}
+
+impl Probe {
+    const fn compared(&mut self) {
+        self.comparisons += 1;
+    }
+
+    const fn hopped(&mut self) {
+        self.hops += 1;
     }
+
+    /// Publishes the work done by the traversal, which just ended.
+    #[cfg(feature = "metrics")]
+    fn finish(self) {
+        metrics::record(metrics::Search {
+            comparisons: self.comparisons,
+            hops: self.hops,
+        });
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[expect(clippy::unused_self, reason = "the counts are only published with the feature")]
+    const fn finish(self) {}
+}
+
+/// Starts loading the node into the cache, if there's one.
+#[inline]
+fn prefetch<T, const N: usize>(ptr: Option<NonNull<SkipNode<T, N>>>) {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse"))]
+    if let Some(ptr) = ptr {
+        use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
+        // SAFETY: SSE is enabled, and prefetching never faults.
+        unsafe { _mm_prefetch::<_MM_HINT_T0>(ptr.as_ptr().cast()) };
+    }
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "sse")))]
+    let _ = ptr;
 }
 
+/// A node holding a value, followed by its links so that the value and the link at the base level
+/// share a cache line unless the value is large.
 #[derive(Debug)]
+#[repr(C)]
 struct SkipNode<T, const N: usize> {
     value: T,
-    nexts: [Option<NonNull<Self>>; N],
+    nexts: Links<T, N>,
+    /// The previous node at the base level, or `None` for the first node, so that the values can
+    /// be iterated from the greatest.
+    prev: Option<NonNull<Self>>,
+    /// Whether the value has been removed lazily, in which case the node stays linked until the
+    /// storage gets vacuumed.
+    removed: bool,
 }
 
 impl<T, const N: usize> SkipNode<T, N> {
@@ -340,6 +2011,8 @@ impl<T, const N: usize> SkipNode<T, N> {
         Self {
             value,
             nexts: [None; N],
+            prev: None,
+            removed: false,
         }
     }
 
@@ -352,4 +2025,122 @@ impl<T, const N: usize> SkipNode<T, N> {
         let node = unsafe { Box::from_raw(ptr.as_ptr()) };
         node.value
     }
+
+    /// Moves the value out of the node, leaving the node without a value.
+    const unsafe fn take(ptr: NonNull<Self>) -> T {
+        unsafe { ptr::read(&raw const (*ptr.as_ptr()).value) }
+    }
+
+    /// Frees a node without a value.
+    unsafe fn free(ptr: NonNull<Self>) {
+        unsafe { alloc::dealloc(ptr.as_ptr().cast(), alloc::Layout::new::<Self>()) };
+    }
+
+    /// Returns a pointer to the links of the node, which can be written through like the pointer
+    /// to the node.
+    const unsafe fn links(ptr: NonNull<Self>) -> NonNull<Links<T, N>> {
+        unsafe { NonNull::new_unchecked(&raw mut (*ptr.as_ptr()).nexts) }
+    }
+
+    /// Returns the node after a pooled node, reading the link at the base level through the raw
+    /// pointer, as it's the only link of the node that's initialized.
+    const unsafe fn pooled_next(ptr: NonNull<Self>) -> Option<NonNull<Self>> {
+        unsafe { Self::links(ptr).cast::<Option<NonNull<Self>>>().read() }
+    }
+
+    /// Returns a pointer to the node owning the links, which must have been returned by
+    /// [`Self::links`].
+    const unsafe fn from_links(links: NonNull<Links<T, N>>) -> NonNull<Self> {
+        unsafe { links.byte_sub(mem::offset_of!(Self, nexts)).cast() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, SeedableRng, rngs::SmallRng};
+
+    use super::{NonEmptyStorage, Pool, RandomBits, SkipMap, SkipNode, level_cap, levels_for};
+
+    /// A generator whose bits are all ones.
+    struct Ones;
+
+    impl RngCore for Ones {
+        fn next_u32(&mut self) -> u32 {
+            u32::MAX
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            u64::MAX
+        }
+
+        fn fill_bytes(&mut self, dst: &mut [u8]) {
+            dst.fill(u8::MAX);
+        }
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_node_layout() {
+        // Small values are stored inline, followed by the links, the link back, and the removal
+        // flag.
+        assert_eq!(size_of::<SkipNode<u64, 4>>(), 56);
+        assert_eq!(size_of::<SkipNode<(u32, u32), 4>>(), 56);
+        assert_eq!(size_of::<SkipNode<u8, 4>>(), 56);
+    }
+
+    #[test]
+    fn test_random_bits() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut bits = RandomBits::new();
+        let mut counts = [0_usize; 64];
+        for _ in 0..1 << 16 {
+            let height = bits.height(&mut rng, 64);
+            assert!((1..=64).contains(&height));
+            counts[height - 1] += 1;
+        }
+        // The greatest height is reachable, and a height keeps growing once the bits left over from
+        // it run out.
+        let mut bits = RandomBits::new();
+        assert_eq!(bits.height(&mut Ones, 64), 64);
+        assert_eq!(bits.height(&mut Ones, 3), 3);
+        // Every level is reached about half as often as the one below it.
+        for level in 1..4 {
+            let (below, above) = (counts[level - 1] * 10, counts[level]);
+            assert!((above * 18..above * 22).contains(&below), "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn test_levels_for() {
+        assert_eq!(levels_for(0), 1);
+        assert_eq!(levels_for(10_000), 14);
+        assert_eq!(levels_for(usize::MAX), 64);
+        // Nodes of a collection with fewer levels take less memory.
+        let mut small = SkipMap::<u32, u32, SmallRng, { levels_for(100) }>::new();
+        let mut large = SkipMap::<u32, u32>::new();
+        for key in 0..100 {
+            small.insert(key, key);
+            large.insert(key, key);
+        }
+        assert!(small.allocated_bytes() < large.allocated_bytes());
+    }
+
+    #[test]
+    fn test_level_cap() {
+        assert_eq!(level_cap::<8>(1), 1);
+        assert_eq!(level_cap::<8>(3), 2);
+        assert_eq!(level_cap::<8>(4), 3);
+        assert_eq!(level_cap::<8>(1 << 20), 8);
+        // Towers only grow as tall as the number of values allows.
+        let rng = SmallRng::seed_from_u64(0);
+        let mut storage = NonEmptyStorage::<_, SmallRng, 16>::new(0, rng, Pool::new());
+        for value in 1..64 {
+            storage.insert(value, None);
+            assert!(storage.levels.get() <= level_cap::<16>(storage.len));
+        }
+        for value in 0..64 {
+            assert_eq!(storage.remove(&value), Some(value));
+            assert_eq!(storage.len, 63 - value);
+        }
+    }
 }
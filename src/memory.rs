@@ -0,0 +1,103 @@
+//! Accounting for the memory held by collections, so that their footprint can be measured without
+//! a heap profiler.
+
+/// A type whose values might own memory on the heap, beyond their own size.
+pub trait HeapSize {
+    /// Returns the number of bytes allocated on the heap by the value, excluding the bytes of the
+    /// value itself.
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_heap_size_for_inline {
+    ($($ty:ty),*) => {
+        $(
+            impl HeapSize for $ty {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_heap_size_for_inline!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T> HeapSize for Vec<T>
+where
+    T: HeapSize,
+{
+    fn heap_size(&self) -> usize {
+        let elements = self.capacity() * size_of::<T>();
+        elements + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T> HeapSize for Box<T>
+where
+    T: HeapSize,
+{
+    fn heap_size(&self) -> usize {
+        size_of::<T>() + T::heap_size(self)
+    }
+}
+
+impl<T> HeapSize for Option<T>
+where
+    T: HeapSize,
+{
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<A, B> HeapSize for (A, B)
+where
+    A: HeapSize,
+    B: HeapSize,
+{
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::HeapSize;
+
+    proptest! {
+        #[test]
+        fn test_heap_size(strings in vec(".*", 0..16)) {
+            let total: usize = strings.iter().map(String::capacity).sum();
+            let expected = strings.capacity() * size_of::<String>() + total;
+            assert_eq!(strings.heap_size(), expected);
+            assert_eq!(Some(strings).heap_size(), expected);
+            assert_eq!(Box::new(0_u64).heap_size(), size_of::<u64>());
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Counting the work done by the searches of the collections, so that the cost of lookups can be
+//! watched in production.
+//!
+//! Every traversal of a [`SkipList`](crate::SkipList) or a [`SkipMap`](crate::SkipMap), whether
+//! for a lookup, an insertion, or a removal, counts the values it compares and the nodes it hops
+//! through. The counts get added to the totals of the thread once the traversal ends, and are
+//! handed to the [`Recorder`] if one has been set. Insertions of values greater than every other
+//! one don't traverse the collection, so they aren't counted.
+//!
+//! Every thread only writes its own totals, so that traversals on different threads never contend
+//! for the same cache line, and [`totals`] adds up those of every thread instead.
+
+use std::{
+    ops::Add,
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// The work done by a single traversal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Search {
+    /// The number of values compared to the searched one.
+    pub comparisons: u64,
+    /// The number of nodes moved past, at any level.
+    pub hops: u64,
+}
+
+/// The work done by every traversal since the process started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Totals {
+    /// The number of traversals.
+    pub searches: u64,
+    /// The number of values compared to the searched ones.
+    pub comparisons: u64,
+    /// The number of nodes moved past, at any level.
+    pub hops: u64,
+}
+
+impl Add for Totals {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            searches: self.searches + other.searches,
+            comparisons: self.comparisons + other.comparisons,
+            hops: self.hops + other.hops,
+        }
+    }
+}
+
+/// Receives the work done by every traversal, e.g. to feed a histogram of a metrics library.
+///
+/// Recording happens on the thread that traversed the collection, within the operation, so it
+/// should be cheap and never touch the collection.
+pub trait Recorder: Send + Sync {
+    /// Records the work done by a traversal that just ended.
+    fn record(&self, search: Search);
+}
+
+/// The totals of a thread, which only that thread writes.
+struct Counters {
+    searches: AtomicU64,
+    comparisons: AtomicU64,
+    hops: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            searches: AtomicU64::new(0),
+            comparisons: AtomicU64::new(0),
+            hops: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds the work done by a traversal, which must only be called by the thread owning the
+    /// counters, since the additions aren't atomic.
+    fn add(&self, search: Search) {
+        let add = |counter: &AtomicU64, n| {
+            counter.store(counter.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+        };
+        add(&self.searches, 1);
+        add(&self.comparisons, search.comparisons);
+        add(&self.hops, search.hops);
+    }
+
+    fn load(&self) -> Totals {
+        Totals {
+            searches: self.searches.load(Ordering::Relaxed),
+            comparisons: self.comparisons.load(Ordering::Relaxed),
+            hops: self.hops.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The counters of a thread, which are registered when the thread first traverses a collection,
+/// and folded into those of the exited threads when it exits.
+struct Local(Arc<Counters>);
+
+impl Local {
+    fn register() -> Self {
+        let counters = Arc::new(Counters::new());
+        lock_threads().push(Arc::clone(&counters));
+        Self(counters)
+    }
+}
+
+impl Drop for Local {
+    #[expect(
+        clippy::significant_drop_tightening,
+        reason = "folding while holding the lock keeps the counts from being missed or added twice"
+    )]
+    fn drop(&mut self) {
+        let mut threads = lock_threads();
+        threads.retain(|counters| !Arc::ptr_eq(counters, &self.0));
+        let totals = self.0.load();
+        EXITED.searches.fetch_add(totals.searches, Ordering::Relaxed);
+        EXITED.comparisons.fetch_add(totals.comparisons, Ordering::Relaxed);
+        EXITED.hops.fetch_add(totals.hops, Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local::register();
+}
+
+/// The counters of the threads that have traversed a collection and haven't exited.
+static THREADS: Mutex<Vec<Arc<Counters>>> = Mutex::new(Vec::new());
+/// The totals of the threads that have exited.
+static EXITED: Counters = Counters::new();
+static RECORDER: OnceLock<&'static dyn Recorder> = OnceLock::new();
+
+/// Returns the work done by every traversal so far, on every thread.
+#[must_use]
+pub fn totals() -> Totals {
+    let threads = lock_threads();
+    let exited = EXITED.load();
+    threads.iter().fold(exited, |totals, counters| totals + counters.load())
+}
+
+/// Sets the recorder of the process, which can only be done once.
+///
+/// # Errors
+///
+/// Gives the recorder back if one has already been set.
+pub fn set_recorder(recorder: &'static dyn Recorder) -> Result<(), &'static dyn Recorder> {
+    RECORDER.set(recorder)
+}
+
+/// Adds the work done by a traversal that just ended to the totals of the thread, and hands it to
+/// the recorder.
+pub(crate) fn record(search: Search) {
+    // Traversals made by destructors of thread locals after this one is gone aren't counted.
+    let _ = LOCAL.try_with(|local| local.0.add(search));
+    if let Some(recorder) = RECORDER.get() {
+        recorder.record(search);
+    }
+}
+
+fn lock_threads() -> MutexGuard<'static, Vec<Arc<Counters>>> {
+    THREADS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, thread};
+
+    use proptest::{collection::btree_set, prelude::*};
+    use rand::rngs::SmallRng;
+
+    use super::{Recorder, Search};
+    use crate::SkipList;
+
+    thread_local! {
+        static LAST: Cell<Option<Search>> = const { Cell::new(None) };
+    }
+
+    /// Keeps the last search of every thread, since tests run concurrently.
+    struct Last;
+
+    impl Recorder for Last {
+        fn record(&self, search: Search) {
+            LAST.with(|last| last.set(Some(search)));
+        }
+    }
+
+    #[test]
+    fn test_exited_threads() {
+        let skiplist = SkipList::<u16, SmallRng, 8>::from((0..100).collect::<Vec<_>>());
+        let before = super::totals();
+        let searches = thread::scope(|scope| {
+            let searches = scope.spawn(|| {
+                assert!(skiplist.contains(&50));
+                super::totals().searches
+            });
+            searches.join().unwrap()
+        });
+        // The searches of the thread are still counted once it has exited.
+        assert!(searches > before.searches);
+        assert!(super::totals().searches >= searches);
+    }
+
+    proptest! {
+        #[test]
+        fn test_metrics(values in btree_set(any::<u16>(), 1..200), value in any::<u16>()) {
+            // Another test might have set a recorder of the same type.
+            let _ = super::set_recorder(&Last);
+            let skiplist = SkipList::<u16, SmallRng, 8>::from(values.clone());
+            let before = super::totals();
+            LAST.with(|last| last.set(None));
+            assert_eq!(skiplist.contains(&value), values.contains(&value));
+            let Some(search) = LAST.with(Cell::get) else {
+                panic!("the search wasn't recorded");
+            };
+            assert!(search.comparisons >= 1);
+            assert!(search.hops <= values.len() as u64);
+            let after = super::totals();
+            assert!(after.searches > before.searches);
+            assert!(after.comparisons >= before.comparisons + search.comparisons);
+        }
+    }
+}
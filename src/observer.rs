@@ -0,0 +1,651 @@
+//! Hooks for keeping external state in sync with the mutations made to a skipmap.
+
+pub mod entry;
+
+use std::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash},
+    iter::FusedIterator,
+    ops::{Bound, RangeBounds},
+};
+
+use rand::{Rng, rngs::SmallRng};
+
+use crate::{
+    SkipMap,
+    iter::{MapDrain, MapExtractIf, MapIter},
+    skipmap::Entry,
+};
+
+/// A successful mutation made to a skipmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a, K, V> {
+    /// A value was inserted at a key that didn't exist.
+    Insert {
+        /// The inserted key.
+        key: &'a K,
+        /// The inserted value.
+        value: &'a V,
+    },
+    /// The value at an existing key was overwritten.
+    Update {
+        /// The key whose value was overwritten.
+        key: &'a K,
+        /// The value that was replaced.
+        old: &'a V,
+        /// The value that is now stored.
+        new: &'a V,
+    },
+    /// A key was removed along with its value.
+    Remove {
+        /// The removed key.
+        key: &'a K,
+        /// The removed value.
+        value: &'a V,
+    },
+}
+
+/// A callback invoked on every successful mutation of an [`Observed`] skipmap.
+///
+/// Closures taking an [`Event`] implement this trait.
+pub trait Observer<K, V> {
+    /// Handles a mutation that has just been applied to the skipmap.
+    fn observe(&mut self, event: Event<'_, K, V>);
+}
+
+impl<K, V, F> Observer<K, V> for F
+where
+    F: FnMut(Event<'_, K, V>),
+{
+    fn observe(&mut self, event: Event<'_, K, V>) {
+        self(event);
+    }
+}
+
+/// An observer maintaining a digest of the content of a skipmap, so that two skipmaps can be
+/// checked for divergence without comparing every entry.
+///
+/// The digest combines the hashes of all entries with XOR. Entries of a skipmap are always in the
+/// order of their unique keys, so skipmaps with the same entries have the same digest regardless
+/// of the order of the mutations that produced them. Digests computed by different processes only
+/// match if their hashers are the same, which is the case for the default hasher as long as the
+/// processes are built with the same version of the standard library.
+#[derive(Debug, Clone, Default)]
+pub struct ContentHash<S = BuildHasherDefault<DefaultHasher>> {
+    hasher: S,
+    digest: u64,
+}
+
+impl ContentHash {
+    /// Creates the digest of an empty skipmap, which hashes entries with the default hasher.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_hasher(BuildHasherDefault::new())
+    }
+}
+
+impl<S> ContentHash<S> {
+    /// Creates the digest of an empty skipmap, which hashes entries with the given hasher.
+    pub const fn with_hasher(hasher: S) -> Self {
+        Self { hasher, digest: 0 }
+    }
+
+    /// Returns the digest of the content of the skipmap.
+    pub const fn digest(&self) -> u64 {
+        self.digest
+    }
+}
+
+impl<S> ContentHash<S>
+where
+    S: BuildHasher,
+{
+    fn toggle<K, V>(&mut self, key: &K, value: &V)
+    where
+        K: Hash,
+        V: Hash,
+    {
+        self.digest ^= self.hasher.hash_one((key, value));
+    }
+}
+
+impl<K, V, S> Observer<K, V> for ContentHash<S>
+where
+    K: Hash,
+    V: Hash,
+    S: BuildHasher,
+{
+    fn observe(&mut self, event: Event<'_, K, V>) {
+        match event {
+            Event::Insert { key, value } | Event::Remove { key, value } => self.toggle(key, value),
+            Event::Update { key, old, new } => {
+                self.toggle(key, old);
+                self.toggle(key, new);
+            }
+        }
+    }
+}
+
+/// A skipmap that notifies an observer about every successful mutation.
+///
+/// Values are only given out by shared reference, so that every change goes through a method that
+/// reports it. [`Observed::for_each_mut`] stands in for mutating the values in place.
+pub struct Observed<K, V, O, R, const N: usize>
+where
+    R: Rng,
+{
+    map: SkipMap<K, V, R, N>,
+    observer: O,
+}
+
+impl<K, V, O, R, const N: usize> fmt::Debug for Observed<K, V, O, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observed")
+            .field("map", &self.map)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, O, const N: usize> Observed<K, V, O, SmallRng, N> {
+    /// Creates an empty skipmap that reports its mutations to the given observer.
+    #[must_use]
+    pub const fn new(observer: O) -> Self {
+        Self::from_parts(SkipMap::new(), observer)
+    }
+}
+
+impl<K, V, O, R, const N: usize> Observed<K, V, O, R, N>
+where
+    R: Rng,
+{
+    /// Wraps an existing skipmap, reporting all subsequent mutations to the given observer.
+    pub const fn from_parts(map: SkipMap<K, V, R, N>, observer: O) -> Self {
+        Self { map, observer }
+    }
+
+    /// Returns the wrapped skipmap and the observer.
+    pub fn into_parts(self) -> (SkipMap<K, V, R, N>, O) {
+        (self.map, self.observer)
+    }
+
+    /// Returns a shared reference to the wrapped skipmap.
+    pub const fn as_map(&self) -> &SkipMap<K, V, R, N> {
+        &self.map
+    }
+
+    /// Returns a shared reference to the observer.
+    pub const fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Returns a mutable reference to the observer.
+    pub const fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+}
+
+impl<K, V, S, R, const N: usize> Observed<K, V, ContentHash<S>, R, N>
+where
+    K: Ord + Hash,
+    V: Hash,
+    S: BuildHasher,
+    R: Rng,
+{
+    /// Wraps an existing skipmap, maintaining the digest of its content with the given hasher.
+    pub fn with_content_hash(map: SkipMap<K, V, R, N>, hasher: S) -> Self {
+        let mut observer = ContentHash::with_hasher(hasher);
+        for (key, value) in &map {
+            observer.toggle(key, value);
+        }
+        Self::from_parts(map, observer)
+    }
+}
+
+impl<K, V, S, R, const N: usize> Observed<K, V, ContentHash<S>, R, N>
+where
+    R: Rng,
+{
+    /// Returns the digest of the content of the skipmap, which is the same for skipmaps holding
+    /// the same entries.
+    pub const fn content_hash(&self) -> u64 {
+        self.observer.digest()
+    }
+}
+
+impl<K, V, O, R, const N: usize> Observed<K, V, O, R, N>
+where
+    R: Rng,
+    O: Observer<K, V>,
+    Entry<K, V>: Ord,
+{
+    /// Returns whether a key exists in the skipmap.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        self.map.contains(key)
+    }
+
+    /// Returns a shared reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        self.map.get(key)
+    }
+
+    /// Inserts a value at the given key into the skipmap, notifying the observer about either an
+    /// insertion or an update.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (entry, old) = self.map.upsert(key, value);
+        let event = old.as_ref().map_or(
+            Event::Insert {
+                key: &entry.key,
+                value: &entry.value,
+            },
+            |old| Event::Update {
+                key: &entry.key,
+                old,
+                new: &entry.value,
+            },
+        );
+        self.observer.observe(event);
+        old
+    }
+
+    /// Removes a value at the given key from the skipmap, returning it if it exists. The observer
+    /// is only notified when the key existed.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let entry = self.map.remove_entry(key)?;
+        self.observer.observe(Event::Remove {
+            key: &entry.key,
+            value: &entry.value,
+        });
+        Some(entry.value)
+    }
+
+    /// Marks the entry at the given key as removed, like [`SkipMap::remove_lazily`], returning
+    /// whether it exists. The observer is only notified when the key existed.
+    pub fn remove_lazily<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        // The observer is notified before the entry gets marked, since marking it can vacuum the
+        // skipmap, dropping the value.
+        let bounds = (Bound::Included(key), Bound::Included(key));
+        let Some((k, value)) = self.map.range::<Q, _>(bounds).next() else {
+            return false;
+        };
+        self.observer.observe(Event::Remove { key: k, value });
+        self.map.remove_lazily(key)
+    }
+
+    /// Removes the entries whose keys are within the range, like [`SkipMap::drain_range`],
+    /// notifying the observer about each of them as the iterator reaches it.
+    pub fn drain_range<Q, B>(&mut self, range: B) -> Drain<'_, K, V, O, N>
+    where
+        Q: Ord + ?Sized,
+        B: RangeBounds<Q>,
+        Entry<K, V>: Borrow<Q>,
+    {
+        Drain {
+            entries: self.map.drain_range(range),
+            observer: &mut self.observer,
+        }
+    }
+
+    /// Appends an entry whose key is greater than every other key, like [`SkipMap::push_max`],
+    /// notifying the observer about the insertion.
+    ///
+    /// # Errors
+    ///
+    /// Gives the entry back if its key isn't greater than the greatest key of the skipmap.
+    pub fn push_max(&mut self, key: K, value: V) -> Result<(), (K, V)> {
+        self.map.push_max(key, value)?;
+        let Some((key, value)) = self.map.last_key_value() else {
+            unreachable!();
+        };
+        self.observer.observe(Event::Insert { key, value });
+        Ok(())
+    }
+}
+
+impl<K, V, O, R, const N: usize> Observed<K, V, O, R, N>
+where
+    R: Rng,
+    O: Observer<K, V>,
+{
+    /// Removes the entry with the least key, returning it if the skipmap isn't empty.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.map.pop_first()?;
+        self.observer.observe(Event::Remove {
+            key: &key,
+            value: &value,
+        });
+        Some((key, value))
+    }
+
+    /// Removes the entry with the greatest key, returning it if the skipmap isn't empty.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.map.pop_last()?;
+        self.observer.observe(Event::Remove {
+            key: &key,
+            value: &value,
+        });
+        Some((key, value))
+    }
+
+    /// Removes every entry, like [`SkipMap::drain`], notifying the observer about each of them as
+    /// the iterator reaches it.
+    pub fn drain(&mut self) -> Drain<'_, K, V, O, N> {
+        Drain {
+            entries: self.map.drain(),
+            observer: &mut self.observer,
+        }
+    }
+
+    /// Returns an iterator that removes the entries whose keys and values match the predicate,
+    /// like [`SkipMap::extract_if`], notifying the observer about each removed entry. The
+    /// predicate only gets shared references to the values, since changes to the entries it keeps
+    /// couldn't be reported.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, O, R, N, F>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        // Only the cursor is kept, and the predicate gets called with shared references instead.
+        let MapExtractIf(entries, _) = self.map.extract_if(|_, _| false);
+        ExtractIf {
+            entries,
+            pred,
+            observer: &mut self.observer,
+        }
+    }
+
+    /// Keeps only the entries whose keys and values match the predicate, notifying the observer
+    /// about every removed entry.
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.extract_if(|k, v| !pred(k, v)).for_each(drop);
+    }
+
+    /// Calls the function on every entry, in ascending order of their keys, through which the
+    /// values can be mutated in place like with [`SkipMap::iter_mut`]. The observer is notified
+    /// about an update for every value that the function changed.
+    pub fn for_each_mut<F>(&mut self, mut f: F)
+    where
+        V: Clone + PartialEq,
+        F: FnMut(&K, &mut V),
+    {
+        for (key, value) in &mut self.map {
+            let old = value.clone();
+            f(key, value);
+            if *value != old {
+                self.observer.observe(Event::Update {
+                    key,
+                    old: &old,
+                    new: value,
+                });
+            }
+        }
+    }
+}
+
+/// An iterator that moves the removed entries out of an [`Observed`] skipmap, which is returned by
+/// [`Observed::drain`] and [`Observed::drain_range`].
+///
+/// The observer is notified about every entry as it's reached, in ascending order of the keys. The
+/// entries are removed as soon as it's created, so dropping it early notifies the observer about
+/// the entries it hasn't reached.
+pub struct Drain<'a, K, V, O, const N: usize>
+where
+    O: Observer<K, V>,
+{
+    entries: MapDrain<'a, K, V, N>,
+    observer: &'a mut O,
+}
+
+impl<K, V, O, const N: usize> fmt::Debug for Drain<'_, K, V, O, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    O: Observer<K, V>,
+{
+    /// Formats the remaining entries like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.entries.fmt(f)
+    }
+}
+
+impl<K, V, O, const N: usize> Iterator for Drain<'_, K, V, O, N>
+where
+    O: Observer<K, V>,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.entries.next()?;
+        self.observer.observe(Event::Remove {
+            key: &key,
+            value: &value,
+        });
+        Some((key, value))
+    }
+}
+
+impl<K, V, O, const N: usize> DoubleEndedIterator for Drain<'_, K, V, O, N>
+where
+    O: Observer<K, V>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.entries.next_back()?;
+        self.observer.observe(Event::Remove {
+            key: &key,
+            value: &value,
+        });
+        Some((key, value))
+    }
+}
+
+impl<K, V, O, const N: usize> FusedIterator for Drain<'_, K, V, O, N> where O: Observer<K, V> {}
+
+impl<K, V, O, const N: usize> Drop for Drain<'_, K, V, O, N>
+where
+    O: Observer<K, V>,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+/// An iterator that removes the entries of an [`Observed`] skipmap that match a predicate, which is
+/// returned by [`Observed::extract_if`].
+///
+/// The observer is notified about every removed entry, in ascending order of the keys. Entries are
+/// only removed as the iterator reaches them, so the entries it hasn't reached stay in the skipmap
+/// if it's dropped early.
+pub struct ExtractIf<'a, K, V, O, R, const N: usize, F>
+where
+    R: Rng,
+{
+    entries: crate::ExtractIf<'a, Entry<K, V>, R, N>,
+    pred: F,
+    observer: &'a mut O,
+}
+
+impl<K, V, O, R, const N: usize, F> fmt::Debug for ExtractIf<'_, K, V, O, R, N, F>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    /// Formats the entries that haven't been reached like a list of pairs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(MapIter(self.entries.as_iter())).finish()
+    }
+}
+
+impl<K, V, O, R, const N: usize, F> Iterator for ExtractIf<'_, K, V, O, R, N, F>
+where
+    R: Rng,
+    O: Observer<K, V>,
+    F: FnMut(&K, &V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next(|entry| (self.pred)(&entry.key, &entry.value))?;
+        self.observer.observe(Event::Remove {
+            key: &entry.key,
+            value: &entry.value,
+        });
+        Some((entry.key, entry.value))
+    }
+}
+
+impl<K, V, O, R, const N: usize, F> FusedIterator for ExtractIf<'_, K, V, O, R, N, F>
+where
+    R: Rng,
+    O: Observer<K, V>,
+    F: FnMut(&K, &V) -> bool,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        hash::{BuildHasherDefault, DefaultHasher},
+    };
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{ContentHash, Event, Observed, entry::Entry};
+    use crate::SkipMap;
+
+    proptest! {
+        #[test]
+        fn test_mirror(ops in vec((0..12u8, 0..16usize, any::<usize>()), 64)) {
+            let mut index = BTreeMap::new();
+            let mut skipmap = Observed::<usize, usize, _, _, 4>::new(|event: Event<'_, _, _>| match event {
+                Event::Insert { key, value } => assert!(index.insert(*key, *value).is_none()),
+                Event::Update { key, old, new } => {
+                    assert_eq!(index.insert(*key, *new), Some(*old));
+                }
+                Event::Remove { key, value } => assert_eq!(index.remove(key), Some(*value)),
+            });
+            for (op, k, v) in ops {
+                match op {
+                    0 | 1 => {
+                        skipmap.insert(k, v);
+                    }
+                    2 => {
+                        skipmap.remove(&k);
+                    }
+                    3 => {
+                        skipmap.remove_lazily(&k);
+                    }
+                    4 => {
+                        skipmap.pop_first();
+                        skipmap.pop_last();
+                    }
+                    5 => {
+                        let _ = skipmap.push_max(k, v);
+                    }
+                    6 => skipmap.retain(|key, value| (key ^ value) % 3 != 0),
+                    7 => {
+                        // Dropping the iterators early still reports what they removed.
+                        skipmap.drain_range(k..k + 4).next();
+                        skipmap.extract_if(|key, _| key % 4 == k % 4).next();
+                    }
+                    8 => match skipmap.entry(k) {
+                        Entry::Occupied(mut entry) if v % 2 == 0 => {
+                            entry.insert(v);
+                        }
+                        Entry::Occupied(entry) => {
+                            let _ = entry.remove();
+                        }
+                        Entry::Vacant(entry) => {
+                            entry.insert(v);
+                        }
+                    },
+                    9 => {
+                        skipmap.entry(k).or_insert(v);
+                    }
+                    10 => skipmap.for_each_mut(|key, value| {
+                        if key % 2 == k % 2 {
+                            *value = value.wrapping_add(v);
+                        }
+                    }),
+                    _ => {
+                        if v % 8 == 0 {
+                            skipmap.drain();
+                        }
+                    }
+                }
+            }
+            let (skipmap, _) = skipmap.into_parts();
+            assert_eq!(skipmap, index);
+        }
+
+        #[test]
+        fn test_content_hash(
+            ops in vec((any::<bool>(), 0..16usize, 0..4usize), 64),
+            other in vec((any::<bool>(), 0..16usize, 0..4usize), 64),
+        ) {
+            let mut skipmap = Observed::<_, _, _, _, 4>::new(ContentHash::new());
+            let mut btree = BTreeMap::new();
+            for (insert, k, v) in ops {
+                if insert {
+                    skipmap.insert(k, v);
+                    btree.insert(k, v);
+                } else {
+                    skipmap.remove(&k);
+                    btree.remove(&k);
+                }
+            }
+            // Rebuilding from the final entries in another order gives the same digest.
+            let mut rebuilt = Observed::<_, _, _, _, 4>::new(ContentHash::new());
+            for (k, v) in btree.iter().rev() {
+                rebuilt.insert(*k, *v);
+            }
+            assert_eq!(rebuilt.content_hash(), skipmap.content_hash());
+            let mut copy = SkipMap::<_, _, _, 4>::new();
+            for (k, v) in &btree {
+                copy.insert(*k, *v);
+            }
+            let wrapped = Observed::with_content_hash(copy, BuildHasherDefault::<DefaultHasher>::new());
+            assert_eq!(wrapped.content_hash(), skipmap.content_hash());
+            let mut diverged = Observed::<_, _, _, _, 4>::new(ContentHash::new());
+            let mut other_btree = BTreeMap::new();
+            for (insert, k, v) in other {
+                if insert {
+                    diverged.insert(k, v);
+                    other_btree.insert(k, v);
+                } else {
+                    diverged.remove(&k);
+                    other_btree.remove(&k);
+                }
+            }
+            if other_btree == btree {
+                assert_eq!(diverged.content_hash(), skipmap.content_hash());
+            } else {
+                assert_ne!(diverged.content_hash(), skipmap.content_hash());
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Views into single entries of an [`Observed`] skipmap.
+//!
+//! They wrap the views of [`crate::entry`], notifying the observer about the insertions and
+//! removals made through them. Values are only given out by shared reference, and an occupied
+//! entry gets a new value through [`OccupiedEntry::insert`], which reports the update.
+
+use std::fmt;
+
+use rand::Rng;
+
+use super::{Event, Observed, Observer};
+use crate::entry as map;
+
+/// A view into the entry at a key of an [`Observed`] skipmap, which is either occupied or vacant.
+pub enum Entry<'a, K, V, O, R, const N: usize>
+where
+    R: Rng,
+{
+    /// An entry holding a value.
+    Occupied(OccupiedEntry<'a, K, V, O, R, N>),
+    /// An entry without a value.
+    Vacant(VacantEntry<'a, K, V, O, R, N>),
+}
+
+/// A view into an entry holding a value.
+pub struct OccupiedEntry<'a, K, V, O, R, const N: usize>
+where
+    R: Rng,
+{
+    entry: map::OccupiedEntry<'a, K, V, R, N>,
+    observer: &'a mut O,
+}
+
+/// A view into an entry without a value.
+pub struct VacantEntry<'a, K, V, O, R, const N: usize>
+where
+    R: Rng,
+{
+    entry: map::VacantEntry<'a, K, V, R, N>,
+    observer: &'a mut O,
+}
+
+impl<K, V, O, R, const N: usize> fmt::Debug for Entry<'_, K, V, O, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Occupied(entry) => f.debug_tuple("Entry").field(entry).finish(),
+            Self::Vacant(entry) => f.debug_tuple("Entry").field(entry).finish(),
+        }
+    }
+}
+
+impl<K, V, O, R, const N: usize> fmt::Debug for OccupiedEntry<'_, K, V, O, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.entry.fmt(f)
+    }
+}
+
+impl<K, V, O, R, const N: usize> fmt::Debug for VacantEntry<'_, K, V, O, R, N>
+where
+    K: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.entry.fmt(f)
+    }
+}
+
+impl<K, V, O, R, const N: usize> Observed<K, V, O, R, N>
+where
+    K: Ord,
+    R: Rng,
+    O: Observer<K, V>,
+{
+    /// Returns the entry at the given key, for an insertion or a removal that the observer gets
+    /// notified about.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, O, R, N> {
+        let observer = &mut self.observer;
+        match self.map.entry(key) {
+            map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry { entry, observer }),
+            map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry { entry, observer }),
+        }
+    }
+}
+
+impl<'a, K, V, O, R, const N: usize> Entry<'a, K, V, O, R, N>
+where
+    K: Ord,
+    R: Rng,
+    O: Observer<K, V>,
+{
+    /// Returns the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns a shared reference to the value of the entry, inserting the given value first if
+    /// the entry is vacant.
+    pub fn or_insert(self, value: V) -> &'a V {
+        self.or_insert_with(|| value)
+    }
+
+    /// Returns a shared reference to the value of the entry, inserting the value returned by the
+    /// function first if the entry is vacant.
+    pub fn or_insert_with<F>(self, f: F) -> &'a V
+    where
+        F: FnOnce() -> V,
+    {
+        self.or_insert_with_key(|_| f())
+    }
+
+    /// Returns a shared reference to the value of the entry, inserting the value returned by the
+    /// function for the key first if the entry is vacant.
+    pub fn or_insert_with_key<F>(self, f: F) -> &'a V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self {
+            Self::Occupied(entry) => entry.entry.into_mut(),
+            Self::Vacant(entry) => {
+                let value = f(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Returns a shared reference to the value of the entry, inserting the default value first if
+    /// the entry is vacant.
+    pub fn or_default(self) -> &'a V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<K, V, O, R, const N: usize> OccupiedEntry<'_, K, V, O, R, N>
+where
+    R: Rng,
+    O: Observer<K, V>,
+{
+    /// Returns the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    /// Returns a shared reference to the value of the entry.
+    #[must_use]
+    pub const fn get(&self) -> &V {
+        self.entry.get()
+    }
+
+    /// Replaces the value of the entry, returning the replaced value, and notifies the observer
+    /// about the update.
+    pub fn insert(&mut self, value: V) -> V {
+        let old = self.entry.insert(value);
+        self.observer.observe(Event::Update {
+            key: self.entry.key(),
+            old: &old,
+            new: self.entry.get(),
+        });
+        old
+    }
+
+    /// Removes the entry from the skipmap, returning its value, and notifies the observer about
+    /// the removal.
+    #[must_use]
+    pub fn remove(self) -> V
+    where
+        K: Ord,
+    {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the skipmap, returning its key and its value, and notifies the
+    /// observer about the removal.
+    #[must_use]
+    pub fn remove_entry(self) -> (K, V)
+    where
+        K: Ord,
+    {
+        let (key, value) = self.entry.remove_entry();
+        self.observer.observe(Event::Remove {
+            key: &key,
+            value: &value,
+        });
+        (key, value)
+    }
+}
+
+impl<'a, K, V, O, R, const N: usize> VacantEntry<'a, K, V, O, R, N>
+where
+    R: Rng,
+    O: Observer<K, V>,
+{
+    /// Returns the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    /// Returns the key of the entry, giving up the insertion.
+    #[must_use]
+    pub fn into_key(self) -> K {
+        self.entry.into_key()
+    }
+
+    /// Inserts the value into the entry, returning a shared reference to it, which lives as long
+    /// as the borrow of the skipmap, and notifies the observer about the insertion.
+    pub fn insert(self, value: V) -> &'a V
+    where
+        K: Ord,
+    {
+        let pair = self.entry.insert_pair(value);
+        self.observer.observe(Event::Insert {
+            key: &pair.key,
+            value: &pair.value,
+        });
+        &pair.value
+    }
+}
@@ -0,0 +1,454 @@
+//! Orderings of values other than their own.
+//!
+//! Collections order their values by [`Ord`]. Wrapping the values in [`ByKey`] orders them by a key
+//! that a [`KeyExtract`] borrows from them instead, so that e.g. tasks can be ordered by their
+//! priority while the rest of every task stays opaque to the collection.
+//!
+//! Floats aren't [`Ord`], since NaN isn't comparable, so they're used as keys through
+//! [`OrderedF32`] and [`OrderedF64`], which order them totally.
+//!
+//! Orderings that can fail, e.g. because they call into plugins or because some values are
+//! incomparable, can panic to signal the failure. The `try_` operations of the collections catch
+//! such a panic and return a [`ComparisonPanicked`] error.
+
+use std::{
+    any::Any,
+    borrow::Borrow,
+    cmp,
+    error::Error,
+    fmt, hash, io,
+    marker::PhantomData,
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{memory::HeapSize, snapshot::Record};
+
+/// Borrows the key by which values of type `T` are ordered.
+///
+/// Extractors are usually types without values, which name the field that the key is borrowed
+/// from.
+pub trait KeyExtract<T> {
+    /// The type of the keys.
+    type Key: Ord + ?Sized;
+
+    /// Borrows the key of the value.
+    fn key(value: &T) -> &Self::Key;
+}
+
+/// A value that is ordered by the key that `E` borrows from it.
+///
+/// Values with equal keys compare equal, whatever the rest of them holds. Lookups only take a key,
+/// since the wrapper borrows as its key. The type of the key is a parameter only so that the
+/// wrapper can borrow as it, and it's always left to its default.
+pub struct ByKey<T, E, K = <E as KeyExtract<T>>::Key>
+where
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    value: T,
+    extract: PhantomData<fn(&K) -> E>,
+}
+
+impl<T, E, K> ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    /// Wraps the value.
+    #[must_use]
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            extract: PhantomData,
+        }
+    }
+
+    /// Returns the wrapped value.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the key by which the value is ordered.
+    #[must_use]
+    pub fn key(&self) -> &K {
+        E::key(&self.value)
+    }
+}
+
+impl<T, E, K> From<T> for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T, E, K> Clone for ByKey<T, E, K>
+where
+    T: Clone,
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<T, E, K> Deref for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, E, K> Borrow<K> for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    fn borrow(&self) -> &K {
+        self.key()
+    }
+}
+
+impl<T, E, K> PartialEq for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: Ord + ?Sized,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T, E, K> Eq for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: Ord + ?Sized,
+{
+}
+
+impl<T, E, K> PartialOrd for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: Ord + ?Sized,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T, E, K> Ord for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: Ord + ?Sized,
+{
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.key().cmp(other.key())
+    }
+}
+
+impl<T, E, K> hash::Hash for ByKey<T, E, K>
+where
+    E: KeyExtract<T, Key = K>,
+    K: Ord + hash::Hash + ?Sized,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: hash::Hasher,
+    {
+        self.key().hash(state);
+    }
+}
+
+impl<T, E, K> fmt::Debug for ByKey<T, E, K>
+where
+    T: fmt::Debug,
+    E: KeyExtract<T, Key = K>,
+    K: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.value, f)
+    }
+}
+
+/// The error returned by the `try_` operations of the collections when a comparison panicked.
+///
+/// Values are only compared before a collection gets changed, so a collection whose comparison
+/// panicked holds the same values as before the operation, and can still be used.
+pub struct ComparisonPanicked(Box<dyn Any + Send>);
+
+impl ComparisonPanicked {
+    /// Returns the payload of the panic, which [`std::panic::resume_unwind`] takes to resume it.
+    #[must_use]
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.0
+    }
+}
+
+impl fmt::Debug for ComparisonPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ComparisonPanicked").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for ComparisonPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(message) = self.0.downcast_ref::<&str>() {
+            write!(f, "comparison panicked: {message}")
+        } else if let Some(message) = self.0.downcast_ref::<String>() {
+            write!(f, "comparison panicked: {message}")
+        } else {
+            write!(f, "comparison panicked")
+        }
+    }
+}
+
+impl Error for ComparisonPanicked {}
+
+/// Runs an operation of a collection, catching a panic of its comparisons.
+///
+/// The collection is only unwind safe because comparisons happen before it gets changed, which
+/// every caller must ensure.
+pub(crate) fn catch<F, U>(operation: F) -> Result<U, ComparisonPanicked>
+where
+    F: FnOnce() -> U,
+{
+    panic::catch_unwind(AssertUnwindSafe(operation)).map_err(ComparisonPanicked)
+}
+
+macro_rules! impl_ordered_float {
+    ($($name:ident($ty:ty, $bits:ty)),*) => {
+        $(
+            #[doc = concat!(
+                "An [`", stringify!($ty), "`] that is ordered by [`",
+                stringify!($ty), "::total_cmp`]."
+            )]
+            ///
+            /// Negative zero is less than positive zero, and NaNs are less than every other value
+            /// if their sign is negative, and greater otherwise. How NaNs get stored is up to the
+            /// constructor: [`Self::new`] keeps them as they are, [`Self::canonical`] turns every
+            /// NaN into the same greatest value and both zeros into positive zero, and
+            /// [`Self::not_nan`] rejects NaNs.
+            #[derive(Clone, Copy, Default)]
+            pub struct $name($ty);
+
+            impl $name {
+                /// Wraps the value as it is.
+                #[must_use]
+                pub const fn new(value: $ty) -> Self {
+                    Self(value)
+                }
+
+                /// Wraps the value, turning every NaN into the positive canonical NaN and negative
+                /// zero into positive zero, so that values comparing equal as floats are equal.
+                #[must_use]
+                pub const fn canonical(value: $ty) -> Self {
+                    if value.is_nan() {
+                        Self(<$ty>::NAN.abs())
+                    } else if value == 0.0 {
+                        Self(0.0)
+                    } else {
+                        Self(value)
+                    }
+                }
+
+                /// Wraps the value if it isn't NaN.
+                #[must_use]
+                pub const fn not_nan(value: $ty) -> Option<Self> {
+                    if value.is_nan() { None } else { Some(Self(value)) }
+                }
+
+                /// Returns the wrapped value.
+                #[must_use]
+                pub const fn get(self) -> $ty {
+                    self.0
+                }
+            }
+
+            impl From<$ty> for $name {
+                fn from(value: $ty) -> Self {
+                    Self::new(value)
+                }
+            }
+
+            impl From<$name> for $ty {
+                fn from(value: $name) -> Self {
+                    value.0
+                }
+            }
+
+            impl PartialEq for $name {
+                fn eq(&self, other: &Self) -> bool {
+                    self.cmp(other).is_eq()
+                }
+            }
+
+            impl Eq for $name {}
+
+            impl PartialOrd for $name {
+                fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+                    Some(self.cmp(other))
+                }
+            }
+
+            impl Ord for $name {
+                fn cmp(&self, other: &Self) -> cmp::Ordering {
+                    self.0.total_cmp(&other.0)
+                }
+            }
+
+            impl hash::Hash for $name {
+                fn hash<H>(&self, state: &mut H)
+                where
+                    H: hash::Hasher,
+                {
+                    // Values are only equal if they have the same bits.
+                    self.0.to_bits().hash(state);
+                }
+            }
+
+            impl fmt::Debug for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Debug::fmt(&self.0, f)
+                }
+            }
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Display::fmt(&self.0, f)
+                }
+            }
+
+            impl HeapSize for $name {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+
+            impl Record for $name {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    self.0.to_bits().encode(buf);
+                }
+
+                fn decode(bytes: &[u8]) -> io::Result<Self> {
+                    <$bits>::decode(bytes).map(|bits| Self(<$ty>::from_bits(bits)))
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $name {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    self.0.serialize(serializer)
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl<'de> serde::Deserialize<'de> for $name {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    <$ty>::deserialize(deserializer).map(Self)
+                }
+            }
+        )*
+    };
+}
+
+impl_ordered_float!(OrderedF32(f32, u32), OrderedF64(f64, u64));
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{ByKey, KeyExtract, OrderedF64};
+    use crate::{SkipList, SkipMap, snapshot::Record};
+
+    struct Task {
+        priority: u32,
+        name: String,
+    }
+
+    struct ByPriority;
+
+    impl KeyExtract<Task> for ByPriority {
+        type Key = u32;
+
+        fn key(task: &Task) -> &u32 {
+            &task.priority
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_by_key(tasks in vec((0..32u32, ".{0,8}"), 0..100)) {
+            let mut skiplist = SkipList::<ByKey<Task, ByPriority>, _, 8>::new();
+            let mut sorted = Vec::new();
+            let mut names = Vec::new();
+            for (priority, name) in tasks {
+                names.push(name.clone());
+                skiplist.insert(ByKey::new(Task { priority, name }));
+                sorted.push(priority);
+            }
+            sorted.sort_unstable();
+            names.sort_unstable();
+            for priority in 0..32 {
+                assert_eq!(skiplist.contains(&priority), sorted.contains(&priority));
+            }
+            let tasks = Vec::from(skiplist);
+            assert!(tasks.iter().map(ByKey::key).eq(&sorted));
+            // The rest of every task is kept along with its priority.
+            let mut other: Vec<_> = tasks.into_iter().map(|task| task.into_inner().name).collect();
+            other.sort_unstable();
+            assert_eq!(other, names);
+        }
+    }
+
+    #[test]
+    fn test_nan_policies() {
+        let nan = OrderedF64::new(f64::NAN);
+        let negative_nan = OrderedF64::new(-f64::NAN);
+        assert!(negative_nan < OrderedF64::new(f64::NEG_INFINITY));
+        assert!(nan > OrderedF64::new(f64::INFINITY));
+        assert!(OrderedF64::new(-0.0) < OrderedF64::new(0.0));
+        assert_eq!(OrderedF64::canonical(-f64::NAN), nan);
+        assert_eq!(OrderedF64::canonical(-0.0), OrderedF64::new(0.0));
+        assert_eq!(OrderedF64::not_nan(f64::NAN), None);
+        assert_eq!(OrderedF64::not_nan(1.5), Some(OrderedF64::new(1.5)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_float_keys(values in vec(any::<f64>(), 0..100)) {
+            let mut skipmap = SkipMap::<OrderedF64, usize, _, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (i, value) in values.iter().enumerate() {
+                skipmap.insert(OrderedF64::canonical(*value), i);
+                btree.insert(OrderedF64::canonical(*value), i);
+            }
+            assert_eq!(skipmap, btree);
+            for value in &values {
+                let key = OrderedF64::canonical(*value);
+                assert!(skipmap.contains(&key));
+                let mut buf = Vec::new();
+                key.encode(&mut buf);
+                assert_eq!(OrderedF64::decode(&buf).unwrap().get().to_bits(), key.get().to_bits());
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Policies deciding what an insertion does with a value equal to one that already exists.
+//!
+//! The policy is a type parameter of [`SkipList`](crate::SkipList) and [`SkipMap`](crate::SkipMap),
+//! so that the semantics of their insertions are part of their types. Skiplists keep duplicates by
+//! default and skipmaps overwrite the values of equal keys by default, like the collections of the
+//! standard library.
+
+/// What an insertion does with a value equal to one that already exists.
+pub trait DuplicatePolicy: sealed::Sealed {
+    /// What an insertion returns, where `T` is the type of the values that might be given back.
+    type Outcome<T>;
+
+    /// Returns the outcome of an insertion, given the existing value that was replaced, or the
+    /// given value that was rejected, if any.
+    fn outcome<T>(equal: Option<T>) -> Self::Outcome<T>;
+}
+
+/// Keeps every equal value. Insertions return nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AllowDuplicates;
+
+/// Replaces the existing equal value. Insertions return the replaced value, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct OverwriteOnEqual;
+
+/// Keeps the existing equal value. Insertions give back the rejected value, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RejectOnEqual;
+
+impl DuplicatePolicy for AllowDuplicates {
+    type Outcome<T> = ();
+
+    fn outcome<T>(_: Option<T>) -> Self::Outcome<T> {}
+}
+
+impl DuplicatePolicy for OverwriteOnEqual {
+    type Outcome<T> = Option<T>;
+
+    fn outcome<T>(equal: Option<T>) -> Self::Outcome<T> {
+        equal
+    }
+}
+
+impl DuplicatePolicy for RejectOnEqual {
+    type Outcome<T> = Result<(), T>;
+
+    fn outcome<T>(equal: Option<T>) -> Self::Outcome<T> {
+        equal.map_or(Ok(()), Err)
+    }
+}
+
+pub(crate) use sealed::OnEqual;
+
+mod sealed {
+    /// How a policy handles an equal value, which the collections dispatch on.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OnEqual {
+        Keep,
+        Replace,
+        Reject,
+    }
+
+    pub trait Sealed {
+        const ON_EQUAL: OnEqual;
+    }
+
+    impl Sealed for super::AllowDuplicates {
+        const ON_EQUAL: OnEqual = OnEqual::Keep;
+    }
+
+    impl Sealed for super::OverwriteOnEqual {
+        const ON_EQUAL: OnEqual = OnEqual::Replace;
+    }
+
+    impl Sealed for super::RejectOnEqual {
+        const ON_EQUAL: OnEqual = OnEqual::Reject;
+    }
+}
+
+/// Returns how the policy handles an equal value.
+pub(crate) const fn on_equal<P>() -> OnEqual
+where
+    P: DuplicatePolicy,
+{
+    <P as sealed::Sealed>::ON_EQUAL
+}
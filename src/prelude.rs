@@ -0,0 +1,27 @@
+//! The types and traits that most uses of the crate need, to be imported with a glob.
+
+pub use crate::{
+    DeterministicSkipList, FixedRng, SkipList, SkipList16, SkipList32, SkipMap, SkipMap16,
+    SkipMap32, levels_for,
+    memory::HeapSize,
+    order::{ByKey, ComparisonPanicked, KeyExtract, OrderedF32, OrderedF64},
+    policy::{AllowDuplicates, DuplicatePolicy, OverwriteOnEqual, RejectOnEqual},
+    sorted::{SortedMap, SortedSet},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        let mut skiplist = SkipList16::new();
+        let mut skipmap = SkipMap32::new();
+        for value in [3, 1, 2] {
+            SortedSet::insert(&mut skiplist, value);
+            SortedMap::insert(&mut skipmap, OrderedF64::new(f64::from(value)), value);
+        }
+        assert_eq!(skiplist.pop_first(), Some(1));
+        assert_eq!(skipmap.pop_last(), Some((OrderedF64::new(3.0), 3)));
+    }
+}
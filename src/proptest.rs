@@ -0,0 +1,92 @@
+//! Strategies generating collections for property tests.
+//!
+//! Every strategy also generates the seed of the random number generator of the collection, so
+//! that a failing case is reproduced with the same structure.
+
+use ::proptest::{
+    collection::{self, SizeRange},
+    prelude::*,
+};
+use rand::{Rng, SeedableRng};
+
+use crate::{SkipList, SkipMap, bulk::Builder};
+
+/// Returns a strategy generating skiplists whose values are generated by the given strategy, and
+/// whose number of values is within the given range.
+pub fn skiplist<S, R, const N: usize>(
+    element: S,
+    size: SizeRange,
+) -> impl Strategy<Value = SkipList<S::Value, R, N>>
+where
+    S: Strategy,
+    S::Value: Ord,
+    R: Rng + SeedableRng,
+{
+    (collection::vec(element, size), any::<u64>()).prop_map(|(mut values, seed)| {
+        values.sort();
+        let mut builder = Builder::with_rng(R::seed_from_u64(seed), false);
+        for value in values {
+            // Sorted values are always accepted.
+            let _ = builder.push(value);
+        }
+        SkipList::from_builder(builder)
+    })
+}
+
+/// Returns a strategy generating skipmaps whose keys and values are generated by the given
+/// strategies, and whose number of entries is within the given range.
+pub fn skipmap<K, V, R, const N: usize>(
+    key: K,
+    value: V,
+    size: SizeRange,
+) -> impl Strategy<Value = SkipMap<K::Value, V::Value, R, N>>
+where
+    K: Strategy,
+    K::Value: Ord,
+    V: Strategy,
+    R: Rng + SeedableRng,
+{
+    (collection::btree_map(key, value, size), any::<u64>()).prop_map(|(entries, seed)| {
+        let mut builder = Builder::with_rng(R::seed_from_u64(seed), true);
+        for entry in entries {
+            // Entries of a map are always sorted by unique keys.
+            let _ = builder.push(entry.into());
+        }
+        SkipMap::from_builder(builder)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ::proptest::prelude::*;
+    use rand::rngs::SmallRng;
+
+    use super::{skiplist, skipmap};
+
+    proptest! {
+        #[test]
+        fn test_skiplist(mut skiplist in skiplist::<_, SmallRng, 8>(0..16u8, (4..32).into())) {
+            let mut removed = 0;
+            for value in 0..16 {
+                while skiplist.remove(&value).is_some() {
+                    removed += 1;
+                }
+            }
+            assert!((4..32).contains(&removed));
+        }
+
+        #[test]
+        fn test_skipmap(skipmap in skipmap::<_, _, SmallRng, 8>(0..64u8, any::<u16>(), (4..32).into())) {
+            let len = (0..64).filter(|key| skipmap.contains(key)).count();
+            assert!((4..32).contains(&len));
+        }
+
+        #[test]
+        fn test_empty(
+            skiplist in skiplist::<_, SmallRng, 4>(any::<u8>(), 0.into()),
+            skipmap in skipmap::<_, _, SmallRng, 4>(any::<u8>(), any::<u8>(), 0.into()),
+        ) {
+            assert!((0..=u8::MAX).all(|x| !skiplist.contains(&x) && !skipmap.contains(&x)));
+        }
+    }
+}
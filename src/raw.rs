@@ -0,0 +1,22 @@
+//! Lookups specialized for keys of primitive types, which get compared by value.
+//!
+//! The generic lookups compare keys through [`Borrow`](std::borrow::Borrow) and [`Ord::cmp`],
+//! reusing the result of every comparison that stops a level for the level below. For keys that
+//! fit in a register, a plain `<` is cheaper than keeping that result around, so the lookups for
+//! such keys copy the key once and only branch on whether to move forward.
+
+/// A key of a primitive type, which is cheap to copy and whose comparison compiles down to a
+/// single instruction.
+pub trait RawKey: Copy + Ord {}
+
+macro_rules! impl_raw_key {
+    ($($ty:ty),*) => {
+        $(
+            impl RawKey for $ty {}
+        )*
+    };
+}
+
+impl_raw_key!(
+    bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
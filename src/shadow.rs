@@ -0,0 +1,1073 @@
+//! Differential testing of the collections against those of the standard library.
+//!
+//! A [`ShadowSet`] or a [`ShadowMap`] mirrors every operation onto a [`BTreeSet`] or a
+//! [`BTreeMap`], and a [`ShadowMultiset`] onto a [`BTreeMap`] counting equal values, and they
+//! panic as soon as the two give different results. Every mutation also checks
+//! that both iterate in the same order, and the invariants of the skiplist, so that a corruption is
+//! caught at the operation causing it rather than at a later lookup. Checking takes linear time,
+//! which makes the wrappers only suited to tests, e.g. driven by property tests.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    hash::Hash,
+    iter,
+    ops::RangeBounds,
+};
+
+use rand::{Rng, rngs::SmallRng};
+
+use crate::{
+    SkipList, SkipMap,
+    entry::Entry,
+    order::ComparisonPanicked,
+    policy::{AllowDuplicates, RejectOnEqual},
+    sorted::{SortedMap, SortedSet},
+};
+
+/// A skiplist of unique values, whose operations are checked against a [`BTreeSet`].
+pub struct ShadowSet<T, R = SmallRng, const N: usize = 32>
+where
+    R: Rng,
+{
+    skiplist: SkipList<T, R, N, RejectOnEqual>,
+    shadow: BTreeSet<T>,
+}
+
+impl<T, const N: usize> ShadowSet<T, SmallRng, N> {
+    /// Creates an empty set.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_skiplist(SkipList::new())
+    }
+}
+
+impl<T, R, const N: usize> ShadowSet<T, R, N>
+where
+    R: Rng,
+{
+    const fn with_skiplist(skiplist: SkipList<T, R, N, RejectOnEqual>) -> Self {
+        Self {
+            skiplist,
+            shadow: BTreeSet::new(),
+        }
+    }
+
+    /// Creates an empty set whose skiplist uses the given random number generator.
+    #[must_use]
+    pub const fn with_rng(rng: R) -> Self {
+        Self::with_skiplist(SkipList::with_rng(rng))
+    }
+
+    /// Returns the checked skiplist.
+    #[must_use]
+    pub const fn skiplist(&self) -> &SkipList<T, R, N, RejectOnEqual> {
+        &self.skiplist
+    }
+
+    /// Returns the checked skiplist, dropping the set it was checked against.
+    #[must_use]
+    pub fn into_inner(self) -> SkipList<T, R, N, RejectOnEqual> {
+        self.skiplist
+    }
+}
+
+#[expect(
+    clippy::missing_panics_doc,
+    reason = "every operation panics on a disagreement, as documented by the module"
+)]
+impl<T, R, const N: usize> ShadowSet<T, R, N>
+where
+    T: Ord + Clone + fmt::Debug,
+    R: Rng,
+{
+    /// Returns whether a value exists in the set.
+    #[track_caller]
+    pub fn contains<U>(&self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let contains = self.skiplist.contains(value);
+        assert_eq!(contains, self.shadow.contains(value), "contains disagrees");
+        contains
+    }
+
+    /// Returns the least value.
+    #[must_use]
+    #[track_caller]
+    pub fn first(&self) -> Option<&T> {
+        let first = self.skiplist.first();
+        assert_eq!(first, self.shadow.first(), "first disagrees");
+        first
+    }
+
+    /// Returns the greatest value.
+    #[must_use]
+    #[track_caller]
+    pub fn last(&self) -> Option<&T> {
+        let last = self.skiplist.last();
+        assert_eq!(last, self.shadow.last(), "last disagrees");
+        last
+    }
+
+    /// Returns the values within the range, in ascending order. The range must be valid for a
+    /// [`BTreeSet`], whose start isn't greater than its end.
+    #[track_caller]
+    pub fn range<B>(&self, range: B) -> Vec<&T>
+    where
+        B: RangeBounds<T> + Clone,
+    {
+        let values: Vec<_> = SortedSet::range(&self.skiplist, range.clone()).collect();
+        let shadow: Vec<_> = self.shadow.range(range).collect();
+        assert_eq!(values, shadow, "range disagrees");
+        values
+    }
+
+    /// Inserts a value unless an equal one exists, which gets the value back as an error.
+    ///
+    /// # Errors
+    ///
+    /// Gives the value back if an equal one exists.
+    #[track_caller]
+    pub fn insert(&mut self, value: T) -> Result<(), T> {
+        let inserted = self.shadow.insert(value.clone());
+        let result = self.skiplist.insert(value);
+        assert_eq!(result.is_ok(), inserted, "insert disagrees");
+        self.check();
+        result
+    }
+
+    /// Removes a value, returning it if it exists.
+    #[track_caller]
+    pub fn remove<U>(&mut self, value: &U) -> Option<T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let removed = self.skiplist.remove(value);
+        assert_eq!(removed, self.shadow.take(value), "remove disagrees");
+        self.check();
+        removed
+    }
+
+    /// Marks a value as removed in the skiplist, returning whether it exists.
+    #[track_caller]
+    pub fn remove_lazily<U>(&mut self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let removed = self.skiplist.remove_lazily(value);
+        assert_eq!(
+            removed,
+            self.shadow.remove(value),
+            "remove_lazily disagrees"
+        );
+        self.check();
+        removed
+    }
+
+    /// Removes the least value, returning it if the set isn't empty.
+    #[track_caller]
+    pub fn pop_first(&mut self) -> Option<T> {
+        let popped = self.skiplist.pop_first();
+        assert_eq!(popped, self.shadow.pop_first(), "pop_first disagrees");
+        self.check();
+        popped
+    }
+
+    /// Removes the greatest value, returning it if the set isn't empty.
+    #[track_caller]
+    pub fn pop_last(&mut self) -> Option<T> {
+        let popped = self.skiplist.pop_last();
+        assert_eq!(popped, self.shadow.pop_last(), "pop_last disagrees");
+        self.check();
+        popped
+    }
+
+    /// Inserts a value unless an equal one exists, like [`ShadowSet::insert`], linking a new node
+    /// into a number of levels derived from the hash of the value.
+    ///
+    /// # Errors
+    ///
+    /// Gives the value back if an equal one exists.
+    #[track_caller]
+    pub fn insert_hashed(&mut self, value: T) -> Result<(), T>
+    where
+        T: Hash,
+    {
+        let inserted = self.shadow.insert(value.clone());
+        let result = self.skiplist.insert_hashed(value);
+        assert_eq!(result.is_ok(), inserted, "insert_hashed disagrees");
+        self.check();
+        result
+    }
+
+    /// Appends a value greater than every other one.
+    ///
+    /// # Errors
+    ///
+    /// Gives the value back if it isn't greater than the greatest value.
+    #[track_caller]
+    pub fn push_max(&mut self, value: T) -> Result<(), T> {
+        let greatest = self.shadow.last().is_none_or(|last| last < &value);
+        let result = self.skiplist.push_max(value.clone());
+        assert_eq!(result.is_ok(), greatest, "push_max disagrees");
+        if greatest {
+            self.shadow.insert(value);
+        }
+        self.check();
+        result
+    }
+
+    /// Inserts a value unless an equal one exists, catching a panic of the comparisons, in which
+    /// case the set is left as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    #[track_caller]
+    pub fn try_insert(&mut self, value: T) -> Result<Result<(), T>, ComparisonPanicked> {
+        let result = self.skiplist.try_insert(value.clone());
+        if let Ok(inserted) = &result {
+            assert_eq!(inserted.is_ok(), self.shadow.insert(value), "try_insert disagrees");
+        }
+        self.check();
+        result
+    }
+
+    /// Removes a value, returning it if it exists, catching a panic of the comparisons, in which
+    /// case the set is left as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    #[track_caller]
+    pub fn try_remove<U>(&mut self, value: &U) -> Result<Option<T>, ComparisonPanicked>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let result = self.skiplist.try_remove(value);
+        if let Ok(removed) = &result {
+            assert_eq!(removed, &self.shadow.take(value), "try_remove disagrees");
+        }
+        self.check();
+        result
+    }
+
+    /// Keeps only the values that match the predicate, which gets called on the values of both
+    /// collections.
+    #[track_caller]
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.skiplist.retain(&mut pred);
+        self.shadow.retain(pred);
+        self.check();
+    }
+
+    /// Removes up to the given number of the values that match the predicate, in ascending order,
+    /// then drops the iterator of the skiplist. The predicate gets called on the values of both
+    /// collections.
+    #[track_caller]
+    pub fn extract_if<F>(&mut self, mut pred: F, limit: usize) -> Vec<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let extracted: Vec<_> = self.skiplist.extract_if(&mut pred).take(limit).collect();
+        let matching = self.shadow.iter().filter(|&value| pred(value)).take(limit);
+        let matching: Vec<_> = matching.cloned().collect();
+        let shadow: Vec<_> = matching.iter().filter_map(|value| self.shadow.take(value)).collect();
+        assert_eq!(extracted, shadow, "extract_if disagrees");
+        self.check();
+        extracted
+    }
+
+    /// Rebuilds the towers of the skiplist.
+    #[track_caller]
+    pub fn compact(&mut self) {
+        self.skiplist.compact();
+        self.check();
+    }
+
+    /// Unlinks the nodes of the values removed lazily from the skiplist.
+    #[track_caller]
+    pub fn vacuum(&mut self) {
+        self.skiplist.vacuum();
+        self.check();
+    }
+
+    /// Checks that the skiplist holds the values of the set in the same order, and its invariants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values differ, or if the structure of the skiplist is corrupted.
+    #[track_caller]
+    pub fn check(&self) {
+        self.skiplist.assert_invariants();
+        let values: Vec<_> = SortedSet::iter(&self.skiplist).collect();
+        let shadow: Vec<_> = self.shadow.iter().collect();
+        assert_eq!(values, shadow, "the values disagree");
+    }
+}
+
+impl<T, const N: usize> Default for ShadowSet<T, SmallRng, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R, const N: usize> fmt::Debug for ShadowSet<T, R, N>
+where
+    T: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.skiplist.fmt(f)
+    }
+}
+
+/// A skiplist keeping equal values, whose operations are checked against a [`BTreeMap`] from the
+/// values to their number of occurrences.
+pub struct ShadowMultiset<T, R = SmallRng, const N: usize = 32>
+where
+    R: Rng,
+{
+    skiplist: SkipList<T, R, N, AllowDuplicates>,
+    shadow: BTreeMap<T, usize>,
+}
+
+impl<T, const N: usize> ShadowMultiset<T, SmallRng, N> {
+    /// Creates an empty multiset.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_skiplist(SkipList::new())
+    }
+}
+
+impl<T, R, const N: usize> ShadowMultiset<T, R, N>
+where
+    R: Rng,
+{
+    const fn with_skiplist(skiplist: SkipList<T, R, N, AllowDuplicates>) -> Self {
+        Self {
+            skiplist,
+            shadow: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an empty multiset whose skiplist uses the given random number generator.
+    #[must_use]
+    pub const fn with_rng(rng: R) -> Self {
+        Self::with_skiplist(SkipList::with_rng(rng))
+    }
+
+    /// Returns the checked skiplist.
+    #[must_use]
+    pub const fn skiplist(&self) -> &SkipList<T, R, N, AllowDuplicates> {
+        &self.skiplist
+    }
+
+    /// Returns the checked skiplist, dropping the multiset it was checked against.
+    #[must_use]
+    pub fn into_inner(self) -> SkipList<T, R, N, AllowDuplicates> {
+        self.skiplist
+    }
+}
+
+#[expect(
+    clippy::missing_panics_doc,
+    reason = "every operation panics on a disagreement, as documented by the module"
+)]
+impl<T, R, const N: usize> ShadowMultiset<T, R, N>
+where
+    T: Ord + Clone + fmt::Debug,
+    R: Rng,
+{
+    /// Returns whether a value exists in the multiset.
+    #[track_caller]
+    pub fn contains<U>(&self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let contains = self.skiplist.contains(value);
+        assert_eq!(contains, self.shadow.contains_key(value), "contains disagrees");
+        contains
+    }
+
+    /// Returns the least value.
+    #[must_use]
+    #[track_caller]
+    pub fn first(&self) -> Option<&T> {
+        let first = self.skiplist.first();
+        let shadow = self.shadow.first_key_value().map(|(value, _)| value);
+        assert_eq!(first, shadow, "first disagrees");
+        first
+    }
+
+    /// Returns the greatest value.
+    #[must_use]
+    #[track_caller]
+    pub fn last(&self) -> Option<&T> {
+        let last = self.skiplist.last();
+        let shadow = self.shadow.last_key_value().map(|(value, _)| value);
+        assert_eq!(last, shadow, "last disagrees");
+        last
+    }
+
+    /// Returns the values within the range, in ascending order, with every equal value repeated.
+    /// The range must be valid for a [`BTreeMap`], whose start isn't greater than its end.
+    #[track_caller]
+    pub fn range<B>(&self, range: B) -> Vec<&T>
+    where
+        B: RangeBounds<T> + Clone,
+    {
+        let values: Vec<_> = SortedSet::range(&self.skiplist, range.clone()).collect();
+        let shadow = self.shadow.range(range);
+        let shadow: Vec<_> = shadow.flat_map(|(value, &n)| iter::repeat_n(value, n)).collect();
+        assert_eq!(values, shadow, "range disagrees");
+        values
+    }
+
+    /// Inserts a value after the values equal to it.
+    #[track_caller]
+    pub fn insert(&mut self, value: T) {
+        *self.shadow.entry(value.clone()).or_default() += 1;
+        self.skiplist.insert(value);
+        self.check();
+    }
+
+    /// Removes one of the values equal to the given one, returning it if it exists.
+    #[track_caller]
+    pub fn remove<U>(&mut self, value: &U) -> Option<T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let removed = self.skiplist.remove(value);
+        assert_eq!(removed, self.take_one(value), "remove disagrees");
+        self.check();
+        removed
+    }
+
+    /// Marks one of the values equal to the given one as removed in the skiplist, returning
+    /// whether it exists.
+    #[track_caller]
+    pub fn remove_lazily<U>(&mut self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let removed = self.skiplist.remove_lazily(value);
+        let shadow = self.take_one(value).is_some();
+        assert_eq!(removed, shadow, "remove_lazily disagrees");
+        self.check();
+        removed
+    }
+
+    /// Removes one of the least values, returning it if the multiset isn't empty.
+    #[track_caller]
+    pub fn pop_first(&mut self) -> Option<T> {
+        let popped = self.skiplist.pop_first();
+        let shadow = self.shadow.first_key_value().map(|(value, _)| value.clone());
+        let shadow = shadow.and_then(|value| self.take_one(&value));
+        assert_eq!(popped, shadow, "pop_first disagrees");
+        self.check();
+        popped
+    }
+
+    /// Removes one of the greatest values, returning it if the multiset isn't empty.
+    #[track_caller]
+    pub fn pop_last(&mut self) -> Option<T> {
+        let popped = self.skiplist.pop_last();
+        let shadow = self.shadow.last_key_value().map(|(value, _)| value.clone());
+        let shadow = shadow.and_then(|value| self.take_one(&value));
+        assert_eq!(popped, shadow, "pop_last disagrees");
+        self.check();
+        popped
+    }
+
+    /// Inserts a value after the values equal to it, like [`ShadowMultiset::insert`], linking a
+    /// new node into a number of levels derived from the hash of the value.
+    #[track_caller]
+    pub fn insert_hashed(&mut self, value: T)
+    where
+        T: Hash,
+    {
+        *self.shadow.entry(value.clone()).or_default() += 1;
+        self.skiplist.insert_hashed(value);
+        self.check();
+    }
+
+    /// Appends a value that isn't less than any other one.
+    ///
+    /// # Errors
+    ///
+    /// Gives the value back if it's less than the greatest value.
+    #[track_caller]
+    pub fn push_max(&mut self, value: T) -> Result<(), T> {
+        let greatest = self.shadow.last_key_value().is_none_or(|(last, _)| last <= &value);
+        let result = self.skiplist.push_max(value.clone());
+        assert_eq!(result.is_ok(), greatest, "push_max disagrees");
+        if greatest {
+            *self.shadow.entry(value).or_default() += 1;
+        }
+        self.check();
+        result
+    }
+
+    /// Inserts a value after the values equal to it, catching a panic of the comparisons, in
+    /// which case the multiset is left as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    #[track_caller]
+    pub fn try_insert(&mut self, value: T) -> Result<(), ComparisonPanicked> {
+        let result = self.skiplist.try_insert(value.clone());
+        if result.is_ok() {
+            *self.shadow.entry(value).or_default() += 1;
+        }
+        self.check();
+        result
+    }
+
+    /// Removes one of the values equal to the given one, returning it if it exists, catching a
+    /// panic of the comparisons, in which case the multiset is left as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    #[track_caller]
+    pub fn try_remove<U>(&mut self, value: &U) -> Result<Option<T>, ComparisonPanicked>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let result = self.skiplist.try_remove(value);
+        if let Ok(removed) = &result {
+            assert_eq!(removed, &self.take_one(value), "try_remove disagrees");
+        }
+        self.check();
+        result
+    }
+
+    /// Keeps only the values that match the predicate, which gets called on the values of both
+    /// collections.
+    #[track_caller]
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.skiplist.retain(&mut pred);
+        self.shadow.retain(|value, _| pred(value));
+        self.check();
+    }
+
+    /// Removes up to the given number of the values that match the predicate, in ascending order,
+    /// then drops the iterator of the skiplist. The predicate gets called on the values of both
+    /// collections.
+    #[track_caller]
+    pub fn extract_if<F>(&mut self, mut pred: F, limit: usize) -> Vec<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let extracted: Vec<_> = self.skiplist.extract_if(&mut pred).take(limit).collect();
+        let matching = self.shadow.iter().flat_map(|(value, &n)| iter::repeat_n(value, n));
+        let matching: Vec<_> = matching.filter(|&value| pred(value)).take(limit).cloned().collect();
+        let shadow: Vec<_> = matching.iter().filter_map(|value| self.take_one(value)).collect();
+        assert_eq!(extracted, shadow, "extract_if disagrees");
+        self.check();
+        extracted
+    }
+
+    /// Rebuilds the towers of the skiplist.
+    #[track_caller]
+    pub fn compact(&mut self) {
+        self.skiplist.compact();
+        self.check();
+    }
+
+    /// Unlinks the nodes of the values removed lazily from the skiplist.
+    #[track_caller]
+    pub fn vacuum(&mut self) {
+        self.skiplist.vacuum();
+        self.check();
+    }
+
+    /// Checks that the skiplist holds as many of every value as the multiset, in the same order,
+    /// and its invariants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values differ, or if the structure of the skiplist is corrupted.
+    #[track_caller]
+    pub fn check(&self) {
+        self.skiplist.assert_invariants();
+        let values: Vec<_> = SortedSet::iter(&self.skiplist).collect();
+        let shadow = self.shadow.iter();
+        let shadow: Vec<_> = shadow.flat_map(|(value, &n)| iter::repeat_n(value, n)).collect();
+        assert_eq!(values, shadow, "the values disagree");
+    }
+
+    /// Takes one occurrence of a value out of the multiset, returning the value if it exists.
+    fn take_one<U>(&mut self, value: &U) -> Option<T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let count = self.shadow.get_mut(value)?;
+        *count -= 1;
+        if *count == 0 {
+            return self.shadow.remove_entry(value).map(|(value, _)| value);
+        }
+        self.shadow.get_key_value(value).map(|(value, _)| value.clone())
+    }
+}
+
+impl<T, const N: usize> Default for ShadowMultiset<T, SmallRng, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R, const N: usize> fmt::Debug for ShadowMultiset<T, R, N>
+where
+    T: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.skiplist.fmt(f)
+    }
+}
+
+/// A skipmap, whose operations are checked against a [`BTreeMap`].
+pub struct ShadowMap<K, V, R = SmallRng, const N: usize = 32>
+where
+    R: Rng,
+{
+    skipmap: SkipMap<K, V, R, N>,
+    shadow: BTreeMap<K, V>,
+}
+
+impl<K, V, const N: usize> ShadowMap<K, V, SmallRng, N> {
+    /// Creates an empty map.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_skipmap(SkipMap::new())
+    }
+}
+
+impl<K, V, R, const N: usize> ShadowMap<K, V, R, N>
+where
+    R: Rng,
+{
+    const fn with_skipmap(skipmap: SkipMap<K, V, R, N>) -> Self {
+        Self {
+            skipmap,
+            shadow: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an empty map whose skipmap uses the given random number generator.
+    #[must_use]
+    pub const fn with_rng(rng: R) -> Self {
+        Self::with_skipmap(SkipMap::with_rng(rng))
+    }
+
+    /// Returns the checked skipmap.
+    #[must_use]
+    pub const fn skipmap(&self) -> &SkipMap<K, V, R, N> {
+        &self.skipmap
+    }
+
+    /// Returns the checked skipmap, dropping the map it was checked against.
+    #[must_use]
+    pub fn into_inner(self) -> SkipMap<K, V, R, N> {
+        self.skipmap
+    }
+}
+
+#[expect(
+    clippy::missing_panics_doc,
+    reason = "every operation panics on a disagreement, as documented by the module"
+)]
+impl<K, V, R, const N: usize> ShadowMap<K, V, R, N>
+where
+    K: Ord + Clone + fmt::Debug,
+    V: Clone + PartialEq + fmt::Debug,
+    R: Rng,
+{
+    /// Returns whether an entry exists at the given key.
+    #[track_caller]
+    pub fn contains_key(&self, key: &K) -> bool {
+        let contains = self.skipmap.contains(key);
+        assert_eq!(
+            contains,
+            self.shadow.contains_key(key),
+            "contains_key disagrees"
+        );
+        contains
+    }
+
+    /// Returns the value at the given key, if it exists.
+    #[track_caller]
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let value = self.skipmap.get(key);
+        assert_eq!(value, self.shadow.get(key), "get disagrees");
+        value
+    }
+
+    /// Returns the entry at the least key.
+    #[must_use]
+    #[track_caller]
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let first = self.skipmap.first_key_value();
+        assert_eq!(
+            first,
+            self.shadow.first_key_value(),
+            "first_key_value disagrees"
+        );
+        first
+    }
+
+    /// Returns the entry at the greatest key.
+    #[must_use]
+    #[track_caller]
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let last = self.skipmap.last_key_value();
+        assert_eq!(
+            last,
+            self.shadow.last_key_value(),
+            "last_key_value disagrees"
+        );
+        last
+    }
+
+    /// Returns the entries whose keys are within the range, in ascending order of keys. The range
+    /// must be valid for a [`BTreeMap`], whose start isn't greater than its end.
+    #[track_caller]
+    pub fn range<B>(&self, range: B) -> Vec<(&K, &V)>
+    where
+        B: RangeBounds<K> + Clone,
+    {
+        let entries: Vec<_> = SortedMap::range(&self.skipmap, range.clone()).collect();
+        let shadow: Vec<_> = self.shadow.range(range).collect();
+        assert_eq!(entries, shadow, "range disagrees");
+        entries
+    }
+
+    /// Inserts an entry, returning the replaced value if one exists at the key.
+    #[track_caller]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let replaced = self.shadow.insert(key.clone(), value.clone());
+        let old = self.skipmap.insert(key, value);
+        assert_eq!(old, replaced, "insert disagrees");
+        self.check();
+        old
+    }
+
+    /// Removes the entry at the given key, returning its value if it exists.
+    #[track_caller]
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let removed = self.skipmap.remove(key);
+        assert_eq!(removed, self.shadow.remove(key), "remove disagrees");
+        self.check();
+        removed
+    }
+
+    /// Marks the entry at the given key as removed in the skipmap, returning whether it exists.
+    #[track_caller]
+    pub fn remove_lazily(&mut self, key: &K) -> bool {
+        let removed = self.skipmap.remove_lazily(key);
+        let shadow = self.shadow.remove(key).is_some();
+        assert_eq!(removed, shadow, "remove_lazily disagrees");
+        self.check();
+        removed
+    }
+
+    /// Removes the entry at the least key, returning it if the map isn't empty.
+    #[track_caller]
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let popped = self.skipmap.pop_first();
+        assert_eq!(popped, self.shadow.pop_first(), "pop_first disagrees");
+        self.check();
+        popped
+    }
+
+    /// Removes the entry at the greatest key, returning it if the map isn't empty.
+    #[track_caller]
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let popped = self.skipmap.pop_last();
+        assert_eq!(popped, self.shadow.pop_last(), "pop_last disagrees");
+        self.check();
+        popped
+    }
+
+    /// Inserts an entry, like [`ShadowMap::insert`], linking a new node into a number of levels
+    /// derived from the hash of the key.
+    #[track_caller]
+    pub fn insert_hashed(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Hash,
+    {
+        let replaced = self.shadow.insert(key.clone(), value.clone());
+        let old = self.skipmap.insert_hashed(key, value);
+        assert_eq!(old, replaced, "insert_hashed disagrees");
+        self.check();
+        old
+    }
+
+    /// Appends an entry whose key is greater than every other key.
+    ///
+    /// # Errors
+    ///
+    /// Gives the entry back if its key isn't greater than the greatest key.
+    #[track_caller]
+    pub fn push_max(&mut self, key: K, value: V) -> Result<(), (K, V)> {
+        let greatest = self.shadow.last_key_value().is_none_or(|(last, _)| last < &key);
+        let result = self.skipmap.push_max(key.clone(), value.clone());
+        assert_eq!(result.is_ok(), greatest, "push_max disagrees");
+        if greatest {
+            self.shadow.insert(key, value);
+        }
+        self.check();
+        result
+    }
+
+    /// Inserts an entry, returning the replaced value if one exists at the key, catching a panic
+    /// of the comparisons, in which case the map is left as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing keys panicked.
+    #[track_caller]
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, ComparisonPanicked> {
+        let result = self.skipmap.try_insert(key.clone(), value.clone());
+        if let Ok(old) = &result {
+            assert_eq!(old, &self.shadow.insert(key, value), "try_insert disagrees");
+        }
+        self.check();
+        result
+    }
+
+    /// Removes the entry at the given key, returning its value if it exists, catching a panic of
+    /// the comparisons, in which case the map is left as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing keys panicked.
+    #[track_caller]
+    pub fn try_remove(&mut self, key: &K) -> Result<Option<V>, ComparisonPanicked> {
+        let result = self.skipmap.try_remove(key);
+        if let Ok(removed) = &result {
+            assert_eq!(removed, &self.shadow.remove(key), "try_remove disagrees");
+        }
+        self.check();
+        result
+    }
+
+    /// Returns a copy of the value at the given key, inserting the given one first through a
+    /// vacant entry of the skipmap if there's none.
+    #[track_caller]
+    pub fn or_insert(&mut self, key: K, value: V) -> V {
+        let shadow = self.shadow.entry(key.clone()).or_insert_with(|| value.clone());
+        let value = self.skipmap.entry(key).or_insert(value).clone();
+        assert_eq!(&value, shadow, "or_insert disagrees");
+        self.check();
+        value
+    }
+
+    /// Removes the entry at the given key through an occupied entry of the skipmap, returning it
+    /// if it exists.
+    #[track_caller]
+    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let removed = match self.skipmap.entry(key.clone()) {
+            Entry::Occupied(entry) => Some(entry.remove_entry()),
+            Entry::Vacant(_) => None,
+        };
+        assert_eq!(removed, self.shadow.remove_entry(key), "remove_entry disagrees");
+        self.check();
+        removed
+    }
+
+    /// Removes the entries whose keys are within the range, in ascending order of keys. The range
+    /// must be valid for a [`BTreeMap`], whose start isn't greater than its end.
+    #[track_caller]
+    pub fn drain_range<B>(&mut self, range: B) -> Vec<(K, V)>
+    where
+        B: RangeBounds<K> + Clone,
+    {
+        let drained: Vec<_> = self.skipmap.drain_range(range.clone()).collect();
+        let keys: Vec<_> = self.shadow.range(range).map(|(key, _)| key.clone()).collect();
+        let shadow: Vec<_> = keys.iter().filter_map(|key| self.shadow.remove_entry(key)).collect();
+        assert_eq!(drained, shadow, "drain_range disagrees");
+        self.check();
+        drained
+    }
+
+    /// Keeps only the entries that match the predicate, which gets called on the entries of both
+    /// collections.
+    #[track_caller]
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.skipmap.retain(|key, value| pred(key, value));
+        self.shadow.retain(|key, value| pred(key, value));
+        self.check();
+    }
+
+    /// Removes up to the given number of the entries that match the predicate, in ascending order
+    /// of keys, then drops the iterator of the skipmap. The predicate gets called on the entries
+    /// of both collections.
+    #[track_caller]
+    pub fn extract_if<F>(&mut self, mut pred: F, limit: usize) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let extracted = self.skipmap.extract_if(|key, value| pred(key, value));
+        let extracted: Vec<_> = extracted.take(limit).collect();
+        let matching = self.shadow.iter().filter(|(key, value)| pred(key, value)).take(limit);
+        let keys: Vec<_> = matching.map(|(key, _)| key.clone()).collect();
+        let shadow: Vec<_> = keys.iter().filter_map(|key| self.shadow.remove_entry(key)).collect();
+        assert_eq!(extracted, shadow, "extract_if disagrees");
+        self.check();
+        extracted
+    }
+
+    /// Rebuilds the towers of the skipmap.
+    #[track_caller]
+    pub fn compact(&mut self) {
+        self.skipmap.compact();
+        self.check();
+    }
+
+    /// Unlinks the nodes of the entries removed lazily from the skipmap.
+    #[track_caller]
+    pub fn vacuum(&mut self) {
+        self.skipmap.vacuum();
+        self.check();
+    }
+
+    /// Checks that the skipmap holds the entries of the map in the same order, and its invariants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entries differ, or if the structure of the skipmap is corrupted.
+    #[track_caller]
+    pub fn check(&self) {
+        self.skipmap.assert_invariants();
+        let entries: Vec<_> = SortedMap::iter(&self.skipmap).collect();
+        let shadow: Vec<_> = self.shadow.iter().collect();
+        assert_eq!(entries, shadow, "the entries disagree");
+    }
+}
+
+impl<K, V, const N: usize> Default for ShadowMap<K, V, SmallRng, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, R, const N: usize> fmt::Debug for ShadowMap<K, V, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.skipmap.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use rand::rngs::SmallRng;
+
+    use super::{ShadowMap, ShadowMultiset, ShadowSet};
+
+    proptest! {
+        #[test]
+        fn test_shadow_set(ops in vec((0..6u8, 0..32u16), 0..300)) {
+            let mut set = ShadowSet::<u16, SmallRng, 8>::new();
+            for (op, value) in ops {
+                match op {
+                    0 | 1 => {
+                        let _ = set.insert(value);
+                    }
+                    2 => {
+                        set.remove(&value);
+                    }
+                    3 => {
+                        set.remove_lazily(&value);
+                    }
+                    4 => {
+                        set.pop_first();
+                    }
+                    _ => {
+                        set.pop_last();
+                    }
+                }
+                set.contains(&value);
+                let _ = (set.first(), set.last());
+            }
+        }
+
+        #[test]
+        fn test_shadow_multiset(ops in vec((0..6u8, 0..8u16), 0..300)) {
+            let mut multiset = ShadowMultiset::<u16, SmallRng, 8>::new();
+            for (op, value) in ops {
+                match op {
+                    0 | 1 => multiset.insert(value),
+                    2 => {
+                        multiset.remove(&value);
+                    }
+                    3 => {
+                        multiset.remove_lazily(&value);
+                    }
+                    4 => {
+                        multiset.pop_first();
+                    }
+                    _ => {
+                        multiset.pop_last();
+                    }
+                }
+                multiset.contains(&value);
+                let _ = (multiset.first(), multiset.last());
+            }
+        }
+
+        #[test]
+        fn test_shadow_map(ops in vec((0..6u8, 0..32u16, any::<u8>()), 0..300)) {
+            let mut map = ShadowMap::<u16, u8, SmallRng, 8>::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 | 1 => {
+                        map.insert(key, value);
+                    }
+                    2 => {
+                        map.remove(&key);
+                    }
+                    3 => {
+                        map.remove_lazily(&key);
+                    }
+                    4 => {
+                        map.pop_first();
+                    }
+                    _ => {
+                        map.pop_last();
+                    }
+                }
+                map.get(&key);
+                let _ = (map.first_key_value(), map.last_key_value());
+            }
+        }
+    }
+}
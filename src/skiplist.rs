@@ -1,46 +1,423 @@
-use std::{borrow::Borrow, fmt, mem::ManuallyDrop};
+use std::{
+    borrow::Borrow, collections::BTreeSet, fmt, hash::Hash, marker::PhantomData, mem,
+    ops::RangeBounds,
+};
 
 use rand::{Rng, SeedableRng, rngs::SmallRng};
 
-use crate::NonEmptyStorage;
+use crate::{
+    FixedRng, NonEmptyStorage, Pool, Spare,
+    bulk::Builder,
+    descending::DescendingSkipList,
+    hashed_height,
+    iter::{Drain, ExtractIf, Iter, Range},
+    memory::HeapSize,
+    order::{self, ComparisonPanicked},
+    policy::{self, AllowDuplicates, DuplicatePolicy, OnEqual},
+    raw::RawKey,
+    sorted,
+};
 
 /// A skiplist.
-pub struct SkipList<T, R, const N: usize>(Option<NonEmptyStorage<T, R, N>>)
+///
+/// Removing a value keeps its node for reuse by a later insertion, until
+/// [`SkipList::shrink_pool`] is called or the skiplist becomes empty.
+///
+/// Nodes link into upto `N` levels, which must be between 1 and 64, and heights are drawn from the
+/// random number generator `R`. Both have defaults that suit most uses, so that a skiplist of any
+/// type can be written as `SkipList<T>`, and [`levels_for`](crate::levels_for) picks a smaller `N`
+/// for a skiplist of known size.
+///
+/// Any other number of levels fails to compile when the skiplist is created:
+///
+/// ```compile_fail
+/// let skiplist = skipidy::SkipList::<u8, rand::rngs::SmallRng, 0>::new();
+/// ```
+///
+/// ```compile_fail
+/// let skiplist = skipidy::SkipList::<u8, rand::rngs::SmallRng, 65>::new();
+/// ```
+///
+/// Equal values are all kept by default, and the [policy](crate::policy) `P` makes insertions
+/// replace or reject them instead, which is what [`SkipList::insert`] then returns.
+pub struct SkipList<T, R = SmallRng, const N: usize = 32, P = AllowDuplicates>
 where
-    R: Rng;
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// The nodes, or nothing while the skiplist is empty.
+    storage: Option<NonEmptyStorage<T, R, N>>,
+    /// The random number generator kept while the skiplist is empty, which gets handed to the
+    /// storage on the next insertion.
+    rng: Spare<R>,
+    /// The nodes reserved while the skiplist is empty, which get handed to the storage on the next
+    /// insertion along with the generator.
+    pool: Pool<T, N>,
+    policy: PhantomData<P>,
+}
 
-impl<T, const N: usize> Default for SkipList<T, SmallRng, N> {
+impl<T, R, const N: usize, P> Default for SkipList<T, R, N, P>
+where
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
     fn default() -> Self {
-        Self::new()
+        Self::with_lazy_rng()
     }
 }
 
-impl<T, R, const N: usize> fmt::Debug for SkipList<T, R, N>
+impl<T, R, const N: usize, P> fmt::Debug for SkipList<T, R, N, P>
 where
     T: fmt::Debug,
     R: Rng,
+    P: DuplicatePolicy,
 {
+    /// Formats the values in order like a list, or with `{:#?}`, the structure of the skiplist,
+    /// where nodes are referred to by their positions instead of their addresses.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(storage) = &self.0 {
-            write!(f, "{storage:?}")
-        } else {
-            write!(f, "SkipList(None)")
+        if f.alternate() {
+            return crate::fmt_structure(self.storage.as_ref(), f, "SkipList");
+        }
+        f.debug_list()
+            .entries(self.storage.iter().flat_map(NonEmptyStorage::iter))
+            .finish()
+    }
+}
+
+impl<T, R, const N: usize, P> fmt::Display for SkipList<T, R, N, P>
+where
+    T: fmt::Display,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Formats the values in order within brackets, like `[1, 2, 3]`. Formatting options such as
+    /// the precision apply to every value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, value) in self.storage.iter().flat_map(NonEmptyStorage::iter).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            value.fmt(f)?;
         }
+        write!(f, "]")
     }
 }
 
-impl<T, const N: usize> SkipList<T, SmallRng, N> {
+impl<T, const N: usize, P> SkipList<T, SmallRng, N, P>
+where
+    P: DuplicatePolicy,
+{
     /// Creates an empty skiplist.
     #[must_use]
     pub const fn new() -> Self {
-        Self(None)
+        Self::with_lazy_rng()
+    }
+
+    /// Creates an empty skiplist holding the nodes of at least the given number of values, so that
+    /// inserting them makes no allocator calls.
+    ///
+    /// The number of levels `N` stays as given, and [`levels_for`](crate::levels_for) picks the
+    /// one that suits a skiplist of known size.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut skiplist = Self::new();
+        skiplist.reserve(capacity);
+        skiplist
     }
 }
 
-impl<T, R, const N: usize> SkipList<T, R, N>
+impl<T, const N: usize, P> SkipList<T, SmallRng, N, P>
+where
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skiplist whose values are ordered from the greatest to the least, so that
+    /// [`DescendingSkipList::first`] and [`DescendingSkipList::pop_first`] return the greatest
+    /// value.
+    #[must_use]
+    pub const fn descending() -> DescendingSkipList<T, SmallRng, N, P> {
+        DescendingSkipList::new()
+    }
+}
+
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
+where
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skiplist whose random number generator is seeded from the given value, so
+    /// that the same operations always build the same structure.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(R::seed_from_u64(seed))
+    }
+
+    /// Creates an empty skiplist whose random number generator only gets created on the first
+    /// insertion, like [`SkipList::new`] does for the default one, so that a skiplist with any
+    /// generator can be created in a const context, e.g. for a static.
+    #[must_use]
+    pub const fn with_lazy_rng() -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            storage: None,
+            rng: Spare::lazy(),
+            pool: Pool::new(),
+            policy: PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize, P> Default for SkipList<T, FixedRng, N, P>
+where
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skiplist whose random number generator is seeded from [`FixedRng::SEED`].
+    fn default() -> Self {
+        Self::with_rng(FixedRng::default())
+    }
+}
+
+impl<T, const N: usize, P> SkipList<T, FixedRng, N, P>
+where
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skiplist whose random number generator is seeded from the given value
+    /// rather than from [`FixedRng::SEED`].
+    #[must_use]
+    pub fn new_seeded(seed: u64) -> Self {
+        Self::with_rng(FixedRng::with_seed(seed))
+    }
+}
+
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skiplist that draws the heights of its nodes from the given random number
+    /// generator.
+    pub const fn with_rng(rng: R) -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            storage: None,
+            rng: Spare::Ready(rng),
+            pool: Pool::new(),
+            policy: PhantomData,
+        }
+    }
+
+    /// Drops the storage, keeping its random number generator for the next insertion.
+    fn drop_storage(&mut self) {
+        if let Some(storage) = self.storage.take() {
+            self.rng = Spare::Ready(storage.into_rng());
+        }
+    }
+}
+
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns the number of nodes kept for reuse by later insertions.
+    #[must_use]
+    pub fn pool_len(&self) -> usize {
+        self.storage.as_ref().map_or(self.pool.len, |storage| storage.pool.len)
+    }
+
+    /// Allocates nodes for reuse by later insertions until at least the given number of values can
+    /// be inserted without allocating, so that a bulk load of known size makes no allocator calls
+    /// while it runs.
+    ///
+    /// An empty skiplist also allocates the head of its storage, which the first insertion takes
+    /// along with the nodes.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Some(storage) = &mut self.storage {
+            storage.pool.reserve(additional);
+        } else {
+            self.pool.reserve_head();
+            self.pool.reserve(additional);
+        }
+    }
+
+    /// Frees the nodes kept for reuse by later insertions.
+    pub fn shrink_pool(&mut self) {
+        self.shrink_pool_to(0);
+    }
+
+    /// Frees the nodes kept for reuse by later insertions, beyond the given number.
+    pub fn shrink_pool_to(&mut self, len: usize) {
+        match &mut self.storage {
+            Some(storage) => storage.pool.shrink_to(len),
+            None => self.pool.shrink_to(len),
+        }
+    }
+
+    /// Returns the number of nodes linked into the skiplist, including those of lazily removed
+    /// values.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.storage.as_ref().map_or(0, |storage| storage.len)
+    }
+
+    /// Returns the number of bytes allocated for the nodes of the skiplist, including the nodes kept
+    /// for reuse, but excluding the memory that values own on the heap.
+    #[must_use]
+    pub fn allocated_bytes(&self) -> usize {
+        self.storage
+            .as_ref()
+            .map_or_else(|| self.pool.allocated_bytes(), NonEmptyStorage::allocated_bytes)
+    }
+
+    /// Returns the number of bytes allocated for the nodes of the skiplist, along with the memory
+    /// that values own on the heap.
+    #[must_use]
+    pub fn memory_usage(&self) -> usize
+    where
+        T: HeapSize,
+    {
+        self.storage.as_ref().map_or_else(|| self.pool.allocated_bytes(), |storage| {
+            storage.allocated_bytes() + storage.heap_size(T::heap_size)
+        })
+    }
+
+    /// Rebuilds the towers of the skiplist in place, so that every other node of a level is also
+    /// linked into the level above, and frees the nodes kept for reuse by later insertions.
+    ///
+    /// Heights drift away from their expected distribution after many insertions and removals,
+    /// which lengthens the search paths until the skiplist gets compacted.
+    pub fn compact(&mut self) {
+        let Some(storage) = &mut self.storage else {
+            return;
+        };
+        storage.compact();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+    }
+
+    /// Unlinks the nodes of the values removed by [`SkipList::remove_lazily`], dropping the values.
+    pub fn vacuum(&mut self) {
+        let Some(storage) = &mut self.storage else {
+            return;
+        };
+        storage.vacuum();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+    }
+
+    /// Returns an iterator over the values, in ascending order, which skips the values removed by
+    /// [`SkipList::remove_lazily`].
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter(self.storage.as_ref().map_or_else(crate::Iter::empty, NonEmptyStorage::iter))
+    }
+
+    /// Removes every value, returning an iterator that moves them out in ascending order.
+    ///
+    /// The skiplist is left empty as soon as this returns, even if the iterator is dropped early,
+    /// and keeps its random number generator for later insertions.
+    pub fn drain(&mut self) -> Drain<'_, T, N> {
+        let Some(storage) = self.storage.take() else {
+            return Drain(crate::IntoIter::empty(), PhantomData);
+        };
+        let (rng, values) = storage.into_values();
+        self.rng = Spare::Ready(rng);
+        Drain(values, PhantomData)
+    }
+
+    /// Returns an iterator that removes the values that match the predicate, in ascending order,
+    /// visiting every value once along the base level and unlinking the matching ones from every
+    /// level as it goes.
+    ///
+    /// Values are only removed as the iterator reaches them, so dropping it early keeps the values
+    /// after the last one it returned. Lazily removed values that it passes get unlinked too.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, T, R, N, F>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        ExtractIf(crate::ExtractIf::new(&mut self.storage, &mut self.rng), pred)
+    }
+
+    /// Keeps only the values that match the predicate, in ascending order. Every value is visited
+    /// once along the base level, and the others get unlinked from every level as they're reached,
+    /// dropping the levels that no longer hold a node.
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.extract_if(|value| !pred(value)).for_each(drop);
+    }
+
+    /// Returns the least value.
+    #[must_use]
+    pub fn first(&self) -> Option<&T> {
+        self.storage.as_ref()?.first()
+    }
+
+    /// Returns the greatest value, without traversing the skiplist, only stepping back over the
+    /// values removed lazily.
+    #[must_use]
+    pub fn last(&self) -> Option<&T> {
+        self.storage.as_ref()?.last()
+    }
+
+    /// Removes the least value, returning it if the skiplist isn't empty.
+    ///
+    /// Only the links of the head get updated, so this takes constant time on average, which
+    /// suits skiplists used as priority queues.
+    pub fn pop_first(&mut self) -> Option<T> {
+        let storage = self.storage.as_mut()?;
+        let value = storage.pop_first();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        value
+    }
+
+    /// Removes the greatest value, returning it if the skiplist isn't empty.
+    pub fn pop_last(&mut self) -> Option<T> {
+        let storage = self.storage.as_mut()?;
+        let value = storage.pop_last();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        value
+    }
+}
+
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Creates a skiplist holding the values added to the builder.
+    pub(crate) fn from_builder(builder: Builder<T, R, N>) -> Self
+    where
+        T: Ord,
+    {
+        match builder.finish() {
+            Ok(storage) => Self {
+                storage: Some(storage),
+                rng: Spare::Lent,
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+            Err(rng) => Self {
+                storage: None,
+                rng: Spare::Ready(rng),
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+        }
+    }
+}
+
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
 where
     T: Ord,
     R: Rng,
+    P: DuplicatePolicy,
 {
     /// Returns whether a value exists in the skiplist.
     pub fn contains<U>(&self, value: &U) -> bool
@@ -48,47 +425,832 @@ where
         T: Borrow<U>,
         U: Ord + ?Sized,
     {
-        let Some(storage) = &self.0 else {
+        let Some(storage) = &self.storage else {
             return false;
         };
         storage.get(value).is_some()
     }
 
-    /// Inserts a value into the skiplist.
-    pub fn insert(&mut self, value: T)
+    /// Returns an iterator over the values within the range, in ascending order, whose bounds can
+    /// be of a borrowed form of the values. Both ends of the range are found by descending the
+    /// levels from the head, so that only the values within the range get visited.
+    ///
+    /// A range whose start is greater than its end holds no value.
+    pub fn range<U, B>(&self, range: B) -> Range<'_, T, N>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+        B: RangeBounds<U>,
+    {
+        let Some(storage) = &self.storage else {
+            return Range(crate::Iter::empty());
+        };
+        Range(storage.range(&range))
+    }
+
+    /// Returns whether a value of a primitive type exists in the skiplist, comparing values with
+    /// fewer branches than [`SkipList::contains`] does.
+    pub fn contains_raw(&self, value: T) -> bool
+    where
+        T: RawKey,
+    {
+        let Some(storage) = &self.storage else {
+            return false;
+        };
+        storage.find_raw(value).is_some()
+    }
+
+    /// Inserts a value into the skiplist. Values that aren't less than any other value get linked
+    /// at the end without a traversal.
+    ///
+    /// An equal value is kept, replaced, or rejected depending on the policy, which determines what
+    /// this returns: nothing, the replaced value, or the rejected value as an error.
+    pub fn insert(&mut self, value: T) -> P::Outcome<T> {
+        P::outcome(self.insert_with_height(value, None))
+    }
+
+    /// Inserts a value into the skiplist, linking its node into a number of levels derived from
+    /// the hash of the value instead of a random one.
+    ///
+    /// A skiplist whose values are all inserted this way has the same structure whatever the order
+    /// of insertion, and on every run of the same build, which makes it reproducible for
+    /// differential testing and across replicas. Heights aren't capped by the number of values, so
+    /// that they only depend on the values.
+    pub fn insert_hashed(&mut self, value: T) -> P::Outcome<T>
+    where
+        T: Hash,
+    {
+        let height = hashed_height::<_, N>(&value);
+        P::outcome(self.insert_with_height(value, Some(height)))
+    }
+
+    /// Inserts a value as the policy decides, linking a new node into the given number of levels,
+    /// or a random one if there's none, and returns the equal value that was replaced or rejected.
+    pub(crate) fn insert_with_height(&mut self, value: T, height: Option<usize>) -> Option<T> {
+        let Some(storage) = &mut self.storage else {
+            let rng = self.rng.take();
+            let pool = mem::take(&mut self.pool);
+            self.storage = Some(match height {
+                Some(height) => NonEmptyStorage::with_height(value, height, rng, pool),
+                None => NonEmptyStorage::new(value, rng, pool),
+            });
+            return None;
+        };
+        storage.insert_on_equal(value, height, policy::on_equal::<P>())
+    }
+
+    /// Appends a value that isn't less than any other value of the skiplist.
+    ///
+    /// The value gets linked after the last node of every level without a traversal, which suits
+    /// values arriving in order, such as timestamps. [`SkipList::insert`] takes the same path for
+    /// such values, but inserts out of order values instead of rejecting them. Lazily removed
+    /// values don't count, so a value less than some of them still gets inserted in order.
+    ///
+    /// # Errors
+    ///
+    /// Gives the value back if it's less than the greatest value of the skiplist, or equal to it
+    /// while the policy doesn't keep equal values.
+    pub fn push_max(&mut self, value: T) -> Result<(), T> {
+        let Some(storage) = &mut self.storage else {
+            let pool = mem::take(&mut self.pool);
+            self.storage = Some(NonEmptyStorage::new(value, self.rng.take(), pool));
+            return Ok(());
+        };
+        let on_equal = policy::on_equal::<P>();
+        let keep = on_equal == OnEqual::Keep;
+        if storage.tail_value() < &value || (keep && storage.tail_value() == &value) {
+            storage.append(value, None);
+            return Ok(());
+        }
+        // The last nodes might hold lazily removed values, which the value can still go before.
+        if storage.last().is_some_and(|last| last > &value || (!keep && last == &value)) {
+            return Err(value);
+        }
+        storage.insert_on_equal(value, None, on_equal);
+        Ok(())
+    }
+
+    /// Removes a value from the skiplist, returning it if it exists.
+    pub fn remove<U>(&mut self, value: &U) -> Option<T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let storage = self.storage.as_mut()?;
+        let value = storage.remove(value);
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        value
+    }
+
+    /// Marks a value as removed, returning whether it exists.
+    ///
+    /// The value is skipped by lookups and iterations from then on, but its node stays linked, so
+    /// removals in a delete-heavy phase only pay for a search. Nodes get unlinked in a single pass
+    /// by [`SkipList::vacuum`], or once most of the nodes hold removed values.
+    pub fn remove_lazily<U>(&mut self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let Some(storage) = &mut self.storage else {
+            return false;
+        };
+        let removed = storage.remove_lazily(value);
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        removed
+    }
+
+    /// Returns whether a value exists in the skiplist, like [`SkipList::contains`], but catches a
+    /// panic of the comparisons.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    pub fn try_contains<U>(&self, value: &U) -> Result<bool, ComparisonPanicked>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        order::catch(|| self.contains(value))
+    }
+
+    /// Inserts a value into the skiplist, like [`SkipList::insert`], but catches a panic of the
+    /// comparisons. The skiplist is left as it was before the insertion, and the value is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    pub fn try_insert(&mut self, value: T) -> Result<P::Outcome<T>, ComparisonPanicked> {
+        order::catch(|| self.insert(value))
+    }
+
+    /// Removes a value from the skiplist, like [`SkipList::remove`], but catches a panic of the
+    /// comparisons. The skiplist then holds the same values as before the removal.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing values panicked.
+    pub fn try_remove<U>(&mut self, value: &U) -> Result<Option<T>, ComparisonPanicked>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let removed = order::catch(|| self.remove(value));
+        // Lazily removed values might have been unlinked before the panic.
+        if self.storage.as_ref().is_some_and(NonEmptyStorage::is_empty) {
+            self.drop_storage();
+        }
+        removed
+    }
+
+    /// Checks the structure of the skiplist, which is meant for tests of code building on the
+    /// skiplist, and for narrowing down a corruption, e.g. due to values whose order isn't total.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values aren't in order, or strictly so while the policy doesn't keep equal
+    /// values, if a level links a node that the level below doesn't, or if a counter of the
+    /// skiplist doesn't match its nodes.
+    #[cfg(any(test, feature = "invariants"))]
+    pub fn assert_invariants(&self) {
+        let lent = matches!(self.rng, Spare::Lent);
+        assert_eq!(
+            self.storage.is_some(),
+            lent,
+            "the random number generator is misplaced"
+        );
+        if let Some(storage) = &self.storage {
+            storage.assert_invariants(policy::on_equal::<P>() != OnEqual::Keep);
+        }
+    }
+}
+
+impl<'a, T, R, const N: usize, P> IntoIterator for &'a SkipList<T, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, R, const N: usize, P> PartialEq<BTreeSet<T>> for SkipList<T, R, N, P>
+where
+    T: PartialEq,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns whether the skiplist holds the same values as the set, so equal values in the
+    /// skiplist make them differ.
+    fn eq(&self, other: &BTreeSet<T>) -> bool {
+        self.storage.iter().flat_map(NonEmptyStorage::iter).eq(other)
+    }
+}
+
+impl<T, R, const N: usize, P> PartialEq<&[T]> for SkipList<T, R, N, P>
+where
+    T: PartialEq,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns whether the skiplist holds the values of the slice, in the same order.
+    fn eq(&self, other: &&[T]) -> bool {
+        self.storage.iter().flat_map(NonEmptyStorage::iter).eq(*other)
+    }
+}
+
+impl<T, R, const N: usize, P> sorted::SortedSet<T> for SkipList<T, R, N, P>
+where
+    T: Ord,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    fn get(&self, value: &T) -> Option<&T> {
+        self.storage.as_ref()?.get(value)
+    }
+
+    fn insert(&mut self, value: T) {
+        let _ = Self::insert(self, value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        Self::remove(self, value)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        Self::iter(self)
+    }
+
+    fn range<'a, B>(&'a self, range: B) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+        B: RangeBounds<T>,
+    {
+        Self::range(self, range)
+    }
+}
+
+impl<T, R, const N: usize, P> From<Vec<T>> for SkipList<T, R, N, P>
+where
+    T: Ord,
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    /// Creates a skiplist by sorting the values, then linking them in order. Only the last of
+    /// equal values is kept if the policy replaces them, and only the first if it rejects them.
+    fn from(mut values: Vec<T>) -> Self {
+        let on_equal = policy::on_equal::<P>();
+        if on_equal == OnEqual::Replace {
+            values.reverse();
+        }
+        // The sort is stable, so the kept value comes first among equal ones.
+        values.sort();
+        if on_equal != OnEqual::Keep {
+            values.dedup();
+        }
+        let mut builder = Builder::new(false);
+        for value in values {
+            // Sorted values are always accepted.
+            let _ = builder.push(value);
+        }
+        Self::from_builder(builder)
+    }
+}
+
+impl<T, R, const N: usize, P> From<BTreeSet<T>> for SkipList<T, R, N, P>
+where
+    T: Ord,
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    /// Creates a skiplist by linking the values of the set in order.
+    fn from(values: BTreeSet<T>) -> Self {
+        let mut builder = Builder::new(true);
+        for value in values {
+            // Values of a set are always sorted and unique.
+            let _ = builder.push(value);
+        }
+        Self::from_builder(builder)
+    }
+}
+
+impl<T, R, const N: usize, P> From<SkipList<T, R, N, P>> for Vec<T>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Moves the values out of the skiplist, in order.
+    fn from(skiplist: SkipList<T, R, N, P>) -> Self {
+        skiplist.storage.map_or_else(Self::new, NonEmptyStorage::into_vec)
+    }
+}
+
+impl<T, R, const N: usize, P> From<SkipList<T, R, N, P>> for BTreeSet<T>
+where
+    T: Ord,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Moves the values out of the skiplist, keeping a single one of equal values.
+    fn from(skiplist: SkipList<T, R, N, P>) -> Self {
+        Vec::from(skiplist).into_iter().collect()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, R, const N: usize> SkipList<T, R, N>
+where
+    T: Send,
+    R: Rng + SeedableRng + Send,
+{
+    /// Creates a skiplist from chunks of values that are sorted, both within every chunk and from
+    /// one chunk to the next.
+    ///
+    /// The chunks are turned into nodes in parallel, then stitched together using a number of
+    /// changes per chunk bounded by the number of levels. Lookups might miss values if the values
+    /// aren't sorted.
+    #[must_use]
+    pub fn from_sorted_chunks_parallel<I, C>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: IntoIterator<Item = T> + Send,
+    {
+        match NonEmptyStorage::from_sorted_chunks(chunks) {
+            Ok(storage) => Self {
+                storage: Some(storage),
+                rng: Spare::Lent,
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+            Err(rng) => Self {
+                storage: None,
+                rng: Spare::Ready(rng),
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
+where
+    T: Send,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Drops the skiplist, freeing its nodes on the threads of rayon's global pool.
+    ///
+    /// The base level gets split into chunks at the nodes of an upper level, which are freed in
+    /// parallel, so that dropping a very large skiplist doesn't take a single thread chasing every
+    /// pointer. Small skiplists are dropped as usual.
+    pub fn drop_parallel(self) {
+        if let Some(storage) = self.storage {
+            storage.drop_parallel();
+        }
+    }
+}
+
+#[cfg(feature = "dot")]
+impl<T, R, const N: usize, P> SkipList<T, R, N, P>
+where
+    T: fmt::Display,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns the structure of the skiplist as a graph in the DOT language of Graphviz, where
+    /// every node is drawn as a tower of its levels below its value, and every link as an edge.
+    ///
+    /// Nodes of lazily removed values are dashed. The graph can be rendered with `dot -Tsvg`, e.g.
+    /// to see how evenly the heights of the nodes are spread.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        crate::dot::to_dot(self.storage.as_ref(), T::to_string)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, R, const N: usize, P> serde::Serialize for SkipList<T, R, N, P>
+where
+    T: serde::Serialize,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        R: SeedableRng,
+        S: serde::Serializer,
     {
-        let Some(storage) = &mut self.0 else {
-            self.0 = Some(NonEmptyStorage::new(value));
-            return;
-        };
-        storage.insert(value);
+        serializer.collect_seq(self.storage.iter().flat_map(NonEmptyStorage::iter))
     }
+}
 
-    /// Removes a value from the skiplist, returning it if it exists.
-    pub fn remove<U>(&mut self, value: &U) -> Option<T>
+#[cfg(feature = "serde")]
+impl<'de, T, R, const N: usize, P> serde::Deserialize<'de> for SkipList<T, R, N, P>
+where
+    T: Ord + serde::Deserialize<'de>,
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        T: Borrow<U>,
-        U: Ord + ?Sized,
+        D: serde::Deserializer<'de>,
     {
-        let storage = self.0.take()?;
-        let (storage, value) = NonEmptyStorage::remove(ManuallyDrop::new(storage), value);
-        self.0 = storage;
-        value
+        struct Visitor<T, R, const N: usize, P>(PhantomData<(T, R, P)>);
+
+        impl<'de, T, R, const N: usize, P> serde::de::Visitor<'de> for Visitor<T, R, N, P>
+        where
+            T: Ord + serde::Deserialize<'de>,
+            R: Rng + SeedableRng,
+            P: DuplicatePolicy,
+        {
+            type Value = SkipList<T, R, N, P>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a sequence")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                // Serialized skiplists are sorted, so values are linked in order until one isn't,
+                // or until one is equal while the policy doesn't keep equal values.
+                let mut builder = Builder::new(policy::on_equal::<P>() != OnEqual::Keep);
+                while let Some(value) = seq.next_element()? {
+                    if let Err(value) = builder.push(value) {
+                        let mut skiplist = SkipList::from_builder(builder);
+                        let _ = skiplist.insert(value);
+                        while let Some(value) = seq.next_element()? {
+                            let _ = skiplist.insert(value);
+                        }
+                        return Ok(skiplist);
+                    }
+                }
+                Ok(SkipList::from_builder(builder))
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor(PhantomData))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        ops::{Bound, RangeBounds},
+        thread,
+    };
+
     use proptest::{collection::vec, prelude::*};
+    use rand::{SeedableRng, rngs::SmallRng};
 
     use super::SkipList;
+    use crate::{
+        DeterministicSkipList, Spare,
+        order::{ByKey, KeyExtract, OrderedF64},
+        policy::{OverwriteOnEqual, RejectOnEqual},
+    };
+
+    /// A generator that can't be seeded, which steps along a xorshift sequence.
+    struct Xorshift(u64);
+
+    impl rand::RngCore for Xorshift {
+        fn next_u32(&mut self) -> u32 {
+            (self.next_u64() >> 32) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dst: &mut [u8]) {
+            rand::rand_core::impls::fill_bytes_via_next(self, dst);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_eq(items in vec(0..64u32, 0..100), other in vec(0..64u32, 0..100)) {
+            let skiplist = SkipList::<u32, SmallRng, 8>::from(items.clone());
+            let mut sorted = items.clone();
+            sorted.sort_unstable();
+            let mut other_sorted = other.clone();
+            other_sorted.sort_unstable();
+            assert_eq!(skiplist == other_sorted.as_slice(), sorted == other_sorted);
+            let set: BTreeSet<_> = items.iter().copied().collect();
+            assert_eq!(skiplist == set, set.len() == items.len());
+            let other: BTreeSet<_> = other.into_iter().collect();
+            assert_eq!(skiplist == other, sorted.iter().eq(&other));
+        }
+
+        #[test]
+        fn test_range_duplicates(items in vec(0..16u8, 0..100), start in 0..16u8, end in 0..16u8) {
+            let skiplist = SkipList::<u8, SmallRng, 8>::from(items.clone());
+            let mut sorted = items;
+            sorted.sort_unstable();
+            let excluded = (Bound::Excluded(start), Bound::Included(end));
+            let expected: Vec<_> = sorted.iter().filter(|x| excluded.contains(x)).collect();
+            assert_eq!(skiplist.range(excluded).collect::<Vec<_>>(), expected);
+            let expected: Vec<_> = sorted.iter().filter(|x| (start..end).contains(x)).collect();
+            assert_eq!(skiplist.range(start..end).collect::<Vec<_>>(), expected);
+        }
+
+        #[test]
+        fn test_conversions(items in vec(0..64u32, 0..100)) {
+            let mut sorted = items.clone();
+            sorted.sort_unstable();
+            let skiplist = SkipList::<u32, SmallRng, 8>::from(items.clone());
+            assert!(items.iter().all(|item| skiplist.contains(item)));
+            assert_eq!(skiplist, sorted.as_slice());
+            assert_eq!(Vec::from(skiplist), sorted);
+            let set: BTreeSet<_> = items.into_iter().collect();
+            let skiplist = SkipList::<u32, SmallRng, 8>::from(set.clone());
+            assert!(set.iter().all(|item| skiplist.contains(item)));
+            assert_eq!(skiplist, set);
+            assert_eq!(BTreeSet::from(skiplist), set);
+        }
+
+        #[test]
+        fn test_iter(items in vec(0..64u32, 0..100), removed in vec(0..64u32, 0..20)) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            assert_eq!(skiplist.iter().next(), None);
+            let mut sorted = items.clone();
+            for item in items {
+                skiplist.insert(item);
+            }
+            for item in &removed {
+                if let Some(position) = sorted.iter().position(|x| x == item) {
+                    sorted.swap_remove(position);
+                }
+                skiplist.remove_lazily(item);
+            }
+            sorted.sort_unstable();
+            assert!(skiplist.iter().eq(&sorted));
+            assert_eq!(format!("{:?}", skiplist.iter()), format!("{sorted:?}"));
+            let mut iterated = Vec::new();
+            for value in &skiplist {
+                iterated.push(*value);
+            }
+            assert_eq!(iterated, sorted);
+            assert!(skiplist.iter().rev().eq(sorted.iter().rev()));
+            // Both ends stop where they meet.
+            let mut iter = skiplist.iter();
+            let mut ends = Vec::new();
+            while let Some(value) = iter.next() {
+                ends.push(value);
+                ends.extend(iter.next_back());
+            }
+            assert_eq!(ends.len(), sorted.len());
+            skiplist.vacuum();
+            skiplist.assert_invariants();
+            assert!(skiplist.iter().rev().eq(sorted.iter().rev()));
+        }
+
+        #[test]
+        fn test_pool(items in vec(".*", 1..100), removed in 0..100usize, len in 0..100usize) {
+            let removed = removed % items.len();
+            let mut skiplist = SkipList::<String, SmallRng, 8>::from(items.clone());
+            for item in &items[..removed] {
+                assert_eq!(skiplist.remove(item).as_ref(), Some(item));
+            }
+            assert_eq!(skiplist.pool_len(), removed);
+            for item in &items[..removed / 2] {
+                skiplist.insert(item.clone());
+            }
+            assert_eq!(skiplist.pool_len(), removed - removed / 2);
+            skiplist.shrink_pool_to(len);
+            assert_eq!(skiplist.pool_len(), (removed - removed / 2).min(len));
+            let mut expected = [&items[..removed / 2], &items[removed..]].concat();
+            expected.sort_unstable();
+            assert_eq!(skiplist, expected.as_slice());
+            skiplist.shrink_pool();
+            assert_eq!(skiplist.pool_len(), 0);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_first_last(ops in vec((0..4u8, 0..64u32), 0..200)) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            let mut sorted = Vec::new();
+            for (op, value) in ops {
+                match op {
+                    0 => {
+                        skiplist.insert(value);
+                        sorted.insert(sorted.partition_point(|v| *v < value), value);
+                    }
+                    1 => {
+                        let removed = sorted.binary_search(&value).ok().map(|i| sorted.remove(i));
+                        assert_eq!(skiplist.remove(&value), removed);
+                    }
+                    2 => {
+                        let popped = (!sorted.is_empty()).then(|| sorted.remove(0));
+                        assert_eq!(skiplist.pop_first(), popped);
+                    }
+                    _ => assert_eq!(skiplist.pop_last(), sorted.pop()),
+                }
+                assert_eq!(skiplist.first(), sorted.first());
+                assert_eq!(skiplist.last(), sorted.last());
+            }
+            assert_eq!(skiplist, sorted.as_slice());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_push_max(items in vec(0..64u32, 0..100), removed in vec(0..64u32, 0..20)) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            let mut sorted = Vec::new();
+            for item in items {
+                if sorted.last().is_none_or(|last| *last <= item) {
+                    sorted.push(item);
+                    assert_eq!(skiplist.push_max(item), Ok(()));
+                } else {
+                    assert_eq!(skiplist.push_max(item), Err(item));
+                }
+            }
+            for item in removed {
+                let index = sorted.binary_search(&item).ok();
+                assert_eq!(skiplist.remove(&item), index.map(|i| sorted.remove(i)));
+            }
+            assert_eq!(skiplist, sorted.as_slice());
+            // Appending after removals, or after popping the last values.
+            skiplist.pop_last();
+            sorted.pop();
+            for item in 64..128 {
+                skiplist.insert(item);
+                sorted.push(item);
+            }
+            assert_eq!(skiplist, sorted.as_slice());
+            for item in &sorted {
+                assert!(skiplist.contains(item));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_memory(items in vec(".*", 1..100), removed in 0..100usize) {
+            let mut skiplist = SkipList::<String, SmallRng, 8>::new();
+            assert_eq!(skiplist.allocated_bytes(), 0);
+            for item in &items {
+                skiplist.insert(item.clone());
+            }
+            let removed = removed % items.len();
+            for item in &items[..removed] {
+                skiplist.remove(item);
+            }
+            let node = size_of::<crate::SkipNode<String, 8>>();
+            let allocated = size_of::<crate::Links<String, 8>>() + items.len() * node;
+            assert_eq!(skiplist.node_count(), items.len() - removed);
+            assert_eq!(skiplist.allocated_bytes(), allocated);
+            let heap: usize = skiplist.iter().map(String::capacity).sum();
+            assert_eq!(skiplist.memory_usage(), allocated + heap);
+            skiplist.shrink_pool();
+            assert_eq!(skiplist.allocated_bytes(), allocated - removed * node);
+        }
+    }
+
+    #[test]
+    fn test_push_max_past_removed() {
+        let mut skiplist = SkipList::<u32, SmallRng, 8, RejectOnEqual>::new();
+        for value in 0..8 {
+            assert_eq!(skiplist.push_max(value), Ok(()));
+        }
+        assert!(skiplist.remove_lazily(&7));
+        assert!(skiplist.remove_lazily(&6));
+        // The lazily removed values don't count as the greatest one.
+        assert_eq!(skiplist.push_max(6), Ok(()));
+        assert_eq!(skiplist.push_max(6), Err(6));
+        assert_eq!(skiplist.push_max(5), Err(5));
+        skiplist.assert_invariants();
+        assert!(skiplist.iter().copied().eq(0..7));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut skiplist = SkipList::<u32, SmallRng, 8>::with_capacity(100);
+        assert_eq!(skiplist.pool_len(), 100);
+        for value in (0..100).rev() {
+            skiplist.insert(value);
+        }
+        assert_eq!(skiplist.pool_len(), 0);
+        skiplist.assert_invariants();
+        assert!(skiplist.iter().copied().eq(0..100));
+        // The reserved nodes and head get freed without a value ever being inserted.
+        let mut skiplist = SkipList::<u32, SmallRng, 8>::with_capacity(10);
+        skiplist.shrink_pool_to(5);
+        assert_eq!(skiplist.pool_len(), 5);
+        skiplist.shrink_pool();
+        assert_eq!(skiplist.allocated_bytes(), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn test_reserve(items in vec(any::<u32>(), 1..100), additional in 0..100usize) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            skiplist.reserve(additional);
+            // An empty skiplist keeps the nodes for its first insertions.
+            assert_eq!(skiplist.pool_len(), additional);
+            skiplist.insert(items[0]);
+            assert_eq!(skiplist.pool_len(), additional.saturating_sub(1));
+            skiplist.reserve(additional);
+            assert_eq!(skiplist.pool_len(), additional);
+            let allocated = skiplist.allocated_bytes();
+            for item in &items[1..] {
+                skiplist.insert(*item);
+            }
+            let reused = additional.min(items.len() - 1);
+            assert_eq!(skiplist.pool_len(), additional - reused);
+            let node = size_of::<crate::SkipNode<u32, 8>>();
+            assert_eq!(skiplist.allocated_bytes(), allocated + (items.len() - 1 - reused) * node);
+            let mut sorted = items;
+            sorted.sort_unstable();
+            assert_eq!(skiplist, sorted.as_slice());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_remove_lazily(ops in vec((0..5u8, 0..16u32), 0..200)) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            let mut sorted = Vec::new();
+            for (op, value) in ops {
+                match op {
+                    0 | 1 => {
+                        skiplist.insert(value);
+                        sorted.insert(sorted.partition_point(|v| *v < value), value);
+                    }
+                    2 => {
+                        let removed = sorted.binary_search(&value).ok().map(|i| sorted.remove(i));
+                        assert_eq!(skiplist.remove_lazily(&value), removed.is_some());
+                    }
+                    3 => {
+                        let removed = sorted.binary_search(&value).ok().map(|i| sorted.remove(i));
+                        assert_eq!(skiplist.remove(&value), removed);
+                    }
+                    _ => assert_eq!(skiplist.pop_last(), sorted.pop()),
+                }
+                assert_eq!(skiplist.contains(&value), sorted.contains(&value));
+                assert_eq!(skiplist.first(), sorted.first());
+                assert_eq!(skiplist.last(), sorted.last());
+                assert_eq!(skiplist, sorted.as_slice());
+            }
+            skiplist.vacuum();
+            if let Some(storage) = &skiplist.storage {
+                assert_eq!(storage.tombstones, 0);
+                assert_eq!(storage.len, sorted.len());
+            }
+            assert_eq!(skiplist.pop_first(), sorted.first().copied());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_compact(items in vec(0..64u32, 1..100), removed in 0..100usize) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            for item in &items {
+                skiplist.insert(*item);
+            }
+            for item in &items[..removed % items.len()] {
+                skiplist.remove(item);
+            }
+            let expected: Vec<_> = skiplist.iter().copied().collect();
+            skiplist.compact();
+            assert_eq!(skiplist.pool_len(), 0);
+            assert!(skiplist.iter().eq(&expected));
+            if let Some(storage) = &skiplist.storage {
+                for (position, (height, _)) in storage.towers().enumerate() {
+                    let ideal = (position + 1).trailing_zeros() as usize + 1;
+                    assert_eq!(height, ideal.min(crate::level_cap::<8>(expected.len())));
+                }
+            }
+            for item in 0..64 {
+                assert_eq!(skiplist.contains(&item), expected.contains(&item));
+            }
+            skiplist.insert(64);
+            assert_eq!(skiplist.iter().next_back(), Some(&64));
+        }
+    }
 
     proptest! {
-        #[cfg_attr(miri, ignore)]
         #[test]
-        fn test_insert_contains(items in vec(any::<usize>(), 1000)) {
+        fn test_insert_contains(items in vec(any::<usize>(), crate::TEST_SCALE)) {
             let mut skiplist = SkipList::<usize, _, 32>::new();
             for item in &items {
                 skiplist.insert(*item);
@@ -109,9 +1271,8 @@ mod tests {
             }
         }
 
-        #[cfg_attr(miri, ignore)]
         #[test]
-        fn test_insert_remove(items in vec(any::<usize>(), 1000)) {
+        fn test_insert_remove(items in vec(any::<usize>(), crate::TEST_SCALE)) {
             let mut skiplist = SkipList::<usize, _, 32>::new();
             for item in &items {
                 skiplist.insert(*item);
@@ -131,5 +1292,566 @@ mod tests {
                 assert!(skiplist.remove(item).is_some_and(|v| v == *item));
             }
         }
+
+        #[test]
+        fn test_insert_remove_invariants(ops in vec((0..3u8, 0..16u8), 0..200)) {
+            let mut allow = SkipList::<u8, SmallRng, 4>::new();
+            let mut reject = SkipList::<u8, SmallRng, 4, RejectOnEqual>::new();
+            for (op, value) in ops {
+                match op {
+                    0 => {
+                        allow.insert(value);
+                        let _ = reject.insert(value);
+                    }
+                    1 => {
+                        allow.remove_lazily(&value);
+                        reject.remove_lazily(&value);
+                    }
+                    _ => {
+                        allow.remove(&value);
+                        reject.remove(&value);
+                    }
+                }
+                allow.assert_invariants();
+                reject.assert_invariants();
+            }
+        }
+
+        #[cfg(feature = "rayon")]
+        #[test]
+        fn test_from_sorted_chunks_parallel(
+            mut items in vec(any::<usize>(), 0..1000),
+            size in 1..100usize,
+        ) {
+            items.sort_unstable();
+            let chunks: Vec<_> = items.chunks(size).map(<[usize]>::to_vec).collect();
+            let mut skiplist = SkipList::<usize, SmallRng, 32>::from_sorted_chunks_parallel(chunks);
+            for item in &items {
+                assert!(skiplist.contains(item));
+            }
+            for item in items.iter().rev() {
+                assert!(skiplist.remove(item).is_some_and(|v| v == *item));
+            }
+            assert!(items.iter().all(|item| !skiplist.contains(item)));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde(mut items in vec(any::<u64>(), 0..100)) {
+            let mut skiplist = SkipList::<u64, _, 8>::new();
+            for item in &items {
+                skiplist.insert(*item);
+            }
+            items.sort_unstable();
+            let json = serde_json::to_string(&skiplist).unwrap();
+            assert_eq!(json, serde_json::to_string(&items).unwrap());
+            let skiplist: SkipList<u64, SmallRng, 8> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&skiplist).unwrap(), json);
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde_unsorted(mut items in vec(0..16u64, 0..100)) {
+            let json = serde_json::to_string(&items).unwrap();
+            let skiplist: SkipList<u64, SmallRng, 8> = serde_json::from_str(&json).unwrap();
+            items.sort_unstable();
+            assert_eq!(serde_json::to_string(&skiplist).unwrap(), serde_json::to_string(&items).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_default_params() {
+        let mut skiplist = SkipList::<u64>::new();
+        skiplist.insert(1);
+        assert!(skiplist.contains(&1));
+        let skiplist: SkipList<u64> = SkipList::default();
+        assert_eq!(skiplist.first(), None);
+    }
+
+    #[test]
+    fn test_move_across_threads() {
+        let mut skiplist = SkipList::<String, _, 4>::new();
+        for i in 0..8 {
+            skiplist.insert(i.to_string());
+        }
+        let skiplist = thread::spawn(move || {
+            skiplist.remove("0");
+            skiplist
+        })
+        .join()
+        .unwrap();
+        assert!(!skiplist.contains("0"));
+        thread::scope(|s| {
+            s.spawn(|| assert!(skiplist.contains("7")));
+            s.spawn(|| assert!(skiplist.contains("1")));
+        });
+    }
+
+    proptest! {
+        #[test]
+        fn test_contains_raw(ops in vec((0..3u8, -8..8i64), 0..200)) {
+            let mut skiplist = SkipList::<i64, SmallRng, 8>::new();
+            for (op, value) in ops {
+                match op {
+                    0 => skiplist.insert(value),
+                    1 => drop(skiplist.remove_lazily(&value)),
+                    _ => drop(skiplist.remove(&value)),
+                }
+                for value in -8..8 {
+                    assert_eq!(skiplist.contains_raw(value), skiplist.contains(&value));
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    proptest! {
+        #[test]
+        fn test_drop_parallel(items in vec(any::<u16>(), 0..5000), removed in vec(any::<u16>(), 0..500)) {
+            use std::sync::Arc;
+
+            let value = Arc::new(());
+            let mut skiplist = SkipList::<(u16, Arc<()>), SmallRng, 16>::new();
+            for item in items {
+                skiplist.insert((item, Arc::clone(&value)));
+            }
+            for (i, item) in removed.into_iter().enumerate() {
+                let item = (item, Arc::clone(&value));
+                if i % 2 == 0 {
+                    skiplist.remove_lazily(&item);
+                } else {
+                    skiplist.remove(&item);
+                }
+            }
+            skiplist.drop_parallel();
+            assert_eq!(Arc::strong_count(&value), 1);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_insert_hashed(items in vec(any::<u32>(), 1..200), removed in 0..200usize) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::new();
+            for item in &items {
+                skiplist.insert_hashed(*item);
+            }
+            let mut sorted = items.clone();
+            sorted.sort_unstable();
+            let mut other = SkipList::<u32, SmallRng, 8>::new();
+            for item in sorted.iter().rev() {
+                other.insert_hashed(*item);
+            }
+            for item in &items[..removed % items.len()] {
+                skiplist.remove(item);
+                other.remove(item);
+            }
+            assert!(skiplist.iter().eq(other.iter()));
+            if let (Some(storage), Some(other)) = (&skiplist.storage, &other.storage) {
+                assert!(storage.towers().eq(other.towers()));
+                for (height, item) in storage.towers() {
+                    assert_eq!(height, crate::hashed_height::<_, 8>(item));
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_unseedable_rng(ops in vec((any::<bool>(), 0..32u32), 0..200)) {
+            let mut skiplist = SkipList::<u32, Xorshift, 8>::with_rng(Xorshift(0x2545_f491));
+            let mut sorted = Vec::new();
+            for (insert, value) in ops {
+                if insert {
+                    skiplist.insert(value);
+                    sorted.insert(sorted.partition_point(|v| *v < value), value);
+                } else {
+                    // Emptying the skiplist keeps its generator for the next insertion.
+                    let removed = sorted.binary_search(&value).ok().map(|i| sorted.remove(i));
+                    assert_eq!(skiplist.remove(&value), removed);
+                }
+            }
+            assert!(skiplist == sorted.as_slice());
+        }
+
+        #[test]
+        fn test_with_seed(seed in any::<u64>(), ops in vec((any::<bool>(), 0..32u32), 0..200)) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::with_seed(seed);
+            let mut other = SkipList::<u32, SmallRng, 8>::with_rng(SmallRng::seed_from_u64(seed));
+            for (insert, value) in ops {
+                if insert {
+                    skiplist.insert(value);
+                    other.insert(value);
+                } else {
+                    // Emptying the skiplists keeps their generators going.
+                    skiplist.remove(&value);
+                    other.remove(&value);
+                }
+                if let (Some(storage), Some(other)) = (&skiplist.storage, &other.storage) {
+                    assert!(storage.towers().eq(other.towers()));
+                } else {
+                    assert!(matches!(skiplist.rng, Spare::Ready(_)));
+                }
+            }
+        }
+
+        #[test]
+        fn test_new_seeded(seed in any::<u64>(), values in vec(any::<u32>(), 0..100)) {
+            let mut skiplist = DeterministicSkipList::new_seeded(seed);
+            let mut other = DeterministicSkipList::new_seeded(seed);
+            for value in values {
+                skiplist.insert(value);
+                other.insert(value);
+            }
+            if let (Some(storage), Some(other)) = (&skiplist.storage, &other.storage) {
+                assert!(storage.towers().eq(other.towers()));
+            }
+        }
+
+        #[test]
+        fn test_deterministic_default(seed in any::<u64>(), values in vec(any::<u32>(), 0..100)) {
+            // The seed of the thread doesn't reach the generator.
+            crate::seed_thread(seed);
+            let mut skiplist = DeterministicSkipList::default();
+            let mut other = DeterministicSkipList::new_seeded(crate::FixedRng::SEED);
+            for value in values {
+                skiplist.insert(value);
+                other.insert(value);
+            }
+            if let (Some(storage), Some(other)) = (&skiplist.storage, &other.storage) {
+                assert!(storage.towers().eq(other.towers()));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_descending(items in vec(0..64u32, 0..100), start in 0..64u32, end in 0..64u32) {
+            let mut skiplist = SkipList::<_, _, 8>::descending();
+            for item in &items {
+                skiplist.insert(*item);
+            }
+            let mut sorted = items.clone();
+            sorted.sort_unstable_by(|a, b| b.cmp(a));
+            assert!(skiplist.iter().eq(&sorted));
+            assert_eq!(skiplist.first(), sorted.first());
+            assert_eq!(skiplist.last(), sorted.last());
+            for item in 0..64 {
+                assert_eq!(skiplist.contains(&item), items.contains(&item));
+            }
+            let within = |item: &&u32| (start..=end).contains(*item);
+            assert!(skiplist.range(start..=end).eq(sorted.iter().filter(within)));
+            assert!(skiplist.range(..end).eq(sorted.iter().filter(|item| **item < end)));
+            let from_start = sorted.iter().rev().filter(|item| **item >= start);
+            assert!(skiplist.range(start..).rev().eq(from_start));
+            if let Some(item) = sorted.pop() {
+                assert_eq!(skiplist.remove(&item), Some(item));
+                assert_eq!(skiplist.pop_last(), sorted.pop());
+            }
+            for item in sorted {
+                assert_eq!(skiplist.pop_first(), Some(item));
+            }
+            assert_eq!(skiplist.pop_first(), None);
+        }
+    }
+
+    /// A value whose comparison panics when it's compared to an equal value.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Fragile(u8);
+
+    impl PartialOrd for Fragile {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Fragile {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            assert_ne!(self.0, other.0, "equal values");
+            self.0.cmp(&other.0)
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_try_ops(ops in vec((0..3u8, 0..16u8), 0..100)) {
+            let mut skiplist = SkipList::<Fragile, SmallRng, 8>::new();
+            let mut set = BTreeSet::new();
+            for (op, value) in ops {
+                let exists = set.contains(&value);
+                match op {
+                    0 => {
+                        assert_eq!(skiplist.try_insert(Fragile(value)).is_err(), exists);
+                        set.insert(value);
+                    }
+                    1 => assert_eq!(skiplist.try_contains(&Fragile(value)).is_err(), exists),
+                    _ => {
+                        let removed = skiplist.try_remove(&Fragile(value));
+                        assert!(matches!(removed, Ok(None)) != exists);
+                    }
+                }
+            }
+            let values = Vec::from(skiplist);
+            assert!(values.iter().map(|value| &value.0).eq(&set));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_drain(
+            items in vec(0..64u32, 0..100),
+            removed in vec(0..64u32, 0..20),
+            front in 0..100usize,
+            back in 0..100usize,
+        ) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::with_seed(0);
+            assert_eq!(skiplist.drain().next(), None);
+            for item in &items {
+                skiplist.insert(*item);
+            }
+            for item in &removed {
+                skiplist.remove_lazily(item);
+            }
+            let expected: Vec<_> = skiplist.iter().copied().collect();
+            let mut drain = skiplist.drain();
+            assert_eq!(format!("{drain:?}"), format!("{expected:?}"));
+            let firsts: Vec<_> = drain.by_ref().take(front).collect();
+            let lasts: Vec<_> = drain.by_ref().rev().take(back).collect();
+            drop(drain);
+            let front = front.min(expected.len());
+            assert_eq!(firsts, expected[..front]);
+            assert!(lasts.iter().eq(expected[front..].iter().rev().take(back)));
+            // The skiplist is empty and reusable, with its generator kept.
+            skiplist.assert_invariants();
+            assert_eq!(skiplist.first(), None);
+            skiplist.insert(1);
+            assert!(skiplist.iter().eq(&[1]));
+        }
+
+        #[test]
+        fn test_extract_if(
+            items in vec(0..64u32, 0..100),
+            removed in vec(0..64u32, 0..20),
+            divisor in 1..8u32,
+            taken in 0..100usize,
+        ) {
+            let mut skiplist = SkipList::<u32, SmallRng, 8>::with_seed(0);
+            for item in &items {
+                skiplist.insert(*item);
+            }
+            for item in &removed {
+                skiplist.remove_lazily(item);
+            }
+            let values: Vec<_> = skiplist.iter().copied().collect();
+            let mut extract = skiplist.extract_if(|value| value % divisor == 0);
+            assert_eq!(format!("{extract:?}"), format!("{values:?}"));
+            let extracted: Vec<_> = extract.by_ref().take(taken).collect();
+            let matching: Vec<_> =
+                values.iter().copied().filter(|value| value % divisor == 0).collect();
+            let mut left = taken.min(matching.len());
+            assert_eq!(extracted, matching[..left]);
+            // The values after the last extracted one are all kept.
+            let kept: Vec<_> = values
+                .iter()
+                .copied()
+                .filter(|value| {
+                    let extracted = left > 0 && value % divisor == 0;
+                    left -= usize::from(extracted);
+                    !extracted
+                })
+                .collect();
+            skiplist.assert_invariants();
+            assert!(skiplist.iter().eq(&kept));
+            assert!(skiplist.iter().rev().eq(kept.iter().rev()));
+            skiplist.insert(1);
+            skiplist.assert_invariants();
+        }
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut skiplist = SkipList::<u32, SmallRng, 8>::with_seed(0);
+        for i in 0..100 {
+            skiplist.insert(i % 10);
+        }
+        skiplist.remove_lazily(&3);
+        let mut visited = Vec::new();
+        skiplist.retain(|&value| {
+            visited.push(value);
+            value % 3 == 0
+        });
+        assert_eq!(visited.len(), 99);
+        assert!(visited.is_sorted());
+        skiplist.assert_invariants();
+        assert_eq!(skiplist.iter().count(), 39);
+        assert!(skiplist.iter().all(|value| value % 3 == 0));
+        skiplist.retain(|_| false);
+        skiplist.assert_invariants();
+        assert_eq!(skiplist.first(), None);
+        skiplist.insert(1);
+        assert!(skiplist.iter().eq(&[1]));
+    }
+
+    #[test]
+    fn test_drain_drops() {
+        let value = std::rc::Rc::new(());
+        let mut skiplist = SkipList::<_, SmallRng, 8>::new();
+        for i in 0..10 {
+            skiplist.insert((i, value.clone()));
+        }
+        skiplist.remove_lazily(&(3, value.clone()));
+        assert_eq!(skiplist.drain().nth(5).map(|(i, _)| i), Some(6));
+        assert_eq!(std::rc::Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_range() {
+        let words = ["apple", "banana", "cherry", "date", "elderberry"];
+        let skiplist = SkipList::<String, SmallRng, 8>::from(words.map(String::from).to_vec());
+        let window = (Bound::Excluded("apple"), Bound::Included("date"));
+        assert!(skiplist.range::<str, _>(window).eq(&words[1..4]));
+        assert!(skiplist.range::<str, _>(window).rev().eq(words[1..4].iter().rev()));
+        let from = (Bound::Included("c"), Bound::Unbounded);
+        assert!(skiplist.range::<str, _>(from).eq(&words[2..]));
+        let until = (Bound::Unbounded, Bound::Included("b"));
+        assert_eq!(format!("{:?}", skiplist.range::<str, _>(until)), r#"["apple"]"#);
+        // A range whose start is greater than its end holds no value.
+        let inverted = (Bound::Included("date"), Bound::Excluded("banana"));
+        assert_eq!(skiplist.range::<str, _>(inverted).next(), None);
+        assert_eq!(SkipList::<String>::new().range::<str, _>(..).next(), None);
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut skiplist = SkipList::<u8, SmallRng, 8>::from(BTreeSet::from([1, 2, 3, 4]));
+        skiplist.compact();
+        skiplist.remove_lazily(&2);
+        assert_eq!(format!("{skiplist:?}"), "[1, 3, 4]");
+        let structure = [
+            "SkipList {",
+            "    nodes: [",
+            "        1,",
+            "        2,",
+            "        3,",
+            "        4,",
+            "    ],",
+            "    levels: [",
+            "        [0, 1, 2, 3],",
+            "        [1, 3],",
+            "        [3],",
+            "    ],",
+            "    removed: [1],",
+            "}",
+        ];
+        assert_eq!(format!("{skiplist:#?}"), structure.join("\n"));
+        let empty = SkipList::<u8>::new();
+        assert_eq!(format!("{empty:?}"), "[]");
+        assert!(!format!("{empty:#?}").contains("0x"));
+    }
+
+    #[test]
+    fn test_display() {
+        let values = [2.5, 1.0, 3.25].map(OrderedF64::new);
+        let skiplist = SkipList::<_, SmallRng, 8>::from(values.to_vec());
+        assert_eq!(skiplist.to_string(), "[1, 2.5, 3.25]");
+        assert_eq!(format!("{skiplist:.1}"), "[1.0, 2.5, 3.2]");
+        assert_eq!(SkipList::<u8>::new().to_string(), "[]");
+    }
+
+    /// A value whose order can be changed after it has been inserted.
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Shifty(std::cell::Cell<u8>);
+
+    #[test]
+    #[should_panic(expected = "out of order")]
+    fn test_assert_invariants() {
+        let mut skiplist = SkipList::<Shifty, SmallRng, 8>::new();
+        for value in 0..4 {
+            skiplist.insert(Shifty(value.into()));
+        }
+        skiplist.assert_invariants();
+        if let Some(first) = skiplist.first() {
+            first.0.set(2);
+        }
+        skiplist.assert_invariants();
+    }
+
+    /// Orders pairs by their first element, so that equal pairs are told apart by the second.
+    struct First;
+
+    impl KeyExtract<(u8, usize)> for First {
+        type Key = u8;
+
+        fn key(value: &(u8, usize)) -> &u8 {
+            &value.0
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_policies(ops in vec((0..3u8, 0..16u8), 0..200)) {
+            let mut allow = SkipList::<ByKey<(u8, usize), First>, SmallRng, 8>::new();
+            let mut overwrite = SkipList::<_, SmallRng, 8, OverwriteOnEqual>::new();
+            let mut reject = SkipList::<_, SmallRng, 8, RejectOnEqual>::new();
+            let mut keys = Vec::new();
+            let mut last = BTreeMap::new();
+            let mut first = BTreeMap::new();
+            for (i, (op, key)) in ops.into_iter().enumerate() {
+                match op {
+                    0 => {
+                        allow.insert(ByKey::new((key, i)));
+                        keys.insert(keys.partition_point(|k| *k <= key), key);
+                        let replaced = overwrite.insert(ByKey::new((key, i))).map(|v| v.1);
+                        assert_eq!(replaced, last.insert(key, i));
+                        let rejected = reject.insert(ByKey::new((key, i))).map_err(|v| v.1);
+                        assert_eq!(rejected.is_err(), first.contains_key(&key));
+                        first.entry(key).or_insert(i);
+                    }
+                    1 => {
+                        let exists = last.remove(&key).is_some();
+                        assert_eq!(overwrite.remove_lazily(&key), exists);
+                        assert_eq!(reject.remove_lazily(&key), first.remove(&key).is_some());
+                        let removed = keys.binary_search(&key).map(|index| keys.remove(index));
+                        assert_eq!(allow.remove_lazily(&key), removed.is_ok());
+                    }
+                    _ => {
+                        let removed = overwrite.remove(&key).map(|v| v.1);
+                        assert_eq!(removed, last.remove(&key));
+                        assert_eq!(reject.remove(&key).map(|v| v.1), first.remove(&key));
+                        let removed = keys.binary_search(&key).map(|index| keys.remove(index));
+                        assert_eq!(allow.remove(&key).map(|v| v.0), removed.ok());
+                    }
+                }
+            }
+            let values = |skiplist: Vec<ByKey<_, First>>| -> Vec<_> {
+                skiplist.into_iter().map(ByKey::into_inner).collect()
+            };
+            assert!(values(Vec::from(allow)).into_iter().map(|v| v.0).eq(keys));
+            assert!(values(Vec::from(overwrite)).into_iter().eq(last));
+            assert!(values(Vec::from(reject)).into_iter().eq(first));
+        }
+
+        #[test]
+        fn test_policies_from_vec(items in vec((0..16u8, any::<usize>()), 0..100)) {
+            let values = |skiplist: Vec<ByKey<_, First>>| -> Vec<_> {
+                skiplist.into_iter().map(ByKey::into_inner).collect()
+            };
+            let wrapped = || items.iter().copied().map(ByKey::new).collect::<Vec<_>>();
+            let mut sorted = items.clone();
+            sorted.sort_by_key(|v| v.0);
+            let last: BTreeMap<_, _> = items.iter().copied().collect();
+            let mut first = BTreeMap::new();
+            for (key, value) in &items {
+                first.entry(*key).or_insert(*value);
+            }
+            let allow = SkipList::<_, SmallRng, 8>::from(wrapped());
+            assert_eq!(values(Vec::from(allow)), sorted);
+            let overwrite = SkipList::<_, SmallRng, 8, OverwriteOnEqual>::from(wrapped());
+            assert!(values(Vec::from(overwrite)).into_iter().eq(last));
+            let mut reject = SkipList::<_, SmallRng, 8, RejectOnEqual>::from(wrapped());
+            if let Some(key) = first.keys().next_back() {
+                assert!(reject.push_max(ByKey::new((*key, 0))).is_err());
+            }
+            assert!(values(Vec::from(reject)).into_iter().eq(first));
+        }
     }
 }
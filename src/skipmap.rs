@@ -1,51 +1,410 @@
 use std::{
     borrow::Borrow,
+    collections::BTreeMap,
     fmt,
     hash::{Hash, Hasher},
-    mem::ManuallyDrop,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem,
+    ops::RangeBounds,
 };
 
+#[cfg(any(feature = "csv", feature = "jsonl"))]
+use std::io::BufRead;
+
 use rand::{Rng, SeedableRng, rngs::SmallRng};
 
-use crate::NonEmptyStorage;
+#[cfg(feature = "columnar")]
+use crate::columnar::Columnar;
+#[cfg(feature = "csv")]
+use crate::csv;
+use crate::{
+    NonEmptyStorage, Pool, Spare,
+    bulk::Builder,
+    descending::DescendingSkipMap,
+    hashed_height,
+    iter::{IterMut, MapDrain, MapExtractIf, MapIter, MapRange, RangeMut, ValuesMut},
+    memory::HeapSize,
+    order::{self, ComparisonPanicked},
+    policy::{self, DuplicatePolicy, OnEqual, OverwriteOnEqual},
+    raw::RawKey,
+    small::SmallString,
+    snapshot::{self, Layout, Record},
+    sorted,
+};
+
+pub mod entry;
 
 /// An ordered map backed by a skiplist.
-pub struct SkipMap<K, V, R, const N: usize>(Option<NonEmptyStorage<Entry<K, V>, R, N>>)
+///
+/// Removing an entry keeps its node for reuse by a later insertion, until
+/// [`SkipMap::shrink_pool`] is called or the skipmap becomes empty.
+///
+/// Nodes link into upto `N` levels, which must be between 1 and 64, and heights are drawn from the
+/// random number generator `R`. Both have defaults that suit most uses, so that a skipmap of any
+/// types can be written as `SkipMap<K, V>`, and [`levels_for`](crate::levels_for) picks a smaller
+/// `N` for a skipmap of known size.
+///
+/// Inserting at an existing key replaces its value by default, and the [policy](crate::policy)
+/// `P` makes insertions keep or reject the entry instead, which is what [`SkipMap::insert`] then
+/// returns. The entry API, the conversions, and the formats are only for the default policy.
+pub struct SkipMap<K, V, R = SmallRng, const N: usize = 32, P = OverwriteOnEqual>
 where
-    R: Rng;
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// The nodes, or nothing while the skipmap is empty.
+    storage: Option<NonEmptyStorage<Entry<K, V>, R, N>>,
+    /// The random number generator kept while the skipmap is empty, which gets handed to the
+    /// storage on the next insertion.
+    rng: Spare<R>,
+    /// The nodes reserved while the skipmap is empty, which get handed to the storage on the next
+    /// insertion along with the generator.
+    pool: Pool<Entry<K, V>, N>,
+    policy: PhantomData<P>,
+}
 
-impl<K, V, const N: usize> Default for SkipMap<K, V, SmallRng, N> {
+impl<K, V, R, const N: usize, P> Default for SkipMap<K, V, R, N, P>
+where
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
     fn default() -> Self {
-        Self::new()
+        Self::with_lazy_rng()
     }
 }
 
-impl<K, V, R, const N: usize> fmt::Debug for SkipMap<K, V, R, N>
+impl<K, V, R, const N: usize, P> fmt::Debug for SkipMap<K, V, R, N, P>
 where
+    K: fmt::Debug,
+    V: fmt::Debug,
     R: Rng,
-    Entry<K, V>: fmt::Debug,
+    P: DuplicatePolicy,
 {
+    /// Formats the entries in order like a map, or with `{:#?}`, the structure of the skipmap,
+    /// where nodes are referred to by their positions instead of their addresses.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(storage) = &self.0 {
-            write!(f, "{storage:?}")
-        } else {
-            write!(f, "SkipMap(None)")
+        if f.alternate() {
+            return crate::fmt_structure(self.storage.as_ref(), f, "SkipMap");
+        }
+        let entries = self.storage.iter().flat_map(NonEmptyStorage::iter);
+        f.debug_map()
+            .entries(entries.map(|entry| (&entry.key, &entry.value)))
+            .finish()
+    }
+}
+
+impl<K, V, R, const N: usize, P> fmt::Display for SkipMap<K, V, R, N, P>
+where
+    K: fmt::Display,
+    V: fmt::Display,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Formats the entries in order of keys within braces, like `{1: a, 2: b}`. Formatting options
+    /// such as the precision apply to every key and value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, entry) in self.storage.iter().flat_map(NonEmptyStorage::iter).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            entry.key.fmt(f)?;
+            write!(f, ": ")?;
+            entry.value.fmt(f)?;
         }
+        write!(f, "}}")
     }
 }
 
-impl<K, V, const N: usize> SkipMap<K, V, SmallRng, N> {
+impl<K, V, const N: usize, P> SkipMap<K, V, SmallRng, N, P>
+where
+    P: DuplicatePolicy,
+{
     /// Creates an empty skipmap.
     #[must_use]
     pub const fn new() -> Self {
-        Self(None)
+        Self::with_lazy_rng()
+    }
+
+    /// Creates an empty skipmap holding the nodes of at least the given number of entries, so that
+    /// inserting them makes no allocator calls.
+    ///
+    /// The number of levels `N` stays as given, and [`levels_for`](crate::levels_for) picks the
+    /// one that suits a skipmap of known size.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut skipmap = Self::new();
+        skipmap.reserve(capacity);
+        skipmap
     }
 }
 
-impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+impl<K, V, const N: usize, P> SkipMap<K, V, SmallRng, N, P>
+where
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skipmap whose entries are ordered from the greatest key to the least, so
+    /// that [`DescendingSkipMap::first_key_value`] and [`DescendingSkipMap::pop_first`] return the
+    /// entry at the greatest key.
+    #[must_use]
+    pub const fn descending() -> DescendingSkipMap<K, V, SmallRng, N, P> {
+        DescendingSkipMap::new()
+    }
+}
+
+impl<K, V, R, const N: usize, P> SkipMap<K, V, R, N, P>
+where
+    R: Rng + SeedableRng,
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skipmap whose random number generator is seeded from the given value, so
+    /// that the same operations always build the same structure.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(R::seed_from_u64(seed))
+    }
+
+    /// Creates an empty skipmap whose random number generator only gets created on the first
+    /// insertion, like [`SkipMap::new`] does for the default one, so that a skipmap with any
+    /// generator can be created in a const context, e.g. for a static.
+    #[must_use]
+    pub const fn with_lazy_rng() -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            storage: None,
+            rng: Spare::lazy(),
+            pool: Pool::new(),
+            policy: PhantomData,
+        }
+    }
+}
+
+impl<K, V, R, const N: usize, P> SkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Creates an empty skipmap that draws the heights of its nodes from the given random number
+    /// generator.
+    pub const fn with_rng(rng: R) -> Self {
+        crate::assert_levels::<N>();
+        Self {
+            storage: None,
+            rng: Spare::Ready(rng),
+            pool: Pool::new(),
+            policy: PhantomData,
+        }
+    }
+
+    /// Drops the storage, keeping its random number generator for the next insertion.
+    pub(crate) fn drop_storage(&mut self) {
+        if let Some(storage) = self.storage.take() {
+            self.rng = Spare::Ready(storage.into_rng());
+        }
+    }
+}
+
+impl<K, V, R, const N: usize, P> SkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns the number of nodes kept for reuse by later insertions.
+    #[must_use]
+    pub fn pool_len(&self) -> usize {
+        self.storage.as_ref().map_or(self.pool.len, |storage| storage.pool.len)
+    }
+
+    /// Allocates nodes for reuse by later insertions until at least the given number of values can
+    /// be inserted without allocating, so that a bulk load of known size makes no allocator calls
+    /// while it runs.
+    ///
+    /// An empty skipmap also allocates the head of its storage, which the first insertion takes
+    /// along with the nodes.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Some(storage) = &mut self.storage {
+            storage.pool.reserve(additional);
+        } else {
+            self.pool.reserve_head();
+            self.pool.reserve(additional);
+        }
+    }
+
+    /// Frees the nodes kept for reuse by later insertions.
+    pub fn shrink_pool(&mut self) {
+        self.shrink_pool_to(0);
+    }
+
+    /// Frees the nodes kept for reuse by later insertions, beyond the given number.
+    pub fn shrink_pool_to(&mut self, len: usize) {
+        match &mut self.storage {
+            Some(storage) => storage.pool.shrink_to(len),
+            None => self.pool.shrink_to(len),
+        }
+    }
+
+    /// Returns the number of nodes linked into the skipmap, including those of lazily removed
+    /// entries.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.storage.as_ref().map_or(0, |storage| storage.len)
+    }
+
+    /// Returns the number of bytes allocated for the nodes of the skipmap, including the nodes kept
+    /// for reuse, but excluding the memory that entries own on the heap.
+    #[must_use]
+    pub fn allocated_bytes(&self) -> usize {
+        self.storage
+            .as_ref()
+            .map_or_else(|| self.pool.allocated_bytes(), NonEmptyStorage::allocated_bytes)
+    }
+
+    /// Returns the number of bytes allocated for the nodes of the skipmap, along with the memory
+    /// that entries own on the heap.
+    #[must_use]
+    pub fn memory_usage(&self) -> usize
+    where
+        K: HeapSize,
+        V: HeapSize,
+    {
+        self.storage.as_ref().map_or_else(|| self.pool.allocated_bytes(), |storage| {
+            storage.allocated_bytes()
+                + storage.heap_size(|entry| entry.key.heap_size() + entry.value.heap_size())
+        })
+    }
+
+    /// Rebuilds the towers of the skipmap in place, so that every other node of a level is also
+    /// linked into the level above, and frees the nodes kept for reuse by later insertions.
+    ///
+    /// Heights drift away from their expected distribution after many insertions and removals,
+    /// which lengthens the search paths until the skipmap gets compacted.
+    pub fn compact(&mut self) {
+        let Some(storage) = &mut self.storage else {
+            return;
+        };
+        storage.compact();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+    }
+
+    /// Unlinks the nodes of the entries removed by [`SkipMap::remove_lazily`], dropping the
+    /// entries.
+    pub fn vacuum(&mut self) {
+        let Some(storage) = &mut self.storage else {
+            return;
+        };
+        storage.vacuum();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+    }
+
+    /// Returns an iterator over the entries, in ascending order of their keys, which skips the
+    /// entries removed by [`SkipMap::remove_lazily`].
+    pub fn iter(&self) -> MapIter<'_, K, V, N> {
+        MapIter(self.storage.as_ref().map_or_else(crate::Iter::empty, NonEmptyStorage::iter))
+    }
+
+    /// Returns an iterator over the entries, in ascending order of their keys, through which the
+    /// values can be mutated in place. Keys are only given out by shared reference, so that they
+    /// stay in order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, N> {
+        IterMut(self.storage.as_mut().map_or_else(crate::IterMut::empty, NonEmptyStorage::iter_mut))
+    }
+
+    /// Returns an iterator over the values, in ascending order of their keys, through which they
+    /// can be mutated in place.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, N> {
+        let storage = self.storage.as_mut();
+        ValuesMut(storage.map_or_else(crate::IterMut::empty, NonEmptyStorage::iter_mut))
+    }
+
+    /// Removes every entry, returning an iterator that moves them out in ascending order of their
+    /// keys.
+    ///
+    /// The skipmap is left empty as soon as this returns, even if the iterator is dropped early,
+    /// and keeps its random number generator for later insertions.
+    pub fn drain(&mut self) -> MapDrain<'_, K, V, N> {
+        let Some(storage) = self.storage.take() else {
+            return MapDrain(crate::IntoIter::empty(), PhantomData);
+        };
+        let (rng, entries) = storage.into_values();
+        self.rng = Spare::Ready(rng);
+        MapDrain(entries, PhantomData)
+    }
+
+    /// Returns an iterator that removes the entries whose keys and values match the predicate, in
+    /// ascending order of their keys, visiting every entry once along the base level and unlinking
+    /// the matching ones from every level as it goes. The predicate can mutate the values of the
+    /// entries it keeps.
+    ///
+    /// Entries are only removed as the iterator reaches them, so dropping it early keeps the
+    /// entries after the last one it returned. Lazily removed entries that it passes get unlinked
+    /// too.
+    pub fn extract_if<F>(&mut self, pred: F) -> MapExtractIf<'_, K, V, R, N, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        MapExtractIf(crate::ExtractIf::new(&mut self.storage, &mut self.rng), pred)
+    }
+
+    /// Keeps only the entries whose keys and values match the predicate, which can mutate the
+    /// values, in ascending order of their keys. Every entry is visited once along the base level,
+    /// and the others get unlinked from every level as they're reached, dropping the levels that
+    /// no longer hold a node.
+    pub fn retain<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !pred(k, v)).for_each(drop);
+    }
+
+    /// Returns the entry with the least key.
+    #[must_use]
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let entry = self.storage.as_ref()?.first()?;
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns the entry with the greatest key, without traversing the skipmap, only stepping back
+    /// over the entries removed lazily.
+    #[must_use]
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let entry = self.storage.as_ref()?.last()?;
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Removes the entry with the least key, returning it if the skipmap isn't empty.
+    ///
+    /// Only the links of the head get updated, so this takes constant time on average, which
+    /// suits skipmaps used as priority queues.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let storage = self.storage.as_mut()?;
+        let entry = storage.pop_first();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        entry.map(|e| (e.key, e.value))
+    }
+
+    /// Removes the entry with the greatest key, returning it if the skipmap isn't empty.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let storage = self.storage.as_mut()?;
+        let entry = storage.pop_last();
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        entry.map(|e| (e.key, e.value))
+    }
+}
+
+impl<K, V, R, const N: usize, P> SkipMap<K, V, R, N, P>
 where
     R: Rng,
     Entry<K, V>: Ord,
+    P: DuplicatePolicy,
 {
     /// Returns whether a key exists in the skipmap.
     pub fn contains<Q>(&self, key: &Q) -> bool
@@ -53,7 +412,7 @@ where
         Q: Ord + ?Sized,
         Entry<K, V>: Borrow<Q>,
     {
-        let Some(storage) = &self.0 else {
+        let Some(storage) = &self.storage else {
             return false;
         };
         storage.get(key).is_some()
@@ -65,48 +424,909 @@ where
         Q: Ord + ?Sized,
         Entry<K, V>: Borrow<Q>,
     {
-        let Some(storage) = &self.0 else {
+        let Some(storage) = &self.storage else {
             return None;
         };
         storage.get(key).map(|e| &e.value)
     }
 
-    /// Inserts a value at the given key into the skipmap.
-    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    /// Returns a mutable reference to the value associated with the given key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
-        R: SeedableRng,
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
     {
-        let Some(storage) = &mut self.0 else {
-            self.0 = Some(NonEmptyStorage::new(Entry { key, value }));
+        let Some(storage) = &mut self.storage else {
             return None;
         };
-        storage.upsert(Entry { key, value }).map(|e| e.value)
+        // Only the value is given out mutably, so the key stays in order.
+        storage.get_mut(key).map(|e| &mut e.value)
+    }
+
+    /// Returns an iterator over the entries whose keys are within the range, in ascending order of
+    /// their keys. Both ends of the range are found by descending the levels from the head, so
+    /// that only the entries within the range get visited.
+    ///
+    /// A range whose start is greater than its end holds no entry.
+    pub fn range<Q, B>(&self, range: B) -> MapRange<'_, K, V, N>
+    where
+        Q: Ord + ?Sized,
+        B: RangeBounds<Q>,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let Some(storage) = &self.storage else {
+            return MapRange(crate::Iter::empty());
+        };
+        MapRange(storage.range(&range))
+    }
+
+    /// Returns an iterator over the entries whose keys are within the range, in ascending order of
+    /// their keys, through which the values can be mutated in place, e.g. to apply a delta to
+    /// every entry of a window.
+    ///
+    /// A range whose start is greater than its end holds no entry.
+    pub fn range_mut<Q, B>(&mut self, range: B) -> RangeMut<'_, K, V, N>
+    where
+        Q: Ord + ?Sized,
+        B: RangeBounds<Q>,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let Some(storage) = &mut self.storage else {
+            return RangeMut(crate::IterMut::empty());
+        };
+        RangeMut(storage.range_mut(&range))
+    }
+
+    /// Removes the entries whose keys are within the range, returning an iterator that moves them
+    /// out in ascending order of their keys. The entries before and after the range are found by
+    /// descending the levels from the head, and get linked to each other once at every level,
+    /// rather than unlinking every entry on its own.
+    ///
+    /// The entries are removed as soon as this returns, even if the iterator is dropped early. A
+    /// range whose start is greater than its end holds no entry.
+    pub fn drain_range<Q, B>(&mut self, range: B) -> MapDrain<'_, K, V, N>
+    where
+        Q: Ord + ?Sized,
+        B: RangeBounds<Q>,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let Some(storage) = &mut self.storage else {
+            return MapDrain(crate::IntoIter::empty(), PhantomData);
+        };
+        let entries = storage.unlink_range(&range);
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        MapDrain(entries, PhantomData)
+    }
+
+    /// Returns whether a key of a primitive type exists in the skipmap, comparing keys with fewer
+    /// branches than [`SkipMap::contains`] does.
+    pub fn contains_raw(&self, key: K) -> bool
+    where
+        K: RawKey,
+    {
+        self.get_raw(key).is_some()
+    }
+
+    /// Returns a shared reference to the value associated with the given key of a primitive type,
+    /// comparing keys with fewer branches than [`SkipMap::get`] does.
+    pub fn get_raw(&self, key: K) -> Option<&V>
+    where
+        K: RawKey,
+    {
+        let storage = self.storage.as_ref()?;
+        let curr_ptr = storage.find_raw(key)?;
+        Some(unsafe { &(*curr_ptr.as_ptr()).value.value })
+    }
+
+    /// Inserts a value at the given key into the skipmap. Keys greater than every other key get
+    /// linked at the end without a traversal.
+    ///
+    /// An existing entry at the key is kept, replaced, or rejected depending on the policy, which
+    /// determines what this returns: nothing, the replaced value, or the rejected value as an
+    /// error.
+    pub fn insert(&mut self, key: K, value: V) -> P::Outcome<V> {
+        self.insert_with_height(Entry { key, value }, None)
+    }
+
+    /// Inserts a value at the given key into the skipmap, linking a new node into a number of
+    /// levels derived from the hash of the key instead of a random one.
+    ///
+    /// A skipmap whose entries are all inserted this way has the same structure whatever the order
+    /// of insertion, and on every run of the same build, which makes it reproducible for
+    /// differential testing and across replicas. Heights aren't capped by the number of entries,
+    /// so that they only depend on the keys.
+    pub fn insert_hashed(&mut self, key: K, value: V) -> P::Outcome<V>
+    where
+        K: Hash,
+    {
+        let height = hashed_height::<_, N>(&key);
+        self.insert_with_height(Entry { key, value }, Some(height))
+    }
+
+    /// Inserts an entry as the policy decides, linking a new node into the given number of levels,
+    /// or a random one if there's none.
+    fn insert_with_height(&mut self, entry: Entry<K, V>, height: Option<usize>) -> P::Outcome<V> {
+        let Some(storage) = &mut self.storage else {
+            let rng = self.rng.take();
+            let pool = mem::take(&mut self.pool);
+            self.storage = Some(match height {
+                Some(height) => NonEmptyStorage::with_height(entry, height, rng, pool),
+                None => NonEmptyStorage::new(entry, rng, pool),
+            });
+            return P::outcome(None);
+        };
+        let equal = storage.insert_on_equal(entry, height, policy::on_equal::<P>());
+        P::outcome(equal.map(|e| e.value))
+    }
+
+    /// Appends an entry whose key is greater than every other key of the skipmap.
+    ///
+    /// The entry gets linked after the last node of every level without a traversal, which suits
+    /// keys arriving in order, such as timestamps. [`SkipMap::insert`] takes the same path for
+    /// such keys, but inserts out of order keys instead of rejecting them. Lazily removed entries
+    /// don't count, so an entry whose key is less than some of theirs still gets inserted in order.
+    ///
+    /// # Errors
+    ///
+    /// Gives the entry back if its key isn't greater than the greatest key of the skipmap, unless
+    /// it's equal to it while the policy keeps equal entries.
+    pub fn push_max(&mut self, key: K, value: V) -> Result<(), (K, V)> {
+        let entry = Entry { key, value };
+        let Some(storage) = &mut self.storage else {
+            let pool = mem::take(&mut self.pool);
+            self.storage = Some(NonEmptyStorage::new(entry, self.rng.take(), pool));
+            return Ok(());
+        };
+        let on_equal = policy::on_equal::<P>();
+        let keep = on_equal == OnEqual::Keep;
+        if storage.tail_value() < &entry || (keep && storage.tail_value() == &entry) {
+            storage.append(entry, None);
+            return Ok(());
+        }
+        // The last nodes might hold lazily removed entries, which the entry can still go before.
+        if storage.last().is_some_and(|last| last > &entry || (!keep && last == &entry)) {
+            return Err((entry.key, entry.value));
+        }
+        storage.insert_on_equal(entry, None, on_equal);
+        Ok(())
     }
 
     /// Removes a value at the given key from the skipmap, returning it if it exists.
     pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
     where
-        Q: Ord + ?Sized,
-        Entry<K, V>: Borrow<Q>,
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        self.remove_entry(key).map(|e| e.value)
+    }
+
+    /// Inserts a value at the given key, returning the stored entry along with the replaced value.
+    #[cfg(feature = "observer")]
+    pub(crate) fn upsert(&mut self, key: K, value: V) -> (&Entry<K, V>, Option<V>) {
+        let entry = Entry { key, value };
+        if self.storage.is_none() {
+            let rng = self.rng.take();
+            let pool = mem::take(&mut self.pool);
+            let storage = self.storage.insert(NonEmptyStorage::new(entry, rng, pool));
+            let Some(entry) = storage.iter().next() else {
+                unreachable!();
+            };
+            return (entry, None);
+        }
+        let Some(storage) = &mut self.storage else {
+            unreachable!();
+        };
+        let (entry, old) = storage.upsert(entry, None);
+        (entry, old.map(|e| e.value))
+    }
+
+    /// Creates a skipmap holding the entries added to the builder.
+    pub(crate) fn from_builder(builder: Builder<Entry<K, V>, R, N>) -> Self {
+        match builder.finish() {
+            Ok(storage) => Self {
+                storage: Some(storage),
+                rng: Spare::Lent,
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+            Err(rng) => Self {
+                storage: None,
+                rng: Spare::Ready(rng),
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+        }
+    }
+
+    /// Removes the entry at the given key, returning it if it exists.
+    pub(crate) fn remove_entry<Q>(&mut self, key: &Q) -> Option<Entry<K, V>>
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let storage = self.storage.as_mut()?;
+        let entry = storage.remove(key);
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        entry
+    }
+
+    /// Marks the entry at the given key as removed, returning whether it exists.
+    ///
+    /// The entry is skipped by lookups and iterations from then on, but its node stays linked, so
+    /// removals in a delete-heavy phase only pay for a search. Nodes get unlinked in a single pass
+    /// by [`SkipMap::vacuum`], or once most of the nodes hold removed entries.
+    pub fn remove_lazily<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let Some(storage) = &mut self.storage else {
+            return false;
+        };
+        let removed = storage.remove_lazily(key);
+        if storage.is_empty() {
+            self.drop_storage();
+        }
+        removed
+    }
+
+    /// Returns a shared reference to the value at the given key, like [`SkipMap::get`], but
+    /// catches a panic of the comparisons.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing keys panicked.
+    pub fn try_get<Q>(&self, key: &Q) -> Result<Option<&V>, ComparisonPanicked>
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        order::catch(|| self.get(key))
+    }
+
+    /// Inserts a value at the given key, like [`SkipMap::insert`], but catches a panic of the
+    /// comparisons. The skipmap is left as it was before the insertion, and the entry is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing keys panicked.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<P::Outcome<V>, ComparisonPanicked> {
+        order::catch(|| self.insert(key, value))
+    }
+
+    /// Removes the value at the given key, like [`SkipMap::remove`], but catches a panic of the
+    /// comparisons. The skipmap then holds the same entries as before the removal.
+    ///
+    /// # Errors
+    ///
+    /// Returns the panic if comparing keys panicked.
+    pub fn try_remove<Q>(&mut self, key: &Q) -> Result<Option<V>, ComparisonPanicked>
+    where
+        Q: Ord + ?Sized,
+        Entry<K, V>: Borrow<Q>,
+    {
+        let removed = order::catch(|| self.remove(key));
+        // Lazily removed entries might have been unlinked before the panic.
+        if self.storage.as_ref().is_some_and(NonEmptyStorage::is_empty) {
+            self.drop_storage();
+        }
+        removed
+    }
+
+    /// Checks the structure of the skipmap, which is meant for tests of code building on the
+    /// skipmap, and for narrowing down a corruption, e.g. due to keys whose order isn't total.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys aren't in order, or strictly so while the policy doesn't keep equal
+    /// keys, if a level links a node that the level below doesn't, or if a counter of the skipmap
+    /// doesn't match its nodes.
+    #[cfg(any(test, feature = "invariants"))]
+    pub fn assert_invariants(&self) {
+        let lent = matches!(self.rng, Spare::Lent);
+        assert_eq!(
+            self.storage.is_some(),
+            lent,
+            "the random number generator is misplaced"
+        );
+        if let Some(storage) = &self.storage {
+            storage.assert_invariants(policy::on_equal::<P>() != OnEqual::Keep);
+        }
+    }
+}
+
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    K: Record,
+    V: Record,
+    R: Rng,
+{
+    /// Writes the entries of the skipmap in the [`snapshot`] format, one at a time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if a key or a value is encoded into more than
+    /// `u32::MAX` bytes.
+    pub fn write_snapshot<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let len = self.storage.iter().flat_map(NonEmptyStorage::iter).count();
+        snapshot::write_header(&mut writer, Layout::Entries, len)?;
+        let mut buf = Vec::new();
+        for entry in self.storage.iter().flat_map(NonEmptyStorage::iter) {
+            snapshot::write_record(&mut writer, &entry.key, &mut buf)?;
+            snapshot::write_record(&mut writer, &entry.value, &mut buf)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a skipmap written in the [`snapshot`] format, one entry at a time.
+    ///
+    /// Entries are linked in order as long as their keys are ascending, which is the case for
+    /// snapshots written by [`SkipMap::write_snapshot`], and inserted one by one otherwise.
+    /// Checkpoints can be read too, in which case their structure is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the data isn't a valid snapshot.
+    pub fn read_snapshot<Rd>(mut reader: Rd) -> io::Result<Self>
+    where
+        K: Ord,
+        R: SeedableRng,
+        Rd: Read,
+    {
+        let (layout, len) = snapshot::read_header(&mut reader)?;
+        let mut buf = Vec::new();
+        if layout == Layout::Structure {
+            snapshot::read_record::<_, Vec<u8>>(&mut reader, &mut buf)?;
+        }
+        Self::try_from_entries((0..len).map(|_| {
+            if layout == Layout::Structure {
+                snapshot::read_height(&mut reader, N)?;
+            }
+            let key = snapshot::read_record(&mut reader, &mut buf)?;
+            let value = snapshot::read_record(&mut reader, &mut buf)?;
+            Ok((key, value))
+        }))
+    }
+}
+
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    K: Ord,
+    R: Rng + SeedableRng,
+{
+    /// Creates a skipmap from fallible entries, stopping at the first error. Entries are linked in
+    /// order as long as their keys are ascending, and inserted one by one otherwise.
+    fn try_from_entries<I, E>(entries: I) -> Result<Self, E>
+    where
+        I: IntoIterator<Item = Result<(K, V), E>>,
+    {
+        let mut entries = entries.into_iter();
+        let mut builder = Builder::new(true);
+        for entry in entries.by_ref() {
+            if let Err(Entry { key, value }) = builder.push(Entry::from(entry?)) {
+                let mut skipmap = Self::from_builder(builder);
+                skipmap.insert(key, value);
+                for entry in entries {
+                    let (key, value) = entry?;
+                    skipmap.insert(key, value);
+                }
+                return Ok(skipmap);
+            }
+        }
+        Ok(Self::from_builder(builder))
+    }
+}
+
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    K: Record,
+    V: Record,
+    R: Rng + SeedableRng,
+{
+    /// Writes the entries of the skipmap in the [`snapshot`] format along with its structure, so
+    /// that [`SkipMap::read_checkpoint`] restores a skipmap whose nodes are linked into the same
+    /// levels, and whose random number generator makes the same choices from then on.
+    ///
+    /// The random number generator of the skipmap gets reseeded with a seed drawn from it, which
+    /// is written along with the entries. An empty skipmap doesn't get its random number generator
+    /// until the first insertion, so its checkpoint holds no seed. Lazily removed entries get
+    /// vacuumed beforehand.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, if a key or a value is encoded into more than
+    /// `u32::MAX` bytes, or if `N` is greater than 255.
+    pub fn write_checkpoint<W>(&mut self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.vacuum();
+        let len = self.storage.iter().flat_map(NonEmptyStorage::iter).count();
+        snapshot::write_header(&mut writer, Layout::Structure, len)?;
+        let mut buf = Vec::new();
+        let mut seed = Vec::new();
+        if let Some(storage) = &mut self.storage {
+            let mut new_seed = R::Seed::default();
+            storage.rng.fill_bytes(new_seed.as_mut());
+            seed.extend_from_slice(new_seed.as_ref());
+            storage.set_rng(R::from_seed(new_seed));
+        }
+        snapshot::write_record(&mut writer, &seed, &mut buf)?;
+        for (height, entry) in self.storage.iter().flat_map(NonEmptyStorage::towers) {
+            snapshot::write_height(&mut writer, height)?;
+            snapshot::write_record(&mut writer, &entry.key, &mut buf)?;
+            snapshot::write_record(&mut writer, &entry.value, &mut buf)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a skipmap written by [`SkipMap::write_checkpoint`], linking every node into the same
+    /// levels as in the original skipmap and seeding the random number generator with the seed of
+    /// the checkpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the data isn't a valid checkpoint for a skipmap
+    /// with the same number of levels and the same kind of random number generator.
+    pub fn read_checkpoint<Rd>(mut reader: Rd) -> io::Result<Self>
+    where
+        K: Ord,
+        Rd: Read,
+    {
+        let (layout, len) = snapshot::read_header(&mut reader)?;
+        if layout != Layout::Structure {
+            return Err(snapshot::invalid("snapshot without structure"));
+        }
+        let mut buf = Vec::new();
+        let bytes: Vec<u8> = snapshot::read_record(&mut reader, &mut buf)?;
+        if len == 0 {
+            return Ok(Self {
+                storage: None,
+                rng: Spare::lazy(),
+                pool: Pool::new(),
+                policy: PhantomData,
+            });
+        }
+        let mut seed = R::Seed::default();
+        if bytes.len() != seed.as_ref().len() {
+            return Err(snapshot::invalid("seed of the wrong size"));
+        }
+        seed.as_mut().copy_from_slice(&bytes);
+        let mut builder = Builder::with_rng(R::from_seed(seed), true);
+        for _ in 0..len {
+            let height = snapshot::read_height(&mut reader, N)?;
+            let key = snapshot::read_record(&mut reader, &mut buf)?;
+            let value = snapshot::read_record(&mut reader, &mut buf)?;
+            builder
+                .push_with_height(Entry { key, value }, height)
+                .map_err(|_| snapshot::invalid("keys that aren't strictly ascending"))?;
+        }
+        Ok(Self::from_builder(builder))
+    }
+}
+
+impl<'a, K, V, R, const N: usize, P> IntoIterator for &'a SkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = MapIter<'a, K, V, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, R, const N: usize, P> IntoIterator for &'a mut SkipMap<K, V, R, N, P>
+where
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, R, const N: usize, P> PartialEq<BTreeMap<K, V>> for SkipMap<K, V, R, N, P>
+where
+    K: PartialEq,
+    V: PartialEq,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns whether the skipmap holds the same entries as the map.
+    fn eq(&self, other: &BTreeMap<K, V>) -> bool {
+        let entries = self.storage.iter().flat_map(NonEmptyStorage::iter);
+        entries.map(|entry| (&entry.key, &entry.value)).eq(other)
+    }
+}
+
+impl<K, V, R, const N: usize> sorted::SortedMap<K, V> for SkipMap<K, V, R, N>
+where
+    K: Ord,
+    R: Rng,
+{
+    fn get(&self, key: &K) -> Option<&V> {
+        Self::get(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        Self::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        Self::remove(self, key)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        Self::iter(self)
+    }
+
+    fn range<'a, B>(&'a self, range: B) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+        B: RangeBounds<K>,
+    {
+        Self::range(self, range)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    K: Send,
+    V: Send,
+    R: Rng + SeedableRng + Send,
+{
+    /// Creates a skipmap from chunks of entries whose keys are strictly ascending, both within
+    /// every chunk and from one chunk to the next.
+    ///
+    /// The chunks are turned into nodes in parallel, then stitched together using a number of
+    /// changes per chunk bounded by the number of levels. Lookups might miss keys if the keys
+    /// aren't strictly ascending.
+    #[must_use]
+    pub fn from_sorted_chunks_parallel<I, C>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: IntoIterator<Item = (K, V)> + Send,
+    {
+        match NonEmptyStorage::from_sorted_chunks(chunks) {
+            Ok(storage) => Self {
+                storage: Some(storage),
+                rng: Spare::Lent,
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+            Err(rng) => Self {
+                storage: None,
+                rng: Spare::Ready(rng),
+                pool: Pool::new(),
+                policy: PhantomData,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, R, const N: usize, P> SkipMap<K, V, R, N, P>
+where
+    K: Send,
+    V: Send,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Drops the skipmap, freeing its nodes on the threads of rayon's global pool.
+    ///
+    /// The base level gets split into chunks at the nodes of an upper level, which are freed in
+    /// parallel, so that dropping a very large skipmap doesn't take a single thread chasing every
+    /// pointer. Small skipmaps are dropped as usual.
+    pub fn drop_parallel(self) {
+        if let Some(storage) = self.storage {
+            storage.drop_parallel();
+        }
+    }
+}
+
+#[cfg(feature = "jsonl")]
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    R: Rng,
+{
+    /// Writes the entries of the skipmap as JSON Lines, one `[key, value]` array per line, in
+    /// ascending order of keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if a key or a value can't be serialized.
+    pub fn export_jsonl<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        K: serde::Serialize,
+        V: serde::Serialize,
+        W: Write,
+    {
+        for entry in self.storage.iter().flat_map(NonEmptyStorage::iter) {
+            serde_json::to_writer(&mut writer, &(&entry.key, &entry.value))?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Reads a skipmap from JSON Lines holding one `[key, value]` array per line, one line at a
+    /// time. Blank lines are skipped.
+    ///
+    /// Entries are linked in order as long as their keys are ascending, which is the case for
+    /// lines written by [`SkipMap::export_jsonl`], and inserted one by one otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if a line isn't a valid entry.
+    pub fn import_jsonl<Rd>(reader: Rd) -> io::Result<Self>
+    where
+        K: Ord + serde::de::DeserializeOwned,
+        V: serde::de::DeserializeOwned,
+        R: SeedableRng,
+        Rd: BufRead,
+    {
+        let lines = reader
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()));
+        Self::try_from_entries(lines.map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+}
+
+#[cfg(feature = "csv")]
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    R: Rng,
+{
+    /// Writes the entries of the skipmap as CSV rows made of a key field and a value field, in
+    /// ascending order of keys, converting keys and values into fields with the given functions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn export_csv<W, FK, FV>(
+        &self,
+        mut writer: W,
+        mut key_to_string: FK,
+        mut value_to_string: FV,
+    ) -> io::Result<()>
+    where
+        W: Write,
+        FK: FnMut(&K) -> String,
+        FV: FnMut(&V) -> String,
+    {
+        for entry in self.storage.iter().flat_map(NonEmptyStorage::iter) {
+            let key = key_to_string(&entry.key);
+            let value = value_to_string(&entry.value);
+            csv::write_row(&mut writer, &[&key, &value])?;
+        }
+        Ok(())
+    }
+
+    /// Reads a skipmap from CSV rows made of a key field and a value field, one row at a time,
+    /// converting fields into keys and values with the given functions. Blank lines are skipped.
+    ///
+    /// Entries are linked in order as long as their keys are ascending, which is the case for
+    /// rows written by [`SkipMap::export_csv`], and inserted one by one otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, if a row doesn't have exactly two fields, or if a field
+    /// can't be converted.
+    pub fn import_csv<Rd, FK, FV, EK, EV>(
+        mut reader: Rd,
+        mut key_from_str: FK,
+        mut value_from_str: FV,
+    ) -> io::Result<Self>
+    where
+        K: Ord,
+        R: SeedableRng,
+        Rd: BufRead,
+        FK: FnMut(&str) -> Result<K, EK>,
+        FV: FnMut(&str) -> Result<V, EV>,
+        EK: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EV: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut line = String::new();
+        let rows = std::iter::from_fn(|| csv::read_row(&mut reader, &mut line).transpose());
+        Self::try_from_entries(rows.map(|row| {
+            let [key, value] = <[String; 2]>::try_from(row?).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "row without exactly two fields")
+            })?;
+            let key =
+                key_from_str(&key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let value = value_from_str(&value)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok((key, value))
+        }))
+    }
+}
+
+#[cfg(feature = "columnar")]
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    K: Columnar,
+    V: Columnar,
+    R: Rng,
+{
+    /// Returns the keys and the values of the skipmap as two parallel columns, in ascending order
+    /// of keys. See [`crate::columnar`] for how the columns are laid out.
+    #[must_use]
+    pub fn to_columns(&self) -> (K::Column, V::Column) {
+        let mut keys = K::Column::default();
+        let mut values = V::Column::default();
+        for entry in self.storage.iter().flat_map(NonEmptyStorage::iter) {
+            entry.key.push(&mut keys);
+            entry.value.push(&mut values);
+        }
+        (keys, values)
+    }
+}
+
+#[cfg(feature = "dot")]
+impl<K, V, R, const N: usize, P> SkipMap<K, V, R, N, P>
+where
+    K: fmt::Display,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    /// Returns the structure of the skipmap as a graph in the DOT language of Graphviz, where
+    /// every node is drawn as a tower of its levels below its key, and every link as an edge.
+    ///
+    /// Nodes of lazily removed entries are dashed. The graph can be rendered with `dot -Tsvg`,
+    /// e.g. to see how evenly the heights of the nodes are spread.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        crate::dot::to_dot(self.storage.as_ref(), |entry| entry.key.to_string())
+    }
+}
+
+/// Skipmaps are serialized as maps for human-readable formats, and as flat sequences alternating
+/// between keys and values otherwise.
+#[cfg(feature = "serde")]
+impl<K, V, R, const N: usize, P> serde::Serialize for SkipMap<K, V, R, N, P>
+where
+    K: serde::Serialize,
+    V: serde::Serialize,
+    R: Rng,
+    P: DuplicatePolicy,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeSeq;
+
+        let entries = self.storage.iter().flat_map(NonEmptyStorage::iter);
+        if serializer.is_human_readable() {
+            return serializer.collect_map(entries.map(|e| (&e.key, &e.value)));
+        }
+        let len = self.storage.iter().flat_map(NonEmptyStorage::iter).count();
+        let mut seq = serializer.serialize_seq(Some(2 * len))?;
+        for entry in entries {
+            seq.serialize_element(&entry.key)?;
+            seq.serialize_element(&entry.value)?;
+        }
+        seq.end()
+    }
+}
+
+/// Skipmaps are deserialized from maps for human-readable formats, and from flat sequences
+/// alternating between keys and values otherwise. Self-describing formats accept both.
+///
+/// Keys and values may borrow from the input when the format allows it, so that a skipmap with
+/// `&'de str` or `&'de [u8]` keys doesn't copy them.
+#[cfg(feature = "serde")]
+impl<'de, K, V, R, const N: usize> serde::Deserialize<'de> for SkipMap<K, V, R, N>
+where
+    K: Ord + serde::Deserialize<'de>,
+    V: serde::Deserialize<'de>,
+    R: Rng + SeedableRng,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
     {
-        let storage = self.0.take()?;
-        let (storage, entry) = NonEmptyStorage::remove(ManuallyDrop::new(storage), key);
-        self.0 = storage;
-        entry.map(|e| e.value)
+        struct Visitor<K, V, R, const N: usize>(std::marker::PhantomData<(K, V, R)>);
+
+        impl<'de, K, V, R, const N: usize> serde::de::Visitor<'de> for Visitor<K, V, R, N>
+        where
+            K: Ord + serde::Deserialize<'de>,
+            V: serde::Deserialize<'de>,
+            R: Rng + SeedableRng,
+        {
+            type Value = SkipMap<K, V, R, N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(
+                    f,
+                    "a map, or a sequence alternating between keys and values"
+                )
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                // Serialized skipmaps are sorted, so entries are linked in order until one isn't.
+                SkipMap::try_from_entries(std::iter::from_fn(|| map.next_entry().transpose()))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut len = 0;
+                SkipMap::try_from_entries(std::iter::from_fn(|| {
+                    let key = match seq.next_element() {
+                        Ok(key) => key?,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    len += 1;
+                    let value = seq.next_element().and_then(|value| {
+                        value.ok_or_else(|| {
+                            serde::de::Error::invalid_length(2 * len - 1, &"an even length")
+                        })
+                    });
+                    Some(value.map(|value| (key, value)))
+                }))
+            }
+        }
+
+        let visitor = Visitor(std::marker::PhantomData);
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_map(visitor)
+        } else {
+            deserializer.deserialize_seq(visitor)
+        }
     }
 }
 
+/// An entry of a skipmap, which is ordered by its key only.
+///
+/// The key comes first in memory, so an entry whose value takes no space, such as `()`, has the
+/// exact layout of its key, and the nodes of a `SkipMap<K, ()>` are those of a `SkipList<K>`.
+#[repr(C)]
 pub struct Entry<K, V> {
     pub key: K,
     pub value: V,
 }
 
+impl<K, V> From<(K, V)> for Entry<K, V> {
+    fn from((key, value): (K, V)) -> Self {
+        Self { key, value }
+    }
+}
+
 impl<K, V> Borrow<K> for Entry<K, V> {
     fn borrow(&self) -> &K {
         &self.key
     }
 }
 
+impl<V> Borrow<str> for Entry<SmallString, V> {
+    fn borrow(&self) -> &str {
+        self.key.as_str()
+    }
+}
+
 impl<K, V> Hash for Entry<K, V>
 where
     K: Hash,
@@ -163,14 +1383,30 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::BTreeMap,
+        io,
+        ops::{Bound, RangeBounds},
+        sync::Mutex,
+        thread,
+    };
+
     use proptest::{collection::btree_map, prelude::*};
+    use rand::rngs::SmallRng;
+    use rand_chacha::ChaCha8Rng;
 
-    use super::SkipMap;
+    use super::{Entry, SkipMap};
+    use crate::{
+        NonEmptyStorage, SkipNode,
+        policy::{AllowDuplicates, RejectOnEqual},
+        snapshot,
+    };
 
     proptest! {
-        #[cfg_attr(miri, ignore)]
         #[test]
-        fn test_insert_get(items in btree_map(any::<usize>(), any::<usize>(), 1000)) {
+        fn test_insert_get(
+            items in btree_map(any::<usize>(), any::<usize>(), crate::TEST_SCALE),
+        ) {
             let mut skipmap = SkipMap::<usize, usize, _, 32>::new();
             for (k, v) in &items {
                 assert!(skipmap.insert(*k, *v).is_none());
@@ -191,9 +1427,10 @@ mod tests {
             }
         }
 
-        #[cfg_attr(miri, ignore)]
         #[test]
-        fn test_insert_remove(items in btree_map(any::<usize>(), any::<usize>(), 1000)) {
+        fn test_insert_remove(
+            items in btree_map(any::<usize>(), any::<usize>(), crate::TEST_SCALE),
+        ) {
             let mut skipmap = SkipMap::<usize, usize, _, 32>::new();
             for (k, v) in &items {
                 assert!(skipmap.insert(*k, *v).is_none());
@@ -214,9 +1451,10 @@ mod tests {
             }
         }
 
-        #[cfg_attr(miri, ignore)]
         #[test]
-        fn test_insert_duplicates(items in btree_map(any::<usize>(), any::<usize>(), 1000)) {
+        fn test_insert_duplicates(
+            items in btree_map(any::<usize>(), any::<usize>(), crate::TEST_SCALE),
+        ) {
             let mut skipmap = SkipMap::<usize, usize, _, 32>::new();
             for (k, v) in &items {
                 assert!(skipmap.insert(*k, *v).is_none());
@@ -236,5 +1474,948 @@ mod tests {
                 assert!(skipmap.insert(*k, 0).is_some_and(|x| x == *v));
             }
         }
+
+        #[cfg(feature = "rayon")]
+        #[test]
+        fn test_from_sorted_chunks_parallel(
+            items in btree_map(any::<usize>(), any::<usize>(), 0..1000),
+            size in 1..100usize,
+        ) {
+            let entries: Vec<_> = items.iter().map(|(k, v)| (*k, *v)).collect();
+            let chunks: Vec<_> = entries.chunks(size).map(<[_]>::to_vec).collect();
+            let mut skipmap = SkipMap::<usize, usize, SmallRng, 32>::from_sorted_chunks_parallel(chunks);
+            for (k, v) in &items {
+                assert!(skipmap.get(k).is_some_and(|x| x == v));
+            }
+            for (k, v) in items.iter().rev() {
+                assert!(skipmap.remove(k).is_some_and(|x| x == *v));
+            }
+            assert!(items.keys().all(|k| !skipmap.contains(k)));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde(items in btree_map(any::<u64>(), any::<u64>(), 0..100)) {
+            let mut skipmap = SkipMap::<u64, u64, _, 8>::new();
+            for (k, v) in items.iter().rev() {
+                skipmap.insert(*k, *v);
+            }
+            let json = serde_json::to_string(&skipmap).unwrap();
+            assert_eq!(json, serde_json::to_string(&items).unwrap());
+            let skipmap: SkipMap<u64, u64, SmallRng, 8> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&skipmap).unwrap(), json);
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde_compact(items in btree_map(any::<u64>(), ".*", 0..100)) {
+            let mut skipmap = SkipMap::<u64, String, _, 8>::new();
+            for (k, v) in items.iter().rev() {
+                skipmap.insert(*k, v.clone());
+            }
+            let mut bytes = Vec::new();
+            ciborium::into_writer(&skipmap, &mut bytes).unwrap();
+            let flat: Vec<ciborium::Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+            assert_eq!(flat.len(), 2 * items.len());
+            let skipmap: SkipMap<u64, String, SmallRng, 8> =
+                ciborium::from_reader(bytes.as_slice()).unwrap();
+            assert_eq!(skipmap, items);
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde_borrowed(items in btree_map("[a-z]*", any::<u64>(), 0..100)) {
+            let json = serde_json::to_string(&items).unwrap();
+            let skipmap: SkipMap<&str, u64, SmallRng, 8> = serde_json::from_str(&json).unwrap();
+            let entries: Vec<_> = crate::sorted::SortedMap::iter(&skipmap).collect();
+            assert_eq!(entries.len(), items.len());
+            for ((key, value), (k, v)) in entries.into_iter().zip(&items) {
+                assert_eq!((*key, value), (k.as_str(), v));
+                assert!(json.as_bytes().as_ptr_range().contains(&key.as_ptr()));
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde_unsorted(entries in proptest::collection::vec((0..16u64, any::<u64>()), 0..100)) {
+            let json: Vec<_> = entries.iter().map(|(k, v)| format!("\"{k}\":{v}")).collect();
+            let json = format!("{{{}}}", json.join(","));
+            let skipmap: SkipMap<u64, u64, SmallRng, 8> = serde_json::from_str(&json).unwrap();
+            let btree: std::collections::BTreeMap<_, _> = entries.into_iter().collect();
+            assert_eq!(serde_json::to_string(&skipmap).unwrap(), serde_json::to_string(&btree).unwrap());
+        }
+    }
+
+    #[cfg(feature = "jsonl")]
+    proptest! {
+        #[test]
+        fn test_jsonl(items in btree_map(any::<u64>(), ".*", 0..100)) {
+            let mut skipmap = SkipMap::<u64, String, _, 8>::new();
+            for (k, v) in items.iter().rev() {
+                skipmap.insert(*k, v.clone());
+            }
+            let mut bytes = Vec::new();
+            skipmap.export_jsonl(&mut bytes).unwrap();
+            assert_eq!(bytes.split(|&b| b == b'\n').count(), items.len() + 1);
+            let skipmap = SkipMap::<u64, String, SmallRng, 8>::import_jsonl(bytes.as_slice()).unwrap();
+            for (k, v) in &items {
+                assert_eq!(skipmap.get(k), Some(v));
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_max_past_removed() {
+        let mut skipmap = SkipMap::<u32, u32, SmallRng, 8>::new();
+        for key in 0..8 {
+            assert_eq!(skipmap.push_max(key, key), Ok(()));
+        }
+        assert!(skipmap.remove_lazily(&7));
+        // The lazily removed entry doesn't count as the greatest one.
+        assert_eq!(skipmap.push_max(7, 0), Ok(()));
+        assert_eq!(skipmap.push_max(7, 1), Err((7, 1)));
+        skipmap.assert_invariants();
+        assert_eq!(skipmap.get(&7), Some(&0));
+        assert!(skipmap.iter().map(|(key, _)| *key).eq(0..8));
+    }
+
+    #[cfg(feature = "jsonl")]
+    #[test]
+    fn test_jsonl_unsorted() {
+        let lines = "[3,\"c\"]\n\n[1,\"a\"]\n  \n[2,\"b\"]\n[1,\"d\"]";
+        let skipmap = SkipMap::<u64, String, SmallRng, 8>::import_jsonl(lines.as_bytes()).unwrap();
+        let mut bytes = Vec::new();
+        skipmap.export_jsonl(&mut bytes).unwrap();
+        assert_eq!(bytes, b"[1,\"d\"]\n[2,\"b\"]\n[3,\"c\"]\n");
+        let err =
+            SkipMap::<u64, String, SmallRng, 8>::import_jsonl(&b"[1,\"a\"]\n{}\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "columnar")]
+    proptest! {
+        #[test]
+        fn test_columns(items in btree_map(any::<u32>(), ".*", 0..100)) {
+            let mut skipmap = SkipMap::<u32, String, _, 8>::new();
+            for (k, v) in items.iter().rev() {
+                skipmap.insert(*k, v.clone());
+            }
+            let (keys, values) = skipmap.to_columns();
+            assert_eq!(keys, items.keys().copied().collect::<Vec<_>>());
+            assert_eq!(values.len(), items.len());
+            for (i, v) in items.values().enumerate() {
+                assert_eq!(values.get(i), Some(v.as_bytes()));
+            }
+        }
+    }
+
+    #[cfg(feature = "csv")]
+    proptest! {
+        #[test]
+        fn test_csv(items in btree_map(any::<i64>(), ".*", 0..100)) {
+            let mut skipmap = SkipMap::<i64, String, _, 8>::new();
+            for (k, v) in items.iter().rev() {
+                skipmap.insert(*k, v.clone());
+            }
+            let mut bytes = Vec::new();
+            skipmap.export_csv(&mut bytes, ToString::to_string, Clone::clone).unwrap();
+            let skipmap = SkipMap::<i64, String, SmallRng, 8>::import_csv(
+                bytes.as_slice(),
+                str::parse,
+                |value| Ok::<_, std::convert::Infallible>(value.to_owned()),
+            )
+            .unwrap();
+            for (k, v) in &items {
+                assert_eq!(skipmap.get(k), Some(v));
+            }
+        }
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_csv_invalid() {
+        let import = |csv: &str| {
+            SkipMap::<u64, u64, SmallRng, 8>::import_csv(csv.as_bytes(), str::parse, str::parse)
+        };
+        let skipmap = import("3,30\n1,10\r\n\n2,20\n").unwrap();
+        assert_eq!(skipmap.get(&1), Some(&10));
+        assert_eq!(skipmap.get(&3), Some(&30));
+        assert_eq!(
+            import("1,10,100\n").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            import("1,x\n").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_push_max(entries in proptest::collection::vec((any::<u8>(), any::<u8>()), 0..100)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (key, value) in entries {
+                let greatest = btree.last_key_value().is_none_or(|(last, _)| *last < key);
+                if greatest {
+                    btree.insert(key, value);
+                    assert_eq!(skipmap.push_max(key, value), Ok(()));
+                } else {
+                    assert_eq!(skipmap.push_max(key, value), Err((key, value)));
+                }
+                assert_eq!(skipmap.last_key_value(), btree.last_key_value());
+            }
+            assert_eq!(skipmap, btree);
+            for key in 0..=u8::MAX {
+                assert_eq!(skipmap.get(&key), btree.get(&key));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_remove_lazily(ops in proptest::collection::vec((0..4u8, 0..16u8, any::<u8>()), 0..200)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 | 1 => assert_eq!(skipmap.insert(key, value), btree.insert(key, value)),
+                    2 => assert_eq!(skipmap.remove_lazily(&key), btree.remove(&key).is_some()),
+                    _ => assert_eq!(skipmap.remove(&key), btree.remove(&key)),
+                }
+                assert_eq!(skipmap.get(&key), btree.get(&key));
+                assert_eq!(skipmap.first_key_value(), btree.first_key_value());
+                assert_eq!(skipmap.last_key_value(), btree.last_key_value());
+            }
+            assert_eq!(skipmap, btree);
+            let mut bytes = Vec::new();
+            skipmap.write_checkpoint(&mut bytes).unwrap();
+            let restored = SkipMap::<u8, u8, SmallRng, 8>::read_checkpoint(bytes.as_slice()).unwrap();
+            assert_eq!(restored, btree);
+        }
+
+        #[test]
+        fn test_iter(ops in proptest::collection::vec((0..3u8, 0..16u8, any::<u8>()), 0..200)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            assert_eq!(skipmap.iter().next(), None);
+            for (op, key, value) in ops {
+                match op {
+                    0 | 1 => assert_eq!(skipmap.insert(key, value), btree.insert(key, value)),
+                    _ => assert_eq!(skipmap.remove_lazily(&key), btree.remove(&key).is_some()),
+                }
+            }
+            assert!(skipmap.iter().eq(btree.iter()));
+            assert_eq!(format!("{:?}", skipmap.iter()), format!("{:?}", btree.iter()));
+            assert!((&skipmap).into_iter().eq(&btree));
+            assert!(skipmap.iter().rev().eq(btree.iter().rev()));
+        }
+
+        #[test]
+        fn test_range(
+            items in btree_map(0..64u8, any::<u8>(), 0..32),
+            removed in 0..64u8,
+            start in 0..64u8,
+            end in 0..64u8,
+        ) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            assert_eq!(skipmap.range(start..end).next(), None);
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            skipmap.remove_lazily(&removed);
+            btree.remove(&removed);
+            let ranges = [
+                (Bound::Included(start), Bound::Included(end)),
+                (Bound::Included(start), Bound::Excluded(end)),
+                (Bound::Excluded(start), Bound::Included(end)),
+                (Bound::Excluded(start), Bound::Excluded(end)),
+                (Bound::Unbounded, Bound::Excluded(end)),
+                (Bound::Excluded(start), Bound::Unbounded),
+            ];
+            for range in ranges {
+                let expected: Vec<_> = btree.iter().filter(|(k, _)| range.contains(*k)).collect();
+                assert!(skipmap.range(range).eq(expected.iter().copied()));
+                assert!(skipmap.range(range).rev().eq(expected.iter().rev().copied()));
+                assert_eq!(format!("{:?}", skipmap.range(range)), format!("{expected:?}"));
+            }
+        }
+
+        #[test]
+        fn test_range_mut(
+            items in btree_map(0..64u8, any::<u8>(), 0..32),
+            removed in 0..64u8,
+            start in 0..64u8,
+            end in 0..64u8,
+        ) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            assert_eq!(skipmap.range_mut(start..=end).next(), None);
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            skipmap.remove_lazily(&removed);
+            btree.remove(&removed);
+            let range = (Bound::Excluded(start), Bound::Included(end));
+            let expected: Vec<_> = btree.iter().filter(|(k, _)| range.contains(*k)).collect();
+            assert_eq!(format!("{:?}", skipmap.range_mut(range)), format!("{expected:?}"));
+            for (_, v) in skipmap.range_mut(range).rev() {
+                *v = v.wrapping_add(1);
+            }
+            for (_, v) in btree.iter_mut().filter(|(k, _)| range.contains(*k)) {
+                *v = v.wrapping_add(1);
+            }
+            assert_eq!(skipmap, btree);
+        }
+
+        #[test]
+        fn test_drain(items in btree_map(0..64u8, any::<u8>(), 0..32), removed in 0..64u8) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            assert_eq!(skipmap.drain().next(), None);
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            skipmap.remove_lazily(&removed);
+            btree.remove(&removed);
+            let drain = skipmap.drain();
+            assert_eq!(format!("{drain:?}"), format!("{:?}", btree.iter()));
+            assert!(drain.rev().eq(btree.clone().into_iter().rev()));
+            skipmap.assert_invariants();
+            assert_eq!(skipmap.first_key_value(), None);
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            assert_eq!(skipmap, btree);
+        }
+
+        #[test]
+        fn test_drain_range(
+            items in btree_map(0..64u8, any::<u8>(), 0..64),
+            removed in proptest::collection::vec(0..64u8, 0..8),
+            start in 0..64u8,
+            end in 0..64u8,
+            kind in 0..6u8,
+            taken in 0..64usize,
+        ) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            for key in &removed {
+                skipmap.remove_lazily(key);
+                btree.remove(key);
+            }
+            let range = match kind {
+                0 => (Bound::Included(start), Bound::Included(end)),
+                1 => (Bound::Included(start), Bound::Excluded(end)),
+                2 => (Bound::Excluded(start), Bound::Included(end)),
+                3 => (Bound::Excluded(start), Bound::Excluded(end)),
+                4 => (Bound::Unbounded, Bound::Included(end)),
+                _ => (Bound::Excluded(start), Bound::Unbounded),
+            };
+            let expected: Vec<_> = btree
+                .iter()
+                .filter(|(k, _)| range.contains(*k))
+                .map(|(k, v)| (*k, *v))
+                .collect();
+            btree.retain(|k, _| !range.contains(k));
+            let drained: Vec<_> = skipmap.drain_range(range).take(taken).collect();
+            assert_eq!(drained, expected[..taken.min(expected.len())]);
+            skipmap.assert_invariants();
+            assert_eq!(skipmap, btree);
+            assert!(skipmap.iter().rev().eq(btree.iter().rev()));
+            for key in 0..64 {
+                skipmap.insert(key, key);
+                btree.insert(key, key);
+            }
+            skipmap.assert_invariants();
+            assert_eq!(skipmap, btree);
+        }
+
+        #[test]
+        fn test_extract_if(
+            items in btree_map(0..64u8, any::<u8>(), 0..64),
+            removed in proptest::collection::vec(0..64u8, 0..8),
+            divisor in 1..8u8,
+            taken in 0..64usize,
+        ) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            for key in &removed {
+                skipmap.remove_lazily(key);
+                btree.remove(key);
+            }
+            let mut extract = skipmap.extract_if(|k, _| k % divisor == 0);
+            assert_eq!(format!("{extract:?}"), format!("{:?}", btree.iter().collect::<Vec<_>>()));
+            let extracted: Vec<_> = extract.by_ref().take(taken).collect();
+            let expected: Vec<_> = btree
+                .iter()
+                .filter(|(k, _)| *k % divisor == 0)
+                .take(taken)
+                .map(|(k, v)| (*k, *v))
+                .collect();
+            assert_eq!(extracted, expected);
+            for (k, _) in &expected {
+                btree.remove(k);
+            }
+            skipmap.assert_invariants();
+            assert_eq!(skipmap, btree);
+            // Exhausting the iterator visits every entry, and the kept ones can be mutated.
+            let extracted: Vec<_> = skipmap
+                .extract_if(|_, v| {
+                    *v = v.wrapping_add(1);
+                    *v % 2 == 0
+                })
+                .collect();
+            let mut expected = Vec::new();
+            btree.retain(|k, v| {
+                *v = v.wrapping_add(1);
+                if *v % 2 == 0 {
+                    expected.push((*k, *v));
+                }
+                *v % 2 != 0
+            });
+            assert_eq!(extracted, expected);
+            skipmap.assert_invariants();
+            assert_eq!(skipmap, btree);
+            assert_eq!(skipmap.extract_if(|_, _| true).count(), btree.len());
+            assert_eq!(skipmap.iter().next(), None);
+            skipmap.insert(1, 1);
+            skipmap.assert_invariants();
+        }
+
+        #[test]
+        fn test_retain(
+            items in btree_map(0..64u8, any::<u8>(), 0..64),
+            removed in proptest::collection::vec(0..64u8, 0..8),
+            divisor in 1..8u8,
+        ) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            for key in &removed {
+                skipmap.remove_lazily(key);
+                btree.remove(key);
+            }
+            let mut visited = Vec::new();
+            skipmap.retain(|k, v| {
+                visited.push(*k);
+                *v = v.wrapping_add(*k);
+                *v % divisor != 0
+            });
+            assert!(visited.iter().eq(btree.keys()));
+            btree.retain(|k, v| {
+                *v = v.wrapping_add(*k);
+                *v % divisor != 0
+            });
+            skipmap.assert_invariants();
+            assert_eq!(skipmap, btree);
+            skipmap.retain(|_, _| false);
+            skipmap.assert_invariants();
+            assert_eq!(skipmap.iter().next(), None);
+        }
+
+        #[test]
+        fn test_iter_mut(items in btree_map(0..64u8, any::<u8>(), 0..32), removed in 0..64u8) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            assert_eq!(skipmap.iter_mut().next(), None);
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            skipmap.remove_lazily(&removed);
+            btree.remove(&removed);
+            assert_eq!(format!("{:?}", skipmap.iter_mut()), format!("{:?}", btree.iter_mut()));
+            for (k, v) in &mut skipmap {
+                *v = v.wrapping_add(*k);
+            }
+            for (k, v) in &mut btree {
+                *v = v.wrapping_add(*k);
+            }
+            assert_eq!(skipmap, btree);
+        }
+
+        #[test]
+        fn test_values_mut(items in btree_map(0..64u8, any::<u8>(), 0..32), removed in 0..64u8) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = items;
+            assert_eq!(skipmap.values_mut().next(), None);
+            for (k, v) in &btree {
+                skipmap.insert(*k, *v);
+            }
+            skipmap.remove_lazily(&removed);
+            btree.remove(&removed);
+            assert_eq!(format!("{:?}", skipmap.values_mut()), format!("{:?}", btree.values_mut()));
+            for value in skipmap.values_mut().rev() {
+                *value = (*value).min(100);
+            }
+            for value in btree.values_mut() {
+                *value = (*value).min(100);
+            }
+            assert_eq!(skipmap, btree);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_first_last(ops in proptest::collection::vec((0..4u8, 0..64u8, any::<u8>()), 0..200)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 => assert_eq!(skipmap.insert(key, value), btree.insert(key, value)),
+                    1 => assert_eq!(skipmap.remove(&key), btree.remove(&key)),
+                    2 => assert_eq!(skipmap.pop_first(), btree.pop_first()),
+                    _ => assert_eq!(skipmap.pop_last(), btree.pop_last()),
+                }
+                assert_eq!(skipmap.first_key_value(), btree.first_key_value());
+                assert_eq!(skipmap.last_key_value(), btree.last_key_value());
+            }
+            assert_eq!(skipmap, btree);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_eq(items in btree_map(0..64u8, 0..4u8, 0..32), key in 0..64u8, value in 0..4u8) {
+            let mut skipmap = SkipMap::<u8, u8, _, 8>::new();
+            for (k, v) in &items {
+                skipmap.insert(*k, *v);
+            }
+            assert_eq!(skipmap, items);
+            let mut other = items.clone();
+            other.insert(key, value);
+            assert_eq!(skipmap == other, items.get(&key) == Some(&value));
+            other.remove(&key);
+            assert_eq!(skipmap == other, !items.contains_key(&key));
+        }
+
+        #[test]
+        fn test_snapshot(items in btree_map(any::<u64>(), ".*", 0..100)) {
+            let mut skipmap = SkipMap::<u64, String, _, 8>::new();
+            for (k, v) in items.iter().rev() {
+                skipmap.insert(*k, v.clone());
+            }
+            let mut bytes = Vec::new();
+            skipmap.write_snapshot(&mut bytes).unwrap();
+            let skipmap = SkipMap::<u64, String, SmallRng, 8>::read_snapshot(bytes.as_slice()).unwrap();
+            for (k, v) in &items {
+                assert_eq!(skipmap.get(k), Some(v));
+            }
+            let mut rewritten = Vec::new();
+            skipmap.write_snapshot(&mut rewritten).unwrap();
+            assert_eq!(rewritten, bytes);
+        }
+
+        #[test]
+        fn test_snapshot_unsorted(entries in proptest::collection::vec((0..16u64, any::<u64>()), 0..100)) {
+            let mut bytes = Vec::new();
+            let mut buf = Vec::new();
+            snapshot::write_header(&mut bytes, snapshot::Layout::Entries, entries.len()).unwrap();
+            for (k, v) in &entries {
+                snapshot::write_record(&mut bytes, k, &mut buf).unwrap();
+                snapshot::write_record(&mut bytes, v, &mut buf).unwrap();
+            }
+            let skipmap = SkipMap::<u64, u64, SmallRng, 8>::read_snapshot(bytes.as_slice()).unwrap();
+            let btree: std::collections::BTreeMap<_, _> = entries.into_iter().collect();
+            for (k, v) in &btree {
+                assert_eq!(skipmap.get(k), Some(v));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_checkpoint(
+            ops in proptest::collection::vec((any::<bool>(), any::<u8>()), 0..200),
+            more in proptest::collection::vec(any::<u8>(), 0..100),
+        ) {
+            let mut skipmap = SkipMap::<u8, u64, SmallRng, 8>::new();
+            for (insert, key) in ops {
+                if insert {
+                    skipmap.insert(key, u64::from(key));
+                } else {
+                    skipmap.remove(&key);
+                }
+            }
+            let mut bytes = Vec::new();
+            skipmap.write_checkpoint(&mut bytes).unwrap();
+            let mut restored = SkipMap::<u8, u64, SmallRng, 8>::read_checkpoint(bytes.as_slice()).unwrap();
+            let towers = |skipmap: &SkipMap<u8, u64, SmallRng, 8>| -> Vec<_> {
+                skipmap.storage.iter().flat_map(NonEmptyStorage::towers).map(|(height, entry)| (height, entry.key)).collect()
+            };
+            assert_eq!(towers(&restored), towers(&skipmap));
+            assert_eq!(restored.storage.as_ref().map(|s| s.levels), skipmap.storage.as_ref().map(|s| s.levels));
+            // An empty skipmap gets a new random number generator on the first insertion.
+            prop_assume!(skipmap.storage.is_some());
+            for key in more {
+                skipmap.insert(key, 0);
+                restored.insert(key, 0);
+            }
+            assert_eq!(towers(&restored), towers(&skipmap));
+            let mut original = Vec::new();
+            skipmap.write_checkpoint(&mut original).unwrap();
+            let mut rewritten = Vec::new();
+            restored.write_checkpoint(&mut rewritten).unwrap();
+            assert_eq!(rewritten, original);
+            let skipmap = SkipMap::<u8, u64, SmallRng, 8>::read_snapshot(original.as_slice()).unwrap();
+            assert_eq!(towers(&skipmap).len(), towers(&restored).len());
+        }
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut skipmap = SkipMap::<u64, u64, SmallRng, 8>::with_capacity(100);
+        for key in 0..100 {
+            assert_eq!(skipmap.push_max(key, key), Ok(()));
+        }
+        assert_eq!(skipmap.pool_len(), 0);
+        skipmap.assert_invariants();
+        assert!(skipmap.iter().map(|(k, v)| (*k, *v)).eq((0..100).map(|key| (key, key))));
+    }
+
+    #[test]
+    fn test_checkpoint_invalid() {
+        let mut skipmap = SkipMap::<u64, u64, SmallRng, 8>::new();
+        skipmap.insert(0, 0);
+        let mut bytes = Vec::new();
+        skipmap.write_snapshot(&mut bytes).unwrap();
+        let err = SkipMap::<u64, u64, SmallRng, 8>::read_checkpoint(bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut bytes = Vec::new();
+        skipmap.write_checkpoint(&mut bytes).unwrap();
+        let err = SkipMap::<u64, u64, SmallRng, 4>::read_checkpoint(bytes.as_slice()).map(|_| ());
+        if bytes[13 + 4 + 32] > 4 {
+            assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+        let mut corrupted = bytes;
+        corrupted[13 + 4 + 32] = 0;
+        let err =
+            SkipMap::<u64, u64, SmallRng, 8>::read_checkpoint(corrupted.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_seed_thread() {
+        let towers = || -> Vec<_> {
+            let mut skipmap = SkipMap::<u64, u64, SmallRng, 8>::new();
+            for key in 0..100 {
+                skipmap.insert(key, key);
+            }
+            let towers = skipmap.storage.iter().flat_map(NonEmptyStorage::towers);
+            towers.map(|(height, _)| height).collect()
+        };
+        crate::seed_thread(7);
+        let first = towers();
+        assert_ne!(towers(), first);
+        crate::seed_thread(7);
+        assert_eq!(towers(), first);
+        assert_ne!(crate::fallback_seed(), crate::fallback_seed());
+    }
+
+    #[test]
+    #[cfg(feature = "deterministic")]
+    fn test_deterministic() {
+        let towers = || -> Vec<_> {
+            let mut skipmap = SkipMap::<u64, u64, SmallRng, 8>::new();
+            for key in 0..100 {
+                skipmap.insert(key, key);
+            }
+            let towers = skipmap.storage.iter().flat_map(NonEmptyStorage::towers);
+            towers.map(|(height, _)| height).collect()
+        };
+        // Threads without their own seed start from the same one.
+        let first = thread::spawn(towers).join().unwrap();
+        assert_eq!(thread::spawn(towers).join().unwrap(), first);
+        assert_eq!(crate::seed(), crate::seed());
+        if std::env::var_os(crate::SEED_VAR).is_none() {
+            assert_eq!(crate::seed(), crate::DEFAULT_SEED);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_invalid() {
+        let mut skipmap = SkipMap::<u64, u64, _, 8>::new();
+        skipmap.insert(0, 0);
+        skipmap.insert(1, 1);
+        let mut bytes = Vec::new();
+        skipmap.write_snapshot(&mut bytes).unwrap();
+        let read =
+            |bytes: &[u8]| SkipMap::<u64, u64, SmallRng, 8>::read_snapshot(bytes).map(|_| ());
+        let err = read(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let mut corrupted = bytes.clone();
+        corrupted[0] = 0;
+        assert_eq!(
+            read(&corrupted).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        let mut corrupted = bytes;
+        corrupted[13] = 7;
+        assert_eq!(
+            read(&corrupted).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_static() {
+        static SKIPMAP: Mutex<SkipMap<u32, u32, ChaCha8Rng, 8>> =
+            Mutex::new(SkipMap::with_lazy_rng());
+        let mut skipmap = SKIPMAP.lock().unwrap();
+        for key in 0..100 {
+            skipmap.insert(key, key);
+        }
+        let found = (0..100).all(|key| skipmap.get(&key) == Some(&key));
+        drop(skipmap);
+        assert!(found);
+    }
+
+    #[test]
+    fn test_default_params() {
+        let mut skipmap = SkipMap::<u64, &str>::new();
+        skipmap.insert(1, "one");
+        assert_eq!(skipmap.get(&1), Some(&"one"));
+    }
+
+    #[test]
+    fn test_unit_values() {
+        fn assert_layout<K>() {
+            assert_eq!(size_of::<Entry<K, ()>>(), size_of::<K>());
+            assert_eq!(align_of::<Entry<K, ()>>(), align_of::<K>());
+            assert_eq!(
+                size_of::<SkipNode<Entry<K, ()>, 8>>(),
+                size_of::<SkipNode<K, 8>>()
+            );
+        }
+        assert_layout::<u8>();
+        assert_layout::<u64>();
+        assert_layout::<String>();
+        assert_layout::<(u32, u8)>();
+    }
+
+    #[test]
+    fn test_move_across_threads() {
+        let mut skipmap = SkipMap::<usize, String, _, 4>::new();
+        for i in 0..8 {
+            skipmap.insert(i, i.to_string());
+        }
+        let skipmap = thread::spawn(move || {
+            skipmap.remove(&0);
+            skipmap
+        })
+        .join()
+        .unwrap();
+        assert!(!skipmap.contains(&0));
+        assert_eq!(skipmap.get(&7).map(String::as_str), Some("7"));
+    }
+
+    #[test]
+    fn test_share_across_threads() {
+        let skipmap = Mutex::new(SkipMap::<usize, usize, _, 4>::new());
+        thread::scope(|s| {
+            for t in 0..4 {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for i in 0..8 {
+                        skipmap.lock().unwrap().insert(t * 8 + i, t);
+                    }
+                });
+            }
+        });
+        let skipmap = skipmap.into_inner().unwrap();
+        thread::scope(|s| {
+            for t in 0..4 {
+                let skipmap = &skipmap;
+                s.spawn(move || {
+                    for i in 0..8 {
+                        assert_eq!(skipmap.get(&(t * 8 + i)), Some(&t));
+                    }
+                });
+            }
+        });
+    }
+
+    proptest! {
+        #[test]
+        fn test_debug(items in btree_map(any::<u8>(), any::<u8>(), 0..50)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            for (k, v) in &items {
+                skipmap.insert(*k, *v);
+            }
+            assert_eq!(format!("{skipmap:?}"), format!("{items:?}"));
+            let structure = format!("{skipmap:#?}");
+            assert!(structure.starts_with("SkipMap {"));
+            assert!(!structure.contains("0x"));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let mut skipmap = SkipMap::<&str, u8, SmallRng, 8>::new();
+        assert_eq!(skipmap.to_string(), "{}");
+        skipmap.insert("b", 2);
+        skipmap.insert("a", 1);
+        assert_eq!(skipmap.to_string(), "{a: 1, b: 2}");
+        assert_eq!(format!("{skipmap:>2}"), "{ a:  1,  b:  2}");
+    }
+
+    proptest! {
+        #[test]
+        fn test_policies(ops in proptest::collection::vec((0..3u8, 0..16u8, any::<u8>()), 0..200)) {
+            let mut allow = SkipMap::<u8, u8, SmallRng, 8, AllowDuplicates>::new();
+            let mut reject = SkipMap::<u8, u8, SmallRng, 8, RejectOnEqual>::new();
+            let mut keys = Vec::new();
+            let mut btree = BTreeMap::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 => {
+                        allow.insert(key, value);
+                        keys.insert(keys.partition_point(|k| *k <= key), key);
+                        let rejected = reject.insert(key, value).err();
+                        assert_eq!(rejected, btree.contains_key(&key).then_some(value));
+                        btree.entry(key).or_insert(value);
+                    }
+                    1 => {
+                        let removed = keys.binary_search(&key).map(|index| keys.remove(index));
+                        assert_eq!(allow.remove_lazily(&key), removed.is_ok());
+                        assert_eq!(reject.remove_lazily(&key), btree.remove(&key).is_some());
+                    }
+                    _ => {
+                        let removed = keys.binary_search(&key).map(|index| keys.remove(index));
+                        assert_eq!(allow.remove(&key).is_some(), removed.is_ok());
+                        assert_eq!(reject.remove(&key), btree.remove(&key));
+                    }
+                }
+            }
+            assert_eq!(reject, btree);
+            assert_eq!(reject.push_max(16, 0), Ok(()));
+            assert_eq!(reject.push_max(16, 1), Err((16, 1)));
+            assert_eq!(allow.push_max(16, 0), Ok(()));
+            assert_eq!(allow.push_max(16, 1), Ok(()));
+            keys.extend([16, 16]);
+            let popped = std::iter::from_fn(|| allow.pop_first().map(|(k, _)| k));
+            assert!(popped.eq(keys));
+        }
+
+        #[test]
+        fn test_policies_invariants(
+            ops in proptest::collection::vec((0..3u8, 0..16u8, any::<u8>()), 0..200),
+        ) {
+            let mut allow = SkipMap::<u8, u8, SmallRng, 4, AllowDuplicates>::new();
+            let mut reject = SkipMap::<u8, u8, SmallRng, 4, RejectOnEqual>::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 => {
+                        allow.insert(key, value);
+                        let _ = reject.insert(key, value);
+                    }
+                    1 => {
+                        allow.remove_lazily(&key);
+                        reject.remove_lazily(&key);
+                    }
+                    _ => {
+                        allow.remove(&key);
+                        reject.remove(&key);
+                    }
+                }
+                allow.assert_invariants();
+                reject.assert_invariants();
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_get_raw(ops in proptest::collection::vec((0..3u8, 0..16u8, any::<u8>()), 0..200)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 => assert_eq!(skipmap.insert(key, value), btree.insert(key, value)),
+                    1 => assert_eq!(skipmap.remove_lazily(&key), btree.remove(&key).is_some()),
+                    _ => assert_eq!(skipmap.remove(&key), btree.remove(&key)),
+                }
+                for key in 0..16 {
+                    assert_eq!(skipmap.get_raw(key), btree.get(&key));
+                    assert_eq!(skipmap.contains_raw(key), btree.contains_key(&key));
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_insert_hashed(entries in proptest::collection::vec((any::<u16>(), any::<u8>()), 1..200)) {
+            let mut skipmap = SkipMap::<u16, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (k, v) in &entries {
+                assert_eq!(skipmap.insert_hashed(*k, *v), btree.insert(*k, *v));
+            }
+            assert_eq!(skipmap, btree);
+            let mut other = SkipMap::<u16, u8, SmallRng, 8>::new();
+            for (k, v) in btree.iter().rev() {
+                other.insert_hashed(*k, *v);
+            }
+            if let (Some(storage), Some(other)) = (&skipmap.storage, &other.storage) {
+                let towers = storage.towers().map(|(height, e)| (height, e.key));
+                assert!(towers.eq(other.towers().map(|(height, e)| (height, e.key))));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_descending(
+            items in btree_map(any::<u16>(), any::<u8>(), 0..100),
+            start in any::<u16>(),
+            end in any::<u16>(),
+        ) {
+            let mut skipmap = SkipMap::<_, _, _, 8>::descending();
+            for (k, v) in &items {
+                skipmap.insert(*k, *v);
+            }
+            assert!(skipmap.iter().eq(items.iter().rev()));
+            assert_eq!(skipmap.first_key_value(), items.last_key_value());
+            assert_eq!(skipmap.last_key_value(), items.first_key_value());
+            for (k, v) in &items {
+                assert!(skipmap.contains(k));
+                assert_eq!(skipmap.get(k), Some(v));
+            }
+            if start <= end {
+                assert!(skipmap.range(start..=end).eq(items.range(start..=end).rev()));
+            } else {
+                assert_eq!(skipmap.range(start..=end).next(), None);
+            }
+            assert!(skipmap.range(..end).eq(items.range(..end).rev()));
+            let mut items = items;
+            for (k, v) in &mut skipmap {
+                *v = v.wrapping_add(1);
+                *items.get_mut(k).unwrap() = *v;
+            }
+            if let Some((k, v)) = items.iter_mut().next() {
+                *v = v.wrapping_add(1);
+                *skipmap.get_mut(k).unwrap() = *v;
+            }
+            assert_eq!(skipmap.get_mut(&start).is_some(), items.contains_key(&start));
+            assert!(skipmap.iter().eq(items.iter().rev()));
+            if let Some((k, v)) = items.pop_first() {
+                assert_eq!(skipmap.remove(&k), Some(v));
+                assert_eq!(skipmap.get(&k), None);
+                assert_eq!(skipmap.pop_last(), items.pop_first());
+            }
+            for (k, v) in items.into_iter().rev() {
+                assert_eq!(skipmap.pop_first(), Some((k, v)));
+            }
+            assert_eq!(skipmap.pop_first(), None);
+        }
     }
 }
@@ -0,0 +1,359 @@
+//! Views into single entries of a [`SkipMap`].
+//!
+//! An entry is found by a single traversal, which also records where a vacant entry would be
+//! linked, so that inserting or removing through the view never traverses the skipmap again.
+
+use std::{
+    fmt,
+    mem::{self, MaybeUninit},
+    ptr::NonNull,
+};
+
+use rand::Rng;
+
+use super::{Entry as Pair, SkipMap};
+use crate::{NonEmptyStorage, SkipNode, Trace};
+
+/// A view into the entry at a key of a skipmap, which is either occupied or vacant.
+pub enum Entry<'a, K, V, R, const N: usize>
+where
+    R: Rng,
+{
+    /// An entry holding a value.
+    Occupied(OccupiedEntry<'a, K, V, R, N>),
+    /// An entry without a value.
+    Vacant(VacantEntry<'a, K, V, R, N>),
+}
+
+/// A view into an entry holding a value.
+pub struct OccupiedEntry<'a, K, V, R, const N: usize>
+where
+    R: Rng,
+{
+    map: &'a mut SkipMap<K, V, R, N>,
+    trace: Trace<Pair<K, V>, N>,
+    node: NonNull<SkipNode<Pair<K, V>, N>>,
+}
+
+/// A view into an entry without a value.
+pub struct VacantEntry<'a, K, V, R, const N: usize>
+where
+    R: Rng,
+{
+    map: &'a mut SkipMap<K, V, R, N>,
+    key: K,
+    trace: Trace<Pair<K, V>, N>,
+    /// The node of an entry at an equal key that was removed lazily, which gets revived instead of
+    /// linking a new node.
+    removed: Option<NonNull<SkipNode<Pair<K, V>, N>>>,
+}
+
+impl<K, V, R, const N: usize> fmt::Debug for Entry<'_, K, V, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Occupied(entry) => f.debug_tuple("Entry").field(entry).finish(),
+            Self::Vacant(entry) => f.debug_tuple("Entry").field(entry).finish(),
+        }
+    }
+}
+
+impl<K, V, R, const N: usize> fmt::Debug for OccupiedEntry<'_, K, V, R, N>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<K, V, R, const N: usize> fmt::Debug for VacantEntry<'_, K, V, R, N>
+where
+    K: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VacantEntry").field(&self.key).finish()
+    }
+}
+
+impl<K, V, R, const N: usize> SkipMap<K, V, R, N>
+where
+    K: Ord,
+    R: Rng,
+{
+    /// Returns the entry at the given key, for an in-place update or insertion.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, R, N> {
+        let mut trace = [MaybeUninit::uninit(); N];
+        let found = self.storage.as_ref().and_then(|storage| storage.trace(&key, &mut trace));
+        match found {
+            Some(node) if !unsafe { node.as_ref() }.removed => Entry::Occupied(OccupiedEntry {
+                map: self,
+                trace,
+                node,
+            }),
+            removed => Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                trace,
+                removed,
+            }),
+        }
+    }
+
+    /// Returns a mutable reference to the value at the given key, inserting the value returned by
+    /// the function first if there's none.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.entry(key).or_insert_with(f)
+    }
+}
+
+impl<'a, K, V, R, const N: usize> Entry<'a, K, V, R, N>
+where
+    K: Ord,
+    R: Rng,
+{
+    /// Returns the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns a mutable reference to the value of the entry, inserting the given value first if
+    /// the entry is vacant.
+    pub fn or_insert(self, value: V) -> &'a mut V {
+        self.or_insert_with(|| value)
+    }
+
+    /// Returns a mutable reference to the value of the entry, inserting the value returned by the
+    /// function first if the entry is vacant.
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.or_insert_with_key(|_| f())
+    }
+
+    /// Returns a mutable reference to the value of the entry, inserting the value returned by the
+    /// function for the key first if the entry is vacant.
+    pub fn or_insert_with_key<F>(self, f: F) -> &'a mut V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => {
+                let value = f(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value of the entry, inserting the default value first if
+    /// the entry is vacant.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls the function on the value of the entry if it's occupied.
+    #[must_use]
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Self::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V, R, const N: usize> OccupiedEntry<'a, K, V, R, N>
+where
+    R: Rng,
+{
+    /// Returns the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        &self.pair().key
+    }
+
+    /// Returns a shared reference to the value of the entry.
+    #[must_use]
+    pub const fn get(&self) -> &V {
+        &self.pair().value
+    }
+
+    /// Returns a mutable reference to the value of the entry.
+    pub const fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut (*self.node.as_ptr()).value.value }
+    }
+
+    /// Returns a mutable reference to the value of the entry, which lives as long as the borrow of
+    /// the skipmap.
+    #[must_use]
+    pub const fn into_mut(self) -> &'a mut V {
+        unsafe { &mut (*self.node.as_ptr()).value.value }
+    }
+
+    /// Replaces the value of the entry, returning the replaced value.
+    pub const fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry from the skipmap, returning its value.
+    #[must_use]
+    pub fn remove(self) -> V
+    where
+        K: Ord,
+    {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the skipmap, returning its key and its value.
+    #[must_use]
+    pub fn remove_entry(self) -> (K, V)
+    where
+        K: Ord,
+    {
+        let Some(storage) = &mut self.map.storage else {
+            unreachable!();
+        };
+        storage.unlink_after(&self.trace, self.node);
+        let pair = unsafe { storage.release(self.node) };
+        if storage.is_empty() {
+            self.map.drop_storage();
+        }
+        (pair.key, pair.value)
+    }
+
+    const fn pair(&self) -> &Pair<K, V> {
+        unsafe { &(*self.node.as_ptr()).value }
+    }
+}
+
+impl<'a, K, V, R, const N: usize> VacantEntry<'a, K, V, R, N>
+where
+    R: Rng,
+{
+    /// Returns the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the key of the entry, giving up the insertion.
+    #[must_use]
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value into the entry, returning a mutable reference to it, which lives as long
+    /// as the borrow of the skipmap.
+    pub fn insert(self, value: V) -> &'a mut V
+    where
+        K: Ord,
+    {
+        &mut self.insert_pair(value).value
+    }
+
+    /// Inserts the value into the entry, returning the stored key along with the value.
+    pub(crate) fn insert_pair(self, value: V) -> &'a mut Pair<K, V>
+    where
+        K: Ord,
+    {
+        let pair = Pair {
+            key: self.key,
+            value,
+        };
+        let node = match &mut self.map.storage {
+            None => {
+                let rng = self.map.rng.take();
+                let pool = mem::take(&mut self.map.pool);
+                let storage = self.map.storage.insert(NonEmptyStorage::new(pair, rng, pool));
+                storage.tail()
+            }
+            Some(storage) => match self.removed {
+                Some(node) => {
+                    drop(unsafe { storage.revive(node, pair) });
+                    node
+                }
+                None => storage.insert_after(&self.trace, pair, None),
+            },
+        };
+        unsafe { &mut (*node.as_ptr()).value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, btree_map};
+
+    use proptest::{collection::vec, prelude::*};
+    use rand::rngs::SmallRng;
+
+    use super::Entry;
+    use crate::SkipMap;
+
+    proptest! {
+        #[test]
+        fn test_entry(ops in vec((0..5u8, 0..16u8, any::<u8>()), 0..200)) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            for (op, key, value) in ops {
+                match op {
+                    0 => {
+                        let inserted = *skipmap.entry(key).or_insert(value);
+                        assert_eq!(inserted, *btree.entry(key).or_insert(value));
+                    }
+                    1 => {
+                        let modified = *skipmap
+                            .entry(key)
+                            .and_modify(|v| *v = v.wrapping_add(value))
+                            .or_default();
+                        let other = *btree
+                            .entry(key)
+                            .and_modify(|v: &mut u8| *v = v.wrapping_add(value))
+                            .or_default();
+                        assert_eq!(modified, other);
+                    }
+                    2 => match (skipmap.entry(key), btree.entry(key)) {
+                        (Entry::Occupied(entry), btree_map::Entry::Occupied(other)) => {
+                            assert_eq!(entry.key(), other.key());
+                            assert_eq!(entry.remove_entry(), other.remove_entry());
+                        }
+                        (Entry::Vacant(entry), btree_map::Entry::Vacant(other)) => {
+                            assert_eq!(entry.into_key(), other.into_key());
+                        }
+                        _ => panic!("entries differ at {key}"),
+                    },
+                    3 => assert_eq!(skipmap.remove_lazily(&key), btree.remove(&key).is_some()),
+                    _ => {
+                        *skipmap.get_or_insert_with(key, || value) ^= 1;
+                        *btree.entry(key).or_insert(value) ^= 1;
+                    }
+                }
+                assert_eq!(skipmap.get(&key), btree.get(&key));
+            }
+            assert_eq!(skipmap, btree);
+        }
+    }
+}
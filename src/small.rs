@@ -0,0 +1,223 @@
+//! Keys that are stored inline in the nodes when they are short.
+//!
+//! Values are always stored inline in the nodes, so keys and values of `Copy` types never cost an
+//! indirection. A [`String`] key, however, points at its bytes on the heap, so that every
+//! comparison during a traversal loads both the node and the bytes.
+
+use std::{borrow::Borrow, cmp, fmt, hash, ops::Deref};
+
+use crate::memory::HeapSize;
+
+/// The number of bytes that a string can hold without allocating, which leaves room for the
+/// length and the tag in as much space as a [`String`].
+const INLINE: usize = size_of::<String>() - 2;
+
+/// A string that holds upto 22 bytes inline on 64-bit targets (10 on 32-bit ones), and only
+/// allocates when it's longer, while taking as much space as a [`String`].
+///
+/// It's meant for keys that are mostly short, such as identifiers or names. Skipmaps whose keys are
+/// small strings can be queried by [`str`], and keys compare like the strings they hold.
+#[derive(Clone)]
+pub struct SmallString(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline { len: u8, bytes: [u8; INLINE] },
+    Heap(Box<str>),
+}
+
+impl SmallString {
+    /// Creates an empty string.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Repr::Inline {
+            len: 0,
+            bytes: [0; INLINE],
+        })
+    }
+
+    /// Returns the string as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Inline { len, bytes } => {
+                // SAFETY: The bytes were copied from a string slice of the same length.
+                unsafe { std::str::from_utf8_unchecked(&bytes[..usize::from(*len)]) }
+            }
+            Repr::Heap(string) => string,
+        }
+    }
+
+    /// Returns whether the string is stored inline instead of on the heap.
+    #[must_use]
+    pub const fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+}
+
+impl Default for SmallString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for SmallString {
+    fn from(string: &str) -> Self {
+        let Ok(len) = u8::try_from(string.len()) else {
+            return Self(Repr::Heap(string.into()));
+        };
+        if usize::from(len) > INLINE {
+            return Self(Repr::Heap(string.into()));
+        }
+        let mut bytes = [0; INLINE];
+        bytes[..string.len()].copy_from_slice(string.as_bytes());
+        Self(Repr::Inline { len, bytes })
+    }
+}
+
+impl From<String> for SmallString {
+    /// Moves a long string without copying its bytes.
+    fn from(string: String) -> Self {
+        if string.len() > INLINE {
+            Self(Repr::Heap(string.into_boxed_str()))
+        } else {
+            Self::from(string.as_str())
+        }
+    }
+}
+
+impl From<SmallString> for String {
+    fn from(string: SmallString) -> Self {
+        match string.0 {
+            Repr::Inline { .. } => string.as_str().to_owned(),
+            Repr::Heap(string) => string.into_string(),
+        }
+    }
+}
+
+impl Deref for SmallString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SmallString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for SmallString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SmallString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SmallString {}
+
+impl PartialOrd for SmallString {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallString {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl hash::Hash for SmallString {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: hash::Hasher,
+    {
+        self.as_str().hash(state);
+    }
+}
+
+impl fmt::Debug for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl HeapSize for SmallString {
+    fn heap_size(&self) -> usize {
+        match &self.0 {
+            Repr::Inline { .. } => 0,
+            Repr::Heap(string) => string.len(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SmallString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SmallString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{INLINE, SmallString};
+    use crate::{SkipMap, memory::HeapSize};
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size_of::<SmallString>(), size_of::<String>());
+    }
+
+    proptest! {
+        #[test]
+        fn test_small_string(a in ".{0,30}", b in ".{0,30}") {
+            let small = SmallString::from(a.as_str());
+            assert_eq!(small.as_str(), a);
+            assert_eq!(small.is_inline(), a.len() <= INLINE);
+            assert_eq!(small.heap_size() == 0, small.is_inline());
+            assert_eq!(SmallString::from(a.clone()), small);
+            assert_eq!(String::from(small.clone()), a);
+            assert_eq!(small.cmp(&SmallString::from(b.as_str())), a.cmp(&b));
+        }
+
+        #[test]
+        fn test_skipmap_keys(keys in proptest::collection::vec(".{0,30}", 0..50)) {
+            let mut skipmap = SkipMap::<SmallString, usize, _, 8>::new();
+            for (i, key) in keys.iter().enumerate() {
+                skipmap.insert(SmallString::from(key.as_str()), i);
+            }
+            for key in &keys {
+                assert!(skipmap.contains(key.as_str()));
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! A compact binary format for persisting collections, independent of any serialization
+//! framework.
+//!
+//! A snapshot starts with a header made of the `SKPY` magic bytes, a format version byte, and the
+//! number of entries as a little-endian `u64`. Entries follow in ascending order of keys, each
+//! written as its encoded key and then its encoded value, every one of them prefixed by its length
+//! in bytes as a little-endian `u32`.
+//!
+//! Checkpoints, which also preserve the structure of the skiplist, use the second version of the
+//! format. The header is then followed by the seed of the random number generator, written as a
+//! length-prefixed record, and every entry is preceded by a byte holding the number of levels that
+//! its node is linked into.
+
+use std::io::{self, Read, Write};
+
+/// The bytes that every snapshot starts with.
+const MAGIC: [u8; 4] = *b"SKPY";
+
+/// The version of the format for snapshots holding only entries.
+const VERSION: u8 = 1;
+
+/// The version of the format for checkpoints, which also hold the structure of the skiplist.
+const CHECKPOINT_VERSION: u8 = 2;
+
+/// What follows the header of a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout {
+    /// Only the entries.
+    Entries,
+    /// The seed of the random number generator, then the entries along with their heights.
+    Structure,
+}
+
+/// A type that can be written into and read back from a snapshot.
+pub trait Record: Sized {
+    /// Appends the encoded representation of the value to the buffer.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a value from the bytes written by [`Record::encode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes don't represent a valid value.
+    fn decode(bytes: &[u8]) -> io::Result<Self>;
+}
+
+macro_rules! impl_record_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Record for $ty {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &[u8]) -> io::Result<Self> {
+                    let bytes = bytes.try_into().map_err(|_| invalid("integer of the wrong size"))?;
+                    Ok(Self::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_record_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Record for usize {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        // Always uses 64 bits so that snapshots can be read on platforms with other pointer sizes.
+        (*self as u64).encode(buf);
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        Self::try_from(u64::decode(bytes)?).map_err(|_| invalid("integer out of range"))
+    }
+}
+
+impl Record for isize {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as i64).encode(buf);
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        Self::try_from(i64::decode(bytes)?).map_err(|_| invalid("integer out of range"))
+    }
+}
+
+impl Record for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid("unit with a non-empty encoding"))
+        }
+    }
+}
+
+impl Record for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(u8::from(*self));
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        match bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(invalid("invalid boolean")),
+        }
+    }
+}
+
+impl Record for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        Self::from_utf8(bytes.to_vec()).map_err(|_| invalid("string that isn't valid UTF-8"))
+    }
+}
+
+impl Record for Vec<u8> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Writes the header of a snapshot holding the given number of entries.
+pub(crate) fn write_header<W>(writer: &mut W, layout: Layout, len: usize) -> io::Result<()>
+where
+    W: Write,
+{
+    let version = match layout {
+        Layout::Entries => VERSION,
+        Layout::Structure => CHECKPOINT_VERSION,
+    };
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[version])?;
+    writer.write_all(&(len as u64).to_le_bytes())
+}
+
+/// Reads the header of a snapshot, returning what follows and the number of entries.
+pub(crate) fn read_header<R>(reader: &mut R) -> io::Result<(Layout, u64)>
+where
+    R: Read,
+{
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid("not a snapshot"));
+    }
+    let mut version = [0];
+    reader.read_exact(&mut version)?;
+    let layout = match version[0] {
+        VERSION => Layout::Entries,
+        CHECKPOINT_VERSION => Layout::Structure,
+        _ => return Err(invalid("unsupported snapshot version")),
+    };
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    Ok((layout, u64::from_le_bytes(len)))
+}
+
+/// Writes the number of levels that the node of an entry is linked into.
+pub(crate) fn write_height<W>(writer: &mut W, height: usize) -> io::Result<()>
+where
+    W: Write,
+{
+    let height = u8::try_from(height)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "more than 255 levels"))?;
+    writer.write_all(&[height])
+}
+
+/// Reads the number of levels that the node of an entry is linked into, which must be between 1
+/// and the given maximum.
+pub(crate) fn read_height<R>(reader: &mut R, max: usize) -> io::Result<usize>
+where
+    R: Read,
+{
+    let mut height = [0];
+    reader.read_exact(&mut height)?;
+    let height = usize::from(height[0]);
+    if height == 0 || height > max {
+        return Err(invalid("invalid number of levels"));
+    }
+    Ok(height)
+}
+
+/// Writes a length-prefixed record, using the buffer as scratch space.
+pub(crate) fn write_record<W, T>(writer: &mut W, record: &T, buf: &mut Vec<u8>) -> io::Result<()>
+where
+    W: Write,
+    T: Record,
+{
+    buf.clear();
+    record.encode(buf);
+    let len = u32::try_from(buf.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record larger than 4 GiB"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(buf)
+}
+
+/// Reads a length-prefixed record, using the buffer as scratch space.
+pub(crate) fn read_record<R, T>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<T>
+where
+    R: Read,
+    T: Record,
+{
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    buf.clear();
+    // Reads through `take` instead of allocating upfront, so that a corrupted length can't cause a
+    // huge allocation.
+    let len = u64::from(u32::from_le_bytes(len));
+    let read = reader.by_ref().take(len).read_to_end(buf)?;
+    if read as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    T::decode(buf)
+}
+
+pub(crate) fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fmt::Debug, io};
+
+    use proptest::prelude::*;
+
+    use super::Record;
+
+    fn roundtrip<T>(value: &T)
+    where
+        T: Record + PartialEq + Debug,
+    {
+        let mut buf = Vec::new();
+        value.encode(&mut buf);
+        assert_eq!(&T::decode(&buf).unwrap(), value);
+    }
+
+    proptest! {
+        #[test]
+        fn test_record(a in any::<u64>(), b in any::<i32>(), c in any::<usize>(), d in ".*", e in any::<Vec<u8>>(), f in any::<bool>()) {
+            roundtrip(&a);
+            roundtrip(&b);
+            roundtrip(&c);
+            roundtrip(&d);
+            roundtrip(&e);
+            roundtrip(&f);
+        }
+    }
+
+    #[test]
+    fn test_record_invalid() {
+        assert_eq!(
+            u32::decode(&[0; 3]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            bool::decode(&[2]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            String::decode(&[0xff]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            <()>::decode(&[0]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
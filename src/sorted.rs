@@ -0,0 +1,222 @@
+//! Traits abstracting over ordered collections, so that code written against them can switch
+//! between the collections of this crate and those of the standard library.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::RangeBounds,
+};
+
+/// An ordered collection of values.
+pub trait SortedSet<T> {
+    /// Returns the value equal to the given one, if it exists.
+    fn get(&self, value: &T) -> Option<&T>;
+
+    /// Returns whether a value equal to the given one exists.
+    fn contains(&self, value: &T) -> bool {
+        self.get(value).is_some()
+    }
+
+    /// Inserts a value. Whether a value equal to an existing one is kept alongside it, replaces it,
+    /// or is dropped follows the [`DuplicatePolicy`](crate::policy::DuplicatePolicy) `P` of a
+    /// skiplist, while sets of the standard library only keep the existing value.
+    fn insert(&mut self, value: T);
+
+    /// Removes a value equal to the given one, returning it if it exists.
+    fn remove(&mut self, value: &T) -> Option<T>;
+
+    /// Returns an iterator over the values, in ascending order.
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a;
+
+    /// Returns an iterator over the values within the range, in ascending order.
+    ///
+    /// # Panics
+    ///
+    /// Sets of the standard library panic if the start of the range is greater than its end, or
+    /// if both bounds are excluded and equal.
+    fn range<'a, B>(&'a self, range: B) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+        B: RangeBounds<T>;
+}
+
+/// An ordered collection of entries, each mapping a unique key to a value.
+pub trait SortedMap<K, V> {
+    /// Returns the value at the given key, if it exists.
+    fn get(&self, key: &K) -> Option<&V>;
+
+    /// Returns whether an entry exists at the given key.
+    fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts an entry, returning the previous value at the key if it exists.
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    /// Removes the entry at the given key, returning its value if it exists.
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    /// Returns an iterator over the entries, in ascending order of keys.
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a;
+
+    /// Returns an iterator over the entries whose keys are within the range, in ascending order
+    /// of keys.
+    ///
+    /// # Panics
+    ///
+    /// Maps of the standard library panic if the start of the range is greater than its end, or
+    /// if both bounds are excluded and equal.
+    fn range<'a, B>(&'a self, range: B) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+        B: RangeBounds<K>;
+}
+
+impl<T> SortedSet<T> for BTreeSet<T>
+where
+    T: Ord,
+{
+    fn get(&self, value: &T) -> Option<&T> {
+        Self::get(self, value)
+    }
+
+    fn insert(&mut self, value: T) {
+        Self::insert(self, value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        Self::take(self, value)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        Self::iter(self)
+    }
+
+    fn range<'a, B>(&'a self, range: B) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+        B: RangeBounds<T>,
+    {
+        Self::range(self, range)
+    }
+}
+
+impl<K, V> SortedMap<K, V> for BTreeMap<K, V>
+where
+    K: Ord,
+{
+    fn get(&self, key: &K) -> Option<&V> {
+        Self::get(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        Self::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        Self::remove(self, key)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        Self::iter(self)
+    }
+
+    fn range<'a, B>(&'a self, range: B) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+        B: RangeBounds<K>,
+    {
+        Self::range(self, range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        ops::Bound,
+    };
+
+    use proptest::{collection::vec, prelude::*};
+    use rand::rngs::SmallRng;
+
+    use super::{SortedMap, SortedSet};
+    use crate::{SkipList, SkipMap};
+
+    fn bounds() -> impl Strategy<Value = (Bound<u8>, Bound<u8>)> {
+        let bound = || {
+            prop_oneof![
+                any::<u8>().prop_map(Bound::Included),
+                any::<u8>().prop_map(Bound::Excluded),
+                Just(Bound::Unbounded),
+            ]
+        };
+        (bound(), bound()).prop_filter("valid range", |(start, end)| match (start, end) {
+            (Bound::Included(s), Bound::Included(e)) => s <= e,
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e))
+            | (Bound::Excluded(s), Bound::Included(e)) => s < e,
+            _ => true,
+        })
+    }
+
+    fn apply_set<S>(set: &mut S, ops: &[(bool, u8)], range: (Bound<u8>, Bound<u8>)) -> Vec<u8>
+    where
+        S: SortedSet<u8>,
+    {
+        for &(insert, value) in ops {
+            if insert {
+                if !set.contains(&value) {
+                    set.insert(value);
+                }
+            } else {
+                set.remove(&value);
+            }
+        }
+        assert!(set.iter().is_sorted());
+        set.range(range).copied().collect()
+    }
+
+    fn apply_map<M>(map: &mut M, ops: &[(bool, u8)], range: (Bound<u8>, Bound<u8>)) -> Vec<u8>
+    where
+        M: SortedMap<u8, u8>,
+    {
+        for &(insert, key) in ops {
+            if insert {
+                map.insert(key, key / 2);
+            } else {
+                map.remove(&key);
+            }
+        }
+        assert!(map.iter().all(|(key, value)| *value == key / 2));
+        map.range(range).map(|(key, _)| *key).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn test_sorted_set(ops in vec((any::<bool>(), any::<u8>()), 0..200), range in bounds()) {
+            let mut skiplist = SkipList::<u8, SmallRng, 8>::new();
+            let mut btree = BTreeSet::new();
+            assert_eq!(apply_set(&mut skiplist, &ops, range), apply_set(&mut btree, &ops, range));
+        }
+
+        #[test]
+        fn test_sorted_map(ops in vec((any::<bool>(), any::<u8>()), 0..200), range in bounds()) {
+            let mut skipmap = SkipMap::<u8, u8, SmallRng, 8>::new();
+            let mut btree = BTreeMap::new();
+            assert_eq!(apply_map(&mut skipmap, &ops, range), apply_map(&mut btree, &ops, range));
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//! A skiplist variant whose nodes each hold a sorted run of values.
+
+use std::{
+    borrow::Borrow,
+    fmt,
+    mem::MaybeUninit,
+    num::NonZeroUsize,
+    ptr::{self, NonNull},
+    slice,
+};
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use crate::RandomBits;
+
+/// A skiplist whose nodes each hold upto `B` values, which are ordered by the first value of every
+/// node.
+///
+/// Runs of values are searched within a node instead of by following links, so lookups hop through
+/// far fewer nodes, and a node only gets allocated once every few insertions. Values are stored
+/// inline in their node, so every hop only loads a single allocation. A full node gets split in
+/// half when a value is inserted into it, and a node left less than half full by a removal takes
+/// the values of the next node, or borrows its first value if they don't fit, so that every node
+/// but the last stays at least half full.
+///
+/// Like for a [`SkipList`](crate::SkipList), the random number generator `R` and the number of
+/// levels `N` have defaults, and nodes hold 16 values by default, so that the skiplist can be
+/// written as `UnrolledSkipList<T>`.
+pub struct UnrolledSkipList<T, R = SmallRng, const N: usize = 32, const B: usize = 16>
+where
+    R: Rng,
+{
+    rng: R,
+    bits: RandomBits,
+    head: [Tower<T, N, B>; N],
+    levels: NonZeroUsize,
+    len: usize,
+}
+
+impl<T, R, const N: usize, const B: usize> Drop for UnrolledSkipList<T, R, N, B>
+where
+    R: Rng,
+{
+    fn drop(&mut self) {
+        let mut curr = self.head[0];
+        while let Some(curr_ptr) = curr {
+            let node = unsafe { Box::from_raw(curr_ptr.as_ptr()) };
+            curr = node.nexts[0];
+        }
+    }
+}
+
+// SAFETY: The skiplist exclusively owns all of its nodes, which are only reachable through it, so
+// sending it sends the values along with it.
+unsafe impl<T, R, const N: usize, const B: usize> Send for UnrolledSkipList<T, R, N, B>
+where
+    T: Send,
+    R: Rng + Send,
+{
+}
+
+// SAFETY: Nodes are only mutated through a mutable reference to the skiplist, so sharing the
+// skiplist only ever gives out shared references to the values.
+unsafe impl<T, R, const N: usize, const B: usize> Sync for UnrolledSkipList<T, R, N, B>
+where
+    T: Sync,
+    R: Rng + Sync,
+{
+}
+
+impl<T, const N: usize, const B: usize> Default for UnrolledSkipList<T, SmallRng, N, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R, const N: usize, const B: usize> fmt::Debug for UnrolledSkipList<T, R, N, B>
+where
+    T: fmt::Debug,
+    R: Rng,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize, const B: usize> UnrolledSkipList<T, SmallRng, N, B> {
+    /// Creates an empty skiplist.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_rng(crate::new_rng())
+    }
+}
+
+impl<T, R, const N: usize, const B: usize> UnrolledSkipList<T, R, N, B>
+where
+    R: Rng + SeedableRng,
+{
+    /// Creates an empty skiplist whose random number generator is seeded from the given value, so
+    /// that the same operations always build the same structure.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(R::seed_from_u64(seed))
+    }
+}
+
+impl<T, R, const N: usize, const B: usize> UnrolledSkipList<T, R, N, B>
+where
+    R: Rng,
+{
+    /// Creates an empty skiplist that draws the heights of its nodes from the given random number
+    /// generator.
+    pub const fn with_rng(rng: R) -> Self {
+        crate::assert_levels::<N>();
+        const { assert!(B >= 2, "nodes must hold at least two values to be split") };
+        Self {
+            rng,
+            bits: RandomBits::new(),
+            head: [None; N],
+            levels: NonZeroUsize::MIN,
+            len: 0,
+        }
+    }
+}
+
+impl<T, R, const N: usize, const B: usize> UnrolledSkipList<T, R, N, B>
+where
+    R: Rng,
+{
+    /// Returns the number of values in the skiplist.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the skiplist holds no value.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the values, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut next = self.head[0];
+        std::iter::from_fn(move || {
+            let node = unsafe { next?.as_ref() };
+            next = node.nexts[0];
+            Some(node.values())
+        })
+        .flatten()
+    }
+
+    /// Links a new node right after the towers that come before it at every level.
+    fn link(&mut self, trace: &[Tower<T, N, B>; N], mut node: UnrolledNode<T, N, B>) {
+        let height = self.random_height();
+        if height > self.levels.get() {
+            // The trace already points at the head for every level above the current ones.
+            self.levels = NonZeroUsize::MIN.saturating_add(height - 1);
+        }
+        node.height = height;
+        let curr_ptr = node.alloc();
+        for (level, prev) in trace.iter().enumerate().take(height) {
+            let next = self.links_mut(*prev)[level].replace(curr_ptr);
+            unsafe { (*curr_ptr.as_ptr()).nexts[level] = next };
+        }
+    }
+
+    /// Unlinks a node from right after the towers that come before it at every level, and frees it
+    /// along with the values it still holds.
+    fn unlink(&mut self, trace: &[Tower<T, N, B>; N], curr_ptr: NonNull<UnrolledNode<T, N, B>>) {
+        let curr = unsafe { curr_ptr.as_ref() };
+        for (level, prev) in trace.iter().enumerate().take(curr.height) {
+            self.links_mut(*prev)[level] = curr.nexts[level];
+        }
+        drop(unsafe { Box::from_raw(curr_ptr.as_ptr()) });
+        while self.levels.get() > 1 && self.head[self.levels.get() - 1].is_none() {
+            self.levels = NonZeroUsize::MIN.saturating_add(self.levels.get() - 2);
+        }
+    }
+
+    /// Returns the towers that come before the node right after the given tower at every level,
+    /// which is the tower itself at the levels it's linked into, and the given trace above them.
+    fn after(tower: Tower<T, N, B>, trace: &[Tower<T, N, B>; N]) -> [Tower<T, N, B>; N] {
+        let height = tower.map_or(N, |ptr| unsafe { ptr.as_ref() }.height);
+        let mut after = *trace;
+        after[..height].fill(tower);
+        after
+    }
+
+    /// Determines the height of a new node, growing by at most one level at a time.
+    fn random_height(&mut self) -> usize {
+        let max = self.levels.saturating_add(1).get().min(N);
+        self.bits.height(&mut self.rng, max)
+    }
+
+    fn links(&self, tower: Tower<T, N, B>) -> &[Tower<T, N, B>; N] {
+        tower.map_or(&self.head, |ptr| unsafe { &(*ptr.as_ptr()).nexts })
+    }
+
+    fn links_mut(&mut self, tower: Tower<T, N, B>) -> &mut [Tower<T, N, B>; N] {
+        tower.map_or(&mut self.head, |ptr| unsafe { &mut (*ptr.as_ptr()).nexts })
+    }
+}
+
+impl<T, R, const N: usize, const B: usize> UnrolledSkipList<T, R, N, B>
+where
+    T: Ord,
+    R: Rng,
+{
+    /// Returns whether a value exists in the skiplist.
+    pub fn contains<U>(&self, value: &U) -> bool
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let trace = self.descend(value);
+        if let Some(prev_ptr) = trace[0] {
+            let prev = unsafe { prev_ptr.as_ref() };
+            if prev.search(value).is_ok() {
+                return true;
+            }
+        }
+        // Otherwise, the value can only be the first one of the next node.
+        self.links(trace[0])[0]
+            .is_some_and(|next_ptr| unsafe { next_ptr.as_ref() }.first().borrow() == value)
+    }
+
+    /// Inserts a value into the skiplist.
+    pub fn insert(&mut self, value: T) {
+        let trace = self.descend(&value);
+        self.len += 1;
+        // Inserts the value into the last node whose first value is less than it, or into the first
+        // node if there's none.
+        let Some(mut curr_ptr) = trace[0].or(self.head[0]) else {
+            let mut node = UnrolledNode::new();
+            node.insert(0, value);
+            self.link(&trace, node);
+            return;
+        };
+        let curr = unsafe { curr_ptr.as_mut() };
+        let index = curr.values().partition_point(|v| v < &value);
+        if curr.len < B {
+            curr.insert(index, value);
+            return;
+        }
+        // Splits the full node in half, moving the upper half into a new node right after it.
+        let mut upper = UnrolledNode::new();
+        curr.move_to(B / 2, &mut upper);
+        if index <= B / 2 {
+            curr.insert(index, value);
+        } else {
+            upper.insert(index - B / 2, value);
+        }
+        self.link(&Self::after(Some(curr_ptr), &trace), upper);
+    }
+
+    /// Removes a value from the skiplist, returning it if it exists.
+    pub fn remove<U>(&mut self, value: &U) -> Option<T>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let trace = self.descend(value);
+        let (curr_ptr, index) = match trace[0] {
+            // The node keeps its first value, which is less than the removed one.
+            Some(prev_ptr) if let Ok(index) = unsafe { prev_ptr.as_ref() }.search(value) => {
+                (prev_ptr, index)
+            }
+            // Otherwise, the value can only be the first one of the next node.
+            prev => {
+                let next_ptr = self.links(prev)[0]?;
+                if unsafe { next_ptr.as_ref() }.first().borrow() != value {
+                    return None;
+                }
+                (next_ptr, 0)
+            }
+        };
+        let curr = unsafe { &mut *curr_ptr.as_ptr() };
+        self.len -= 1;
+        let removed = curr.remove(index);
+        if curr.len == 0 {
+            // Only the first node at or after the value can have held a single value, so the trace
+            // comes right before it at every level.
+            self.unlink(&trace, curr_ptr);
+        } else if curr.len < B / 2 {
+            self.refill(curr_ptr, &trace);
+        }
+        Some(removed)
+    }
+
+    /// Refills a node that's less than half full from the next node, taking all of its values if
+    /// they fit and borrowing its first value otherwise, where the trace holds the towers that come
+    /// before the next node at the levels above the node. The last node is left as it is.
+    fn refill(&mut self, curr_ptr: NonNull<UnrolledNode<T, N, B>>, trace: &[Tower<T, N, B>; N]) {
+        let curr = unsafe { &mut *curr_ptr.as_ptr() };
+        let Some(next_ptr) = curr.nexts[0] else {
+            return;
+        };
+        let next = unsafe { &mut *next_ptr.as_ptr() };
+        if curr.len + next.len <= B {
+            next.move_to(0, curr);
+            self.unlink(&Self::after(Some(curr_ptr), trace), next_ptr);
+        } else {
+            // The next node keeps more than half of `B` values, and a greater first value.
+            curr.insert(curr.len, next.remove(0));
+        }
+    }
+
+    /// Traverses the skiplist, descending down all levels, and returning the last encountered tower
+    /// at each level whose first value is less than the given one. Levels above the current number
+    /// of levels point at the head.
+    fn descend<U>(&self, value: &U) -> [Tower<T, N, B>; N]
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        let mut trace = [None; N];
+        let mut prev = None;
+        for level in (0..self.levels.get()).rev() {
+            while let Some(curr_ptr) = self.links(prev)[level]
+                && unsafe { curr_ptr.as_ref() }.first().borrow() < value
+            {
+                prev = Some(curr_ptr);
+            }
+            trace[level] = prev;
+        }
+        trace
+    }
+}
+
+/// A pointer to either a node or the head when absent.
+type Tower<T, const N: usize, const B: usize> = Option<NonNull<UnrolledNode<T, N, B>>>;
+
+struct UnrolledNode<T, const N: usize, const B: usize> {
+    /// The number of values at the start of the array, which is never zero once the node is linked.
+    len: usize,
+    /// The values of the node, in order, of which the first `len` are initialized.
+    values: [MaybeUninit<T>; B],
+    height: usize,
+    nexts: [Tower<T, N, B>; N],
+}
+
+impl<T, const N: usize, const B: usize> Drop for UnrolledNode<T, N, B> {
+    fn drop(&mut self) {
+        let values = ptr::slice_from_raw_parts_mut(self.values.as_mut_ptr().cast::<T>(), self.len);
+        unsafe { ptr::drop_in_place(values) };
+    }
+}
+
+impl<T, const N: usize, const B: usize> UnrolledNode<T, N, B> {
+    /// Creates a node holding no value, which gets its height once it's linked.
+    const fn new() -> Self {
+        Self {
+            len: 0,
+            values: [const { MaybeUninit::uninit() }; B],
+            height: 0,
+            nexts: [None; N],
+        }
+    }
+
+    fn alloc(self) -> NonNull<Self> {
+        let ptr = Box::into_raw(Box::new(self));
+        unsafe { NonNull::new_unchecked(ptr) }
+    }
+
+    const fn values(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.values.as_ptr().cast::<T>(), self.len) }
+    }
+
+    const fn first(&self) -> &T {
+        &self.values()[0]
+    }
+
+    /// Inserts a value at the index, shifting the values after it, which the node must have room
+    /// for.
+    fn insert(&mut self, index: usize, value: T) {
+        assert!(self.len < B && index <= self.len);
+        unsafe {
+            let ptr = self.values.as_mut_ptr().add(index);
+            ptr::copy(ptr, ptr.add(1), self.len - index);
+            ptr.write(MaybeUninit::new(value));
+        }
+        self.len += 1;
+    }
+
+    /// Removes the value at the index, shifting the values after it.
+    fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len);
+        self.len -= 1;
+        unsafe {
+            let ptr = self.values.as_mut_ptr().add(index);
+            let value = ptr.read().assume_init();
+            ptr::copy(ptr.add(1), ptr, self.len - index);
+            value
+        }
+    }
+
+    /// Moves the values from the index onwards to the end of the other node, which must have room
+    /// for them.
+    fn move_to(&mut self, index: usize, other: &mut Self) {
+        let count = self.len - index;
+        assert!(other.len + count <= B);
+        unsafe {
+            let src = self.values.as_ptr().add(index);
+            ptr::copy_nonoverlapping(src, other.values.as_mut_ptr().add(other.len), count);
+        }
+        self.len = index;
+        other.len += count;
+    }
+
+    fn search<U>(&self, value: &U) -> Result<usize, usize>
+    where
+        T: Borrow<U>,
+        U: Ord + ?Sized,
+    {
+        self.values().binary_search_by(|v| v.borrow().cmp(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use rand::{Rng, rngs::SmallRng};
+
+    use super::UnrolledSkipList;
+
+    /// Returns the number of values held by every node, in order.
+    fn node_lens<T, R, const N: usize, const B: usize>(
+        skiplist: &UnrolledSkipList<T, R, N, B>,
+    ) -> Vec<usize>
+    where
+        R: Rng,
+    {
+        let mut lens = Vec::new();
+        let mut next = skiplist.head[0];
+        while let Some(curr_ptr) = next {
+            let curr = unsafe { curr_ptr.as_ref() };
+            lens.push(curr.len);
+            next = curr.nexts[0];
+        }
+        lens
+    }
+
+    #[test]
+    fn test_remove_refills() {
+        let mut skiplist = UnrolledSkipList::<u32, SmallRng, 8, 8>::with_seed(0);
+        for value in 0..256 {
+            skiplist.insert(value);
+        }
+        for value in (0..256).filter(|value| value % 4 != 0) {
+            assert_eq!(skiplist.remove(&value), Some(value));
+        }
+        assert!(skiplist.iter().copied().eq((0..256).step_by(4)));
+        // Every node but the last is at least half full, rather than down to a value or two.
+        let lens = node_lens(&skiplist);
+        assert!(lens[..lens.len() - 1].iter().all(|&len| len >= 4), "{lens:?}");
+        assert!(lens.len() <= 64 / 4 + 1);
+        for value in (0..256).step_by(4) {
+            assert_eq!(skiplist.remove(&value), Some(value));
+        }
+        assert!(skiplist.is_empty());
+        assert_eq!(skiplist.levels.get(), 1);
+        assert!(node_lens(&skiplist).is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn test_insert_remove(ops in vec((any::<bool>(), 0..64u32), 512)) {
+            let mut skiplist = UnrolledSkipList::<u32, _, 8, 4>::new();
+            let mut sorted = Vec::new();
+            for (insert, value) in ops {
+                if insert {
+                    skiplist.insert(value);
+                    let index = sorted.partition_point(|v| *v < value);
+                    sorted.insert(index, value);
+                } else {
+                    let removed = sorted.binary_search(&value).ok().map(|i| sorted.remove(i));
+                    assert_eq!(skiplist.remove(&value), removed);
+                }
+                assert_eq!(skiplist.len(), sorted.len());
+            }
+            let lens = node_lens(&skiplist);
+            assert!(lens.iter().rev().skip(1).all(|&len| len >= 2), "{lens:?}");
+            for value in 0..64 {
+                assert_eq!(skiplist.contains(&value), sorted.contains(&value));
+            }
+            assert!(skiplist.iter().eq(&sorted));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Checks that operations on reserved nodes make no allocator calls, in a binary of its own, so
+//! that the counting allocator doesn't slow down or interfere with the unit tests.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use rand::rngs::SmallRng;
+use skipidy::{SkipList, SkipMap};
+
+/// The system allocator, counting the allocations made by every thread.
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+struct CountingAllocator;
+
+thread_local! {
+    /// The number of allocations made by the current thread.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.set(ALLOCATIONS.get() + 1);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+    }
+}
+
+/// Returns the number of allocations made by the current thread while running the function.
+fn allocations<F>(f: F) -> usize
+where
+    F: FnOnce(),
+{
+    // With the `metrics` feature, the first traversal on a thread registers its counters, which
+    // allocates, so it's made before counting.
+    let skiplist = SkipList::<u8, SmallRng, 1>::from(vec![0, 1]);
+    assert!(skiplist.contains(&1));
+    let before = ALLOCATIONS.get();
+    f();
+    ALLOCATIONS.get() - before
+}
+
+#[test]
+fn test_skiplist_with_capacity() {
+    let mut skiplist = SkipList::<u32, SmallRng, 8>::with_capacity(100);
+    let allocations = allocations(|| {
+        for value in (0..100).rev() {
+            skiplist.insert(value);
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert!(skiplist.iter().copied().eq(0..100));
+    // Every reserved node is in use, so the next insertion allocates one.
+    assert_eq!(crate::allocations(|| skiplist.insert(100)), 1);
+}
+
+#[test]
+fn test_skipmap_with_capacity() {
+    let mut skipmap = SkipMap::<u64, u64, SmallRng, 8>::with_capacity(100);
+    let allocations = allocations(|| {
+        for key in 0..100 {
+            assert_eq!(skipmap.push_max(key, key), Ok(()));
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert!(skipmap.iter().map(|(k, v)| (*k, *v)).eq((0..100).map(|key| (key, key))));
+}